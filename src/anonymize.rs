@@ -0,0 +1,389 @@
+//! `fyaml anonymize`: a copy of a FYAML tree that is safe to attach to a bug report.
+//!
+//! Every entry keeps its place in the tree, so key derivation, collisions, and ignore rules
+//! reproduce as before. Scalar values in YAML fragments become placeholders of the same
+//! type (`string-1`, `1`, `1.5`), with equal values getting equal placeholders; booleans
+//! and nulls are kept. Other files are replaced by a placeholder line. With `hash_keys`,
+//! entry names and mapping keys become `k` plus eight hex digits of their SHA-256, keeping
+//! sequence indexes, `NN-` order prefixes, extensions, and conventional names such as
+//! `_root.yml`.
+
+use crate::config::BigIntegers;
+use crate::diagnostics::{Category, Diagnostic};
+use crate::digest::sha256_hex;
+use crate::emitter::{to_yaml_string, EmitterOptions};
+use crate::engine::{is_yaml_file, CACHE_FILE_NAME};
+use crate::keymap::KEYMAP_FILE_NAME;
+use crate::loader::load_documents;
+use crate::manifest::MANIFEST_FILE_NAME;
+use serde::Serialize;
+use serde_yaml::{Mapping, Number, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Entry stems with a meaning to the engine, kept under `hash_keys`.
+const CONVENTIONAL_STEMS: &[&str] = &["_root", "root", "_", "index", "value", "_value", "_anchors"];
+
+/// Written in place of a fragment that does not parse, so the copy fails the same way.
+const UNPARSED_PLACEHOLDER: &str = "unparsed: [\n";
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AnonymizeReport {
+    /// Files written, relative to the output directory.
+    pub files: Vec<String>,
+    /// Input entries left out of the copy, relative to the input directory.
+    pub skipped: Vec<SkippedEntry>,
+    /// YAML fragments that did not parse and were replaced by invalid YAML.
+    pub unparsed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedEntry {
+    pub path: String,
+    pub reason: String,
+}
+
+/// Copies the tree at `input` to `output`, which must not exist yet and must not lie inside
+/// `input`.
+pub fn anonymize(
+    input: &Path,
+    output: &Path,
+    hash_keys: bool,
+) -> Result<AnonymizeReport, Box<Diagnostic>> {
+    if !input.is_dir() {
+        return Err(Box::new(
+            Diagnostic::error(
+                "E000",
+                "input directory is missing or not a directory",
+                Category::InvalidInput,
+            )
+            .with_location(input.display().to_string())
+            .with_cause("`fyaml anonymize` copies a FYAML directory.")
+            .with_action("Pass the FYAML directory as the first argument."),
+        ));
+    }
+    if output.exists() {
+        return Err(Box::new(
+            Diagnostic::error(
+                "E216",
+                "anonymize output directory already exists",
+                Category::InvalidInput,
+            )
+            .with_location(output.display().to_string())
+            .with_cause(
+                "The anonymized copy is written into a new directory, so nothing is overwritten.",
+            )
+            .with_action("Pass a path that does not exist yet."),
+        ));
+    }
+    if is_inside(output, input) {
+        return Err(Box::new(
+            Diagnostic::error(
+                "E217",
+                "anonymize output is inside the input directory",
+                Category::InvalidInput,
+            )
+            .with_location(output.display().to_string())
+            .with_cause("The copy would become part of the tree it was made from.")
+            .with_action("Write the copy outside the FYAML directory."),
+        ));
+    }
+
+    let mut copy = Copy {
+        input,
+        output,
+        placeholders: Placeholders {
+            hash_keys,
+            ..Placeholders::default()
+        },
+        report: AnonymizeReport::default(),
+    };
+    copy.directory(input, output)?;
+    Ok(copy.report)
+}
+
+struct Copy<'a> {
+    input: &'a Path,
+    output: &'a Path,
+    placeholders: Placeholders,
+    report: AnonymizeReport,
+}
+
+impl Copy<'_> {
+    fn directory(&mut self, from: &Path, to: &Path) -> Result<(), Box<Diagnostic>> {
+        fs::create_dir_all(to).map_err(|err| copy_failed(to, err))?;
+        let mut entries = fs::read_dir(from)
+            .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+            .map_err(|err| copy_failed(from, err))?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let file_type = entry.file_type().map_err(|err| copy_failed(&path, err))?;
+            let skip = if file_type.is_symlink() {
+                Some("symlinks are not copied")
+            } else if from == self.input && (name == MANIFEST_FILE_NAME || name == CACHE_FILE_NAME)
+            {
+                Some("records the original contents")
+            } else if from == self.input && name == KEYMAP_FILE_NAME && self.placeholders.hash_keys
+            {
+                Some("records the original key names")
+            } else {
+                None
+            };
+            if let Some(reason) = skip {
+                self.report.skipped.push(SkippedEntry {
+                    path: relative(self.input, &path),
+                    reason: reason.to_string(),
+                });
+                continue;
+            }
+
+            let target = to.join(self.placeholders.entry_name(&name, file_type.is_dir()));
+            if file_type.is_dir() {
+                self.directory(&path, &target)?;
+            } else {
+                self.file(&path, &target)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn file(&mut self, from: &Path, to: &Path) -> Result<(), Box<Diagnostic>> {
+        let contents = if is_yaml_file(from) {
+            let text = fs::read(from).map_err(|err| copy_failed(from, err))?;
+            let parsed = std::str::from_utf8(&text)
+                .ok()
+                .and_then(|text| load_documents(text, BigIntegers::String).ok());
+            match parsed {
+                Some(documents) => self.placeholders.documents(documents)?,
+                None => {
+                    self.report.unparsed.push(relative(self.input, from));
+                    UNPARSED_PLACEHOLDER.to_string()
+                }
+            }
+        } else {
+            format!("{}\n", self.placeholders.text())
+        };
+        fs::write(to, contents).map_err(|err| copy_failed(to, err))?;
+        self.report.files.push(relative(self.output, to));
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Placeholders {
+    hash_keys: bool,
+    strings: HashMap<String, usize>,
+    numbers: HashMap<String, usize>,
+    texts: usize,
+}
+
+impl Placeholders {
+    fn documents(&mut self, documents: Vec<Value>) -> Result<String, Box<Diagnostic>> {
+        let mut out = String::new();
+        for (index, document) in documents.into_iter().enumerate() {
+            if index > 0 {
+                out.push_str("---\n");
+            }
+            let document = self.value(document);
+            let text = to_yaml_string(&document, &EmitterOptions::default()).map_err(|err| {
+                Box::new(
+                    Diagnostic::error(
+                        "E300",
+                        "unable to serialize YAML output",
+                        Category::Internal,
+                    )
+                    .with_cause(err.to_string())
+                    .with_action("Report this issue; anonymized values should serialize."),
+                )
+            })?;
+            out.push_str(&text);
+        }
+        Ok(out)
+    }
+
+    fn value(&mut self, value: Value) -> Value {
+        match value {
+            Value::String(text) if text.is_empty() => Value::String(text),
+            Value::String(text) => {
+                let next = self.strings.len() + 1;
+                let n = *self.strings.entry(text).or_insert(next);
+                Value::String(format!("string-{n}"))
+            }
+            Value::Number(number) => {
+                let next = self.numbers.len() + 1;
+                let n = *self.numbers.entry(number.to_string()).or_insert(next) as u64;
+                if number.is_f64() {
+                    Value::Number(Number::from(n as f64 + 0.5))
+                } else if number.as_i64().is_some_and(|i| i < 0) {
+                    Value::Number(Number::from(-(n as i64)))
+                } else {
+                    Value::Number(Number::from(n))
+                }
+            }
+            Value::Sequence(items) => {
+                Value::Sequence(items.into_iter().map(|item| self.value(item)).collect())
+            }
+            Value::Mapping(map) => Value::Mapping(
+                map.into_iter()
+                    .map(|(key, item)| (self.key(key), self.value(item)))
+                    .collect::<Mapping>(),
+            ),
+            Value::Tagged(mut tagged) => {
+                tagged.value = self.value(std::mem::take(&mut tagged.value));
+                Value::Tagged(tagged)
+            }
+            other => other,
+        }
+    }
+
+    fn key(&self, key: Value) -> Value {
+        match key {
+            Value::String(text) if self.hash_keys => Value::String(hash_segments(&text)),
+            other => other,
+        }
+    }
+
+    fn text(&mut self) -> String {
+        self.texts += 1;
+        format!("text-{}", self.texts)
+    }
+
+    /// The name of an entry in the copy: unchanged, or with its key part hashed.
+    fn entry_name(&self, name: &str, directory: bool) -> String {
+        if !self.hash_keys {
+            return name.to_string();
+        }
+        let (dot, name) = match name.strip_prefix('.') {
+            Some(rest) => (".", rest),
+            None => ("", name),
+        };
+        let (stem, extension) = match name.rfind('.') {
+            Some(index) if !directory && index > 0 => name.split_at(index),
+            _ => (name, ""),
+        };
+        let (prefix, stem) = match stem.split_once('-') {
+            Some((digits, _))
+                if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) =>
+            {
+                stem.split_at(digits.len() + 1)
+            }
+            _ => ("", stem),
+        };
+        let keep = stem.is_empty()
+            || stem.bytes().all(|b| b.is_ascii_digit())
+            || CONVENTIONAL_STEMS.contains(&stem);
+        let stem = if keep {
+            stem.to_string()
+        } else {
+            hash_segments(stem)
+        };
+        format!("{dot}{prefix}{stem}{extension}")
+    }
+}
+
+/// Hashes each `.`-separated segment, so dotted keys keep their shape.
+fn hash_segments(key: &str) -> String {
+    key.split('.')
+        .map(|segment| {
+            if segment.is_empty() {
+                String::new()
+            } else {
+                format!("k{}", &sha256_hex(segment.as_bytes())[..8])
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Whether `path`, which need not exist, would lie inside the directory `dir`.
+fn is_inside(path: &Path, dir: &Path) -> bool {
+    let Ok(dir) = fs::canonicalize(dir) else {
+        return false;
+    };
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    match (fs::canonicalize(parent), path.file_name()) {
+        (Ok(parent), Some(name)) => parent.join(name).starts_with(dir),
+        _ => false,
+    }
+}
+
+fn relative(base: &Path, path: &Path) -> String {
+    path.strip_prefix(base)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+fn copy_failed(path: &Path, err: std::io::Error) -> Box<Diagnostic> {
+    Box::new(
+        Diagnostic::error(
+            "E218",
+            "unable to copy entry into the anonymized tree",
+            Category::Write,
+        )
+        .with_location(path.display().to_string())
+        .with_cause(err.to_string())
+        .with_action("Check permissions on the input and output directories."),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().expect("parent")).expect("create dirs");
+        fs::write(path, contents).expect("write");
+    }
+
+    #[test]
+    fn values_become_typed_placeholders_and_keys_hash() {
+        let dir = tempdir().expect("temp dir");
+        let input = dir.path().join("tree");
+        write(
+            &input.join("db.yml"),
+            "host: secret.internal\nreplica: secret.internal\nport: 5432\nratio: 0.3\ntls: true\n",
+        );
+        write(&input.join("steps/0.yml"), "- -7\n");
+        write(&input.join("motd.txt"), "hello\n");
+        write(&input.join("broken.yml"), "a: [\n");
+        write(&input.join(MANIFEST_FILE_NAME), "{}");
+
+        let output = dir.path().join("copy");
+        let report = anonymize(&input, &output, false).expect("anonymized");
+        assert_eq!(
+            fs::read_to_string(output.join("db.yml")).expect("read"),
+            "host: string-1\nreplica: string-1\nport: 1\nratio: 2.5\ntls: true\n"
+        );
+        assert_eq!(
+            fs::read_to_string(output.join("steps/0.yml")).expect("read"),
+            "- -3\n"
+        );
+        assert_eq!(
+            fs::read_to_string(output.join("motd.txt")).expect("read"),
+            "text-1\n"
+        );
+        assert_eq!(report.unparsed, vec!["broken.yml"]);
+        assert_eq!(report.skipped[0].path, MANIFEST_FILE_NAME);
+
+        let hashed = dir.path().join("hashed");
+        anonymize(&input, &hashed, true).expect("anonymized");
+        let db = format!("{}.yml", hash_segments("db"));
+        let contents = fs::read_to_string(hashed.join(&db)).expect("hashed file name");
+        assert!(contents.starts_with(&format!("{}: string-1\n", hash_segments("host"))));
+        assert!(hashed.join(hash_segments("steps")).join("0.yml").is_file());
+
+        let err = anonymize(&input, &hashed, false).expect_err("output exists");
+        assert_eq!(err.code, "E216");
+        let err = anonymize(&input, &input.join("copy"), false).expect_err("inside input");
+        assert_eq!(err.code, "E217");
+    }
+}
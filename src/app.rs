@@ -1,16 +1,66 @@
-use crate::cli::{Cli, Command, DiffArgs, ExplainArgs, PackArgs, ValidateArgs};
-use crate::config::{DiffFormat, OutputFormat};
-use crate::diagnostics::{Category, Diagnostic, ExitCode, Severity};
-use crate::engine::{build, BuildOutcome};
+use crate::anonymize::anonymize;
+use crate::catalog::Catalog;
+use crate::cli::{
+    AnonymizeArgs, BuildFlags, CanonicalizeArgs, CheckFileArgs, Cli, CodesArgs, Command,
+    DaemonAction, DaemonArgs, DiffArgs, EnvReportArgs, ExplainArgs, ExplainCodeArgs, ExportEnvArgs,
+    FlattenArgs, HashArgs, HeaderFlags, ManifestArgs, OrphansArgs, PackArgs, PlanMvArgs,
+    UnflattenArgs, ValidateArgs, VerifyArgs, WatchArgs,
+};
+use crate::codes;
+use crate::compress::{self, Compressor};
+use crate::config::{
+    BuildOptions, CanonicalOptions, Compression, DiagnosticsTarget, DiffFormat, FixMode,
+    JsonLayout, OutputFormat, RootMode, WhatIf,
+};
+#[cfg(unix)]
+use crate::daemon;
+use crate::diagnostics::{Category, Diagnostic, ExitCode, Severity, SuggestedEdit};
+use crate::diff::{differences, directory_mode_changes, first_difference, first_difference_fast};
+use crate::emitter::{EmitError, EmitterOptions};
+use crate::engine::{
+    apply_key_orders, build, build_with_cache, value_kind, BuildOutcome, ParseCache, ParseStats,
+    CACHE_FILE_NAME, ROOT_INLINE_LOCATION,
+};
+use crate::env_report::{env_report, EnvReport};
+use crate::export_env::{env_vars, render, ExportOptions};
+use crate::flat::{unflatten, write_flat};
+use crate::gha::GhaReport;
+use crate::hash::{fingerprint, path_hashes};
+use crate::keypath::{KeyPath, Missing};
+use crate::manifest::{Manifest, MANIFEST_FILE_NAME};
+use crate::metrics::{CountingWriter, RunMetrics};
+use crate::orphans::{find_orphans, OrphanReport};
+use crate::overlay::overlay;
+use crate::plan::{plan_move, MovePlan};
+use crate::preserve::Preserved;
 use crate::scaffold;
-use crate::serializer::{canonicalize_yaml, emit_json, emit_yaml};
-use clap::Parser;
+use crate::serializer::{
+    canonicalize_yaml_into, emission_order, emit_yaml, header_fingerprint, json_losses, write_json,
+    write_json_compact, write_ndjson, write_yaml, write_yaml_documents, write_yaml_preserved,
+    Header, HeaderFields, HEADER_PLACEHOLDERS,
+};
+use crate::source_map::{self, source_map};
+use crate::split;
+use crate::table::{write_table, Delimiter, SourceIndex};
+use crate::timestamp::utc_from_unix_seconds;
+use crate::unwrap::{unwrap_fragment, Unwrap};
+use crate::watch::{self, TreeStamp};
+use clap::{Parser, ValueEnum};
 use serde::Serialize;
 use serde_yaml::{Mapping, Value};
-use std::cmp::Ordering;
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::fs;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// JSON output layouts this build can produce, selected with `--output-schema-version`.
+/// Within a version, fields are only ever added; removing, renaming, or retyping one
+/// needs a new version.
+const OUTPUT_SCHEMA_VERSIONS: &[u32] = &[1];
+const OUTPUT_SCHEMA_VERSION: u32 = 1;
 
 pub fn run_from_env() -> i32 {
     let cli = Cli::parse();
@@ -18,249 +68,2347 @@ pub fn run_from_env() -> i32 {
 }
 
 pub fn run(cli: Cli) -> ExitCode {
+    let reporter = match Reporter::new(&cli) {
+        Ok(reporter) => reporter,
+        Err(diagnostic) => return Reporter::default().fatal(*diagnostic),
+    };
+    if let Some(version) = cli.output_schema_version {
+        if !OUTPUT_SCHEMA_VERSIONS.contains(&version) {
+            let supported = OUTPUT_SCHEMA_VERSIONS
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            return reporter.fatal(
+                Diagnostic::error(
+                    "E092",
+                    "unsupported output schema version",
+                    Category::InvalidInput,
+                )
+                .with_cause(format!(
+                    "--output-schema-version {version} was requested; fyaml {APP_VERSION} produces version {supported}."
+                ))
+                .with_action(format!(
+                    "Pass --output-schema-version {OUTPUT_SCHEMA_VERSION}, or use a fyaml release that supports version {version}."
+                )),
+            );
+        }
+    }
+
     match cli.command {
-        Command::Pack(args) => run_pack(args),
-        Command::Validate(args) => run_validate(args),
-        Command::Explain(args) => run_explain(args),
-        Command::Diff(args) => run_diff(args),
-        Command::Scaffold(args) => run_scaffold(args),
+        Command::Pack(args) => run_pack(args, &reporter),
+        Command::Validate(args) => run_validate(args, &reporter),
+        Command::CheckFile(args) => run_check_file(args, &reporter),
+        Command::Canonicalize(args) => run_canonicalize(args, &reporter),
+        Command::Flatten(args) => run_flatten(args, &reporter),
+        Command::Unflatten(args) => run_unflatten(args, &reporter),
+        Command::Explain(args) => run_explain(args, &reporter),
+        Command::Diff(args) => run_diff(args, &reporter),
+        Command::Scaffold(args) => run_scaffold(args, &reporter),
+        Command::PlanMv(args) => run_plan_mv(args, &reporter),
+        Command::Manifest(args) => run_manifest(args, &reporter),
+        Command::Hash(args) => run_hash(args, &reporter),
+        Command::ExplainCode(args) => run_explain_code(args, &reporter),
+        Command::Codes(args) => run_codes(args, &reporter),
+        Command::Daemon(args) => run_daemon(args, &reporter),
+        Command::Watch(args) => run_watch(args, &reporter),
+        Command::Orphans(args) => run_orphans(args, &reporter),
+        Command::Anonymize(args) => run_anonymize(args, &reporter),
+        Command::EnvReport(args) => run_env_report(args, &reporter),
+        Command::Verify(args) => run_verify(args, &reporter),
+        Command::ExportEnv(args) => run_export_env(args, &reporter),
+    }
+}
+
+fn run_pack(args: PackArgs, reporter: &Reporter) -> ExitCode {
+    let started = Instant::now();
+    let mut options = match resolve_build_options(&args.flags) {
+        Ok(options) => options,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+    let header = match resolve_header(&args.header, &args.dir) {
+        Ok(header) => header,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+    options.output = args
+        .output
+        .as_deref()
+        .or(args.output_dir.as_deref())
+        .map(watch::resolve);
+    options.source_positions = args.source_map.is_some();
+    let mut outcome = build_dir(&args.dir, &options, &args.flags);
+    if !args.overlays.is_empty() {
+        let overlays = args
+            .overlays
+            .iter()
+            .map(|dir| (dir.clone(), build_dir(dir, &options, &args.flags)))
+            .collect();
+        outcome = overlay(&args.dir, outcome, overlays, args.overlay_merge);
+    }
+    let mut metrics = RunMetrics::new(
+        "pack",
+        outcome.fragments.len(),
+        &outcome.diagnostics,
+        Duration::ZERO,
+    );
+
+    let diagnostics = if args.gha_output {
+        outcome.diagnostics.clone()
+    } else {
+        Vec::new()
+    };
+    let (code, packed) = pack_outcome(&args, &options, &header, outcome, reporter);
+    metrics.duration = started.elapsed();
+    metrics.output_bytes = Some(packed.bytes);
+    let code = write_metrics(args.metrics_out.as_deref(), &metrics, code, reporter);
+    if !args.gha_output {
+        return code;
+    }
+    let report = GhaReport {
+        command: "pack",
+        diagnostics: &diagnostics,
+        output_path: args
+            .output
+            .as_ref()
+            .or(args.output_dir.as_ref())
+            .map(|path| path.display().to_string()),
+        content_hash: packed.content_hash,
+    };
+    write_gha(&report, code, reporter)
+}
+
+/// What [`pack_outcome`] wrote.
+#[derive(Default)]
+struct Packed {
+    bytes: u64,
+    content_hash: Option<String>,
+}
+
+/// Reports the build and emits the packed document, returning the exit code and what was
+/// written.
+fn pack_outcome(
+    args: &PackArgs,
+    options: &BuildOptions,
+    header: &Header,
+    outcome: BuildOutcome,
+    reporter: &Reporter,
+) -> (ExitCode, Packed) {
+    if has_errors(&outcome.diagnostics) {
+        reporter.print_diagnostics_human(&outcome.diagnostics);
+        return (
+            ExitCode::from_diagnostics(&outcome.diagnostics),
+            Packed::default(),
+        );
+    }
+
+    reporter.print_warnings_human(&outcome.diagnostics);
+
+    let mut sources = pack_sources(&outcome, options);
+    if args.select.is_some() || args.wrap_as_string.is_some() || args.prefix.is_some() {
+        // Preserved comments and anchors are filed under key paths these options move.
+        sources = sources.with_preserved(Preserved::default());
+    }
+    let Some(value) = outcome.value else {
+        return (ExitCode::Internal, Packed::default());
+    };
+
+    let canonical = args.canonical.to_canonical_options(options.jobs);
+    let mut value = emission_order(value, options, &canonical);
+    apply_key_orders(&mut value, &outcome.key_orders);
+    let positions = args
+        .source_map
+        .as_ref()
+        .map(|file| (file, source_map(&value, &outcome.positions)));
+    if let Some(path) = &args.select {
+        value = match path.select(&value) {
+            Ok(selected) => selected.clone(),
+            Err(missing) => {
+                return (
+                    reporter.fatal(select_error(path, &missing)),
+                    Packed::default(),
+                )
+            }
+        };
+    }
+    if let Some(path) = &args.wrap_as_string {
+        let text = match emit_yaml(&value, &Header::none(), &canonical) {
+            Ok(text) => text,
+            Err(EmitError::Yaml(err)) => {
+                return (reporter.fatal(yaml_failed(err)), Packed::default())
+            }
+            Err(EmitError::Io(err)) => {
+                return (
+                    reporter.fatal(write_failed("--wrap-as-string", err)),
+                    Packed::default(),
+                )
+            }
+        };
+        value = path.wrap(Value::String(text));
+    }
+    if let Some(prefix) = &args.prefix {
+        value = prefix.wrap(value);
+    }
+
+    let format = match (args.explode_docs, args.format) {
+        (false, format) => format,
+        (true, OutputFormat::Yaml) => OutputFormat::YamlDocuments,
+        (true, _) => {
+            let cause =
+                "--explode-docs writes YAML documents; --format ndjson is the JSON equivalent.";
+            return (
+                reporter.fatal(explode_error(cause.to_string())),
+                Packed::default(),
+            );
+        }
+    };
+    if let (true, Some(output)) = (args.check, &args.output) {
+        return check_output(
+            &value, output, format, header, &canonical, &sources, reporter,
+        );
+    }
+    let written = match &args.output_dir {
+        Some(dir) => emit_split(&value, dir, format, header, &canonical, &sources, reporter),
+        None => {
+            let destination = match (args.output.as_deref(), args.compress) {
+                (Some(output), Some(compression)) => Destination::Compressed(output, compression),
+                (Some(output), None) => Destination::File(output),
+                (None, _) => Destination::Stdout,
+            };
+            emit_to(
+                &value,
+                destination,
+                format,
+                header,
+                &canonical,
+                &sources,
+                reporter,
+            )
+        }
+    };
+    if let (Ok(_), Some((file, map))) = (&written, positions) {
+        if let Err(err) = source_map::write(file, &map) {
+            let diag = Diagnostic::error("E322", "unable to write source map", Category::Write)
+                .with_location(file.display().to_string())
+                .with_cause(err.to_string())
+                .with_action("Check that the directory exists and is writable.");
+            return (reporter.fatal(diag), Packed::default());
+        }
+    }
+    match written {
+        Ok(bytes) => (
+            ExitCode::Success,
+            Packed {
+                bytes,
+                content_hash: Some(fingerprint(&value)),
+            },
+        ),
+        Err(code) => (code, Packed::default()),
+    }
+}
+
+/// `pack --check`: renders the document in memory and compares it with `output`, which is
+/// left as it is.
+fn check_output(
+    value: &Value,
+    output: &Path,
+    format: OutputFormat,
+    header: &Header,
+    canonical: &CanonicalOptions,
+    sources: &SourceIndex,
+    reporter: &Reporter,
+) -> (ExitCode, Packed) {
+    let mut rendered = Vec::new();
+    let bytes = match emit_to(
+        value,
+        Destination::Memory(&mut rendered),
+        format,
+        header,
+        canonical,
+        sources,
+        reporter,
+    ) {
+        Ok(bytes) => bytes,
+        Err(code) => return (code, Packed::default()),
+    };
+    let packed = Packed {
+        bytes,
+        content_hash: Some(fingerprint(value)),
+    };
+    let location = output.display().to_string();
+    let existing = match compress::read(output) {
+        Ok(existing) if existing == rendered => {
+            println!("up to date: {location}");
+            return (ExitCode::Success, packed);
+        }
+        Ok(existing) => existing,
+        Err(err) => {
+            let diag =
+                stale_output(&location).with_cause(format!("{location} could not be read: {err}."));
+            return (reporter.fatal(diag), packed);
+        }
+    };
+
+    let line = first_differing_line(&existing, &rendered);
+    let mut diag = stale_output(&location).with_cause(format!(
+        "{location} first differs from the packed document at line {line}."
+    ));
+    let parsed = match format {
+        OutputFormat::Yaml | OutputFormat::Json | OutputFormat::JsonCompact => {
+            serde_yaml::from_slice::<Value>(&existing)
+                .ok()
+                .zip(serde_yaml::from_slice::<Value>(&rendered).ok())
+        }
+        _ => None,
+    };
+    if let Some((existing, rendered)) = parsed {
+        let changed = differences(&existing, &rendered);
+        diag = diag.with_context(if changed.is_empty() {
+            "Only the formatting differs; the documents are equal.".to_string()
+        } else {
+            format!(
+                "Differences: {}",
+                changed
+                    .iter()
+                    .map(|difference| format!("{} ({})", difference.path, difference.reason))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        });
+    }
+    (reporter.fatal(diag), packed)
+}
+
+fn stale_output(location: &str) -> Diagnostic {
+    Diagnostic::error("E130", "output file is out of date", Category::InvalidInput)
+        .with_location(location.to_string())
+        .with_action("Re-run `fyaml pack` without --check and commit the result.")
+}
+
+/// The one-based number of the first line where `left` and `right` differ.
+fn first_differing_line(left: &[u8], right: &[u8]) -> usize {
+    let mut left = left.split(|&b| b == b'\n');
+    let mut right = right.split(|&b| b == b'\n');
+    let mut line = 1;
+    while let (Some(a), Some(b)) = (left.next(), right.next()) {
+        if a != b {
+            break;
+        }
+        line += 1;
+    }
+    line
+}
+
+/// Writes each top-level key of `value` to its own file in `dir`, then the `_index.yml`
+/// manifest, returning the number of bytes written across all of them.
+fn emit_split(
+    value: &Value,
+    dir: &Path,
+    format: OutputFormat,
+    header: &Header,
+    canonical: &CanonicalOptions,
+    sources: &SourceIndex,
+    reporter: &Reporter,
+) -> Result<u64, ExitCode> {
+    let Value::Mapping(map) = value else {
+        let diag = Diagnostic::error(
+            "E318",
+            "--output-dir requires a mapping document",
+            Category::InvalidInput,
+        )
+        .with_location(dir.display().to_string())
+        .with_cause(format!(
+            "--output-dir writes one file per top-level key, but the packed document is a {}.",
+            value_kind(value)
+        ))
+        .with_action("Write the document with -o, or --select a mapping to split.");
+        return Err(reporter.fatal(diag));
+    };
+    if let Err(err) = fs::create_dir_all(dir) {
+        return Err(reporter.fatal(write_failed(dir.display(), err)));
+    }
+    let mut bytes = 0;
+    let mut index = Mapping::new();
+    for (key, name) in split::file_names(map, format.extension()) {
+        let child = &map[&key];
+        bytes += emit_counted(
+            child,
+            Some(&dir.join(&name)),
+            format,
+            header,
+            canonical,
+            sources,
+            reporter,
+        )?;
+        index.insert(key, Value::String(name));
+    }
+    bytes += emit_counted(
+        &Value::Mapping(index),
+        Some(&dir.join(split::INDEX_FILE)),
+        OutputFormat::Yaml,
+        header,
+        canonical,
+        sources,
+        reporter,
+    )?;
+    Ok(bytes)
+}
+
+fn select_error(path: &KeyPath, missing: &Missing) -> Diagnostic {
+    let diag = Diagnostic::error(
+        "E125",
+        "selected key path is not in the packed document",
+        Category::InvalidInput,
+    )
+    .with_derived_key_path(path.to_string())
+    .with_cause(format!(
+        "The document has `{}`, but no `{path}` under it.",
+        missing.found
+    ))
+    .with_action("Check the key path against `fyaml flatten` output.");
+    match missing.parent {
+        Value::Mapping(map) => diag.with_context(format!(
+            "Keys at `{}`: {}",
+            missing.found,
+            map.keys()
+                .map(|key| match key {
+                    Value::String(text) => text.clone(),
+                    other => serde_json::to_string(other).unwrap_or_default(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+        Value::Sequence(items) => diag.with_context(format!(
+            "`{}` is a sequence of {} item(s)",
+            missing.found,
+            items.len()
+        )),
+        _ => diag,
+    }
+}
+
+/// Writes `metrics` to `path`, if given. Failing to write them fails a run that had
+/// otherwise succeeded; a run that already failed keeps its own exit code.
+fn write_metrics(
+    path: Option<&Path>,
+    metrics: &RunMetrics,
+    code: ExitCode,
+    reporter: &Reporter,
+) -> ExitCode {
+    let Some(path) = path else {
+        return code;
+    };
+    let Err(err) = metrics.write_textfile(path) else {
+        return code;
+    };
+    let diag = Diagnostic::error("E311", "unable to write metrics file", Category::Write)
+        .with_location(path.display().to_string())
+        .with_cause(err.to_string())
+        .with_action("Check that the directory exists and is writable.");
+    let failed = reporter.fatal(diag);
+    if code == ExitCode::Success {
+        failed
+    } else {
+        code
+    }
+}
+
+/// Writes the GitHub Actions outputs and summary for `report`. Like [`write_metrics`], a
+/// failure only changes the exit code of a run that had otherwise succeeded.
+fn write_gha(report: &GhaReport, code: ExitCode, reporter: &Reporter) -> ExitCode {
+    let Err(cause) = report.write() else {
+        return code;
+    };
+    let diag = Diagnostic::error(
+        "E320",
+        "unable to write GitHub Actions outputs",
+        Category::Write,
+    )
+    .with_cause(cause)
+    .with_action("Run inside a GitHub Actions step, or drop --gha-output.");
+    let failed = reporter.fatal(diag);
+    if code == ExitCode::Success {
+        failed
+    } else {
+        code
+    }
+}
+
+/// Where each leaf of a packed document came from, for CSV and TSV output.
+fn pack_sources(outcome: &BuildOutcome, options: &BuildOptions) -> SourceIndex {
+    let root_file = match options.root_mode {
+        RootMode::FileRoot if options.root_inline.is_some() => {
+            Some(ROOT_INLINE_LOCATION.to_string())
+        }
+        RootMode::FileRoot => options
+            .root_file
+            .as_ref()
+            .map(|file| file.display().to_string()),
+        _ => None,
+    };
+    SourceIndex::from_derived_keys(&outcome.explain.derived_keys, root_file)
+        .with_preserved(outcome.preserved.clone())
+}
+
+/// Writes the packed document to `output`, or stdout, reporting any failure. `sources`
+/// fills the `source_file` column of CSV and TSV output.
+fn emit_document(
+    value: &Value,
+    output: Option<&Path>,
+    format: OutputFormat,
+    header: &Header,
+    canonical: &CanonicalOptions,
+    sources: &SourceIndex,
+    reporter: &Reporter,
+) -> ExitCode {
+    match emit_counted(value, output, format, header, canonical, sources, reporter) {
+        Ok(_) => ExitCode::Success,
+        Err(code) => code,
+    }
+}
+
+/// Like [`emit_document`], returning the number of bytes written.
+fn emit_counted(
+    value: &Value,
+    output: Option<&Path>,
+    format: OutputFormat,
+    header: &Header,
+    canonical: &CanonicalOptions,
+    sources: &SourceIndex,
+    reporter: &Reporter,
+) -> Result<u64, ExitCode> {
+    let destination = output.map_or(Destination::Stdout, Destination::File);
+    emit_to(
+        value,
+        destination,
+        format,
+        header,
+        canonical,
+        sources,
+        reporter,
+    )
+}
+
+/// Where [`emit_to`] writes the document.
+enum Destination<'a> {
+    Stdout,
+    File(&'a Path),
+    /// Piped through a compression program into the file, for `pack --compress`.
+    Compressed(&'a Path, Compression),
+    /// Rendered in memory, for `pack --check`.
+    Memory(&'a mut Vec<u8>),
+}
+
+fn emit_to(
+    value: &Value,
+    destination: Destination<'_>,
+    format: OutputFormat,
+    header: &Header,
+    canonical: &CanonicalOptions,
+    sources: &SourceIndex,
+    reporter: &Reporter,
+) -> Result<u64, ExitCode> {
+    let items = match (format, value) {
+        (OutputFormat::Ndjson, Value::Sequence(items)) => items.as_slice(),
+        (OutputFormat::Ndjson, other) => {
+            let diag = Diagnostic::error(
+                "E316",
+                "--format ndjson requires a sequence document",
+                Category::InvalidInput,
+            )
+            .with_cause(format!(
+                "NDJSON writes one line per sequence item, but the packed document is a {}.",
+                value_kind(other)
+            ))
+            .with_action("Pack with --root-mode seq-root, select a sequence with --select, or use --format json-compact.");
+            return Err(reporter.fatal(diag));
+        }
+        (OutputFormat::YamlDocuments, Value::Sequence(items)) => items.as_slice(),
+        (OutputFormat::YamlDocuments, other) => {
+            return Err(reporter.fatal(explode_error(format!(
+                "--explode-docs writes one document per sequence item, but the packed document is a {}.",
+                value_kind(other)
+            ))));
+        }
+        _ => &[],
+    };
+    if matches!(
+        format,
+        OutputFormat::Json | OutputFormat::JsonCompact | OutputFormat::Ndjson
+    ) {
+        let diagnostics: Vec<Diagnostic> = json_losses(value)
+            .into_iter()
+            .map(|loss| {
+                let diagnostic = Diagnostic::error(
+                    "E314",
+                    "packed document cannot be written as JSON without loss",
+                    Category::InvalidInput,
+                )
+                .with_cause(loss.reason)
+                .with_action("Use --format yaml, or change the key or value in its fragment.");
+                match loss.key_path.as_str() {
+                    "" => diagnostic,
+                    path => diagnostic
+                        .with_context(format!("Key path: {path}"))
+                        .with_derived_key_path(path),
+                }
+            })
+            .collect();
+        if !diagnostics.is_empty() {
+            reporter.print_diagnostics_human(&diagnostics);
+            return Err(ExitCode::from_diagnostics(&diagnostics));
+        }
+    }
+
+    // Streamed straight to the destination so the document is never held twice in memory.
+    let mut compressor = None;
+    let (out, location): (Box<dyn std::io::Write + '_>, String) = match destination {
+        Destination::Compressed(output_path, compression) => {
+            let file = match fs::File::create(output_path) {
+                Ok(file) => file,
+                Err(err) => return Err(reporter.fatal(write_failed(output_path.display(), err))),
+            };
+            match Compressor::new(compression, file) {
+                Ok(created) => (
+                    Box::new(compressor.insert((created, output_path)).0.by_ref()),
+                    output_path.display().to_string(),
+                ),
+                Err(err) => return Err(reporter.fatal(compress_failed(output_path.display(), err))),
+            }
+        }
+        Destination::File(output_path) => match fs::File::create(output_path) {
+            Ok(file) => (
+                Box::new(BufWriter::new(file)),
+                output_path.display().to_string(),
+            ),
+            Err(err) => return Err(reporter.fatal(write_failed(output_path.display(), err))),
+        },
+        Destination::Stdout => (
+            Box::new(BufWriter::new(std::io::stdout().lock())),
+            "stdout".to_string(),
+        ),
+        Destination::Memory(buffer) => (Box::new(buffer), "memory".to_string()),
+    };
+    let mut out = CountingWriter::new(out);
+
+    let written = match format {
+        OutputFormat::Yaml | OutputFormat::YamlDocuments => {
+            let yaml = if format == OutputFormat::YamlDocuments {
+                write_yaml_documents(&mut out, items, header, canonical)
+            } else {
+                // Recorded for `fyaml verify`; several documents have no single fingerprint.
+                let header = header.clone().with_fingerprint(&fingerprint(value));
+                match sources.preserved() {
+                    preserved if preserved.is_empty() => {
+                        write_yaml(&mut out, value, &header, canonical)
+                    }
+                    preserved => {
+                        write_yaml_preserved(&mut out, value, &header, canonical, preserved)
+                    }
+                }
+            };
+            match yaml {
+                Ok(()) => Ok(()),
+                Err(EmitError::Io(err)) => Err(err),
+                Err(EmitError::Yaml(err)) => return Err(reporter.fatal(yaml_failed(err))),
+            }
+        }
+        OutputFormat::Json | OutputFormat::JsonCompact | OutputFormat::Ndjson => {
+            let json = match format {
+                OutputFormat::JsonCompact => write_json_compact(&mut out, value, canonical),
+                OutputFormat::Ndjson => write_ndjson(&mut out, items, canonical),
+                _ => write_json(&mut out, value, canonical),
+            };
+            match json {
+                Ok(()) => Ok(()),
+                Err(err) if err.is_io() => Err(err.into()),
+                Err(err) => {
+                    let diag = Diagnostic::error(
+                        "E301",
+                        "unable to serialize JSON output",
+                        Category::Write,
+                    )
+                    .with_cause(err.to_string())
+                    .with_action(
+                        "Ensure YAML mapping keys are JSON-compatible strings when using --format json.",
+                    );
+                    return Err(reporter.fatal(diag));
+                }
+            }
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let delimiter = if format == OutputFormat::Csv {
+                Delimiter::Comma
+            } else {
+                Delimiter::Tab
+            };
+            let emitter_options = EmitterOptions {
+                float_format: canonical.float_format,
+                ..EmitterOptions::default()
+            };
+            match write_table(&mut out, value, delimiter, sources, &emitter_options) {
+                Ok(()) => Ok(()),
+                Err(EmitError::Io(err)) => Err(err),
+                Err(EmitError::Yaml(err)) => return Err(reporter.fatal(yaml_failed(err))),
+            }
+        }
+    };
+
+    if let Err(err) = written.and_then(|()| out.flush()) {
+        return Err(reporter.fatal(write_failed(location, err)));
+    }
+    let count = out.count();
+    drop(out);
+    match compressor {
+        // Reported at its compressed size, as stored.
+        Some((compressor, output_path)) => compressor
+            .finish()
+            .and_then(|()| fs::metadata(output_path))
+            .map(|metadata| metadata.len())
+            .map_err(|err| reporter.fatal(compress_failed(location, err))),
+        None => Ok(count),
+    }
+}
+
+fn compress_failed(location: impl std::fmt::Display, err: std::io::Error) -> Diagnostic {
+    Diagnostic::error("E323", "unable to compress output", Category::Write)
+        .with_location(location.to_string())
+        .with_cause(err.to_string())
+        .with_action("Check that the -o path is writable and its disk has space, then retry.")
+}
+
+fn explode_error(cause: String) -> Diagnostic {
+    Diagnostic::error(
+        "E317",
+        "--explode-docs requires a sequence document written as YAML",
+        Category::InvalidInput,
+    )
+    .with_cause(cause)
+    .with_action(
+        "Pack with --root-mode seq-root or --multi-doc all, or select a sequence with --select.",
+    )
+}
+
+fn yaml_failed(err: serde_yaml::Error) -> Diagnostic {
+    Diagnostic::error(
+        "E300",
+        "unable to serialize YAML output",
+        Category::Internal,
+    )
+    .with_cause(err.to_string())
+    .with_action("Report this issue; serialization should succeed for parsed input.")
+}
+
+fn write_failed(location: impl std::fmt::Display, err: std::io::Error) -> Diagnostic {
+    Diagnostic::error("E302", "unable to write output file", Category::Write)
+        .with_location(location.to_string())
+        .with_cause(err.to_string())
+        .with_action("Check path permissions and available disk space.")
+}
+
+fn run_validate(args: ValidateArgs, reporter: &Reporter) -> ExitCode {
+    let mut options = match resolve_build_options(&args.flags) {
+        Ok(options) => options,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+    options.only = args.only.clone();
+    let started = Instant::now();
+    let mut fix_failures = Vec::new();
+    for dir in &args.dirs {
+        match args.fix {
+            Some(FixMode::Unwrap) => {
+                fix_failures.extend(unwrap_fragments(dir, &options, &args.flags));
+            }
+            Some(FixMode::Collisions) => fix_failures.extend(apply_suggestions(
+                dir,
+                &options,
+                &args.flags,
+                usize::from(args.apply_suggestion),
+            )),
+            None => {}
+        }
+    }
+    let (per_root, stats) = validate_roots(&args, &options);
+    let mut diagnostics = Vec::new();
+    for (dir, mut root_diagnostics) in args.dirs.iter().zip(per_root) {
+        if !args.what_if.is_empty() {
+            root_diagnostics.extend(what_if_reports(dir, &options, &args.what_if));
+        }
+        if args.dirs.len() > 1 {
+            qualify_locations(dir, &mut root_diagnostics);
+        }
+        diagnostics.append(&mut root_diagnostics);
+    }
+    diagnostics.append(&mut fix_failures);
+
+    let code = if has_errors(&diagnostics) {
+        ExitCode::from_diagnostics(&diagnostics)
+    } else {
+        ExitCode::Success
+    };
+
+    match (args.json, args.json_layout) {
+        (true, JsonLayout::Array) => reporter.print_diagnostics_json(&diagnostics),
+        (true, JsonLayout::Envelope) => {
+            #[derive(Serialize)]
+            struct ValidateJson {
+                diagnostics: Vec<Diagnostic>,
+            }
+
+            let report = ValidateJson {
+                diagnostics: reporter.localize_all(&diagnostics),
+            };
+            let envelope = JsonEnvelope::new(report, &diagnostics, code, &options, started);
+            reporter.print_json(&envelope);
+        }
+        (false, _) => reporter.print_diagnostics_human(&diagnostics),
+    }
+    if args.stats {
+        println!(
+            "parsed {} fragment(s), reused {} identical fragment(s)",
+            stats.parsed, stats.reused
+        );
+    }
+
+    let metrics = RunMetrics::new(
+        "validate",
+        stats.parsed + stats.reused,
+        &diagnostics,
+        started.elapsed(),
+    );
+    let code = write_metrics(args.metrics_out.as_deref(), &metrics, code, reporter);
+    if !args.gha_output {
+        return code;
+    }
+    let report = GhaReport {
+        command: "validate",
+        diagnostics: &diagnostics,
+        output_path: None,
+        content_hash: None,
+    };
+    write_gha(&report, code, reporter)
+}
+
+/// How many changed key paths a `W026` context lists.
+const WHAT_IF_CHANGES_SHOWN: usize = 5;
+
+/// `validate --what-if`: packs `dir` again with each flag toggled, reporting the change to
+/// the document (`W026`) or that there is none (`I201`).
+fn what_if_reports(dir: &Path, options: &BuildOptions, toggles: &[WhatIf]) -> Vec<Diagnostic> {
+    let canonical = CanonicalOptions::default();
+    let mut cache = ParseCache::default();
+    let packed = |outcome: BuildOutcome| {
+        let failed = has_errors(&outcome.diagnostics);
+        let read = outcome
+            .fragments
+            .into_iter()
+            .map(|fragment| fragment.path)
+            .collect::<HashSet<_>>();
+        let value = outcome
+            .value
+            .filter(|_| !failed)
+            .map(|value| canonicalize_yaml_into(value, &canonical));
+        (value, read)
+    };
+    let (base, base_read) = packed(build_with_cache(dir, options, &mut cache));
+
+    let mut reports = Vec::new();
+    for toggle in toggles {
+        let mut toggled = options.clone();
+        let setting = if toggle.toggle(&mut toggled) {
+            format!("with {}", toggle.flag())
+        } else {
+            format!("without {}", toggle.flag())
+        };
+        let (value, read) = packed(build_with_cache(dir, &toggled, &mut cache));
+
+        let mut context = Vec::new();
+        for (label, mut paths) in [
+            (
+                "Newly read",
+                read.difference(&base_read).collect::<Vec<_>>(),
+            ),
+            ("No longer read", base_read.difference(&read).collect()),
+        ] {
+            if !paths.is_empty() {
+                paths.sort();
+                let paths = paths.into_iter().map(String::as_str).collect::<Vec<_>>();
+                context.push(format!("{label}: {}", paths.join(", ")));
+            }
+        }
+        let cause = match (&base, &value) {
+            (Some(base), Some(value)) => {
+                let changes = differences(base, value);
+                if changes.is_empty() {
+                    reports.push(
+                        Diagnostic::info("I201", format!("packed document is the same {setting}"))
+                            .with_location(dir.display().to_string())
+                            .with_cause(format!("Packing {setting} gives an identical document."))
+                            .with_action("No action needed."),
+                    );
+                    continue;
+                }
+                let mut shown = changes
+                    .iter()
+                    .take(WHAT_IF_CHANGES_SHOWN)
+                    .map(|change| format!("{} ({})", change.path, change.reason))
+                    .collect::<Vec<_>>();
+                if changes.len() > WHAT_IF_CHANGES_SHOWN {
+                    shown.push(format!(
+                        "and {} more",
+                        changes.len() - WHAT_IF_CHANGES_SHOWN
+                    ));
+                }
+                context.push(format!("Changes: {}", shown.join("; ")));
+                format!(
+                    "{} key path(s) of the packed document differ {setting}.",
+                    changes.len()
+                )
+            }
+            (Some(_), None) => format!("The build fails {setting}."),
+            (None, Some(_)) => format!("The build fails as given, but succeeds {setting}."),
+            (None, None) => continue,
+        };
+        let mut diagnostic =
+            Diagnostic::warn("W026", format!("packed document would change {setting}"))
+                .with_location(dir.display().to_string())
+                .with_cause(cause)
+                .with_action(
+                    "Check whether the difference is intended, and pass or drop the flag to match.",
+                );
+        if !context.is_empty() {
+            diagnostic = diagnostic.with_context(context.join(". "));
+        }
+        reports.push(diagnostic);
+    }
+    reports
+}
+
+fn run_check_file(args: CheckFileArgs, reporter: &Reporter) -> ExitCode {
+    let diagnostics = match build_file(&args.file, args.flags.to_build_options()) {
+        Ok(outcome) => outcome.diagnostics,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+
+    if args.json {
+        reporter.print_diagnostics_json(&diagnostics);
+    } else {
+        reporter.print_diagnostics_human(&diagnostics);
+    }
+    if has_errors(&diagnostics) {
+        ExitCode::from_diagnostics(&diagnostics)
+    } else {
+        ExitCode::Success
+    }
+}
+
+fn run_canonicalize(args: CanonicalizeArgs, reporter: &Reporter) -> ExitCode {
+    let header = match resolve_header(&args.header, &args.input) {
+        Ok(header) => header,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+    let outcome = match build_file(&args.input, args.flags.to_build_options()) {
+        Ok(outcome) => outcome,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+    if has_errors(&outcome.diagnostics) {
+        reporter.print_diagnostics_human(&outcome.diagnostics);
+        return ExitCode::from_diagnostics(&outcome.diagnostics);
+    }
+    reporter.print_warnings_human(&outcome.diagnostics);
+
+    let Some(value) = outcome.value else {
+        return ExitCode::Internal;
+    };
+    let canonical = args.canonical.to_canonical_options(args.flags.jobs);
+    let mut value = canonicalize_yaml_into(value, &canonical);
+    apply_key_orders(&mut value, &outcome.key_orders);
+    emit_document(
+        &value,
+        args.output.as_deref(),
+        args.format,
+        &header,
+        &canonical,
+        &SourceIndex::single(args.input.display().to_string()),
+        reporter,
+    )
+}
+
+/// Builds one YAML file on its own, as the root file of its directory with nothing else
+/// listed, so it goes through exactly the per-fragment checks of a normal build. Diagnostic
+/// locations are relative to the current directory, like the path that was given.
+fn build_file(file: &Path, options: BuildOptions) -> Result<BuildOutcome, Box<Diagnostic>> {
+    let file_name = match (fs::metadata(file), file.file_name()) {
+        (Ok(metadata), Some(name)) if metadata.is_file() => PathBuf::from(name),
+        _ => {
+            return Err(Box::new(
+                Diagnostic::error(
+                    "E050",
+                    "input file does not exist or is not a file",
+                    Category::InvalidInput,
+                )
+                .with_location(file.display().to_string())
+                .with_cause("This command reads exactly one existing YAML file.")
+                .with_action("Pass the path of a YAML file."),
+            ))
+        }
+    };
+    let parent = file
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty());
+
+    let options = BuildOptions {
+        root_mode: RootMode::FileRoot,
+        root_file: Some(file_name.clone()),
+        file_list: Some(vec![file_name]),
+        ..options
+    };
+    let mut outcome = build(parent.unwrap_or(Path::new(".")), &options);
+    // The file's siblings are not part of the build, so they are not reported as ignored.
+    outcome
+        .diagnostics
+        .retain(|diagnostic| diagnostic.code != "W050");
+    if let Some(parent) = parent {
+        qualify_locations(parent, &mut outcome.diagnostics);
+    }
+    Ok(outcome)
+}
+
+fn run_flatten(args: FlattenArgs, reporter: &Reporter) -> ExitCode {
+    let options = match resolve_build_options(&args.flags) {
+        Ok(options) => options,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+    let outcome = if args.input.is_file() {
+        match build_file(&args.input, options.clone()) {
+            Ok(outcome) => outcome,
+            Err(diagnostic) => return reporter.fatal(*diagnostic),
+        }
+    } else {
+        build_dir(&args.input, &options, &args.flags)
+    };
+    if has_errors(&outcome.diagnostics) {
+        reporter.print_diagnostics_human(&outcome.diagnostics);
+        return ExitCode::from_diagnostics(&outcome.diagnostics);
+    }
+    reporter.print_warnings_human(&outcome.diagnostics);
+
+    let canonical = args.canonical.to_canonical_options(options.jobs);
+    let value = canonicalize_yaml_into(outcome.value.unwrap_or(Value::Null), &canonical);
+    let emitter_options = EmitterOptions {
+        float_format: canonical.float_format,
+        ..EmitterOptions::default()
+    };
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    let written = match write_flat(&mut out, &value, &emitter_options) {
+        Ok(()) => out.flush(),
+        Err(EmitError::Io(err)) => Err(err),
+        Err(EmitError::Yaml(err)) => return reporter.fatal(yaml_failed(err)),
+    };
+    if let Err(err) = written {
+        return reporter.fatal(write_failed("stdout", err));
+    }
+    ExitCode::Success
+}
+
+fn run_unflatten(args: UnflattenArgs, reporter: &Reporter) -> ExitCode {
+    let header = match resolve_header(&args.header, &args.input) {
+        Ok(header) => header,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+    let read = if args.input == Path::new("-") {
+        let mut contents = String::new();
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .map(|_| contents)
+    } else {
+        fs::read_to_string(&args.input)
+    };
+    let location = args.input.display().to_string();
+    let text = match read {
+        Ok(text) => text,
+        Err(err) => {
+            return reporter.fatal(
+                Diagnostic::error(
+                    "E110",
+                    "unable to read flattened input",
+                    Category::InvalidInput,
+                )
+                .with_location(location)
+                .with_cause(err.to_string())
+                .with_action("Pass a readable file (or `-` for stdin)."),
+            )
+        }
+    };
+    let value = match unflatten(&text, &location) {
+        Ok(value) => value,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+
+    let canonical = args.canonical.to_canonical_options(1);
+    let value = canonicalize_yaml_into(value, &canonical);
+    emit_document(
+        &value,
+        args.output.as_deref(),
+        args.format,
+        &header,
+        &canonical,
+        &SourceIndex::single(args.input.display().to_string()),
+        reporter,
+    )
+}
+
+/// Builds `dir`, through its on-disk parse cache with `--cache`.
+fn build_dir(dir: &Path, options: &BuildOptions, flags: &BuildFlags) -> BuildOutcome {
+    build_cached(dir, options, flags, &mut ParseCache::default())
+}
+
+fn build_cached(
+    dir: &Path,
+    options: &BuildOptions,
+    flags: &BuildFlags,
+    cache: &mut ParseCache,
+) -> BuildOutcome {
+    if !flags.uses_cache() {
+        return build_with_cache(dir, options, cache);
+    }
+
+    let cache_path = dir.join(CACHE_FILE_NAME);
+    let resumed = cache.read_file(&cache_path);
+    let mut outcome = build_with_cache(dir, options, cache);
+    if !dir.is_dir() {
+        return outcome;
+    }
+    if flags.resume_from_cache && !resumed {
+        outcome.diagnostics.push(
+            Diagnostic::warn("W061", "no parse cache to resume from")
+                .with_location(cache_path.display().to_string())
+                .with_cause(
+                    "The cache is missing, damaged, or from another fyaml version, so every fragment was parsed.",
+                )
+                .with_action("Keep --resume-from-cache on the failing run too; the cache it saves is used next time."),
+        );
+    }
+    if let Err(err) = cache.write_file(&cache_path, &outcome.fragments, options.big_integers) {
+        outcome.diagnostics.push(
+            Diagnostic::warn("W060", "unable to update the parse cache")
+                .with_location(cache_path.display().to_string())
+                .with_cause(err.to_string())
+                .with_action("Check directory permissions, or build without --cache."),
+        );
+    }
+    outcome
+}
+
+/// Removes the redundant wrapper key from each fragment of `dir` reported by `W020`, unless
+/// another entry contributes the same key. Returns diagnostics for files it could not write.
+fn unwrap_fragments(dir: &Path, options: &BuildOptions, flags: &BuildFlags) -> Vec<Diagnostic> {
+    let outcome = build_dir(dir, options, flags);
+    let mut failures = Vec::new();
+    for wrapped in &outcome.explain.wrapped_fragments {
+        let path = dir.join(&wrapped.source);
+        if !wrapped.shared_with.is_empty() {
+            eprintln!(
+                "left {} wrapped: `{}` is also contributed by {}",
+                path.display(),
+                wrapped.derived_key_path,
+                wrapped.shared_with.join(", ")
+            );
+            continue;
+        }
+        match unwrap_fragment(&path, &wrapped.key) {
+            Ok(Unwrap::Done) => eprintln!("unwrapped {}", path.display()),
+            Ok(Unwrap::Skipped(reason)) => eprintln!("left {} wrapped: {reason}", path.display()),
+            Err(err) => failures.push(
+                Diagnostic::error("E313", "unable to rewrite fragment", Category::Write)
+                    .with_location(path.display().to_string())
+                    .with_cause(err.to_string())
+                    .with_action("Check that the file is writable."),
+            ),
+        }
+    }
+    failures
+}
+
+/// Applies suggestion `rank` (from 1) of each collision in `dir`. Returns diagnostics for
+/// the moves that failed.
+fn apply_suggestions(
+    dir: &Path,
+    options: &BuildOptions,
+    flags: &BuildFlags,
+    rank: usize,
+) -> Vec<Diagnostic> {
+    let outcome = build_dir(dir, options, flags);
+    let mut failures = Vec::new();
+    for diagnostic in &outcome.diagnostics {
+        if diagnostic.suggestions.is_empty() {
+            continue;
+        }
+        let Some(suggestion) = diagnostic.suggestions.get(rank - 1) else {
+            eprintln!(
+                "left {} [{}] as it is: it has {} suggestion(s)",
+                diagnostic.derived_key_path.as_deref().unwrap_or("."),
+                diagnostic.code,
+                diagnostic.suggestions.len()
+            );
+            continue;
+        };
+        for edit in &suggestion.edits {
+            let SuggestedEdit::Rename { from, to } = edit;
+            let (from, to) = (dir.join(from), dir.join(to));
+            let moved = if to.exists() {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("{} already exists", to.display()),
+                ))
+            } else {
+                fs::rename(&from, &to)
+            };
+            match moved {
+                Ok(()) => eprintln!("renamed {} to {}", from.display(), to.display()),
+                Err(err) => failures.push(
+                    Diagnostic::error("E321", "unable to apply suggestion", Category::Write)
+                        .with_location(from.display().to_string())
+                        .with_cause(err.to_string())
+                        .with_action(format!(
+                            "Apply `{}` by hand, or pick another --apply-suggestion.",
+                            suggestion.title
+                        )),
+                ),
+            }
+        }
+    }
+    failures
+}
+
+/// Diagnostics per root, from the running daemon when there is one.
+fn validate_roots(
+    args: &ValidateArgs,
+    options: &BuildOptions,
+) -> (Vec<Vec<Diagnostic>>, ParseStats) {
+    #[cfg(unix)]
+    if !args.no_daemon {
+        if let Some(delegated) = daemon::validate(&daemon::socket_path(), &args.dirs, options) {
+            return delegated;
+        }
+    }
+
+    let mut cache = ParseCache::default();
+    let diagnostics = args
+        .dirs
+        .iter()
+        .map(|dir| build_cached(dir, options, &args.flags, &mut cache).diagnostics)
+        .collect();
+    (diagnostics, cache.stats())
+}
+
+#[cfg(unix)]
+fn run_daemon(args: DaemonArgs, reporter: &Reporter) -> ExitCode {
+    let socket = daemon::socket_path();
+    let unreachable = |err: std::io::Error| {
+        Diagnostic::error(
+            "E310",
+            "no fyaml daemon is reachable",
+            Category::InvalidInput,
+        )
+        .with_location(socket.display().to_string())
+        .with_cause(err.to_string())
+        .with_action("Start one with `fyaml daemon start`, or check FYAML_DAEMON_SOCKET.")
+    };
+
+    match args.action {
+        DaemonAction::Start => {
+            eprintln!("fyaml daemon listening on {}", socket.display());
+            if let Err(err) = daemon::serve(&socket) {
+                let diag =
+                    Diagnostic::error("E309", "unable to run fyaml daemon", Category::Write)
+                        .with_location(socket.display().to_string())
+                        .with_cause(err.to_string())
+                        .with_action(
+                            "Stop the daemon already using this socket, or set FYAML_DAEMON_SOCKET to another path.",
+                        );
+                return reporter.fatal(diag);
+            }
+        }
+        DaemonAction::Stop => match daemon::stop(&socket) {
+            Ok(()) => println!("fyaml daemon stopped"),
+            Err(err) => return reporter.fatal(unreachable(err)),
+        },
+        DaemonAction::Status => match daemon::status(&socket) {
+            Ok(status) => println!(
+                "fyaml daemon {} on {}: {} cached fragment(s); parsed {}, reused {}",
+                status.version,
+                socket.display(),
+                status.cached_fragments,
+                status.stats.parsed,
+                status.stats.reused
+            ),
+            Err(err) => return reporter.fatal(unreachable(err)),
+        },
+    }
+    ExitCode::Success
+}
+
+#[cfg(not(unix))]
+fn run_daemon(_args: DaemonArgs, reporter: &Reporter) -> ExitCode {
+    let diag = Diagnostic::error("E309", "unable to run fyaml daemon", Category::Write)
+        .with_cause("The daemon needs unix domain sockets, which this platform does not provide.")
+        .with_action("Run fyaml commands without the daemon.");
+    reporter.fatal(diag)
+}
+
+fn run_watch(args: WatchArgs, reporter: &Reporter) -> ExitCode {
+    let mut options = match resolve_build_options(&args.flags) {
+        Ok(options) => options,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+    options.output = args.output.as_deref().map(watch::resolve);
+    if let Err(diagnostic) = resolve_header(&args.header, &args.dir) {
+        return reporter.fatal(*diagnostic);
+    }
+    let canonical = args.canonical.to_canonical_options(options.jobs);
+    // Writing the output must not count as a change when it lives inside the tree.
+    let skip: Vec<PathBuf> = args
+        .output
+        .iter()
+        .chain(&args.diff_against)
+        .map(|path| watch::resolve(path))
+        .collect();
+    let poll = Duration::from_millis(args.poll);
+    let debounce = Duration::from_millis(args.debounce);
+
+    let mut cache = ParseCache::default();
+    let mut stamp = TreeStamp::scan(&args.dir, &skip);
+    let mut last = watch_build(&args, &options, &canonical, &mut cache, None, reporter);
+    loop {
+        let (next, changes) = watch::wait_for_change(&args.dir, &skip, &stamp, poll, debounce);
+        stamp = next;
+        for change in &changes {
+            println!("{} {}", change.kind.as_str(), change.path);
+        }
+        if let Some(value) = watch_build(
+            &args,
+            &options,
+            &canonical,
+            &mut cache,
+            last.as_ref(),
+            reporter,
+        ) {
+            last = Some(value);
+        }
+    }
+}
+
+/// Builds once for `fyaml watch`, reports its diagnostics and a one-line summary, and writes
+/// `-o`. Returns the packed value, or `None` when the build or the write failed.
+fn watch_build(
+    args: &WatchArgs,
+    options: &BuildOptions,
+    canonical: &CanonicalOptions,
+    cache: &mut ParseCache,
+    previous: Option<&Value>,
+    reporter: &Reporter,
+) -> Option<Value> {
+    let outcome = build_cached(&args.dir, options, &args.flags, cache);
+    if has_errors(&outcome.diagnostics) {
+        reporter.print_diagnostics_human(&outcome.diagnostics);
+        let errors = outcome.diagnostics.iter().filter(|d| d.is_error()).count();
+        let kept = if args.output.is_some() {
+            "; kept the last output"
+        } else {
+            ""
+        };
+        println!("build failed: {errors} error(s){kept}");
+        return None;
+    }
+    reporter.print_warnings_human(&outcome.diagnostics);
+
+    let value = outcome.value.as_ref()?.clone();
+    let mut value = emission_order(value, options, canonical);
+    apply_key_orders(&mut value, &outcome.key_orders);
+    // Read before -o is rewritten, which may be the same file.
+    let artifact = args.diff_against.as_deref().map(read_artifact);
+
+    let mut summary = match previous {
+        Some(previous) => format!(
+            "rebuilt: {} key path(s) changed",
+            differences(previous, &value).len()
+        ),
+        None => "built".to_string(),
+    };
+    if let Some(output) = &args.output {
+        // Resolved per build so `{timestamp}` and `{git_sha}` stay current.
+        let header = match resolve_header(&args.header, &args.dir) {
+            Ok(header) => header,
+            Err(diagnostic) => {
+                reporter.print_diagnostics_human(&[*diagnostic]);
+                return None;
+            }
+        };
+        let written = emit_document(
+            &value,
+            Some(output),
+            args.format,
+            &header,
+            canonical,
+            &pack_sources(&outcome, options),
+            reporter,
+        );
+        if !matches!(written, ExitCode::Success) {
+            println!("build failed: unable to write {}", output.display());
+            return None;
+        }
+        summary.push_str(&format!(", wrote {}", output.display()));
+    }
+    println!("{summary}");
+    match artifact {
+        Some(Ok(Some(artifact))) => {
+            let artifact = canonicalize_yaml_into(artifact, canonical);
+            let packed = canonicalize_yaml_into(value.clone(), canonical);
+            for difference in differences(&artifact, &packed) {
+                println!("  {}: {}", difference.path, difference.reason);
+            }
+        }
+        Some(Ok(None)) => {}
+        Some(Err(diagnostic)) => reporter.print_diagnostics_human(&[*diagnostic]),
+        None => {}
+    }
+    Some(value)
+}
+
+/// Loads the `watch --diff-against` file; `None` when it does not exist yet.
+fn read_artifact(path: &Path) -> Result<Option<Value>, Box<Diagnostic>> {
+    let unusable = |cause: String| {
+        Box::new(
+            Diagnostic::error(
+                "E126",
+                "unable to load --diff-against file",
+                Category::InvalidInput,
+            )
+            .with_location(path.display().to_string())
+            .with_cause(cause)
+            .with_action("Pass a packed YAML or JSON document, such as the watch -o file."),
+        )
+    };
+    let contents = match compress::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(unusable(err.to_string())),
+    };
+    serde_yaml::from_str(&contents)
+        .map(Some)
+        .map_err(|err| unusable(err.to_string()))
+}
+
+/// Prefixes root-relative locations with `root` so diagnostics from several roots stay
+/// distinguishable.
+fn qualify_locations(root: &Path, diags: &mut [Diagnostic]) {
+    let qualify = |location: &mut String| {
+        if location == "." {
+            *location = root.display().to_string();
+        } else if !Path::new(location.as_str()).starts_with(root) {
+            *location = root.join(&*location).display().to_string();
+        }
+    };
+    for diagnostic in diags {
+        if let Some(location) = &mut diagnostic.location {
+            qualify(location);
+        }
+        diagnostic.paths.iter_mut().for_each(qualify);
+    }
+}
+
+fn run_explain(args: ExplainArgs, reporter: &Reporter) -> ExitCode {
+    let started = Instant::now();
+    let options = match resolve_build_options(&args.flags) {
+        Ok(options) => options,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+    let outcome = build_dir(&args.dir, &options, &args.flags);
+    let code = if has_errors(&outcome.diagnostics) {
+        ExitCode::from_diagnostics(&outcome.diagnostics)
+    } else {
+        ExitCode::Success
+    };
+
+    if args.json {
+        #[derive(Serialize)]
+        struct ExplainJson<'a> {
+            diagnostics: &'a [Diagnostic],
+            explain: &'a crate::engine::ExplainReport,
+        }
+
+        let diagnostics = reporter.localize_all(&outcome.diagnostics);
+        let report = ExplainJson {
+            diagnostics: &diagnostics,
+            explain: &outcome.explain,
+        };
+        let rendered = match args.json_layout {
+            JsonLayout::Array => serde_json::to_string_pretty(&report),
+            JsonLayout::Envelope => serde_json::to_string_pretty(&JsonEnvelope::new(
+                report,
+                &outcome.diagnostics,
+                code,
+                &options,
+                started,
+            )),
+        };
+
+        match rendered {
+            Ok(json) => println!("{json}"),
+            Err(err) => {
+                let diag =
+                    Diagnostic::error("E303", "unable to render explain JSON", Category::Internal)
+                        .with_cause(err.to_string())
+                        .with_action("Report this issue; JSON serialization should succeed.");
+                reporter.report(&diag);
+                return ExitCode::Internal;
+            }
+        }
+    } else {
+        print_explain_human(&outcome, reporter);
+    }
+    code
+}
+
+/// `--json-layout envelope` output: the command's JSON report, with `summary` and `tool`
+/// alongside its fields.
+#[derive(Serialize)]
+struct JsonEnvelope<'a, T> {
+    #[serde(flatten)]
+    report: T,
+    summary: RunSummary,
+    tool: ToolInfo<'a>,
+}
+
+#[derive(Serialize)]
+struct RunSummary {
+    errors: usize,
+    warnings: usize,
+    info: usize,
+    exit_code: i32,
+    duration_seconds: f64,
+}
+
+#[derive(Serialize)]
+struct ToolInfo<'a> {
+    name: &'static str,
+    version: &'static str,
+    output_schema_version: u32,
+    options: &'a BuildOptions,
+}
+
+impl<'a, T> JsonEnvelope<'a, T> {
+    fn new(
+        report: T,
+        diagnostics: &[Diagnostic],
+        code: ExitCode,
+        options: &'a BuildOptions,
+        started: Instant,
+    ) -> Self {
+        let count = |severity| {
+            diagnostics
+                .iter()
+                .filter(|d| d.severity == severity)
+                .count()
+        };
+        Self {
+            report,
+            summary: RunSummary {
+                errors: count(Severity::Error),
+                warnings: count(Severity::Warn),
+                info: count(Severity::Info),
+                exit_code: code as i32,
+                duration_seconds: started.elapsed().as_secs_f64(),
+            },
+            tool: ToolInfo {
+                name: "fyaml",
+                version: APP_VERSION,
+                output_schema_version: OUTPUT_SCHEMA_VERSION,
+                options,
+            },
+        }
+    }
+}
+
+fn run_diff(args: DiffArgs, reporter: &Reporter) -> ExitCode {
+    let options = match resolve_build_options(&args.flags) {
+        Ok(options) => options,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+
+    let left = build_dir(&args.dir_a, &options, &args.flags);
+    let packed_file = args.dir_b.is_file();
+    let mut right = (!packed_file).then(|| build_dir(&args.dir_b, &options, &args.flags));
+
+    let mut diagnostics = left.diagnostics.clone();
+    if let Some(right) = &right {
+        diagnostics.extend(right.diagnostics.clone());
+    }
+    let packed = match packed_file.then(|| read_packed(&args.dir_b)) {
+        Some(Ok((_, value))) => Some(value),
+        Some(Err(diagnostic)) => {
+            diagnostics.push(*diagnostic);
+            None
+        }
+        None => None,
+    };
+
+    if has_errors(&diagnostics) {
+        match args.format {
+            DiffFormat::Path => reporter.print_diagnostics_human(&diagnostics),
+            DiffFormat::Json => reporter.print_diagnostics_json(&diagnostics),
+        }
+        return ExitCode::from_diagnostics(&diagnostics);
+    }
+
+    let canonical = args.canonical.to_canonical_options(options.jobs);
+    let left_value = canonicalize_yaml_into(left.value.unwrap_or(Value::Null), &canonical);
+    let right_value = canonicalize_yaml_into(
+        packed
+            .or_else(|| right.as_mut().and_then(|right| right.value.take()))
+            .unwrap_or(Value::Null),
+        &canonical,
+    );
+
+    let difference = if args.fast {
+        first_difference_fast(&left_value, &right_value, &canonical.collation)
+    } else {
+        first_difference(&left_value, &right_value, &canonical.collation)
+    };
+    let mode_changes = right.filter(|_| args.directory_modes).map(|right| {
+        directory_mode_changes(
+            &left.explain.directory_modes,
+            &right.explain.directory_modes,
+        )
+    });
+    match args.format {
+        DiffFormat::Path => match &difference {
+            None => println!("equal"),
+            Some(difference) => {
+                println!("different at {}: {}", difference.path, difference.reason);
+            }
+        },
+        DiffFormat::Json => {
+            let mut payload = match &difference {
+                None => serde_json::json!({ "equal": true }),
+                Some(difference) => serde_json::json!({
+                    "equal": false,
+                    "first_difference_path": difference.path,
+                    "reason": difference.reason
+                }),
+            };
+            let compact = difference.is_none() && mode_changes.is_none();
+            if let Some(changes) = mode_changes {
+                payload["directory_mode_changes"] = serde_json::json!(changes);
+            }
+            if compact {
+                println!("{payload}");
+            } else {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&payload).unwrap_or_else(|_| payload.to_string())
+                );
+            }
+        }
+    }
+    if difference.is_some() {
+        ExitCode::InvalidInput
+    } else {
+        ExitCode::Success
+    }
+}
+
+fn run_hash(args: HashArgs, reporter: &Reporter) -> ExitCode {
+    let options = match resolve_build_options(&args.flags) {
+        Ok(options) => options,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+
+    let outcome = build_dir(&args.dir, &options, &args.flags);
+    if has_errors(&outcome.diagnostics) {
+        reporter.print_diagnostics_human(&outcome.diagnostics);
+        return ExitCode::from_diagnostics(&outcome.diagnostics);
+    }
+    reporter.print_warnings_human(&outcome.diagnostics);
+
+    let canonical = args.canonical.to_canonical_options(options.jobs);
+    let value = canonicalize_yaml_into(outcome.value.unwrap_or(Value::Null), &canonical);
+    let hashes = path_hashes(&value, args.depth);
+    if args.json {
+        let payload = serde_json::json!({ "algorithm": "sha256", "subtrees": hashes });
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&payload).unwrap_or_else(|_| payload.to_string())
+        );
+    } else {
+        for entry in hashes {
+            println!("{}  {}", entry.hash, entry.path);
+        }
+    }
+    ExitCode::Success
+}
+
+fn run_verify(args: VerifyArgs, reporter: &Reporter) -> ExitCode {
+    let options = match resolve_build_options(&args.flags) {
+        Ok(options) => options,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+    let location = args.file.display().to_string();
+    let (contents, packed) = match read_packed(&args.file) {
+        Ok(read) => read,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+
+    let outcome = build_dir(&args.dir, &options, &args.flags);
+    if has_errors(&outcome.diagnostics) {
+        reporter.print_diagnostics_human(&outcome.diagnostics);
+        return ExitCode::from_diagnostics(&outcome.diagnostics);
+    }
+    reporter.print_warnings_human(&outcome.diagnostics);
+    let Some(value) = outcome.value else {
+        return ExitCode::Internal;
+    };
+    let canonical = args.canonical.to_canonical_options(options.jobs);
+    let value = emission_order(value, &options, &canonical);
+
+    let expected = fingerprint(&value);
+    let actual = fingerprint(&packed);
+    let mut diagnostics = Vec::new();
+    if let Some(recorded) = header_fingerprint(&contents).filter(|recorded| *recorded != actual) {
+        diagnostics.push(
+            Diagnostic::warn("W027", "packed file was edited after it was packed")
+                .with_location(location.clone())
+                .with_cause(format!(
+                    "The header records {recorded}, but the document below it is {actual}."
+                ))
+                .with_action("Re-run `fyaml pack` instead of editing the packed file."),
+        );
+    }
+    if actual != expected {
+        let changed = differences(&packed, &value);
+        let mut diagnostic = Diagnostic::error(
+            "E128",
+            "packed file does not match its source tree",
+            Category::InvalidInput,
+        )
+        .with_location(location.clone())
+        .with_cause(format!(
+            "{location} is {actual}, but {} packs to {expected}.",
+            args.dir.display()
+        ))
+        .with_action("Re-run `fyaml pack` with the same flags and commit the result.");
+        if !changed.is_empty() {
+            diagnostic = diagnostic.with_context(format!(
+                "Differences: {}",
+                changed
+                    .iter()
+                    .map(|difference| format!("{} ({})", difference.path, difference.reason))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        diagnostics.push(diagnostic);
+    }
+    if has_errors(&diagnostics) {
+        reporter.print_diagnostics_human(&diagnostics);
+        return ExitCode::from_diagnostics(&diagnostics);
+    }
+    reporter.print_warnings_human(&diagnostics);
+    println!(
+        "verified: {location} matches {} ({expected})",
+        args.dir.display()
+    );
+    ExitCode::Success
+}
+
+/// The text and document of a packed file, decompressed if it was compressed.
+fn read_packed(path: &Path) -> Result<(String, Value), Box<Diagnostic>> {
+    let unusable = |cause: String| {
+        Box::new(
+            Diagnostic::error("E129", "unable to load packed file", Category::InvalidInput)
+                .with_location(path.display().to_string())
+                .with_cause(cause)
+                .with_action("Pass the YAML or JSON file `fyaml pack` wrote for this tree."),
+        )
+    };
+    let contents = compress::read_to_string(path).map_err(|err| unusable(err.to_string()))?;
+    let packed = serde_yaml::from_str(&contents).map_err(|err| unusable(err.to_string()))?;
+    Ok((contents, packed))
+}
+
+fn run_export_env(args: ExportEnvArgs, reporter: &Reporter) -> ExitCode {
+    let options = match resolve_build_options(&args.flags) {
+        Ok(options) => options,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+    let outcome = build_dir(&args.dir, &options, &args.flags);
+    if has_errors(&outcome.diagnostics) {
+        reporter.print_diagnostics_human(&outcome.diagnostics);
+        return ExitCode::from_diagnostics(&outcome.diagnostics);
+    }
+    reporter.print_warnings_human(&outcome.diagnostics);
+    let Some(mut value) = outcome.value else {
+        return ExitCode::Internal;
+    };
+    apply_key_orders(&mut value, &outcome.key_orders);
+
+    let (value, base) = match &args.path {
+        Some(path) => match path.select(&value) {
+            Ok(selected) => (selected, path.to_string()),
+            Err(missing) => return reporter.fatal(select_error(path, &missing)),
+        },
+        None => (&value, String::new()),
+    };
+    let export = ExportOptions {
+        prefix: args.prefix,
+        lists: args.lists,
+        list_separator: args.list_separator,
+        maps: args.maps,
+    };
+    match env_vars(value, &base, &export) {
+        Ok(vars) => {
+            print!("{}", render(&vars));
+            ExitCode::Success
+        }
+        Err(diagnostic) => reporter.fatal(*diagnostic),
     }
 }
 
-fn run_pack(args: PackArgs) -> ExitCode {
-    let options = args.flags.to_build_options();
-    let outcome = build(&args.dir, &options);
+fn run_scaffold(args: crate::cli::ScaffoldArgs, reporter: &Reporter) -> ExitCode {
+    let options = args.to_options();
+    let outcome = scaffold::scaffold(&args.input, &args.dir, &options);
+
+    reporter.print_diagnostics(&outcome.diagnostics);
 
     if has_errors(&outcome.diagnostics) {
-        print_diagnostics_human(&outcome.diagnostics);
         return ExitCode::from_diagnostics(&outcome.diagnostics);
     }
+    if let Some(file) = &args.report {
+        let report =
+            scaffold::ScaffoldReport::new(&args.input, &args.dir, &options, &outcome.entries);
+        if let Err(err) = report.write(file) {
+            let diag =
+                Diagnostic::error("E219", "unable to write scaffold report", Category::Write)
+                    .with_location(file.display().to_string())
+                    .with_cause(err.to_string())
+                    .with_action("Check that the directory exists and is writable.");
+            return reporter.fatal(diag);
+        }
+    }
+    ExitCode::Success
+}
 
-    print_warnings_human(&outcome.diagnostics);
-
-    let Some(value) = outcome.value else {
-        return ExitCode::Internal;
+fn run_plan_mv(args: PlanMvArgs, reporter: &Reporter) -> ExitCode {
+    let options = match resolve_build_options(&args.flags) {
+        Ok(options) => options,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
     };
-
-    let value = if options.preserve {
-        value
-    } else {
-        canonicalize_yaml(&value)
+    let plan = match plan_move(&args.dir, &args.from, &args.to, &options) {
+        Ok(plan) => plan,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
     };
 
-    let rendered = match args.format {
-        OutputFormat::Yaml => match emit_yaml(&value, !args.no_header, APP_VERSION) {
-            Ok(output) => output,
+    if args.json {
+        let localized = MovePlan {
+            new_diagnostics: reporter.localize_all(&plan.new_diagnostics),
+            ..plan.clone()
+        };
+        match serde_json::to_string_pretty(&localized) {
+            Ok(json) => println!("{json}"),
             Err(err) => {
                 let diag = Diagnostic::error(
-                    "E300",
-                    "unable to serialize YAML output",
+                    "E305",
+                    "unable to render move plan JSON",
                     Category::Internal,
                 )
                 .with_cause(err.to_string())
-                .with_action("Report this issue; serialization should succeed for parsed input.");
-                eprintln!("{}", diag.render_human());
+                .with_action("Report this issue; JSON serialization should succeed.");
+                reporter.report(&diag);
                 return ExitCode::Internal;
             }
-        },
-        OutputFormat::Json => match emit_json(&value) {
-            Ok(output) => output,
-            Err(err) => {
-                let diag = Diagnostic::error("E301", "unable to serialize JSON output", Category::Write)
-                    .with_cause(err.to_string())
-                    .with_action(
-                        "Ensure YAML mapping keys are JSON-compatible strings when using --format json.",
-                    );
-                eprintln!("{}", diag.render_human());
-                return ExitCode::WriteError;
-            }
-        },
-    };
-
-    if let Some(output_path) = args.output {
-        if let Err(err) = fs::write(&output_path, rendered) {
-            let diag = Diagnostic::error("E302", "unable to write output file", Category::Write)
-                .with_location(output_path.display().to_string())
-                .with_cause(err.to_string())
-                .with_action("Check path permissions and available disk space.");
-            eprintln!("{}", diag.render_human());
-            return ExitCode::WriteError;
         }
     } else {
-        print!("{rendered}");
+        print_move_plan_human(&plan, reporter);
     }
 
-    ExitCode::Success
+    if plan.introduces_errors() {
+        ExitCode::from_diagnostics(&plan.new_diagnostics)
+    } else {
+        ExitCode::Success
+    }
 }
 
-fn run_validate(args: ValidateArgs) -> ExitCode {
-    let options = args.flags.to_build_options();
-    let outcome = build(&args.dir, &options);
+fn run_orphans(args: OrphansArgs, reporter: &Reporter) -> ExitCode {
+    let options = match resolve_build_options(&args.flags) {
+        Ok(options) => options,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+    let report = match find_orphans(&args.repo_root, &args.trees, &args.skip, &options) {
+        Ok(report) => report,
+        Err(diagnostics) => {
+            reporter.print_diagnostics_human(&diagnostics);
+            return ExitCode::from_diagnostics(&diagnostics);
+        }
+    };
 
     if args.json {
-        print_diagnostics_json(&outcome.diagnostics);
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{json}"),
+            Err(err) => {
+                let diag = Diagnostic::error(
+                    "E312",
+                    "unable to render orphan report JSON",
+                    Category::Internal,
+                )
+                .with_cause(err.to_string())
+                .with_action("Report this issue; JSON serialization should succeed.");
+                return reporter.fatal(diag);
+            }
+        }
     } else {
-        print_diagnostics_human(&outcome.diagnostics);
+        print_orphans_human(&report);
     }
 
-    if has_errors(&outcome.diagnostics) {
-        ExitCode::from_diagnostics(&outcome.diagnostics)
-    } else {
+    // Orphans never ship, so finding any fails the run like a validation error.
+    if report.orphans.is_empty() {
         ExitCode::Success
+    } else {
+        ExitCode::InvalidInput
     }
 }
 
-fn run_explain(args: ExplainArgs) -> ExitCode {
-    let options = args.flags.to_build_options();
-    let outcome = build(&args.dir, &options);
+fn run_anonymize(args: AnonymizeArgs, reporter: &Reporter) -> ExitCode {
+    let report = match anonymize(&args.dir, &args.out_dir, args.hash_keys) {
+        Ok(report) => report,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
 
     if args.json {
-        #[derive(Serialize)]
-        struct ExplainJson<'a> {
-            diagnostics: &'a [Diagnostic],
-            explain: &'a crate::engine::ExplainReport,
-        }
-
-        let payload = ExplainJson {
-            diagnostics: &outcome.diagnostics,
-            explain: &outcome.explain,
-        };
-
-        match serde_json::to_string_pretty(&payload) {
+        match serde_json::to_string_pretty(&report) {
             Ok(json) => println!("{json}"),
             Err(err) => {
-                let diag =
-                    Diagnostic::error("E303", "unable to render explain JSON", Category::Internal)
-                        .with_cause(err.to_string())
-                        .with_action("Report this issue; JSON serialization should succeed.");
-                eprintln!("{}", diag.render_human());
-                return ExitCode::Internal;
+                let diag = Diagnostic::error(
+                    "E315",
+                    "unable to render anonymize report JSON",
+                    Category::Internal,
+                )
+                .with_cause(err.to_string())
+                .with_action("Report this issue; JSON serialization should succeed.");
+                return reporter.fatal(diag);
             }
         }
     } else {
-        print_explain_human(&outcome);
+        println!(
+            "Anonymized {} file(s) into {}",
+            report.files.len(),
+            args.out_dir.display()
+        );
+        for skipped in &report.skipped {
+            println!("  skipped {} ({})", skipped.path, skipped.reason);
+        }
+        for unparsed in &report.unparsed {
+            println!("  unparsed {unparsed} (replaced by invalid YAML)");
+        }
     }
+    ExitCode::Success
+}
 
-    if has_errors(&outcome.diagnostics) {
-        ExitCode::from_diagnostics(&outcome.diagnostics)
+fn run_env_report(args: EnvReportArgs, reporter: &Reporter) -> ExitCode {
+    let options = match resolve_build_options(&args.flags) {
+        Ok(options) => options,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+    // Failed builds are what bug reports are about, so their diagnostics are only counted.
+    let outcome = build_dir(&args.dir, &options, &args.flags);
+    let report = env_report(&args.dir, &options, &outcome, APP_VERSION);
+
+    if args.json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{json}"),
+            Err(err) => {
+                let diag = Diagnostic::error(
+                    "E319",
+                    "unable to render environment report JSON",
+                    Category::Internal,
+                )
+                .with_cause(err.to_string())
+                .with_action("Report this issue; JSON serialization should succeed.");
+                return reporter.fatal(diag);
+            }
+        }
     } else {
-        ExitCode::Success
+        print_env_report_human(&report);
     }
+    ExitCode::Success
 }
 
-fn run_diff(args: DiffArgs) -> ExitCode {
-    let options = args.flags.to_build_options();
+fn run_manifest(args: ManifestArgs, reporter: &Reporter) -> ExitCode {
+    let mut options = match resolve_build_options(&args.flags) {
+        Ok(options) => options,
+        Err(diagnostic) => return reporter.fatal(*diagnostic),
+    };
+    options.frozen = false;
+    let outcome = build_dir(&args.dir, &options, &args.flags);
 
-    let left = build(&args.dir_a, &options);
-    let right = build(&args.dir_b, &options);
+    if has_errors(&outcome.diagnostics) {
+        reporter.print_diagnostics_human(&outcome.diagnostics);
+        return ExitCode::from_diagnostics(&outcome.diagnostics);
+    }
 
-    let mut diagnostics = left.diagnostics.clone();
-    diagnostics.extend(right.diagnostics.clone());
+    reporter.print_warnings_human(&outcome.diagnostics);
 
-    if has_errors(&diagnostics) {
-        match args.format {
-            DiffFormat::Path => print_diagnostics_human(&diagnostics),
-            DiffFormat::Json => print_diagnostics_json(&diagnostics),
+    let manifest = Manifest::from_fragments(&outcome.fragments);
+    let rendered = match manifest.to_json() {
+        Ok(json) => json,
+        Err(err) => {
+            let diag = Diagnostic::error(
+                "E306",
+                "unable to render fyaml manifest",
+                Category::Internal,
+            )
+            .with_cause(err.to_string())
+            .with_action("Report this issue; JSON serialization should succeed.");
+            return reporter.fatal(diag);
         }
-        return ExitCode::from_diagnostics(&diagnostics);
-    }
+    };
 
-    let left_value = canonicalize_yaml(&left.value.unwrap_or(Value::Null));
-    let right_value = canonicalize_yaml(&right.value.unwrap_or(Value::Null));
+    let output_path = args
+        .output
+        .unwrap_or_else(|| args.dir.join(MANIFEST_FILE_NAME));
+    if let Err(err) = fs::write(&output_path, rendered) {
+        return reporter.fatal(write_failed(output_path.display(), err));
+    }
 
-    let diff = first_difference(&left_value, &right_value, "$".to_string());
+    println!(
+        "recorded {} fragment(s) in {}",
+        manifest.files.len(),
+        output_path.display()
+    );
+    ExitCode::Success
+}
 
-    match diff {
-        None => {
-            match args.format {
-                DiffFormat::Path => println!("equal"),
-                DiffFormat::Json => println!("{{\"equal\":true}}"),
-            }
+fn run_explain_code(args: ExplainCodeArgs, reporter: &Reporter) -> ExitCode {
+    match codes::lookup(&args.code) {
+        Some(info) => {
+            print!("{}", info.render_human());
             ExitCode::Success
         }
-        Some((path, reason)) => {
-            match args.format {
-                DiffFormat::Path => {
-                    println!("different at {path}: {reason}");
-                }
-                DiffFormat::Json => {
-                    let payload = serde_json::json!({
-                        "equal": false,
-                        "first_difference_path": path,
-                        "reason": reason
-                    });
-                    println!(
-                        "{}",
-                        serde_json::to_string_pretty(&payload)
-                            .unwrap_or_else(|_| payload.to_string())
-                    );
-                }
-            }
-            ExitCode::InvalidInput
+        None => {
+            let diag = Diagnostic::error("E091", "unknown diagnostic code", Category::InvalidInput)
+                .with_location(args.code)
+                .with_cause("The code is not listed in the diagnostic registry.")
+                .with_action("Pass a code printed by fyaml, such as E001 or W010.");
+            reporter.fatal(diag)
         }
     }
 }
 
-fn run_scaffold(args: crate::cli::ScaffoldArgs) -> ExitCode {
-    let outcome = scaffold::scaffold(&args.input, &args.dir, &args.to_options());
+fn run_codes(args: CodesArgs, reporter: &Reporter) -> ExitCode {
+    if !args.json {
+        for info in codes::CODES {
+            let severity = match info.severity {
+                Severity::Error => "error",
+                Severity::Warn => "warn",
+                Severity::Info => "info",
+            };
+            println!("{}  {severity:<5}  {}", info.code, info.summary);
+        }
+        return ExitCode::Success;
+    }
 
-    for diagnostic in &outcome.diagnostics {
-        match diagnostic.severity {
-            Severity::Error | Severity::Warn => eprintln!("{}", diagnostic.render_human()),
-            Severity::Info => println!("{}", diagnostic.render_human()),
+    match serde_json::to_string_pretty(codes::CODES) {
+        Ok(json) => {
+            println!("{json}");
+            ExitCode::Success
+        }
+        Err(err) => {
+            let diag = Diagnostic::error(
+                "E307",
+                "unable to render diagnostic code registry",
+                Category::Internal,
+            )
+            .with_cause(err.to_string())
+            .with_action("Report this issue; JSON serialization should succeed.");
+            reporter.fatal(diag)
         }
     }
+}
 
-    if has_errors(&outcome.diagnostics) {
-        ExitCode::from_diagnostics(&outcome.diagnostics)
+/// Turns CLI build flags into options, loading any auxiliary inputs they reference.
+/// The header comment `--no-header` and `--header-template` ask for; `source` is the input
+/// whose `{git_sha}` and `{source_dir}` are filled in.
+fn resolve_header(flags: &HeaderFlags, source: &Path) -> Result<Header, Box<Diagnostic>> {
+    if flags.no_header {
+        return Ok(Header::none());
+    }
+    let Some(template) = &flags.header_template else {
+        return Ok(Header::packed_by(APP_VERSION));
+    };
+    let dir = if source.is_dir() {
+        source
     } else {
-        ExitCode::Success
+        source.parent().unwrap_or(Path::new("."))
+    };
+    let fields = HeaderFields {
+        version: APP_VERSION.to_string(),
+        git_sha: if template.contains("{git_sha}") {
+            git_sha(dir)
+        } else {
+            String::new()
+        },
+        source_dir: dir.display().to_string(),
+        timestamp: flags.header_timestamp.then(header_timestamp),
+    };
+    Header::from_template(template, &fields).map_err(|cause| {
+        Box::new(
+            Diagnostic::error("E127", "invalid --header-template", Category::InvalidInput)
+                .with_cause(cause)
+                .with_action(format!(
+                    "Use only {}; {{timestamp}} also needs --header-timestamp.",
+                    HEADER_PLACEHOLDERS
+                        .iter()
+                        .map(|name| format!("{{{name}}}"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )),
+        )
+    })
+}
+
+/// The commit checked out at `dir`, or `unknown` outside a git work tree.
+fn git_sha(dir: &Path) -> String {
+    std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|sha| sha.trim().to_string())
+        .filter(|sha| !sha.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// `SOURCE_DATE_EPOCH` when set, so reproducible builds stay byte-identical; otherwise now.
+fn header_timestamp() -> String {
+    let seconds = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|value| value.trim().parse::<i64>().ok())
+        .unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs() as i64)
+                .unwrap_or(0)
+        });
+    utc_from_unix_seconds(seconds)
+}
+
+fn resolve_build_options(flags: &BuildFlags) -> Result<BuildOptions, Box<Diagnostic>> {
+    let mut options = flags.to_build_options();
+    if let Some(list_path) = &flags.from_list {
+        options.file_list = Some(read_file_list(list_path)?);
+    }
+    if flags.root_stdin {
+        options.root_inline = Some(read_root_stdin(flags)?);
+    }
+    Ok(options)
+}
+
+fn read_root_stdin(flags: &BuildFlags) -> Result<String, Box<Diagnostic>> {
+    let unreadable = |cause: String| {
+        Box::new(
+            Diagnostic::error(
+                "E047",
+                "unable to read root document from stdin",
+                Category::InvalidInput,
+            )
+            .with_location("stdin")
+            .with_cause(cause)
+            .with_action("Pipe the root YAML document to fyaml, or pass --root-inline <YAML>."),
+        )
+    };
+    if flags.from_list.as_deref() == Some(Path::new("-")) {
+        return Err(unreadable(
+            "--from-list - already reads the fragment list from stdin.".to_string(),
+        ));
     }
+
+    let mut contents = String::new();
+    std::io::stdin()
+        .read_to_string(&mut contents)
+        .map_err(|err| unreadable(err.to_string()))?;
+    Ok(contents)
+}
+
+fn read_file_list(list_path: &Path) -> Result<Vec<PathBuf>, Box<Diagnostic>> {
+    let read = if list_path == Path::new("-") {
+        let mut contents = String::new();
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .map(|_| contents)
+    } else {
+        fs::read_to_string(list_path)
+    };
+
+    let contents = read.map_err(|err| {
+        Box::new(
+            Diagnostic::error(
+                "E072",
+                "unable to read fragment list",
+                Category::InvalidInput,
+            )
+            .with_location(list_path.display().to_string())
+            .with_cause(err.to_string())
+            .with_action("Pass a readable file (or `-` for stdin) to --from-list."),
+        )
+    })?;
+
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(PathBuf::from)
+        .collect())
 }
 
 fn has_errors(diags: &[Diagnostic]) -> bool {
     diags.iter().any(Diagnostic::is_error)
 }
 
-fn print_diagnostics_human(diags: &[Diagnostic]) {
-    if diags.is_empty() {
-        println!("no diagnostics");
-        return;
+/// Renders diagnostics in the selected language and writes them to the selected target.
+#[derive(Default)]
+struct Reporter {
+    catalog: Catalog,
+    sink: Sink,
+}
+
+#[derive(Default)]
+enum Sink {
+    Stdout,
+    #[default]
+    Stderr,
+    File(RefCell<fs::File>),
+}
+
+impl Reporter {
+    fn new(cli: &Cli) -> Result<Self, Box<Diagnostic>> {
+        let mut catalog = Catalog::builtin(cli.lang);
+        if let Some(path) = &cli.catalog {
+            let overrides = fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| Catalog::from_json(&text).map_err(|err| err.to_string()))
+                .map_err(|cause| {
+                    Box::new(
+                        Diagnostic::error(
+                            "E090",
+                            "unable to load message catalog",
+                            Category::InvalidInput,
+                        )
+                        .with_location(path.display().to_string())
+                        .with_cause(cause)
+                        .with_action(
+                            "Pass a JSON object mapping codes to `message`, `cause`, and `action` strings.",
+                        ),
+                    )
+                })?;
+            catalog.extend(overrides);
+        }
+
+        let sink = match &cli.diagnostics_to {
+            DiagnosticsTarget::Stdout => Sink::Stdout,
+            DiagnosticsTarget::Stderr => Sink::Stderr,
+            DiagnosticsTarget::File(path) => {
+                let file = fs::File::create(path).map_err(|err| {
+                    Box::new(
+                        Diagnostic::error(
+                            "E308",
+                            "unable to open diagnostics file",
+                            Category::Write,
+                        )
+                        .with_location(path.display().to_string())
+                        .with_cause(err.to_string())
+                        .with_action("Check that the directory exists and is writable."),
+                    )
+                })?;
+                Sink::File(RefCell::new(file))
+            }
+        };
+        Ok(Self { catalog, sink })
     }
 
-    for diagnostic in diags {
-        match diagnostic.severity {
-            Severity::Error | Severity::Warn => eprintln!("{}", diagnostic.render_human()),
-            Severity::Info => println!("{}", diagnostic.render_human()),
+    fn render(&self, diagnostic: &Diagnostic) -> String {
+        self.catalog.localize(diagnostic).render_human()
+    }
+
+    fn localize_all(&self, diags: &[Diagnostic]) -> Vec<Diagnostic> {
+        diags.iter().map(|d| self.catalog.localize(d)).collect()
+    }
+
+    fn report(&self, diagnostic: &Diagnostic) {
+        let rendered = self.render(diagnostic);
+        match &self.sink {
+            Sink::Stdout => println!("{rendered}"),
+            Sink::Stderr => eprintln!("{rendered}"),
+            Sink::File(file) => {
+                // Nowhere left to report a failing diagnostics sink; the exit code still does.
+                let _ = writeln!(file.borrow_mut(), "{rendered}");
+            }
         }
     }
-}
 
-fn print_warnings_human(diags: &[Diagnostic]) {
-    for diagnostic in diags {
-        if diagnostic.severity == Severity::Warn {
-            eprintln!("{}", diagnostic.render_human());
+    fn fatal(&self, diagnostic: Diagnostic) -> ExitCode {
+        self.report(&diagnostic);
+        ExitCode::from_diagnostics(&[diagnostic])
+    }
+
+    fn print_diagnostics(&self, diags: &[Diagnostic]) {
+        for diagnostic in diags {
+            self.report(diagnostic);
         }
     }
-}
 
-fn print_diagnostics_json(diags: &[Diagnostic]) {
-    match serde_json::to_string_pretty(diags) {
-        Ok(json) => println!("{json}"),
-        Err(err) => {
-            let diag = Diagnostic::error(
-                "E304",
-                "unable to render diagnostics JSON",
-                Category::Internal,
-            )
-            .with_cause(err.to_string())
-            .with_action("Report this issue; JSON serialization should succeed.");
-            eprintln!("{}", diag.render_human());
+    fn print_diagnostics_human(&self, diags: &[Diagnostic]) {
+        if diags.is_empty() {
+            println!("no diagnostics");
+            return;
+        }
+        self.print_diagnostics(diags);
+    }
+
+    fn print_warnings_human(&self, diags: &[Diagnostic]) {
+        for diagnostic in diags {
+            if diagnostic.severity == Severity::Warn {
+                self.report(diagnostic);
+            }
+        }
+    }
+
+    fn print_diagnostics_json(&self, diags: &[Diagnostic]) {
+        self.print_json(&self.localize_all(diags));
+    }
+
+    fn print_json(&self, payload: &impl Serialize) {
+        match serde_json::to_string_pretty(payload) {
+            Ok(json) => println!("{json}"),
+            Err(err) => {
+                let diag = Diagnostic::error(
+                    "E304",
+                    "unable to render diagnostics JSON",
+                    Category::Internal,
+                )
+                .with_cause(err.to_string())
+                .with_action("Report this issue; JSON serialization should succeed.");
+                self.report(&diag);
+            }
         }
     }
 }
 
-fn print_explain_human(outcome: &BuildOutcome) {
+fn print_explain_human(outcome: &BuildOutcome, reporter: &Reporter) {
+    if let Some(root_file) = &outcome.explain.auto_root_file {
+        println!("Root File:\n  {root_file} (auto-selected)\n");
+    }
     println!("Derived Key Tree:");
     if outcome.explain.derived_keys.is_empty() {
         println!("  (none)");
@@ -282,6 +2430,50 @@ fn print_explain_human(outcome: &BuildOutcome) {
         }
     }
 
+    if let Some(numbering) = &outcome.explain.sequence_numbering {
+        println!("\nSequence Numbering:");
+        println!(
+            "  first index {}, {} order, gaps: {}",
+            numbering.start,
+            numbering.order.as_str(),
+            numbering
+                .gaps
+                .to_possible_value()
+                .map(|value| value.get_name().to_string())
+                .unwrap_or_default()
+        );
+    }
+
+    if !outcome.explain.transliterated_keys.is_empty() {
+        println!("\nTransliterated Keys:");
+        for renamed in &outcome.explain.transliterated_keys {
+            println!(
+                "  {} => {} ({})",
+                renamed.original, renamed.key, renamed.source
+            );
+        }
+    }
+
+    if !outcome.explain.ordered_keys.is_empty() {
+        println!("\nOrdered Keys:");
+        for ordered in &outcome.explain.ordered_keys {
+            println!(
+                "  {} <- {} (order {})",
+                ordered.derived_key_path, ordered.name, ordered.order
+            );
+        }
+    }
+
+    if !outcome.explain.includes.is_empty() {
+        println!("\nIncludes:");
+        for edge in &outcome.explain.includes {
+            println!(
+                "  {} -> {} (at {})",
+                edge.source, edge.target, edge.derived_key_path
+            );
+        }
+    }
+
     println!("\nIgnored Entries:");
     if outcome.explain.ignored.is_empty() {
         println!("  (none)");
@@ -296,123 +2488,107 @@ fn print_explain_human(outcome: &BuildOutcome) {
         println!("  no diagnostics");
     } else {
         for diagnostic in &outcome.diagnostics {
-            print!("{}", diagnostic.render_human());
+            print!("{}", reporter.render(diagnostic));
         }
     }
 }
 
-fn first_difference(left: &Value, right: &Value, path: String) -> Option<(String, String)> {
-    match (left, right) {
-        (Value::Null, Value::Null)
-        | (Value::Bool(_), Value::Bool(_))
-        | (Value::Number(_), Value::Number(_))
-        | (Value::String(_), Value::String(_)) => {
-            if left == right {
-                None
-            } else {
-                Some((path, "scalar value differs".to_string()))
-            }
-        }
-        (Value::Sequence(a), Value::Sequence(b)) => {
-            if a.len() != b.len() {
-                return Some((
-                    path,
-                    format!("sequence length differs ({} vs {})", a.len(), b.len()),
-                ));
-            }
+fn print_env_report_human(report: &EnvReport) {
+    println!("fyaml {} ({}, {})", report.version, report.os, report.arch);
+    let case = match report.case_sensitive {
+        Some(true) => "case-sensitive",
+        Some(false) => "case-insensitive",
+        None => "unknown (no entry name to probe)",
+    };
+    println!("File system: {case}");
 
-            for (index, (left_item, right_item)) in a.iter().zip(b.iter()).enumerate() {
-                let child_path = format!("{path}[{index}]");
-                if let Some(diff) = first_difference(left_item, right_item, child_path) {
-                    return Some(diff);
-                }
-            }
+    let tree = &report.tree;
+    println!("\nTree:");
+    println!(
+        "  {} directories, {} files ({} YAML), {} symlinks, {} hidden entries",
+        tree.directories, tree.files, tree.yaml_files, tree.symlinks, tree.hidden_entries
+    );
+    println!(
+        "  {} bytes, largest file {} bytes, {} levels deep",
+        tree.total_bytes, tree.largest_file_bytes, tree.max_depth
+    );
 
-            None
-        }
-        (Value::Mapping(a), Value::Mapping(b)) => first_map_difference(a, b, path),
-        (Value::Tagged(a), Value::Tagged(b)) => first_difference(&a.value, &b.value, path),
-        _ => Some((path, "value type differs".to_string())),
+    let build = &report.build;
+    println!("\nBuild:");
+    println!(
+        "  {} fragments, {} derived keys, {} ignored entries",
+        build.fragments, build.derived_keys, build.ignored_entries
+    );
+    let modes = build
+        .directory_modes
+        .iter()
+        .map(|(mode, count)| format!("{count} {mode}"))
+        .collect::<Vec<_>>();
+    if !modes.is_empty() {
+        println!("  directories: {}", modes.join(", "));
     }
-}
-
-fn first_map_difference(left: &Mapping, right: &Mapping, path: String) -> Option<(String, String)> {
-    let mut left_keys: Vec<&Value> = left.keys().collect();
-    let mut right_keys: Vec<&Value> = right.keys().collect();
-
-    left_keys.sort_by(|a, b| compare_yaml_key(a, b));
-    right_keys.sort_by(|a, b| compare_yaml_key(a, b));
+    println!("  {} errors, {} warnings", build.errors, build.warnings);
 
-    for key in &left_keys {
-        if !right.contains_key(*key) {
-            let key_text = yaml_key_text(key);
-            return Some((
-                path.clone(),
-                format!("key missing on right side: {key_text}"),
-            ));
-        }
+    println!("\nOptions changed from the defaults:");
+    if report.changed_options.is_empty() {
+        println!("  (none)");
     }
-
-    for key in &right_keys {
-        if !left.contains_key(*key) {
-            let key_text = yaml_key_text(key);
-            return Some((
-                path.clone(),
-                format!("key missing on left side: {key_text}"),
-            ));
-        }
+    for name in &report.changed_options {
+        println!("  {name} = {}", report.options[name.as_str()]);
     }
+}
 
-    for key in left_keys {
-        let left_value = left.get(key).expect("left key exists");
-        let right_value = right.get(key).expect("right key exists");
-        let next_path = if path == "$" {
-            format!("$.{}", yaml_key_text(key))
-        } else {
-            format!("{}.{}", path, yaml_key_text(key))
-        };
-
-        if let Some(diff) = first_difference(left_value, right_value, next_path) {
-            return Some(diff);
-        }
+fn print_orphans_human(report: &OrphanReport) {
+    if report.orphans.is_empty() {
+        println!("no orphaned YAML files outside {}", report.trees.join(", "));
+        return;
+    }
+    println!("Orphaned YAML files:");
+    for orphan in &report.orphans {
+        let resembles = orphan
+            .resembles
+            .iter()
+            .map(|r| format!("{} in {}", r.key_path, r.tree))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("  {} (looks like {resembles})", orphan.path);
     }
-
-    None
 }
 
-fn compare_yaml_key(a: &Value, b: &Value) -> Ordering {
-    yaml_sort_key(a).cmp(&yaml_sort_key(b))
-}
+fn print_move_plan_human(plan: &MovePlan, reporter: &Reporter) {
+    println!("Move: {} -> {}", plan.from, plan.to);
 
-fn yaml_sort_key(value: &Value) -> Vec<u8> {
-    match value {
-        Value::String(s) => s.as_bytes().to_vec(),
-        _ => serde_yaml::to_string(value)
-            .unwrap_or_else(|_| format!("{value:?}"))
-            .into_bytes(),
+    println!("\nKey Path Changes:");
+    if plan.key_changes.is_empty() {
+        println!("  (none)");
+    } else {
+        for change in &plan.key_changes {
+            let after = change
+                .key_path_after
+                .as_deref()
+                .unwrap_or("(no longer contributes)");
+            println!(
+                "  {} -> {} ({} -> {})",
+                change.key_path_before, after, change.source_before, change.source_after
+            );
+        }
     }
-}
 
-fn yaml_key_text(value: &Value) -> String {
-    match value {
-        Value::String(s) => s.clone(),
-        _ => serde_yaml::to_string(value)
-            .unwrap_or_else(|_| format!("{value:?}"))
-            .trim()
-            .to_string(),
+    println!("\nPacked Output Changes:");
+    if plan.output_differences.is_empty() {
+        println!("  (none)");
+    } else {
+        for difference in &plan.output_differences {
+            println!("  {}: {}", difference.path, difference.reason);
+        }
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    #[test]
-    fn first_difference_finds_nested_path() {
-        let left: Value = serde_yaml::from_str("a:\n  b: 1\n").expect("left parse");
-        let right: Value = serde_yaml::from_str("a:\n  b: 2\n").expect("right parse");
-
-        let diff = first_difference(&left, &right, "$".to_string()).expect("difference exists");
-        assert_eq!(diff.0, "$.a.b");
+    println!("\nNew Diagnostics:");
+    if plan.new_diagnostics.is_empty() {
+        println!("  no diagnostics");
+    } else {
+        for diagnostic in &plan.new_diagnostics {
+            print!("{}", reporter.render(diagnostic));
+        }
     }
 }
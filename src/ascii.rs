@@ -0,0 +1,76 @@
+//! ASCII transliteration of derived keys for `--ascii-keys transliterate`.
+//!
+//! Letters are decomposed (NFD) and their combining marks dropped, so `café` becomes
+//! `cafe`; a short table covers Latin letters that do not decompose (`ß`, `æ`, `ø`, ...).
+//! Anything else, such as CJK text, has no ASCII spelling and is reported instead.
+
+use icu_normalizer::DecomposingNormalizer;
+
+/// `key` spelled in ASCII, or the first character that has no ASCII spelling.
+pub fn transliterate(key: &str) -> Result<String, char> {
+    let decomposed = DecomposingNormalizer::new_nfd().normalize(key);
+    let mut ascii = String::with_capacity(decomposed.len());
+    for c in decomposed.chars() {
+        if c.is_ascii() {
+            ascii.push(c);
+        } else if is_combining_mark(c) {
+            continue;
+        } else if let Some(spelling) = spelling(c) {
+            ascii.push_str(spelling);
+        } else {
+            return Err(c);
+        }
+    }
+    Ok(ascii)
+}
+
+fn is_combining_mark(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+fn spelling(c: char) -> Option<&'static str> {
+    Some(match c {
+        'ß' => "ss",
+        'ẞ' => "SS",
+        'æ' => "ae",
+        'Æ' => "AE",
+        'œ' => "oe",
+        'Œ' => "OE",
+        'ø' => "o",
+        'Ø' => "O",
+        'đ' | 'ð' => "d",
+        'Đ' | 'Ð' => "D",
+        'ł' => "l",
+        'Ł' => "L",
+        'þ' => "th",
+        'Þ' => "TH",
+        'ı' => "i",
+        'ħ' => "h",
+        'Ħ' => "H",
+        '\u{2010}'..='\u{2015}' => "-",
+        '\u{00A0}' => " ",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transliterates_latin_letters_and_rejects_the_rest() {
+        assert_eq!(transliterate("café"), Ok("cafe".to_string()));
+        assert_eq!(transliterate("Straße"), Ok("Strasse".to_string()));
+        assert_eq!(transliterate("Ærøskøbing"), Ok("AEroskobing".to_string()));
+        assert_eq!(transliterate("Łódź"), Ok("Lodz".to_string()));
+        assert_eq!(transliterate("plain-key_1"), Ok("plain-key_1".to_string()));
+        assert_eq!(transliterate("設定"), Err('設'));
+    }
+}
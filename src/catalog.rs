@@ -0,0 +1,104 @@
+//! Message catalogs for diagnostic text.
+//!
+//! English text is written where each diagnostic is raised. A catalog replaces the message,
+//! cause, or action of a diagnostic by code; codes themselves never change, since they are
+//! the machine contract. Catalogs use the same JSON shape as the built-in ones, e.g.
+//! `{"E001": {"message": "...", "action": "..."}}`.
+
+use crate::config::Lang;
+use crate::diagnostics::Diagnostic;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+const GERMAN: &str = include_str!("catalogs/de.json");
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CatalogEntry {
+    pub message: Option<String>,
+    pub cause: Option<String>,
+    pub action: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    entries: BTreeMap<String, CatalogEntry>,
+}
+
+impl Catalog {
+    pub fn builtin(lang: Lang) -> Self {
+        match lang {
+            Lang::En => Self::default(),
+            Lang::De => Self::from_json(GERMAN).expect("built-in German catalog is valid JSON"),
+        }
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            entries: serde_json::from_str(text)?,
+        })
+    }
+
+    /// Layers `other` on top of this catalog, field by field.
+    pub fn extend(&mut self, other: Catalog) {
+        for (code, entry) in other.entries {
+            let existing = self.entries.entry(code).or_default();
+            existing.message = entry.message.or(existing.message.take());
+            existing.cause = entry.cause.or(existing.cause.take());
+            existing.action = entry.action.or(existing.action.take());
+        }
+    }
+
+    pub fn localize(&self, diagnostic: &Diagnostic) -> Diagnostic {
+        let mut localized = diagnostic.clone();
+        let Some(entry) = self.entries.get(&diagnostic.code) else {
+            return localized;
+        };
+        if let Some(message) = &entry.message {
+            localized.message = message.clone();
+        }
+        if let Some(cause) = &entry.cause {
+            localized.cause = cause.clone();
+        }
+        if let Some(action) = &entry.action {
+            localized.action = action.clone();
+        }
+        localized
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::Category;
+
+    #[test]
+    fn localize_replaces_text_but_keeps_code() {
+        let diagnostic =
+            Diagnostic::error("E001", "key collision detected", Category::InvalidInput)
+                .with_cause("Multiple inputs resolve to the same FYAML key.")
+                .with_action("Rename one source or move it into a different directory.");
+
+        let localized = Catalog::builtin(Lang::De).localize(&diagnostic);
+        assert_eq!(localized.code, "E001");
+        assert_eq!(localized.message, "Schlüsselkollision erkannt");
+        assert_eq!(localized.cause, diagnostic.cause);
+
+        let mut catalog = Catalog::builtin(Lang::De);
+        catalog.extend(
+            Catalog::from_json(r#"{"E001": {"action": "Ask #platform before renaming."}}"#)
+                .expect("valid catalog"),
+        );
+        let localized = catalog.localize(&diagnostic);
+        assert_eq!(localized.message, "Schlüsselkollision erkannt");
+        assert_eq!(localized.action, "Ask #platform before renaming.");
+    }
+
+    #[test]
+    fn german_catalog_covers_the_code_registry() {
+        let catalog = Catalog::builtin(Lang::De);
+        let registered: Vec<_> = crate::codes::CODES.iter().map(|info| info.code).collect();
+        let catalogued: Vec<_> = catalog.entries.keys().map(String::as_str).collect();
+        assert_eq!(catalogued, registered);
+    }
+}
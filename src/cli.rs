@@ -1,5 +1,16 @@
-use crate::config::{BuildOptions, DiffFormat, MultiDocMode, OutputFormat, RootMode, SeqGapMode};
-use crate::scaffold::{ScaffoldLayout, ScaffoldOptions, SequenceLayout};
+use crate::config::{
+    AsciiKeys, BigIntegers, BuildOptions, CanonicalOptions, Collation, CollisionPolicy,
+    Compression, DatesMode, DiagOrder, DiagnosticsTarget, DiffFormat, EnvLists, EnvMaps,
+    ExtensionConflict, FixMode, FloatFormat, FoldStrings, Includes, IntegralFloats, ItemCount,
+    ItemKeys, JsonLayout, Lang, MergeKeys, MultiDocMode, NegativeZero, OutputFormat, OverlayMerge,
+    QuoteStrings, RootMode, SeqGapMode, SeqOrder, SortRule, TagPolicy, WhatIf, YamlExtension,
+    DEFAULT_MAX_LINE_WIDTH,
+};
+use crate::glob::Glob;
+use crate::keypath::{KeyPath, PathPattern};
+use crate::scaffold::{
+    FsyncMode, ScaffoldLayout, ScaffoldMultiDoc, ScaffoldOptions, SequenceLayout,
+};
 use clap::{Args, Parser, Subcommand};
 use std::path::PathBuf;
 
@@ -11,6 +22,22 @@ use std::path::PathBuf;
     long_about = "FYAML packs a directory tree of YAML fragments into one deterministic YAML document.\n\nFYAML packing is one-way; directory layout is not recoverable from the packed YAML."
 )]
 pub struct Cli {
+    /// Language of diagnostic messages (codes do not change)
+    #[arg(long, global = true, value_enum, default_value_t = Lang::En)]
+    pub lang: Lang,
+
+    /// JSON message catalog overriding diagnostic text by code, applied on top of --lang
+    #[arg(long, global = true, value_name = "FILE")]
+    pub catalog: Option<PathBuf>,
+
+    /// Where diagnostics are written: stdout, stderr, or file:PATH (results always go to stdout)
+    #[arg(long, global = true, default_value = "stderr", value_name = "TARGET")]
+    pub diagnostics_to: DiagnosticsTarget,
+
+    /// Version of the JSON layouts to produce; fails when this fyaml does not support it
+    #[arg(long, global = true, value_name = "N")]
+    pub output_schema_version: Option<u32>,
+
     #[command(subcommand)]
     pub command: Command,
 }
@@ -21,12 +48,45 @@ pub enum Command {
     Pack(PackArgs),
     /// Validate that a FYAML directory packs under the configured rules
     Validate(ValidateArgs),
+    /// Apply the fragment parsing and value policies to one already-packed YAML file
+    CheckFile(CheckFileArgs),
+    /// Emit one YAML file in FYAML canonical form
+    Canonicalize(CanonicalizeArgs),
+    /// Print one `key.path: value` line per leaf of a FYAML directory or YAML file
+    Flatten(FlattenArgs),
+    /// Rebuild a YAML document from `key.path: value` lines
+    Unflatten(UnflattenArgs),
     /// Explain derived keys, ignored files, and sequence/mapping decisions
     Explain(ExplainArgs),
     /// Compare two FYAML directories by packed semantics
     Diff(DiffArgs),
     /// Generate a FYAML-friendly starter layout from YAML (non-invertible helper)
     Scaffold(ScaffoldArgs),
+    /// Preview the key-path and packed-output impact of moving an entry, without moving it
+    PlanMv(PlanMvArgs),
+    /// Record every contributing fragment and its checksum for `--frozen` builds
+    Manifest(ManifestArgs),
+    /// Print the structural hash of the packed document and, with --depth, of its subtrees
+    Hash(HashArgs),
+    /// Print extended documentation for a diagnostic code, e.g. `fyaml explain-code E001`
+    ExplainCode(ExplainCodeArgs),
+    /// List every diagnostic code with its severity and summary
+    Codes(CodesArgs),
+    /// Keep parsed fragments cached between `fyaml validate` runs (unix only)
+    Daemon(DaemonArgs),
+    /// Rebuild a FYAML directory whenever its fragments change
+    Watch(WatchArgs),
+    /// Find YAML files outside the FYAML trees of a repository that look like misplaced fragments
+    Orphans(OrphansArgs),
+    /// Copy a FYAML directory with its values replaced by placeholders, for bug reports
+    Anonymize(AnonymizeArgs),
+    /// Print the version, platform, file system, options, and tree shape for a bug report
+    /// (printed locally, never sent)
+    EnvReport(EnvReportArgs),
+    /// Check that a packed file matches what its source directory packs to now
+    Verify(VerifyArgs),
+    /// Print a subtree as `export NAME='value'` lines for a shell to source
+    ExportEnv(ExportEnvArgs),
 }
 
 #[derive(Debug, Args)]
@@ -34,17 +94,73 @@ pub struct PackArgs {
     /// Input directory
     pub dir: PathBuf,
 
+    /// Directories packed the same way and laid over DIR in order, later ones overriding
+    /// earlier ones (e.g. `fyaml pack base/ overlays/prod/`)
+    #[arg(value_name = "OVERLAY")]
+    pub overlays: Vec<PathBuf>,
+
+    /// How an overlay combines with the layers before it
+    #[arg(long, value_enum, default_value_t = OverlayMerge::Deep)]
+    pub overlay_merge: OverlayMerge,
+
     /// Output file path (defaults to stdout)
     #[arg(short = 'o')]
     pub output: Option<PathBuf>,
 
+    /// Write each top-level key to `<key>.yml` in this directory, with an `_index.yml`
+    /// manifest, instead of one document
+    #[arg(long, value_name = "DIR", conflicts_with = "output")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Compare the packed document with the -o file instead of writing it, failing if the
+    /// file is out of date
+    #[arg(long, requires = "output", conflicts_with = "output_dir")]
+    pub check: bool,
+
     /// Output format
     #[arg(long, default_value = "yaml")]
     pub format: OutputFormat,
 
-    /// Suppress the default version header comment
+    /// Compress the -o file with gzip or zstd
+    #[arg(long, value_enum, requires = "output", conflicts_with = "output_dir")]
+    pub compress: Option<Compression>,
+
+    #[command(flatten)]
+    pub header: HeaderFlags,
+
+    /// Write each item of a sequence document as its own YAML document, separated by `---`
     #[arg(long)]
-    pub no_header: bool,
+    pub explode_docs: bool,
+
+    /// Emit only the subtree at this key path, e.g. `services.api` or `jobs[0]`
+    #[arg(long, value_name = "KEY_PATH")]
+    pub select: Option<KeyPath>,
+
+    /// Render the document as YAML and emit it as a block-scalar string under this key path,
+    /// e.g. `data["app.yaml"]` (after --select, before --prefix)
+    #[arg(long, value_name = "KEY_PATH", value_parser = KeyPath::parse_keys)]
+    pub wrap_as_string: Option<KeyPath>,
+
+    /// Nest the whole document under this key path, e.g. `spec.config` (after --select)
+    #[arg(long, value_name = "KEY_PATH", value_parser = KeyPath::parse_keys)]
+    pub prefix: Option<KeyPath>,
+
+    /// Write a JSON map from each leaf's derived key path to the file, line, and column of
+    /// the fragment that defines it
+    #[arg(long, value_name = "FILE")]
+    pub source_map: Option<PathBuf>,
+
+    /// Write run metrics to this file in the Prometheus textfile format
+    #[arg(long, value_name = "FILE")]
+    pub metrics_out: Option<PathBuf>,
+
+    /// Append error_count, warning_count, output_path, and content_hash to $GITHUB_OUTPUT,
+    /// and a results table to $GITHUB_STEP_SUMMARY
+    #[arg(long)]
+    pub gha_output: bool,
+
+    #[command(flatten)]
+    pub canonical: CanonicalFlags,
 
     #[command(flatten)]
     pub flags: BuildFlags,
@@ -52,17 +168,182 @@ pub struct PackArgs {
 
 #[derive(Debug, Args)]
 pub struct ValidateArgs {
-    /// Input directory
-    pub dir: PathBuf,
+    /// Input directories; fragments with identical content are parsed once across all of them
+    #[arg(required = true)]
+    pub dirs: Vec<PathBuf>,
+
+    /// Emit machine-readable diagnostics as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// JSON shape: the bare report, or an envelope adding `summary` and `tool`
+    #[arg(long, value_enum, default_value_t = JsonLayout::Array, requires = "json")]
+    pub json_layout: JsonLayout,
+
+    /// Print how many fragments were parsed and how many reused identical content
+    #[arg(long, conflicts_with = "json")]
+    pub stats: bool,
+
+    /// Build in this process even when a `fyaml daemon` is running
+    #[arg(long)]
+    pub no_daemon: bool,
+
+    /// Rewrite fragments to fix what this reports before validating (`unwrap`: W020,
+    /// `collisions`: E001 and E004)
+    #[arg(long, value_enum)]
+    pub fix: Option<FixMode>,
+
+    /// Which ranked suggestion `--fix collisions` applies to each collision
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    pub apply_suggestion: u16,
+
+    /// Also report how the packed document would change with this flag toggled, e.g.
+    /// `include-hidden` (repeatable)
+    #[arg(long, value_enum, value_name = "FLAG")]
+    pub what_if: Vec<WhatIf>,
+
+    /// Write run metrics to this file in the Prometheus textfile format
+    #[arg(long, value_name = "FILE")]
+    pub metrics_out: Option<PathBuf>,
+
+    /// Append error_count, warning_count, output_path, and content_hash to $GITHUB_OUTPUT,
+    /// and a results table to $GITHUB_STEP_SUMMARY
+    #[arg(long)]
+    pub gha_output: bool,
+
+    /// Only scan and check the subtree at this key path, e.g. `env.prod` or `workflows.*`
+    /// (repeatable)
+    #[arg(long, value_name = "KEY_PATH")]
+    pub only: Vec<PathPattern>,
+
+    #[command(flatten)]
+    pub flags: BuildFlags,
+}
+
+#[derive(Debug, Args)]
+pub struct CheckFileArgs {
+    /// YAML file to check
+    pub file: PathBuf,
 
     /// Emit machine-readable diagnostics as JSON
     #[arg(long)]
     pub json: bool,
 
+    #[command(flatten)]
+    pub flags: FileFlags,
+}
+
+#[derive(Debug, Args)]
+pub struct CanonicalizeArgs {
+    /// YAML file to canonicalize
+    pub input: PathBuf,
+
+    /// Output file path (defaults to stdout)
+    #[arg(short = 'o')]
+    pub output: Option<PathBuf>,
+
+    /// Output format
+    #[arg(long, default_value = "yaml")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub header: HeaderFlags,
+
+    #[command(flatten)]
+    pub canonical: CanonicalFlags,
+
+    #[command(flatten)]
+    pub flags: FileFlags,
+}
+
+#[derive(Debug, Args)]
+pub struct FlattenArgs {
+    /// FYAML directory, or a single YAML file (directory-only flags are then ignored)
+    pub input: PathBuf,
+
+    #[command(flatten)]
+    pub canonical: CanonicalFlags,
+
     #[command(flatten)]
     pub flags: BuildFlags,
 }
 
+#[derive(Debug, Args)]
+pub struct UnflattenArgs {
+    /// File of `key.path: value` lines; `-` reads stdin
+    pub input: PathBuf,
+
+    /// Output file path (defaults to stdout)
+    #[arg(short = 'o')]
+    pub output: Option<PathBuf>,
+
+    /// Output format
+    #[arg(long, default_value = "yaml")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub header: HeaderFlags,
+
+    #[command(flatten)]
+    pub canonical: CanonicalFlags,
+}
+
+/// The parsing and value policies of [`BuildFlags`] that apply to a single file.
+#[derive(Debug, Clone, Args)]
+pub struct FileFlags {
+    /// Multi-document handling
+    #[arg(long, default_value = "error")]
+    pub multi_doc: MultiDocMode,
+
+    /// Treat warnings as errors
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Maximum file size in bytes
+    #[arg(long)]
+    pub max_yaml_bytes: Option<u64>,
+
+    /// Integers wider than 64 bits: error, or keep their digits as a string
+    #[arg(long, value_enum, default_value_t = BigIntegers::Error)]
+    pub big_integers: BigIntegers,
+
+    /// ISO-8601 strings: keep-string, tag, or parse
+    #[arg(long, value_enum, default_value_t = DatesMode::KeepString)]
+    pub dates: DatesMode,
+
+    /// YAML tags: preserve, strip, or error
+    #[arg(long, value_enum, default_value_t = TagPolicy::Preserve)]
+    pub tags: TagPolicy,
+
+    /// Do not warn about anchors and aliases
+    #[arg(long)]
+    pub preserve: bool,
+
+    /// Worker threads for canonicalizing large documents
+    #[arg(long, default_value_t = 1, value_name = "N")]
+    pub jobs: usize,
+}
+
+impl FileFlags {
+    pub fn to_build_options(&self) -> BuildOptions {
+        BuildOptions {
+            multi_doc: self.multi_doc,
+            strict: self.strict,
+            max_yaml_bytes: self.max_yaml_bytes,
+            preserve: self.preserve,
+            big_integers: self.big_integers,
+            dates: self.dates,
+            tags: self.tags,
+            ..BuildOptions::default()
+        }
+    }
+}
+
 #[derive(Debug, Args)]
 pub struct ExplainArgs {
     /// Input directory
@@ -72,6 +353,10 @@ pub struct ExplainArgs {
     #[arg(long)]
     pub json: bool,
 
+    /// JSON shape: the bare report, or an envelope adding `summary` and `tool`
+    #[arg(long, value_enum, default_value_t = JsonLayout::Array, requires = "json")]
+    pub json_layout: JsonLayout,
+
     #[command(flatten)]
     pub flags: BuildFlags,
 }
@@ -81,17 +366,247 @@ pub struct DiffArgs {
     /// First FYAML directory
     pub dir_a: PathBuf,
 
-    /// Second FYAML directory
+    /// Second FYAML directory, or a packed YAML or JSON file (gzip or zstd compressed too)
     pub dir_b: PathBuf,
 
     /// Diff output format
     #[arg(long, default_value = "path")]
     pub format: DiffFormat,
 
+    /// Hash every subtree first and only descend where the hashes differ
+    #[arg(long, conflicts_with = "exact")]
+    pub fast: bool,
+
+    /// Compare every node directly (the default)
+    #[arg(long)]
+    pub exact: bool,
+
+    /// With --format json, also list directories assembled as a mapping in one tree and a
+    /// sequence (or another mode) in the other
+    #[arg(long)]
+    pub directory_modes: bool,
+
+    #[command(flatten)]
+    pub canonical: CanonicalFlags,
+
+    #[command(flatten)]
+    pub flags: BuildFlags,
+}
+
+#[derive(Debug, Args)]
+pub struct PlanMvArgs {
+    /// Input directory
+    pub dir: PathBuf,
+
+    /// Entry to move, relative to the input directory
+    pub from: PathBuf,
+
+    /// Destination, relative to the input directory (an existing directory receives the entry)
+    pub to: PathBuf,
+
+    /// Emit the move plan as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub flags: BuildFlags,
+}
+
+#[derive(Debug, Args)]
+pub struct ManifestArgs {
+    /// Input directory
+    pub dir: PathBuf,
+
+    /// Manifest path (defaults to <DIR>/fyaml.manifest.json)
+    #[arg(short = 'o')]
+    pub output: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub flags: BuildFlags,
+}
+
+#[derive(Debug, Args)]
+pub struct VerifyArgs {
+    /// Input directory
+    pub dir: PathBuf,
+
+    /// Packed YAML or JSON file to check, e.g. the committed output of `fyaml pack`
+    pub file: PathBuf,
+
+    #[command(flatten)]
+    pub canonical: CanonicalFlags,
+
+    #[command(flatten)]
+    pub flags: BuildFlags,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportEnvArgs {
+    /// Input directory
+    pub dir: PathBuf,
+
+    /// Prepended to every variable name, e.g. `APP_`
+    #[arg(long, default_value = "")]
+    pub prefix: String,
+
+    /// Export only the subtree at this key path, e.g. `env.prod`
+    #[arg(long, value_name = "KEY_PATH")]
+    pub path: Option<KeyPath>,
+
+    /// How a sequence is exported
+    #[arg(long, value_enum, default_value_t = EnvLists::Json)]
+    pub lists: EnvLists,
+
+    /// Placed between items with --lists join
+    #[arg(long, default_value = ",", value_name = "TEXT")]
+    pub list_separator: String,
+
+    /// How a nested mapping is exported
+    #[arg(long, value_enum, default_value_t = EnvMaps::Flatten)]
+    pub maps: EnvMaps,
+
     #[command(flatten)]
     pub flags: BuildFlags,
 }
 
+#[derive(Debug, Args)]
+pub struct HashArgs {
+    /// Input directory
+    pub dir: PathBuf,
+
+    /// Also list subtrees this many levels below the root
+    #[arg(long, default_value_t = 0, value_name = "N")]
+    pub depth: usize,
+
+    /// Emit the hashes as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub canonical: CanonicalFlags,
+
+    #[command(flatten)]
+    pub flags: BuildFlags,
+}
+
+#[derive(Debug, Args)]
+pub struct OrphansArgs {
+    /// Repository root to scan
+    pub repo_root: PathBuf,
+
+    /// FYAML tree, relative to the repository root (repeatable)
+    #[arg(long = "tree", required = true, value_name = "DIR")]
+    pub trees: Vec<PathBuf>,
+
+    /// Do not scan entries matching this glob, relative to the repository root (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    pub skip: Vec<Glob>,
+
+    /// Emit the report as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub flags: BuildFlags,
+}
+
+#[derive(Debug, Args)]
+pub struct AnonymizeArgs {
+    /// Input directory
+    pub dir: PathBuf,
+
+    /// Directory to create for the copy; it must not exist yet
+    pub out_dir: PathBuf,
+
+    /// Also replace entry names and mapping keys with hashes
+    #[arg(long)]
+    pub hash_keys: bool,
+
+    /// Emit the report as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct EnvReportArgs {
+    /// Input directory
+    pub dir: PathBuf,
+
+    /// Emit the report as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub flags: BuildFlags,
+}
+
+#[derive(Debug, Args)]
+pub struct ExplainCodeArgs {
+    /// Diagnostic code such as E001 or W010
+    pub code: String,
+}
+
+#[derive(Debug, Args)]
+pub struct WatchArgs {
+    /// Input directory
+    pub dir: PathBuf,
+
+    /// Rewrite this file after every successful build (otherwise only a change summary is printed)
+    #[arg(short = 'o')]
+    pub output: Option<PathBuf>,
+
+    /// After every successful build, print the semantic differences from this packed file,
+    /// read again each time (pass the -o file to see what each edit changed)
+    #[arg(long, value_name = "FILE")]
+    pub diff_against: Option<PathBuf>,
+
+    /// Output format
+    #[arg(long, default_value = "yaml")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub header: HeaderFlags,
+
+    /// Milliseconds between scans of the tree
+    #[arg(long, default_value_t = 500, value_name = "MS")]
+    pub poll: u64,
+
+    /// Milliseconds the tree must stay unchanged before rebuilding
+    #[arg(long, default_value_t = 200, value_name = "MS")]
+    pub debounce: u64,
+
+    #[command(flatten)]
+    pub canonical: CanonicalFlags,
+
+    #[command(flatten)]
+    pub flags: BuildFlags,
+}
+
+#[derive(Debug, Args)]
+pub struct DaemonArgs {
+    #[command(subcommand)]
+    pub action: DaemonAction,
+}
+
+/// The socket defaults to `fyaml-$USER.sock` in the temporary directory; set
+/// `FYAML_DAEMON_SOCKET` to choose another path.
+#[derive(Debug, Clone, Copy, Subcommand)]
+pub enum DaemonAction {
+    /// Serve requests in the foreground until `fyaml daemon stop`
+    Start,
+    /// Ask the running daemon to exit
+    Stop,
+    /// Show the running daemon's version and cache statistics
+    Status,
+}
+
+#[derive(Debug, Args)]
+pub struct CodesArgs {
+    /// Emit the full registry (descriptions, examples, options) as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
 #[derive(Debug, Args)]
 pub struct ScaffoldArgs {
     /// Input YAML file
@@ -111,6 +626,29 @@ pub struct ScaffoldArgs {
     /// Optional split threshold for large scalar fragments
     #[arg(long)]
     pub split_threshold_bytes: Option<usize>,
+
+    /// Flush to disk before returning: none, dir (directory entries), or all (files too)
+    #[arg(long, value_enum, default_value_t = FsyncMode::None)]
+    pub fsync: FsyncMode,
+
+    /// Threads writing fragment files
+    #[arg(long, default_value_t = 1, value_name = "N")]
+    pub jobs: usize,
+
+    /// Multi-document input: error, first (warn and drop the rest), or split (one numbered
+    /// directory per document)
+    #[arg(long, value_enum, default_value_t = ScaffoldMultiDoc::Error)]
+    pub multi_doc: ScaffoldMultiDoc,
+
+    /// Write a nested mapping with fewer than N entries, all one-line scalars, as one
+    /// `<key>.yml` file instead of a directory of one-line files
+    #[arg(long, value_name = "N")]
+    pub group_leaves: Option<usize>,
+
+    /// Write a JSON record of every directory and file created: the input key path it holds,
+    /// the layout rule that produced it, and any renaming of its key
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<PathBuf>,
 }
 
 impl ScaffoldArgs {
@@ -119,6 +657,10 @@ impl ScaffoldArgs {
             layout: self.layout,
             seq: self.seq,
             split_threshold_bytes: self.split_threshold_bytes,
+            fsync: self.fsync,
+            jobs: self.jobs,
+            multi_doc: self.multi_doc,
+            group_leaves: self.group_leaves,
         }
     }
 }
@@ -129,10 +671,18 @@ pub struct BuildFlags {
     #[arg(long, default_value = "map-root")]
     pub root_mode: RootMode,
 
-    /// Root file path (required with --root-mode file-root)
-    #[arg(long)]
+    /// Root file path (with --root-mode file-root, unless --root-inline or --root-stdin is given)
+    #[arg(long, conflicts_with_all = ["root_inline", "root_stdin"])]
     pub root_file: Option<PathBuf>,
 
+    /// Root YAML document given inline, e.g. 'version: 2.1', instead of --root-file
+    #[arg(long, value_name = "YAML", conflicts_with = "root_stdin")]
+    pub root_inline: Option<String>,
+
+    /// Read the root YAML document from stdin instead of --root-file
+    #[arg(long)]
+    pub root_stdin: bool,
+
     /// Merge packed directory mapping under this key in file-root mode
     #[arg(long)]
     pub merge_under: Option<String>,
@@ -141,49 +691,364 @@ pub struct BuildFlags {
     #[arg(long)]
     pub include_hidden: bool,
 
+    /// Only scan entries matching this root-relative glob (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<Glob>,
+
+    /// Skip entries matching this root-relative glob, e.g. 'env/dev/**' (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<Glob>,
+
+    /// Read files matching this glob as bytes and emit them as `!!binary` base64 scalars,
+    /// e.g. '*.png' (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    pub accept_binary: Vec<Glob>,
+
     /// Sequence gap handling
     #[arg(long, default_value = "warn")]
     pub seq_gaps: SeqGapMode,
 
+    /// Index of the first item of a sequence directory, e.g. 1 for trees numbered from 1.yml
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub seq_start: u64,
+
+    /// Order of sequence items: by index value, or by file name
+    #[arg(long, value_enum, default_value_t = SeqOrder::Numeric)]
+    pub seq_order: SeqOrder,
+
     /// Multi-document YAML handling
     #[arg(long, default_value = "error")]
     pub multi_doc: MultiDocMode,
 
+    /// Key collisions: error, first-wins, last-wins, or deep-merge (mappings merge recursively)
+    #[arg(long, value_enum, default_value_t = CollisionPolicy::Error)]
+    pub on_collision: CollisionPolicy,
+
+    /// `name.yml` next to `name.yaml`: error, prefer-yml, or prefer-yaml
+    #[arg(long, value_enum, default_value_t = ExtensionConflict::Error)]
+    pub extension_conflict: ExtensionConflict,
+
+    /// Warn about fragments that do not use this extension (yml or yaml)
+    #[arg(long, value_enum, value_name = "EXT")]
+    pub require_extension: Option<YamlExtension>,
+
     /// Suppress warnings for dotted keys derived from filenames
     #[arg(long)]
     pub allow_dotted_keys: bool,
 
+    /// Expand dots in file names into nested keys (`server.http.port.yml` -> server.http.port)
+    #[arg(long)]
+    pub expand_dotted_keys: bool,
+
     /// Allow YAML reserved words as keys
     #[arg(long)]
     pub allow_reserved_keys: bool,
 
-    /// Attempt to preserve source order/styles where possible
+    /// Keys derived from non-ASCII names: allow, warn, error, or transliterate to ASCII
+    #[arg(long, value_enum, default_value_t = AsciiKeys::Allow)]
+    pub ascii_keys: AsciiKeys,
+
+    /// Merge `_.yml` and `index.yml` into their directory's mapping instead of using them as keys
+    #[arg(long)]
+    pub index_files: bool,
+
+    /// A directory holding only `value.yml` or `_value.yml` takes that file's value (as `scaffold --seq dir` writes scalars)
+    #[arg(long)]
+    pub value_files: bool,
+
+    /// Read `.txt` and `.md` files as string values keyed by their stem (`motd.txt` -> motd)
+    #[arg(long)]
+    pub text_files: bool,
+
+    /// Fail when an item of a directory-assembled sequence differs in type from the others
+    #[arg(long)]
+    pub uniform_sequences: bool,
+
+    /// Require these keys in every mapping item of the sequences at PATH, where `*` matches
+    /// any one key, e.g. 'steps=name,uses' (repeatable)
+    #[arg(long, value_name = "PATH=KEY,...")]
+    pub item_keys: Vec<ItemKeys>,
+
+    /// Bound the number of items in the sequences built from the directories at PATH, e.g.
+    /// 'workflows.*.jobs:min=1' (repeatable)
+    #[arg(long, value_name = "PATH:min=N,max=N")]
+    pub item_count: Vec<ItemCount>,
+
+    /// `<<: *base` merge keys: keep `<<` as an ordinary key, expand it into the mapping, or
+    /// error
+    #[arg(long, value_enum, default_value_t = MergeKeys::Keep)]
+    pub merge_keys: MergeKeys,
+
+    /// Deprecated alias for --merge-keys expand
+    #[arg(long, hide = true, conflicts_with = "merge_keys")]
+    pub resolve_merge_keys: bool,
+
+    /// Let `*alias` name values defined in <DIR>/_anchors.yml
+    #[arg(long)]
+    pub shared_anchors: bool,
+
+    /// Replace `!include PATH` values with the parsed file at PATH (relative to the including
+    /// file, inside <DIR>, not through symlinks); include errors exit with code 4
+    #[arg(long, value_enum, default_value_t = Includes::Off)]
+    pub includes: Includes,
+
+    /// Read `.md` files as their YAML front matter, with the body under --content-key
+    #[arg(long)]
+    pub front_matter: bool,
+
+    /// Key the Markdown body is stored under with --front-matter
+    #[arg(long, value_name = "KEY", default_value = "content")]
+    pub content_key: String,
+
+    /// Strip leading `NN-` prefixes from names and emit those keys in prefix order
+    #[arg(long)]
+    pub ordered_prefixes: bool,
+
+    /// Keep source key order, comments, and anchors in YAML output
     #[arg(long)]
     pub preserve: bool,
 
+    /// Emit the mappings under PATH in sorted key order (`keys`) or in build order (`none`),
+    /// overriding --preserve or the default for that subtree, e.g. 'services=keys' (repeatable)
+    #[arg(long, value_name = "PATH=keys|none")]
+    pub sort: Vec<SortRule>,
+
     /// Promote warnings to errors
     #[arg(long)]
     pub strict: bool,
 
+    /// Report diagnostics sorted by location, code, and key path, or in discovery order
+    #[arg(long, value_enum, default_value_t = DiagOrder::Sorted)]
+    pub diag_order: DiagOrder,
+
     /// Maximum YAML bytes allowed per input file
     #[arg(long)]
     pub max_yaml_bytes: Option<u64>,
+
+    /// Read fragment paths (relative to the input directory) from this file instead of
+    /// scanning; `-` reads the list from stdin
+    #[arg(long, value_name = "FILE")]
+    pub from_list: Option<PathBuf>,
+
+    /// Fail if contributing fragments differ from fyaml.manifest.json (new, missing, or changed)
+    #[arg(long)]
+    pub frozen: bool,
+
+    /// Integers wider than 64 bits: error, or keep their digits as a string
+    #[arg(long, value_enum, default_value_t = BigIntegers::Error)]
+    pub big_integers: BigIntegers,
+
+    /// ISO-8601-looking scalars: keep-string, tag as !!timestamp, or parse (normalize to UTC)
+    #[arg(long, value_enum, default_value_t = DatesMode::KeepString)]
+    pub dates: DatesMode,
+
+    /// YAML tags such as !Ref: preserve, strip to the untagged value, or error
+    #[arg(long, value_enum, default_value_t = TagPolicy::Preserve)]
+    pub tags: TagPolicy,
+
+    /// Worker threads for parsing fragments and canonicalizing output (output is identical
+    /// for any value)
+    #[arg(long, default_value_t = 1, value_name = "N")]
+    pub jobs: usize,
+
+    /// Keep parsed fragments in <DIR>/.fyaml-cache and reuse them for unchanged content
+    #[arg(long, overrides_with = "no_cache")]
+    pub cache: bool,
+
+    /// Do not read or write the parse cache (the default; overrides an earlier --cache)
+    #[arg(long, overrides_with = "cache")]
+    pub no_cache: bool,
+
+    /// Like --cache, after a failed run: only fragments changed since then are parsed again,
+    /// and a missing cache is reported
+    #[arg(long, conflicts_with = "no_cache")]
+    pub resume_from_cache: bool,
 }
 
 impl BuildFlags {
+    /// Whether `<DIR>/.fyaml-cache` is read and written.
+    pub fn uses_cache(&self) -> bool {
+        self.cache || self.resume_from_cache
+    }
+
     pub fn to_build_options(&self) -> BuildOptions {
         BuildOptions {
             include_hidden: self.include_hidden,
             allow_dotted_keys: self.allow_dotted_keys,
             allow_reserved_keys: self.allow_reserved_keys,
             seq_gaps: self.seq_gaps,
+            seq_start: self.seq_start,
+            seq_order: self.seq_order,
             multi_doc: self.multi_doc,
             strict: self.strict,
+            diag_order: self.diag_order,
             max_yaml_bytes: self.max_yaml_bytes,
             root_mode: self.root_mode,
             root_file: self.root_file.clone(),
+            root_inline: self.root_inline.clone(),
             merge_under: self.merge_under.clone(),
             preserve: self.preserve,
+            file_list: None,
+            frozen: self.frozen,
+            output: None,
+            source_positions: false,
+            big_integers: self.big_integers,
+            dates: self.dates,
+            tags: self.tags,
+            jobs: self.jobs,
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+            accept_binary: self.accept_binary.clone(),
+            ascii_keys: self.ascii_keys,
+            index_files: self.index_files,
+            value_files: self.value_files,
+            text_files: self.text_files,
+            resolve_includes: self.includes == Includes::On,
+            shared_anchors: self.shared_anchors,
+            merge_keys: if self.resolve_merge_keys {
+                MergeKeys::Expand
+            } else {
+                self.merge_keys
+            },
+            uniform_sequences: self.uniform_sequences,
+            item_keys: self.item_keys.clone(),
+            item_counts: self.item_count.clone(),
+            only: Vec::new(),
+            sort_rules: self.sort.clone(),
+            front_matter: self.front_matter,
+            content_key: self.content_key.clone(),
+            ordered_prefixes: self.ordered_prefixes,
+            expand_dotted_keys: self.expand_dotted_keys,
+            on_collision: self.on_collision,
+            extension_conflict: self.extension_conflict,
+            require_extension: self.require_extension,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct CanonicalFlags {
+    /// Mapping key order in canonical output: bytewise, unicode, natural (digit runs as
+    /// numbers), or cldr:<locale>
+    #[arg(long, default_value = "bytewise", value_name = "COLLATION")]
+    pub collation: Collation,
+
+    /// Float text in YAML output: native, or canonical (independent of the YAML library)
+    #[arg(long, value_enum, default_value_t = FloatFormat::Native)]
+    pub float_format: FloatFormat,
+
+    /// Floats with no fractional part: keep, or write as integers
+    #[arg(long, value_enum, default_value_t = IntegralFloats::Keep)]
+    pub integral_floats: IntegralFloats,
+
+    /// Negative zero: keep, or write as positive zero
+    #[arg(long, value_enum, default_value_t = NegativeZero::Keep)]
+    pub negative_zero: NegativeZero,
+
+    /// Read string values spelled as YAML 1.1 booleans (yes/no/on/off) as true/false
+    #[arg(long)]
+    pub yaml11_booleans: bool,
+
+    /// Normalize scalars so stylistic differences pack and diff alike: shorthand for
+    /// --yaml11-booleans --integral-floats int --negative-zero zero
+    #[arg(long)]
+    pub normalize_scalars: bool,
+
+    /// Rewrite ISO-8601 datetime strings that carry an offset as UTC RFC 3339
+    /// (`2001-12-15T02:59:43.1Z`), in any input, packed or not
+    #[arg(long)]
+    pub utc_timestamps: bool,
+
+    /// Spaces per nesting level of mappings in YAML output
+    #[arg(
+        long,
+        default_value_t = 2,
+        value_name = "N",
+        value_parser = clap::value_parser!(u8).range(2..=8)
+    )]
+    pub yaml_indent: u8,
+
+    /// Quoting of strings in YAML output: auto, ambiguous (also YAML 1.1 booleans such as
+    /// `on` and `no`), single, or double
+    #[arg(long, value_enum, default_value_t = QuoteStrings::Auto)]
+    pub quote_strings: QuoteStrings,
+
+    /// Write mappings and sequences of at most N scalars in flow style (`{a: 1}`, `[1, 2]`)
+    /// in YAML output; 0 writes every collection in block style
+    #[arg(long, default_value_t = 0, value_name = "N")]
+    pub flow_max_items: usize,
+
+    /// Block scalars for strings in YAML output: literal (`|` for strings with line breaks),
+    /// folded (also `>-` for long single-line strings, wrapped at --max-line-width), or
+    /// plain (none; line breaks written as `\n`). Defaults to folded when
+    /// --max-line-width is given, otherwise literal
+    #[arg(long, value_enum, value_name = "STYLE")]
+    pub fold_strings: Option<FoldStrings>,
+
+    /// Wrap single-line strings that would run past column N of YAML output in folded `>-`
+    /// blocks (default 80 under --fold-strings folded)
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u16).range(20..)
+    )]
+    pub max_line_width: Option<u16>,
+
+    /// Sort a mapping whose keys are all integers (`2`, `10`, or the strings "2", "10") by
+    /// value, the way directory sequences are ordered, instead of by --collation
+    #[arg(long)]
+    pub numeric_keys: bool,
+}
+
+impl CanonicalFlags {
+    /// `jobs` comes from the build flags, which share one `--jobs` for parsing and
+    /// canonicalization.
+    pub fn to_canonical_options(&self, jobs: usize) -> CanonicalOptions {
+        let normalize = self.normalize_scalars;
+        CanonicalOptions {
+            collation: self.collation.clone(),
+            float_format: self.float_format,
+            integral_floats: if normalize {
+                IntegralFloats::Int
+            } else {
+                self.integral_floats
+            },
+            negative_zero: if normalize {
+                NegativeZero::Zero
+            } else {
+                self.negative_zero
+            },
+            jobs,
+            yaml_indent: usize::from(self.yaml_indent),
+            quote_strings: self.quote_strings,
+            flow_max_items: self.flow_max_items,
+            fold_strings: self.fold_strings.unwrap_or(match self.max_line_width {
+                Some(_) => FoldStrings::Folded,
+                None => FoldStrings::Literal,
+            }),
+            max_line_width: self
+                .max_line_width
+                .map_or(DEFAULT_MAX_LINE_WIDTH, usize::from),
+            numeric_keys: self.numeric_keys,
+            yaml11_booleans: self.yaml11_booleans || normalize,
+            utc_timestamps: self.utc_timestamps,
         }
     }
 }
+
+/// The comment block YAML output starts with.
+#[derive(Debug, Clone, Args)]
+pub struct HeaderFlags {
+    /// Suppress the default version header comment
+    #[arg(long)]
+    pub no_header: bool,
+
+    /// Header comment text instead of `packed by fyaml v{version}`, with {version},
+    /// {git_sha}, {source_dir}, and {timestamp} filled in; `\n` starts a new line
+    #[arg(long, value_name = "TEXT", conflicts_with = "no_header")]
+    pub header_template: Option<String>,
+
+    /// Fill in {timestamp}: SOURCE_DATE_EPOCH when set, otherwise the time of the run
+    #[arg(long)]
+    pub header_timestamp: bool,
+}
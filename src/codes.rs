@@ -0,0 +1,918 @@
+//! Registry of every diagnostic code, used by `fyaml explain-code` and `fyaml codes`.
+//!
+//! A code is added here before it is raised anywhere. Uniqueness and the prefix/severity
+//! pairing (`E` error, `W` warn, `I` info) are checked at compile time; a unit test checks
+//! that every code literal in the sources is registered.
+
+use crate::diagnostics::{Category, Severity};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CodeInfo {
+    pub code: &'static str,
+    /// Severity before `--strict` promotes warnings to errors.
+    pub severity: Severity,
+    /// Determines the exit code when the diagnostic is an error.
+    pub category: Category,
+    pub summary: &'static str,
+    pub description: &'static str,
+    /// A minimal tree or command that triggers the diagnostic.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub example: Option<&'static str>,
+    /// Flags that change whether or how the diagnostic is reported.
+    pub options: &'static [&'static str],
+    /// Section of `fyaml-spec.md` that defines the behavior.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference: Option<&'static str>,
+}
+
+const fn error(code: &'static str, category: Category, summary: &'static str) -> CodeInfo {
+    CodeInfo {
+        code,
+        severity: Severity::Error,
+        category,
+        summary,
+        description: "",
+        example: None,
+        options: &[],
+        reference: None,
+    }
+}
+
+const fn warning(code: &'static str, summary: &'static str) -> CodeInfo {
+    CodeInfo {
+        severity: Severity::Warn,
+        ..error(code, Category::InvalidInput, summary)
+    }
+}
+
+impl CodeInfo {
+    const fn describe(self, description: &'static str) -> Self {
+        Self {
+            description,
+            ..self
+        }
+    }
+
+    const fn example(self, example: &'static str) -> Self {
+        Self {
+            example: Some(example),
+            ..self
+        }
+    }
+
+    const fn options(self, options: &'static [&'static str]) -> Self {
+        Self { options, ..self }
+    }
+
+    const fn reference(self, reference: &'static str) -> Self {
+        Self {
+            reference: Some(reference),
+            ..self
+        }
+    }
+}
+
+use Category::{Include, Internal, InvalidInput, Parse, Write};
+
+pub const CODES: &[CodeInfo] = &[
+    error("E000", InvalidInput, "input directory is missing or not a directory")
+        .describe("Every command that reads a FYAML tree needs an existing directory as its root.")
+        .example("fyaml pack ./does-not-exist")
+        .reference("§3.1"),
+    error("E001", InvalidInput, "key collision")
+        .describe(
+            "Two sources resolve to the same key path, such as `auth.yml` next to an `auth/` \
+             directory, a root-file key that a directory key would \
+             overwrite, or an index-file key that a sibling entry also provides. Collisions are \
+             errors by default because either choice would silently drop data; `--on-collision` \
+             can keep one value (W018) or merge mappings recursively.",
+        )
+        .example("config/auth.yml\nconfig/auth/provider.yml")
+        .options(&["--on-collision", "--merge-under", "--index-files"])
+        .reference("§4.7"),
+    error("E002", InvalidInput, "mixed numeric and non-numeric children in directory")
+        .describe(
+            "A directory is a sequence only when all contributors are numeric. Mixing `0.yml` \
+             with `name.yml` is ambiguous and rejected.",
+        )
+        .example("steps/0.yml\nsteps/notes.yml")
+        .reference("§4.4"),
+    error("E003", InvalidInput, "sequence has index gaps")
+        .describe(
+            "Sequence indices must be contiguous from --seq-start (0 by default) when \
+             `--seq-gaps=error` is set; an index below the start counts as a gap.",
+        )
+        .example("items/0.yml\nitems/2.yml")
+        .options(&["--seq-gaps", "--seq-start"])
+        .reference("§4.4"),
+    error("E004", InvalidInput, "case-only key collision")
+        .describe(
+            "Keys that differ only by letter case cannot coexist on case-insensitive filesystems \
+             (macOS and Windows defaults), so the tree would pack differently across platforms.",
+        )
+        .example("Readme.yml\nREADME.yml")
+        .reference("§4.7"),
+    error("E005", InvalidInput, "index file is not a mapping")
+        .describe(
+            "With `--index-files`, `_.yml` and `index.yml` are merged into their directory's \
+             mapping, so they must hold a mapping (or be empty).",
+        )
+        .example("services/_.yml   # contains a list")
+        .options(&["--index-files"]),
+    error("E006", InvalidInput, "same key from .yml and .yaml files")
+        .describe(
+            "`name.yml` and `name.yaml` in one directory both derive the key `name`. \
+             `--extension-conflict prefer-yml` or `prefer-yaml` uses one and lists the other as \
+             ignored in `fyaml explain`.",
+        )
+        .example("config/app.yml\nconfig/app.yaml")
+        .options(&["--extension-conflict", "--require-extension"])
+        .reference("§4.7"),
+    error("E007", InvalidInput, "sequence item differs in type from the other items")
+        .describe(
+            "With --uniform-sequences, an item of a sequence built from a directory is not of \
+             the type most of its siblings have, which usually means a misnamed file.",
+        )
+        .example("steps/0.yml and steps/1.yml are mappings, steps/2.yml is a string")
+        .options(&["--uniform-sequences"]),
+    error("E008", InvalidInput, "sequence item lacks a required key")
+        .describe(
+            "An item of a sequence matched by --item-keys is not a mapping, or lacks one of the \
+             keys listed for that sequence; the context names the missing keys.",
+        )
+        .example("--item-keys steps=name with steps/3.yml lacking `name`")
+        .options(&["--item-keys"]),
+    error("E009", InvalidInput, "sequence directory has the wrong number of items")
+        .describe(
+            "A directory at a path matched by --item-count assembled fewer or more items than \
+             the bounds allow. A directory whose entries were all ignored or misnamed counts as \
+             an empty sequence; the context lists its ignored entries.",
+        )
+        .example("--item-count 'workflows.*.jobs:min=1' with every file in jobs/ ignored")
+        .options(&["--item-count"]),
+    error("E020", InvalidInput, "reserved YAML word used as a directory name")
+        .describe(
+            "Directory names such as `true`, `no`, or `null` read as booleans or null in YAML 1.1 \
+             consumers when used as keys.",
+        )
+        .example("flags/on/enabled.yml")
+        .options(&["--allow-reserved-keys"])
+        .reference("§4.2"),
+    error("E021", InvalidInput, "empty key derived from a YAML filename")
+        .describe(
+            "A file named `.yml` or `.yaml` has no key once the extension is removed, or a name \
+             expanded by `--expand-dotted-keys` has an empty segment.",
+        )
+        .example("config/.yml --include-hidden")
+        .options(&["--expand-dotted-keys"])
+        .reference("§4.2"),
+    error("E022", InvalidInput, "reserved YAML word used as a filename")
+        .describe("Filenames such as `yes.yml` derive keys that YAML 1.1 consumers read as booleans.")
+        .example("flags/off.yml")
+        .options(&["--allow-reserved-keys"])
+        .reference("§4.2"),
+    error("E023", InvalidInput, "non-ASCII key derived from a file or directory name")
+        .describe(
+            "Reported under `--ascii-keys error` for names that contain characters outside ASCII.",
+        )
+        .example("regions/zürich.yml --ascii-keys error")
+        .options(&["--ascii-keys"]),
+    error("E024", InvalidInput, "derived key has no ASCII spelling")
+        .describe(
+            "`--ascii-keys transliterate` drops accents and spells letters such as `ß` in ASCII, \
+             but scripts such as CJK have no ASCII spelling.",
+        )
+        .example("regions/東京.yml --ascii-keys transliterate")
+        .options(&["--ascii-keys"]),
+    error("E030", InvalidInput, "unable to read directory")
+        .describe("Listing a directory failed, usually because of permissions."),
+    error("E031", InvalidInput, "unable to iterate directory entry")
+        .describe("The operating system reported an error while listing a directory entry."),
+    error("E032", InvalidInput, "unable to read entry file type")
+        .describe("The type of a directory entry (file, directory, symlink) could not be determined."),
+    error("E033", InvalidInput, "unable to read file metadata")
+        .describe("The size of a fragment could not be read before parsing it."),
+    error("E034", InvalidInput, "YAML fragment exceeds the maximum size")
+        .describe(
+            "A fragment, or a file read with `--accept-binary`, is larger than the limit set with \
+             `--max-yaml-bytes`.",
+        )
+        .options(&["--max-yaml-bytes", "--accept-binary"])
+        .reference("§8.4"),
+    error("E035", InvalidInput, "unable to read YAML file")
+        .describe("A fragment could not be read; fragments must be readable UTF-8 text."),
+    error("E040", InvalidInput, "seq-root requires numeric root contributors")
+        .describe("With `--root-mode seq-root`, every top-level entry must have a numeric key.")
+        .example("fyaml pack ./tree --root-mode seq-root   # tree contains name.yml")
+        .options(&["--root-mode"])
+        .reference("§4.3"),
+    error("E041", InvalidInput, "file-root mode requires --root-file")
+        .describe(
+            "`--root-mode file-root` takes the root document from `--root-file`, \
+             `--root-inline`, or `--root-stdin`, and none was given. Without them, a root file \
+             named `_root.yml`, `_root.yaml`, `root.yml`, or `root.yaml` is used if there is one.",
+        )
+        .options(&["--root-mode", "--root-file", "--root-inline", "--root-stdin"])
+        .reference("§4.3"),
+    error("E042", InvalidInput, "root file does not exist")
+        .describe("The path passed to `--root-file` does not resolve to a file under the root.")
+        .options(&["--root-file"])
+        .reference("§4.3"),
+    error("E043", Internal, "mapping assembly failed in file-root mode")
+        .describe("An internal invariant was violated; please report it with the tree that triggers it.")
+        .reference("§4.3"),
+    error("E044", InvalidInput, "merge target exists but is not a mapping")
+        .describe(
+            "`--merge-under <KEY>` merges directory keys into an existing mapping at `<KEY>`; \
+             the root file defines that key with a non-mapping value.",
+        )
+        .options(&["--merge-under"])
+        .reference("§4.3"),
+    error("E045", InvalidInput, "file-root merge requires a mapping root")
+        .describe("Directory keys can only be merged into a root file whose document is a mapping.")
+        .options(&["--merge-under"])
+        .reference("§4.3"),
+    error("E046", InvalidInput, "file-root YAML is not a mapping")
+        .describe("Directory keys cannot be merged into a scalar or sequence root document.")
+        .options(&["--merge-under", "--root-file"])
+        .reference("§4.3"),
+    error("E047", InvalidInput, "unable to read root document from stdin")
+        .describe(
+            "`--root-stdin` reads the file-root document from stdin, which fails when stdin is \
+             unreadable or `--from-list -` already reads it.",
+        )
+        .example("fyaml pack ./config --root-mode file-root --root-stdin --from-list -")
+        .options(&["--root-stdin", "--root-inline", "--from-list"]),
+    error("E048", InvalidInput, "several conventional root files found")
+        .describe(
+            "In file-root mode without `--root-file`, the root document is `_root.yml`, \
+             `_root.yaml`, `root.yml`, or `root.yaml`, whichever exists, and more than one does.",
+        )
+        .example("config/_root.yml\nconfig/root.yaml")
+        .options(&["--root-mode", "--root-file"])
+        .reference("§4.3"),
+    error("E050", InvalidInput, "input file does not exist or is not a file")
+        .describe(
+            "`fyaml check-file` or `fyaml canonicalize` was given a path that is missing, a \
+             directory, or unreadable.",
+        )
+        .example("fyaml canonicalize input.yml"),
+    error("E060", InvalidInput, "move source does not exist")
+        .describe("`fyaml plan-mv` was given a <FROM> path that is not part of the tree.")
+        .example("fyaml plan-mv ./tree missing.yml other.yml"),
+    error("E061", InvalidInput, "move destination is inside the move source")
+        .describe("An entry cannot be moved into itself.")
+        .example("fyaml plan-mv ./tree auth auth/nested"),
+    error("E062", InvalidInput, "move destination is not available")
+        .describe("The destination already exists or its parent directory does not.")
+        .example("fyaml plan-mv ./tree a.yml missing-dir/a.yml"),
+    error("E063", InvalidInput, "move path is not inside the FYAML root")
+        .describe("`plan-mv` paths must be relative, must not use `..`, and must not name the root itself.")
+        .example("fyaml plan-mv ./tree ../outside.yml a.yml"),
+    error("E070", InvalidInput, "listed fragment path is outside the FYAML root")
+        .describe(
+            "Entries in a `--from-list` file, and paths given to `build_from_entries`, must be \
+             relative paths without `..` components.",
+        )
+        .options(&["--from-list"]),
+    error("E071", InvalidInput, "listed fragment does not exist or is not a file")
+        .describe("Every `--from-list` entry must name an existing file under the root.")
+        .options(&["--from-list"]),
+    error("E072", InvalidInput, "unable to read fragment list")
+        .describe("The file passed to `--from-list` (or stdin for `-`) could not be read.")
+        .options(&["--from-list"]),
+    error("E080", InvalidInput, "unable to load fyaml manifest")
+        .describe("`--frozen` needs a readable `fyaml.manifest.json` at the root; create it with `fyaml manifest`.")
+        .options(&["--frozen"]),
+    error("E081", InvalidInput, "fragment is not recorded in the manifest")
+        .describe("A fragment contributes to the build but is absent from `fyaml.manifest.json`.")
+        .options(&["--frozen"]),
+    error("E082", InvalidInput, "manifest fragment is missing from the tree")
+        .describe("A fragment recorded in the manifest did not contribute to this build.")
+        .options(&["--frozen"]),
+    error("E083", InvalidInput, "fragment content differs from the manifest")
+        .describe("The SHA-256 of a fragment no longer matches the recorded checksum.")
+        .options(&["--frozen"]),
+    error("E084", InvalidInput, "unable to load fyaml keymap")
+        .describe("`fyaml.keymap.json` at the root maps renamed entries back to their keys; it must be valid JSON written by `fyaml scaffold`."),
+    error("E090", InvalidInput, "unable to load message catalog")
+        .describe("The file passed to `--catalog` is unreadable or is not a JSON object of code entries.")
+        .options(&["--catalog", "--lang"]),
+    error("E091", InvalidInput, "unknown diagnostic code")
+        .describe("`fyaml explain-code` only documents codes that fyaml reports.")
+        .example("fyaml explain-code E999"),
+    error("E092", InvalidInput, "unsupported output schema version")
+        .describe(
+            "`--output-schema-version` asked for a JSON layout this fyaml does not produce. \
+             Each version's layouts are frozen: fields are only added, never removed, renamed, \
+             or retyped.",
+        )
+        .example("fyaml --output-schema-version 2 validate config --json")
+        .options(&["--output-schema-version"]),
+    error("E100", Parse, "invalid YAML fragment")
+        .describe(
+            "A fragment failed to parse. The parser location is included when available. \
+             Integers wider than 64 bits are also reported here.",
+        )
+        .example("config/app.yml containing `a: [1`")
+        .options(&["--big-integers"])
+        .reference("§4.6"),
+    error("E101", Parse, "multi-document YAML is not supported in current mode")
+        .describe("A fragment contains several `---` documents and `--multi-doc=error` is in effect.")
+        .options(&["--multi-doc"])
+        .reference("§4.6"),
+    error("E102", Parse, "tagged value is not allowed")
+        .describe("`--tags=error` rejects every YAML tag such as `!Ref` or `!vault`.")
+        .options(&["--tags"]),
+    error("E103", Parse, "stripping tags produced a duplicate mapping key")
+        .describe("With `--tags=strip`, two mapping keys that differed only by their tags became equal.")
+        .options(&["--tags"]),
+    error("E104", Parse, "front matter is not a mapping")
+        .describe(
+            "With `--front-matter`, a Markdown file's front matter becomes a mapping that also \
+             holds the body, so it must be a mapping (or empty).",
+        )
+        .example("posts/hello.md starting with `---\\n- a\\n---`")
+        .options(&["--front-matter"]),
+    error("E105", Parse, "front matter already defines the content key")
+        .describe("The body of a Markdown file would overwrite a front matter key of the same name.")
+        .example("posts/hello.md with `content:` in its front matter")
+        .options(&["--front-matter", "--content-key"]),
+    error("E106", Include, "!include target is outside the FYAML root")
+        .describe(
+            "An `!include` path is absolute, its `..` components climb above the input \
+             directory, or it passes through a symlink, which includes never follow. Paths \
+             are relative to the including file.",
+        )
+        .example("config/db.yml with `tls: !include ../../certs.yml`")
+        .options(&["--includes"]),
+    error("E107", Include, "unable to read !include target")
+        .describe(
+            "The included file does not exist, is a directory, cannot be read, or is larger \
+             than --max-yaml-bytes.",
+        )
+        .example("config/db.yml with `tls: !include tls.yml` and no config/tls.yml")
+        .options(&["--includes", "--max-yaml-bytes"]),
+    error("E108", Include, "!include cycle")
+        .describe("A file includes itself, directly or through other includes; the context lists the chain.")
+        .example("a.yml with `b: !include b.yml` and b.yml with `a: !include a.yml`")
+        .options(&["--includes"]),
+    error("E109", Include, "!include needs a relative file path")
+        .describe("The value tagged `!include` is a mapping, sequence, or non-string scalar.")
+        .example("config/db.yml with `tls: !include [a.yml]`")
+        .options(&["--includes"]),
+    error("E110", InvalidInput, "unable to read flattened input")
+        .describe("`fyaml unflatten` could not read its input file or stdin.")
+        .example("fyaml flatten ./tree | fyaml unflatten -"),
+    error("E111", Parse, "malformed flattened line")
+        .describe(
+            "A line given to `fyaml unflatten` is not a key path followed by `: ` and a \
+             single-line YAML value.",
+        )
+        .example("fyaml unflatten flat.txt"),
+    error("E112", Parse, "flattened paths conflict")
+        .describe(
+            "Two lines give the same path a value, a path has both a value and entries below \
+             it, or sequence indexes skip a position.",
+        )
+        .example("fyaml unflatten flat.txt"),
+    error("E121", InvalidInput, "unable to load shared anchors file")
+        .describe(
+            "With --shared-anchors, `_anchors.yml` at the root could not be read or is not a \
+             mapping of anchor names to values.",
+        )
+        .example("_anchors.yml containing `- a`")
+        .options(&["--shared-anchors"]),
+    error("E122", Parse, "unknown alias")
+        .describe(
+            "With --shared-anchors, an `*alias` names no anchor in its own fragment or in \
+             `_anchors.yml`; the context lists the shared names.",
+        )
+        .example("services/api.yml with `limits: *defualt_limits`")
+        .options(&["--shared-anchors"]),
+    error("E123", Parse, "merge key value is not a mapping")
+        .describe(
+            "With --merge-keys expand, a `<<` key holds a scalar, or a sequence with an item \
+             that is not a mapping.",
+        )
+        .example("services/api.yml with `<<: base`")
+        .options(&["--merge-keys"]),
+    error("E125", InvalidInput, "selected key path is not in the packed document")
+        .describe(
+            "`fyaml pack --select` names a key path the packed document does not have. The \
+             context lists the keys where the path stopped matching.",
+        )
+        .example("fyaml pack ./config --select services.web")
+        .options(&["--select"]),
+    error("E126", InvalidInput, "unable to load --diff-against file")
+        .describe(
+            "`fyaml watch --diff-against` could not read or parse the packed file it compares \
+             each build with. The build and -o still go ahead; only the differences are not \
+             printed. A file that does not exist yet is not an error.",
+        )
+        .example("fyaml watch ./config -o out.yml --diff-against out.yml")
+        .options(&["--diff-against"]),
+    error("E127", InvalidInput, "invalid --header-template")
+        .describe(
+            "The `--header-template` text names a placeholder FYAML does not fill in, or uses \
+             `{timestamp}` without `--header-timestamp`. Timestamps are opt-in so that packing \
+             the same tree twice gives the same bytes.",
+        )
+        .example("fyaml pack ./config --header-template 'generated from {source_dir} at {git_sha}'")
+        .options(&["--header-template", "--header-timestamp"]),
+    error("E128", InvalidInput, "packed file does not match its source tree")
+        .describe(
+            "`fyaml verify` packed the directory again and its fingerprint differs from the \
+             packed file's document. Fingerprints ignore key order, so only values, keys, tags, \
+             and types count. The context lists the changed key paths.",
+        )
+        .example("fyaml verify ./config config.yml after editing ./config/db.yml"),
+    error("E129", InvalidInput, "unable to load packed file")
+        .describe(
+            "`fyaml verify`, or `fyaml diff` given a file, could not read the packed file, \
+             decompress it, or parse it as YAML or JSON.",
+        ),
+    error("E130", InvalidInput, "output file is out of date")
+        .describe(
+            "`fyaml pack --check` rendered the document in memory and it differs byte for byte \
+             from the -o file, or the file could not be read. Nothing is written. The cause \
+             gives the first differing line; for YAML and JSON the context lists the changed \
+             key paths, or says that only the formatting differs.",
+        )
+        .example("fyaml pack ./config -o config.yml --check   # in CI, after editing ./config")
+        .options(&["--check"]),
+    error("E131", InvalidInput, "key path does not give a valid environment variable name")
+        .describe(
+            "`fyaml export-env` names each variable after its key path, upper-cased with other \
+             characters as `_`, after `--prefix`. A name must start with a letter or `_`, so \
+             sequence items and keys that start with a digit need a prefix, as does a \
+             `--path` that selects a single value.",
+        )
+        .example("fyaml export-env ./config --path ports --lists index   # gives 0=...")
+        .options(&["--prefix", "--path"]),
+    error("E132", InvalidInput, "two key paths export the same environment variable")
+        .describe(
+            "Keys that differ only in case or punctuation, such as `db-host` and `db_host`, \
+             give the same variable name, and a shell keeps only the last assignment.",
+        )
+        .example("fyaml export-env ./config   # with db-host and db_host in the same mapping")
+        .options(&["--path"]),
+    error("E133", InvalidInput, "value cannot be exported as an environment variable")
+        .describe(
+            "The value is a sequence under `--lists error`, a sequence of collections under \
+             `--lists join`, or a collection with keys JSON cannot hold under `--lists json` \
+             or `--maps json`.",
+        )
+        .example("fyaml export-env ./config --lists error   # with hosts: [a, b]")
+        .options(&["--lists", "--maps"]),
+    error("E135", Parse, "merge key is not allowed")
+        .describe(
+            "`--merge-keys error` rejects `<<` merge keys, so that every value in the packed \
+             document is written out where it appears. `--merge-keys expand` adds the merged \
+             entries to the mapping instead, and `keep` (the default) packs `<<` as an \
+             ordinary key.",
+        )
+        .example("fyaml pack ./config --merge-keys error   # with api: {<<: *base}")
+        .options(&["--merge-keys"]),
+    error("E200", InvalidInput, "unable to read scaffold input file")
+        .describe("`fyaml scaffold` could not read its input YAML file.")
+        .reference("§10"),
+    error("E201", Parse, "invalid YAML in scaffold input")
+        .describe("The scaffold input file failed to parse.")
+        .reference("§10"),
+    error("E202", Parse, "scaffold input must be a single YAML document")
+        .describe(
+            "Scaffolding several documents would not produce one deterministic layout. \
+             `--multi-doc split` writes one numbered directory per document instead, and \
+             `--multi-doc first` keeps only the first.",
+        )
+        .example("fyaml scaffold bundle.yml ./out   # bundle.yml holds `---`-separated manifests")
+        .options(&["--multi-doc"])
+        .reference("§10"),
+    error("E203", Write, "unable to create scaffold output directory")
+        .describe("The output directory for `fyaml scaffold` could not be created."),
+    error("E204", Write, "unable to create mapping directory")
+        .describe("A directory for a nested mapping could not be created."),
+    error("E205", InvalidInput, "non-string mapping keys are unsupported for scaffold")
+        .describe("Scaffold turns keys into file and directory names, so keys must be strings.")
+        .reference("§10"),
+    error("E206", Write, "unable to create sequence directory")
+        .describe("A directory for a sequence could not be created."),
+    error("E207", Write, "unable to create sequence item directory")
+        .describe("A directory for a sequence item could not be created."),
+    error("E208", Internal, "unable to serialize YAML fragment")
+        .describe("A scaffolded value could not be written as YAML; please report it."),
+    error("E209", Write, "unable to create split directory")
+        .describe("A directory for a split fragment could not be created."),
+    error("E210", Write, "unable to write split YAML fragment")
+        .describe("A split fragment file could not be written."),
+    error("E211", Write, "unable to write YAML fragment")
+        .describe("A fragment file could not be written."),
+    error("E212", InvalidInput, "mapping key contains path separators and cannot be scaffolded")
+        .describe("Keys containing `/` or `\\` cannot become a single file or directory name.")
+        .reference("§10"),
+    error("E213", InvalidInput, "empty mapping key cannot be scaffolded")
+        .describe("Filesystem entries need non-empty names.")
+        .reference("§10"),
+    error("E214", Write, "unable to move scaffold output into place")
+        .describe("Scaffold stages its output next to the output directory and moves it into place last; that move failed."),
+    error("E215", InvalidInput, "scaffold input is inside the output directory")
+        .describe(
+            "Scaffolding `config.yml` into its own directory would overwrite it with a fragment, \
+             or leave it behind to be packed as one, so nothing is written.",
+        )
+        .example("fyaml scaffold config/app.yml config"),
+    error("E216", InvalidInput, "anonymize output directory already exists")
+        .describe("`fyaml anonymize` writes its copy into a new directory and never overwrites one.")
+        .example("fyaml anonymize config repro  # with repro/ already present"),
+    error("E217", InvalidInput, "anonymize output is inside the input directory")
+        .describe("The anonymized copy would become part of the tree it is copied from.")
+        .example("fyaml anonymize config config/repro"),
+    error("E218", Write, "unable to copy entry into the anonymized tree")
+        .describe("An input entry could not be read, or its anonymized copy could not be written."),
+    error("E219", Write, "unable to write scaffold report")
+        .describe(
+            "`scaffold --report` wrote the layout but could not write the report file, usually \
+             because its directory does not exist or is not writable.",
+        )
+        .options(&["--report"]),
+    error("E300", Internal, "unable to serialize YAML output")
+        .describe("The packed value could not be emitted as YAML; please report it.")
+        .reference("§5.2"),
+    error("E301", Write, "unable to serialize JSON output")
+        .describe("`--format json` needs mapping keys that convert to JSON strings.")
+        .options(&["--format"])
+        .reference("§5.4"),
+    error("E302", Write, "unable to write output file")
+        .describe("The file given with `-o` (or the manifest path) could not be written.")
+        .options(&["-o"]),
+    error("E303", Internal, "unable to render explain JSON")
+        .describe("Explain output could not be serialized; please report it."),
+    error("E304", Internal, "unable to render diagnostics JSON")
+        .describe("Diagnostics could not be serialized; please report it.")
+        .reference("§7.3"),
+    error("E305", Internal, "unable to render move plan JSON")
+        .describe("`plan-mv --json` output could not be serialized; please report it."),
+    error("E306", Internal, "unable to render fyaml manifest")
+        .describe("The manifest could not be serialized; please report it."),
+    error("E307", Internal, "unable to render diagnostic code registry")
+        .describe("`fyaml codes --json` output could not be serialized; please report it."),
+    error("E308", Write, "unable to open diagnostics file")
+        .describe("The file named by `--diagnostics-to file:PATH` could not be created.")
+        .options(&["--diagnostics-to"]),
+    error("E309", Write, "unable to run fyaml daemon")
+        .describe(
+            "`fyaml daemon start` could not bind its socket, usually because another daemon \
+             already listens there.",
+        )
+        .example("FYAML_DAEMON_SOCKET=/tmp/fyaml.sock fyaml daemon start"),
+    error("E310", InvalidInput, "no fyaml daemon is reachable")
+        .describe(
+            "`fyaml daemon stop` and `fyaml daemon status` found no daemon on the socket. \
+             `fyaml validate` never reports this; it builds in-process instead.",
+        )
+        .example("fyaml daemon status"),
+    error("E311", Write, "unable to write metrics file")
+        .describe(
+            "The file named by `--metrics-out` could not be written. The packed output, if any, \
+             was already written.",
+        )
+        .options(&["--metrics-out"]),
+    error("E312", Internal, "unable to render orphan report JSON")
+        .describe("`fyaml orphans --json` output could not be serialized; please report it."),
+    error("E313", Write, "unable to rewrite fragment")
+        .describe("`validate --fix unwrap` could not write the unwrapped fragment back.")
+        .options(&["--fix"]),
+    error("E314", InvalidInput, "packed document cannot be written as JSON without loss")
+        .describe(
+            "`--format json` was requested for a document with a YAML-only construct: a mapping \
+             key that is null, a collection, or tagged; two keys that become the same JSON string \
+             (`1` and `\"1\"`); or a NaN or infinite float, which JSON would write as null. \
+             Nothing is written. Integer and boolean keys are written as strings.",
+        )
+        .example("ports.yml containing `1: a` and `\"1\": b`, packed with --format json")
+        .options(&["--format"])
+        .reference("§5.4"),
+    error("E315", Internal, "unable to render anonymize report JSON")
+        .describe("`fyaml anonymize --json` output could not be serialized; please report it."),
+    error("E316", InvalidInput, "--format ndjson requires a sequence document")
+        .describe(
+            "NDJSON output writes one line of JSON per item of the packed sequence, but the \
+             document is a mapping or scalar. Nothing is written.",
+        )
+        .example("fyaml pack ./config --format ndjson without --root-mode seq-root")
+        .options(&["--format", "--root-mode", "--select"]),
+    error("E317", InvalidInput, "--explode-docs requires a sequence document written as YAML")
+        .describe(
+            "`pack --explode-docs` writes each item of the packed sequence as its own YAML \
+             document, but the document is a mapping or scalar, or --format is not yaml. \
+             Nothing is written.",
+        )
+        .example("fyaml pack ./manifests --explode-docs without --root-mode seq-root")
+        .options(&["--explode-docs", "--root-mode", "--multi-doc", "--select", "--format"]),
+    error("E318", InvalidInput, "--output-dir requires a mapping document")
+        .describe(
+            "`pack --output-dir` writes one file per top-level key, but the packed document is \
+             a sequence or scalar. Nothing is written.",
+        )
+        .example("fyaml pack ./jobs --root-mode seq-root --output-dir out/")
+        .options(&["--output-dir", "-o", "--select"]),
+    error("E319", Internal, "unable to render environment report JSON")
+        .describe("`fyaml env-report --json` output could not be serialized; please report it."),
+    error("E320", Write, "unable to write GitHub Actions outputs")
+        .describe(
+            "`--gha-output` could not append to the file `$GITHUB_OUTPUT` or \
+             `$GITHUB_STEP_SUMMARY` names, usually because the run is not inside a GitHub \
+             Actions step. A run that already failed keeps its own exit code.",
+        )
+        .options(&["--gha-output"]),
+    error("E321", Write, "unable to apply suggestion")
+        .describe(
+            "`validate --fix collisions` could not move an entry as the chosen suggestion \
+             says, usually because the target appeared since the suggestion was made or the \
+             directory is not writable. Other collisions are still fixed.",
+        )
+        .options(&["--fix", "--apply-suggestion"]),
+    error("E322", Write, "unable to write source map")
+        .describe(
+            "`pack --source-map` wrote the packed document but could not write the source map \
+             file, usually because its directory does not exist or is not writable.",
+        )
+        .options(&["--source-map"]),
+    error("E323", Write, "unable to compress output")
+        .describe(
+            "`pack --compress` could not finish writing the compressed -o file, typically \
+             because the disk is full. The file may be left incomplete.",
+        )
+        .example("fyaml pack ./config -o /full-disk/out.yml.zst --compress zstd")
+        .options(&["--compress"]),
+    CodeInfo {
+        severity: Severity::Info,
+        ..error("I200", Internal, "scaffold generated a deterministic layout")
+            .describe("Scaffold is a one-way helper; packing its output is not guaranteed to reproduce every input detail.")
+            .reference("§10")
+    },
+    CodeInfo {
+        severity: Severity::Info,
+        ..error("I201", Internal, "packed document is the same with a toggled flag")
+            .describe("`validate --what-if FLAG` packed the tree again with the flag toggled and got an identical document.")
+            .options(&["--what-if"])
+    },
+    warning("W010", "dotted key derived from filename")
+        .describe("Keys containing dots are easy to confuse with nested key paths.")
+        .example("config/db.primary.yml")
+        .options(&["--allow-dotted-keys", "--expand-dotted-keys", "--strict"])
+        .reference("§4.2"),
+    warning("W011", "sequence has index gaps")
+        .describe("Sequence indices are not contiguous; the gaps are closed when packing.")
+        .example("items/0.yml\nitems/2.yml")
+        .options(&["--seq-gaps", "--seq-start", "--strict"])
+        .reference("§4.4"),
+    warning("W012", "large YAML fragment detected")
+        .describe("Fragments larger than 1 MiB are hard to review; consider splitting them.")
+        .options(&["--max-yaml-bytes", "--strict"])
+        .reference("§8.4"),
+    warning("W013", "possible YAML anchors/aliases may not be preserved")
+        .describe(
+            "Anchors and aliases are expanded in canonical output, so the source style is lost. \
+             `--preserve` writes them back wherever the aliased value is still unchanged.",
+        )
+        .options(&["--preserve", "--strict"])
+        .reference("§5.3"),
+    warning("W014", "multi-document YAML: using the first document")
+        .describe(
+            "`--multi-doc=first` keeps the first document of a fragment, or of the `scaffold` \
+             input, and ignores the rest.",
+        )
+        .options(&["--multi-doc", "--strict"])
+        .reference("§4.6"),
+    warning("W015", "naive datetime has no UTC offset")
+        .describe(
+            "Consumers disagree on whether datetimes without an offset are local time or UTC. \
+             Reported under `--dates tag` and `--dates parse`.",
+        )
+        .example("release.yml containing `cut: 2001-12-14 21:59:43`")
+        .options(&["--dates", "--strict"]),
+    warning("W016", "sibling keys differ only by letter case")
+        .describe(
+            "Reported by `fyaml scaffold`. Keys such as `API` and `api` would overwrite each other \
+             on case-insensitive filesystems, so later ones are written as `api~2` and recorded in \
+             `fyaml.keymap.json`, which `pack` reads.",
+        )
+        .reference("§10"),
+    warning("W017", "non-ASCII key derived from a file or directory name")
+        .describe("Reported under `--ascii-keys warn`; the key is used as it is.")
+        .example("regions/zürich.yml --ascii-keys warn")
+        .options(&["--ascii-keys", "--strict"]),
+    warning("W018", "key collision resolved by --on-collision")
+        .describe(
+            "`--on-collision first-wins` or `last-wins` kept one of two values for the same key \
+             and dropped the other. Sources are ordered by path; the root file comes before the \
+             directory, and a directory's entries before its index files.",
+        )
+        .example("config/auth.yml\nconfig/auth/provider.yml   # --on-collision last-wins")
+        .options(&["--on-collision", "--strict"])
+        .reference("§4.7"),
+    warning("W019", "fragment does not use the required extension")
+        .describe(
+            "`--require-extension yml` or `yaml` enforces one extension across the tree; every \
+             YAML file with the other one is reported, including files shadowed by \
+             `--extension-conflict`.",
+        )
+        .example("config/app.yaml --require-extension yml")
+        .options(&["--require-extension", "--strict"]),
+    warning("W020", "fragment's only top-level key repeats its file name")
+        .describe(
+            "`database.yml` holding `database: {...}` packs to `database.database`. `fyaml \
+             validate --fix unwrap` removes the wrapper unless another entry contributes the same \
+             key.",
+        )
+        .example("config/database.yml containing `database:` at the top")
+        .options(&["--fix", "--strict"]),
+    warning("W021", "entry name has trailing whitespace or dots")
+        .describe(
+            "`app.yml ` and `app .yml` derive the key `app`, as Windows would name them; the \
+             context lists the removed bytes. Another entry with the same key is a collision.",
+        )
+        .example("config/app .yml")
+        .options(&["--strict"]),
+    warning("W022", "YAML extension is not lowercase")
+        .describe(
+            "`CONFIG.YML` is read as a fragment with the key `CONFIG`, but a lowercase extension \
+             keeps names distinct on case-insensitive filesystems.",
+        )
+        .example("config/CONFIG.YML")
+        .options(&["--strict"]),
+    warning("W024", "explicit key overrides a merged value")
+        .describe(
+            "A mapping sets a key itself and merges a different value for it through `<<`. As \
+             YAML specifies, the explicit value is kept.",
+        )
+        .example("`<<: *defaults` with `defaults: {replicas: 2}` next to `replicas: 3`")
+        .options(&["--merge-keys", "--strict"]),
+    warning("W025", "overlay replaces a value of another type")
+        .describe(
+            "`fyaml pack BASE OVERLAY...` found a key path where a later layer's value is of a \
+             different type than the earlier layers'. The later value is kept; the context names \
+             the file in each layer.",
+        )
+        .example("base/log.yml holds `level: info`, overlays/prod/log.yml holds `warn`")
+        .options(&["--overlay-merge", "--strict"]),
+    warning("W026", "packed document would change with a toggled flag")
+        .describe(
+            "`validate --what-if FLAG` packed the tree again with the flag turned on (or off, \
+             when it was given) and the document differs. The context lists fragments newly \
+             read or no longer read and the first changed key paths.",
+        )
+        .example("fyaml validate ./config --what-if include-hidden with a .secrets.yml present")
+        .options(&["--what-if"]),
+    warning("W027", "packed file was edited after it was packed")
+        .describe(
+            "The fingerprint in the packed file's header does not match the document below it, \
+             so the file was changed by hand or by another tool after `fyaml pack` wrote it. \
+             `fyaml verify` still compares the document itself with the tree.",
+        ),
+    warning("W050", "entries ignored while scanning")
+        .describe("Some entries were skipped by the ignoring rules; `fyaml explain` lists them.")
+        .options(&["--include-hidden", "--strict"])
+        .reference("§4.8"),
+    warning("W060", "unable to update the parse cache")
+        .describe("`--cache` could not write `.fyaml-cache`; the build result is unaffected.")
+        .options(&["--cache", "--no-cache"]),
+    warning("W061", "no parse cache to resume from")
+        .describe(
+            "`--resume-from-cache` found no usable `.fyaml-cache`, so every fragment was parsed. \
+             The run saves a cache, so the next resumed run only parses what changed.",
+        )
+        .options(&["--resume-from-cache", "--cache"]),
+];
+
+const _: () = assert!(
+    registry_is_consistent(CODES),
+    "diagnostic codes must be unique and their prefix must match their severity"
+);
+
+const fn registry_is_consistent(codes: &[CodeInfo]) -> bool {
+    let mut i = 0;
+    while i < codes.len() {
+        let prefix = match codes[i].severity {
+            Severity::Error => b'E',
+            Severity::Warn => b'W',
+            Severity::Info => b'I',
+        };
+        if codes[i].code.as_bytes()[0] != prefix {
+            return false;
+        }
+        let mut j = i + 1;
+        while j < codes.len() {
+            if same_code(codes[i].code, codes[j].code) {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn same_code(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+pub fn lookup(code: &str) -> Option<&'static CodeInfo> {
+    CODES
+        .iter()
+        .find(|info| info.code.eq_ignore_ascii_case(code))
+}
+
+impl CodeInfo {
+    /// Extended help in the style of `rustc --explain`.
+    pub fn render_human(&self) -> String {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warn => "warn",
+            Severity::Info => "info",
+        };
+        let mut out = format!("{} ({severity}): {}\n", self.code, self.summary);
+        if !self.description.is_empty() {
+            out.push_str(&format!("\n{}\n", self.description));
+        }
+        if let Some(example) = self.example {
+            out.push_str("\nExample:\n");
+            for line in example.lines() {
+                out.push_str(&format!("    {line}\n"));
+            }
+        }
+        if !self.options.is_empty() {
+            out.push_str(&format!("\nRelated options: {}\n", self.options.join(", ")));
+        }
+        if let Some(reference) = self.reference {
+            out.push_str(&format!("\nSee: fyaml-spec.md {reference}\n"));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_is_case_insensitive_and_renders_sections() {
+        let info = lookup("e001").expect("E001 documented");
+        let rendered = info.render_human();
+        assert!(rendered.starts_with("E001 (error): key collision\n"));
+        assert!(rendered.contains("\nExample:\n    config/auth.yml\n"));
+        assert!(rendered.contains("See: fyaml-spec.md §4.7"));
+        assert!(lookup("E999").is_none());
+    }
+
+    #[test]
+    fn every_code_literal_in_the_sources_is_registered() {
+        let src = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut pending = vec![src];
+        let mut unregistered = Vec::new();
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(&dir).expect("read src dir") {
+                let path = entry.expect("dir entry").path();
+                if path.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                if path.extension().map_or(true, |ext| ext != "rs") || path.ends_with("codes.rs") {
+                    continue;
+                }
+                let text = std::fs::read_to_string(&path).expect("read source");
+                for window in text.as_bytes().windows(6) {
+                    let is_code = window[0] == b'"'
+                        && window[5] == b'"'
+                        && matches!(window[1], b'E' | b'W' | b'I')
+                        && window[2..5].iter().all(u8::is_ascii_digit);
+                    if !is_code {
+                        continue;
+                    }
+                    let code = std::str::from_utf8(&window[1..5]).expect("ASCII");
+                    if lookup(code).is_none() {
+                        unregistered.push(format!("{code} in {}", path.display()));
+                    }
+                }
+            }
+        }
+        assert!(
+            unregistered.is_empty(),
+            "unregistered codes: {unregistered:?}"
+        );
+    }
+}
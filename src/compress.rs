@@ -0,0 +1,137 @@
+//! `pack --compress`: the packed document written gzip- or zstd-compressed, and the
+//! transparent decompression of such files where a packed file is read back (`verify`,
+//! `pack --check`, and `watch --diff-against`).
+//!
+//! Compression runs in-process, so the output bytes depend only on the fyaml version.
+//! Compressed files are recognized by their magic bytes, not their extension.
+
+use crate::config::Compression;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// zstd's own default level, as the `zstd` program uses.
+const ZSTD_LEVEL: i32 = 3;
+
+impl Compression {
+    /// The compression `bytes` were written with, if any.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(GZIP_MAGIC) {
+            Some(Self::Gzip)
+        } else if bytes.starts_with(ZSTD_MAGIC) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// Writes everything it is given, compressed, to a file.
+pub enum Compressor {
+    /// The gzip header carries no file name and a zero modification time, so output stays
+    /// reproducible.
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl Compressor {
+    pub fn new(compression: Compression, output: File) -> io::Result<Self> {
+        let output = BufWriter::new(output);
+        Ok(match compression {
+            Compression::Gzip => Self::Gzip(GzEncoder::new(output, flate2::Compression::default())),
+            Compression::Zstd => Self::Zstd(zstd::Encoder::new(output, ZSTD_LEVEL)?),
+        })
+    }
+
+    /// Writes the end of the compressed stream and flushes the file.
+    pub fn finish(self) -> io::Result<()> {
+        let mut output = match self {
+            Self::Gzip(encoder) => encoder.finish()?,
+            Self::Zstd(encoder) => encoder.finish()?,
+        };
+        output.flush()
+    }
+}
+
+impl Write for Compressor {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Gzip(encoder) => encoder.write(buf),
+            Self::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Gzip(encoder) => encoder.flush(),
+            Self::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// The contents of `path`, decompressed when it was compressed.
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+    let bytes = std::fs::read(path)?;
+    match Compression::detect(&bytes) {
+        Some(compression) => decompress(compression, &bytes),
+        None => Ok(bytes),
+    }
+}
+
+/// [`read`] for text.
+pub fn read_to_string(path: &Path) -> io::Result<String> {
+    String::from_utf8(read(path)?).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn decompress(compression: Compression, bytes: &[u8]) -> io::Result<Vec<u8>> {
+    match compression {
+        // `gzip` writes one member per input file, and concatenated members are one stream.
+        Compression::Gzip => {
+            let mut out = Vec::new();
+            MultiGzDecoder::new(bytes).read_to_end(&mut out)?;
+            Ok(out)
+        }
+        Compression::Zstd => zstd::decode_all(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_compressed_files_by_magic_bytes() {
+        assert_eq!(
+            Compression::detect(&[0x1f, 0x8b, 8, 0]),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::detect(&[0x28, 0xb5, 0x2f, 0xfd, 4]),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::detect(b"a: 1\n"), None);
+        assert_eq!(Compression::detect(&[0x1f]), None);
+    }
+
+    #[test]
+    fn compressed_files_read_back_unchanged() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let text = "a: 1\nb: [x, y]\n".repeat(100);
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let path = dir.path().join("packed");
+            let file = File::create(&path).expect("create");
+            let mut compressor = Compressor::new(compression, file).expect("compressor");
+            compressor.write_all(text.as_bytes()).expect("write");
+            compressor.finish().expect("finish");
+
+            let bytes = std::fs::read(&path).expect("read");
+            assert_eq!(Compression::detect(&bytes), Some(compression));
+            assert_eq!(read_to_string(&path).expect("decompress"), text);
+        }
+    }
+}
@@ -1,8 +1,12 @@
+use crate::glob::Glob;
+use crate::keypath::PathPattern;
 use clap::ValueEnum;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::path::PathBuf;
+use std::str::FromStr;
 
-#[derive(Debug, Clone, Copy, ValueEnum, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "kebab-case")]
 pub enum RootMode {
     MapRoot,
@@ -10,7 +14,7 @@ pub enum RootMode {
     FileRoot,
 }
 
-#[derive(Debug, Clone, Copy, ValueEnum, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "kebab-case")]
 pub enum SeqGapMode {
     Error,
@@ -18,7 +22,38 @@ pub enum SeqGapMode {
     Allow,
 }
 
-#[derive(Debug, Clone, Copy, ValueEnum, Serialize, PartialEq, Eq)]
+/// Order in which a build reports its diagnostics.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiagOrder {
+    /// As the tree walk found them, which can vary with the platform's directory order.
+    Discovery,
+    /// By location, then code, then derived key path.
+    #[default]
+    Sorted,
+}
+
+/// Order of the items of a directory-assembled sequence.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum SeqOrder {
+    /// By index value: `2.yml` before `10.yml`.
+    #[default]
+    Numeric,
+    /// By file name: `10.yml` before `2.yml`.
+    Lexical,
+}
+
+impl SeqOrder {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Numeric => "numeric",
+            Self::Lexical => "lexical",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "kebab-case")]
 pub enum MultiDocMode {
     Error,
@@ -31,6 +66,40 @@ pub enum MultiDocMode {
 pub enum OutputFormat {
     Yaml,
     Json,
+    /// JSON on a single line.
+    JsonCompact,
+    /// One line of JSON per item of a sequence document.
+    Ndjson,
+    /// One YAML document per item of a sequence document (`pack --explode-docs`).
+    #[value(skip)]
+    YamlDocuments,
+    /// One `path,type,value,source_file` row per leaf.
+    Csv,
+    /// Like `csv`, tab-separated.
+    Tsv,
+}
+
+impl OutputFormat {
+    /// The file extension `pack --output-dir` gives files written in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Yaml | Self::YamlDocuments => "yml",
+            Self::Json | Self::JsonCompact => "json",
+            Self::Ndjson => "ndjson",
+            Self::Csv => "csv",
+            Self::Tsv => "tsv",
+        }
+    }
+}
+
+/// Compression applied to the `pack -o` file.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Compression {
+    /// gzip, at the default level.
+    Gzip,
+    /// Zstandard, at the default level.
+    Zstd,
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum, Serialize, PartialEq, Eq)]
@@ -40,19 +109,793 @@ pub enum DiffFormat {
     Json,
 }
 
+/// Shape of `validate --json` and `explain --json` output.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum JsonLayout {
+    /// The report alone: the diagnostics list, or the explain object.
+    Array,
+    /// An object with the report plus `summary` (tallies, exit code, duration) and `tool`
+    /// (version, build options).
+    Envelope,
+}
+
+/// Automatic fixes applied by `validate --fix`.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FixMode {
+    /// Remove a top-level key that repeats the fragment's file name (`W020`).
+    Unwrap,
+    /// Apply a suggested resolution to each key collision (`E001`, `E004`).
+    Collisions,
+}
+
+/// How `export-env` exports a sequence.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum EnvLists {
+    /// One variable holding the sequence as JSON.
+    Json,
+    /// One variable per item, named with its index (`HOSTS_0`).
+    Index,
+    /// One variable holding the scalar items joined by `--list-separator`.
+    Join,
+    /// Refuse to export sequences.
+    Error,
+}
+
+/// How `export-env` exports a nested mapping.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum EnvMaps {
+    /// One variable per key below it (`DATABASE_HOST`).
+    Flatten,
+    /// One variable holding the mapping as JSON.
+    Json,
+}
+
+/// Whether `!include` tags are resolved (spec Appendix B). Off by default.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Includes {
+    /// `!include` values are kept as tagged strings.
+    #[default]
+    Off,
+    /// `!include PATH` values are replaced by the parsed file at `PATH`.
+    On,
+}
+
+/// A build flag `validate --what-if` turns on (or off, when it is already given) to report
+/// how the packed document would change.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WhatIf {
+    IncludeHidden,
+    TextFiles,
+    FrontMatter,
+    IndexFiles,
+    ValueFiles,
+    ExpandDottedKeys,
+    Includes,
+    MergeKeys,
+    SharedAnchors,
+    OrderedPrefixes,
+}
+
+impl WhatIf {
+    /// The command-line flag this toggles.
+    pub fn flag(self) -> &'static str {
+        match self {
+            Self::IncludeHidden => "--include-hidden",
+            Self::TextFiles => "--text-files",
+            Self::FrontMatter => "--front-matter",
+            Self::IndexFiles => "--index-files",
+            Self::ValueFiles => "--value-files",
+            Self::ExpandDottedKeys => "--expand-dotted-keys",
+            Self::Includes => "--includes=on",
+            Self::MergeKeys => "--merge-keys=expand",
+            Self::SharedAnchors => "--shared-anchors",
+            Self::OrderedPrefixes => "--ordered-prefixes",
+        }
+    }
+
+    /// Flips the flag in `options`, returning whether it is now on.
+    pub fn toggle(self, options: &mut BuildOptions) -> bool {
+        let flag = match self {
+            Self::IncludeHidden => &mut options.include_hidden,
+            Self::TextFiles => &mut options.text_files,
+            Self::FrontMatter => &mut options.front_matter,
+            Self::IndexFiles => &mut options.index_files,
+            Self::ValueFiles => &mut options.value_files,
+            Self::ExpandDottedKeys => &mut options.expand_dotted_keys,
+            Self::Includes => &mut options.resolve_includes,
+            Self::MergeKeys => {
+                options.merge_keys = match options.merge_keys {
+                    MergeKeys::Expand => MergeKeys::Keep,
+                    MergeKeys::Keep | MergeKeys::Error => MergeKeys::Expand,
+                };
+                return options.merge_keys == MergeKeys::Expand;
+            }
+            Self::SharedAnchors => &mut options.shared_anchors,
+            Self::OrderedPrefixes => &mut options.ordered_prefixes,
+        };
+        *flag = !*flag;
+        *flag
+    }
+}
+
+/// Language of diagnostic text; codes are the same in every language.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Lang {
+    #[default]
+    En,
+    De,
+}
+
+/// Where rendered diagnostics are written, so stdout can carry only the command's result.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum DiagnosticsTarget {
+    Stdout,
+    #[default]
+    Stderr,
+    /// `file:PATH`; the file is truncated when the command starts.
+    File(PathBuf),
+}
+
+impl FromStr for DiagnosticsTarget {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "stdout" => Ok(Self::Stdout),
+            "stderr" => Ok(Self::Stderr),
+            other => match other.strip_prefix("file:") {
+                Some("") => Err("`file:` needs a path, e.g. file:diagnostics.log".to_string()),
+                Some(path) => Ok(Self::File(PathBuf::from(path))),
+                None => Err(format!(
+                    "unknown diagnostics target `{other}` (expected stdout, stderr, or file:PATH)"
+                )),
+            },
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticsTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stdout => f.write_str("stdout"),
+            Self::Stderr => f.write_str("stderr"),
+            Self::File(path) => write!(f, "file:{}", path.display()),
+        }
+    }
+}
+
+/// Handling of scalars that look like ISO-8601 dates or datetimes.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum DatesMode {
+    /// Leave them as plain strings.
+    #[default]
+    KeepString,
+    /// Tag them `!!timestamp` so YAML consumers read them as timestamps.
+    Tag,
+    /// Rewrite datetimes with an offset as UTC (`YYYY-MM-DDTHH:MM:SSZ`).
+    Parse,
+}
+
+/// Handling of YAML tags (`!Ref`, `!vault`, ...) found in fragments.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum TagPolicy {
+    /// Keep tags on their values through canonicalization, diff, and output.
+    #[default]
+    Preserve,
+    /// Replace each tagged value with its untagged contents.
+    Strip,
+    /// Report every tagged value as an error.
+    Error,
+}
+
+/// Handling of `<<` merge keys found in fragments.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeKeys {
+    /// Pack `<<` as an ordinary key holding the merged value.
+    #[default]
+    Keep,
+    /// Add the merged entries to the mapping, so diff and canonical output see them resolved.
+    Expand,
+    /// Report every `<<` key as an error.
+    Error,
+}
+
+/// Handling of derived keys (from file and directory names) that are not ASCII.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum AsciiKeys {
+    /// Use the key as it is.
+    #[default]
+    Allow,
+    /// Use the key as it is, with a warning.
+    Warn,
+    /// Report the key as an error.
+    Error,
+    /// Spell the key in ASCII (`café` becomes `cafe`) and record the rename.
+    Transliterate,
+}
+
+/// What to do when two sources define the same key.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum CollisionPolicy {
+    /// Report the collision (E001).
+    #[default]
+    Error,
+    /// Keep the value from the first source, with a warning.
+    FirstWins,
+    /// Keep the value from the last source, with a warning.
+    LastWins,
+    /// Merge mappings recursively; keys where either side is not a mapping are still E001.
+    DeepMerge,
+}
+
+/// How `fyaml pack BASE OVERLAY...` combines a layer with the layers before it.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverlayMerge {
+    /// Merge mappings recursively; any other value in the later layer replaces the earlier one.
+    #[default]
+    Deep,
+    /// Like deep, but sequences are concatenated, earlier layers' items first.
+    Append,
+    /// Each top-level key of the later layer replaces the earlier value as a whole.
+    Shallow,
+}
+
+/// What to do when `name.yml` and `name.yaml` exist side by side.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExtensionConflict {
+    /// Report the pair (E006).
+    #[default]
+    Error,
+    /// Use the `.yml` file and ignore the `.yaml` one.
+    PreferYml,
+    /// Use the `.yaml` file and ignore the `.yml` one.
+    PreferYaml,
+}
+
+/// A YAML file extension, for `--require-extension`.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum YamlExtension {
+    Yml,
+    Yaml,
+}
+
+impl YamlExtension {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Yml => "yml",
+            Self::Yaml => "yaml",
+        }
+    }
+}
+
+/// How floats are written in YAML output.
+/// How string scalars are quoted in YAML output. Strings written as block scalars keep that
+/// style either way.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum QuoteStrings {
+    /// Quote only strings that would otherwise read back as another value.
+    #[default]
+    Auto,
+    /// Also quote words YAML 1.1 reads as booleans, such as `on`, `off`, `yes`, and `no`.
+    Ambiguous,
+    /// Quote every string with single quotes, or double quotes where it needs escapes.
+    Single,
+    /// Quote every string with double quotes.
+    Double,
+}
+
+impl QuoteStrings {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Ambiguous => "ambiguous",
+            Self::Single => "single",
+            Self::Double => "double",
+        }
+    }
+}
+
+/// Spaces per nesting level in YAML output unless `--yaml-indent` says otherwise.
+pub const DEFAULT_YAML_INDENT: usize = 2;
+
+/// Columns `--fold-strings folded` wraps long strings at unless `--max-line-width` says
+/// otherwise.
+pub const DEFAULT_MAX_LINE_WIDTH: usize = 80;
+
+/// Which block scalar styles string values may be written in in YAML output.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum FoldStrings {
+    /// Strings with line breaks are written as literal `|` blocks; others stay on one line.
+    #[default]
+    Literal,
+    /// Also write single-line strings that run past the line width as folded `>-` blocks,
+    /// wrapped at spaces. Strings whose spacing folding would change stay on one line.
+    Folded,
+    /// No block scalars: strings with line breaks are written double-quoted with `\n`.
+    Plain,
+}
+
+impl FoldStrings {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Literal => "literal",
+            Self::Folded => "folded",
+            Self::Plain => "plain",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum FloatFormat {
+    /// Shortest round-trip text as produced by the YAML library (e.g. `1e16`, `1.0`).
+    #[default]
+    Native,
+    /// Positional for exponents -4..=15, otherwise `d.ddde[+-]N`; always has a fraction digit.
+    Canonical,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum IntegralFloats {
+    #[default]
+    Keep,
+    /// Write floats with no fractional part (e.g. `3.0`) as integers when they fit in 64 bits.
+    Int,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum NegativeZero {
+    #[default]
+    Keep,
+    /// Write `-0.0` as `0.0`.
+    Zero,
+}
+
+/// What to do with integers that do not fit in 64 bits.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum BigIntegers {
+    #[default]
+    Error,
+    /// Keep the decimal digits as a string scalar.
+    String,
+}
+
+/// Ordering applied to mapping keys in canonical output.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Collation {
+    /// UTF-8 byte order (the determinism default).
+    #[default]
+    Bytewise,
+    /// Unicode Collation Algorithm with the CLDR root order.
+    Unicode,
+    /// CLDR tailoring for a BCP 47 locale, e.g. `cldr:de` or `cldr:sv-SE`.
+    Cldr(icu_locid::Locale),
+    /// Byte order, except that runs of ASCII digits compare as numbers, so `item2` comes
+    /// before `item10`.
+    Natural,
+}
+
+impl FromStr for Collation {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "bytewise" => Ok(Self::Bytewise),
+            "unicode" => Ok(Self::Unicode),
+            "natural" => Ok(Self::Natural),
+            other => match other.strip_prefix("cldr:") {
+                Some(tag) => match tag.parse::<icu_locid::Locale>() {
+                    Ok(locale) if has_cldr_language(&locale) => Ok(Self::Cldr(locale)),
+                    Ok(_) => Err(format!(
+                        "CLDR has no data for the language of `{tag}`, so its collation is unknown"
+                    )),
+                    Err(_) => Err(format!("invalid CLDR locale `{tag}`")),
+                },
+                None => Err(format!(
+                    "unknown collation `{other}` (expected bytewise, unicode, natural, or cldr:<locale>)"
+                )),
+            },
+        }
+    }
+}
+
+/// Whether CLDR knows the language of `locale`. Collation data falls back to the root order
+/// for any language it has no tailoring for, so without this check an unknown language
+/// would be accepted and sorted as if `unicode` had been asked for.
+fn has_cldr_language(locale: &icu_locid::Locale) -> bool {
+    let mut language = icu_locid::LanguageIdentifier::from(locale.id.language);
+    icu_locid_transform::LocaleExpander::new_extended().maximize(&mut language)
+        == icu_locid_transform::TransformResult::Modified
+}
+
+impl fmt::Display for Collation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bytewise => f.write_str("bytewise"),
+            Self::Unicode => f.write_str("unicode"),
+            Self::Cldr(locale) => write!(f, "cldr:{locale}"),
+            Self::Natural => f.write_str("natural"),
+        }
+    }
+}
+
+/// `--item-keys PATH=KEY,...`: keys every mapping item of the sequences at `PATH` must have.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ItemKeys {
+    pub sequence: PathPattern,
+    pub keys: Vec<String>,
+}
+
+impl FromStr for ItemKeys {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let Some((path, keys)) = value.split_once('=') else {
+            return Err(format!(
+                "expected PATH=KEY,... such as steps=name, got `{value}`"
+            ));
+        };
+        let keys: Vec<String> = keys
+            .split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(str::to_string)
+            .collect();
+        if keys.is_empty() {
+            return Err(format!("`{value}` names no required keys"));
+        }
+        Ok(Self {
+            sequence: path.parse()?,
+            keys,
+        })
+    }
+}
+
+impl TryFrom<String> for ItemKeys {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ItemKeys> for String {
+    fn from(item_keys: ItemKeys) -> Self {
+        item_keys.to_string()
+    }
+}
+
+impl fmt::Display for ItemKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.sequence, self.keys.join(","))
+    }
+}
+
+/// `--item-count PATH:min=N,max=N`: bounds on the number of items in the directory-assembled
+/// sequences at `PATH`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ItemCount {
+    pub sequence: PathPattern,
+    pub min: Option<usize>,
+    pub max: Option<usize>,
+}
+
+impl FromStr for ItemCount {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let Some((path, bounds)) = value.rsplit_once(':') else {
+            return Err(format!(
+                "expected PATH:min=N,max=N such as jobs:min=1, got `{value}`"
+            ));
+        };
+        let (mut min, mut max) = (None, None);
+        for bound in bounds.split(',').map(str::trim).filter(|b| !b.is_empty()) {
+            let (name, count) = bound
+                .split_once('=')
+                .ok_or_else(|| format!("expected min=N or max=N, got `{bound}`"))?;
+            let count = count
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| format!("`{bound}` is not a non-negative count"))?;
+            match name.trim() {
+                "min" => min = Some(count),
+                "max" => max = Some(count),
+                other => return Err(format!("unknown bound `{other}`; expected min or max")),
+            }
+        }
+        match (min, max) {
+            (None, None) => Err(format!("`{value}` sets neither min nor max")),
+            (Some(min), Some(max)) if min > max => {
+                Err(format!("`{value}` has min {min} above max {max}"))
+            }
+            _ => Ok(Self {
+                sequence: path.trim().parse()?,
+                min,
+                max,
+            }),
+        }
+    }
+}
+
+impl TryFrom<String> for ItemCount {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ItemCount> for String {
+    fn from(item_count: ItemCount) -> Self {
+        item_count.to_string()
+    }
+}
+
+impl fmt::Display for ItemCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bounds = [("min", self.min), ("max", self.max)]
+            .into_iter()
+            .filter_map(|(name, count)| count.map(|count| format!("{name}={count}")))
+            .collect::<Vec<_>>();
+        write!(f, "{}:{}", self.sequence, bounds.join(","))
+    }
+}
+
+/// `--sort PATH=keys|none`: whether the mappings in the subtrees at `PATH` are emitted in
+/// canonical key order or in build order, whatever the rest of the document does.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SortRule {
+    pub path: PathPattern,
+    pub sorted: bool,
+}
+
+impl FromStr for SortRule {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let Some((path, order)) = value.rsplit_once('=') else {
+            return Err(format!(
+                "expected PATH=keys or PATH=none such as services=keys, got `{value}`"
+            ));
+        };
+        let sorted = match order.trim() {
+            "keys" => true,
+            "none" => false,
+            other => return Err(format!("unknown sort `{other}`; expected keys or none")),
+        };
+        Ok(Self {
+            path: path.trim().parse()?,
+            sorted,
+        })
+    }
+}
+
+impl TryFrom<String> for SortRule {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<SortRule> for String {
+    fn from(rule: SortRule) -> Self {
+        rule.to_string()
+    }
+}
+
+impl fmt::Display for SortRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let order = if self.sorted { "keys" } else { "none" };
+        write!(f, "{}={order}", self.path)
+    }
+}
+
+/// Options controlling canonical ordering and normalization of packed values.
 #[derive(Debug, Clone)]
+pub struct CanonicalOptions {
+    pub collation: Collation,
+    pub float_format: FloatFormat,
+    pub integral_floats: IntegralFloats,
+    pub negative_zero: NegativeZero,
+    /// Worker threads for canonicalization; 0 and 1 both mean the calling thread only.
+    pub jobs: usize,
+    /// Spaces a nested mapping is indented by in YAML output.
+    pub yaml_indent: usize,
+    pub quote_strings: QuoteStrings,
+    /// Collections of scalars with at most this many items are written in flow style in
+    /// YAML output; 0 keeps everything in block style.
+    pub flow_max_items: usize,
+    pub fold_strings: FoldStrings,
+    /// Columns a line of YAML output may run to before `FoldStrings::Folded` wraps the
+    /// string on it.
+    pub max_line_width: usize,
+    /// A mapping whose keys are all integers, or strings of digits, is sorted by their value
+    /// instead of by `collation`.
+    pub numeric_keys: bool,
+    /// String values spelled as YAML 1.1 booleans (`yes`, `no`, `on`, `off`, in lower, title,
+    /// or upper case) become `true` and `false`. Keys are left alone.
+    pub yaml11_booleans: bool,
+    /// ISO-8601 datetime string values with a UTC offset are rewritten as UTC in RFC 3339
+    /// form, so the same instant written with different offsets compares equal.
+    pub utc_timestamps: bool,
+}
+
+impl Default for CanonicalOptions {
+    fn default() -> Self {
+        Self {
+            collation: Collation::default(),
+            float_format: FloatFormat::default(),
+            integral_floats: IntegralFloats::default(),
+            negative_zero: NegativeZero::default(),
+            jobs: 0,
+            yaml_indent: DEFAULT_YAML_INDENT,
+            quote_strings: QuoteStrings::default(),
+            flow_max_items: 0,
+            fold_strings: FoldStrings::default(),
+            max_line_width: DEFAULT_MAX_LINE_WIDTH,
+            numeric_keys: false,
+            yaml11_booleans: false,
+            utc_timestamps: false,
+        }
+    }
+}
+
+impl CanonicalOptions {
+    /// Header comment lines recording non-default canonicalization choices.
+    pub fn header_notes(&self) -> Vec<String> {
+        let mut notes = Vec::new();
+        if self.collation != Collation::Bytewise {
+            notes.push(format!("collation: {}", self.collation));
+        }
+        if self.numeric_keys {
+            notes.push("numeric keys: integer-keyed mappings sorted by value".to_string());
+        }
+        let mut numbers = Vec::new();
+        if self.float_format != FloatFormat::Native {
+            numbers.push("float-format=canonical");
+        }
+        if self.integral_floats != IntegralFloats::Keep {
+            numbers.push("integral-floats=int");
+        }
+        if self.negative_zero != NegativeZero::Keep {
+            numbers.push("negative-zero=zero");
+        }
+        if !numbers.is_empty() {
+            notes.push(format!("numbers: {}", numbers.join(", ")));
+        }
+        if self.yaml11_booleans {
+            notes.push("booleans: yes/no/on/off read as true/false".to_string());
+        }
+        if self.utc_timestamps {
+            notes.push("timestamps: datetimes with an offset rewritten as UTC".to_string());
+        }
+        let mut style = Vec::new();
+        if self.yaml_indent != DEFAULT_YAML_INDENT {
+            style.push(format!("yaml-indent={}", self.yaml_indent));
+        }
+        if self.quote_strings != QuoteStrings::Auto {
+            style.push(format!("quote-strings={}", self.quote_strings.as_str()));
+        }
+        if self.flow_max_items > 0 {
+            style.push(format!("flow-max-items={}", self.flow_max_items));
+        }
+        match self.fold_strings {
+            FoldStrings::Literal => {}
+            FoldStrings::Folded => style.push(format!(
+                "fold-strings=folded, max-line-width={}",
+                self.max_line_width
+            )),
+            FoldStrings::Plain => style.push("fold-strings=plain".to_string()),
+        }
+        if !style.is_empty() {
+            notes.push(format!("style: {}", style.join(", ")));
+        }
+        notes
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BuildOptions {
     pub include_hidden: bool,
     pub allow_dotted_keys: bool,
     pub allow_reserved_keys: bool,
     pub seq_gaps: SeqGapMode,
+    /// The index of the first item of every directory-assembled sequence.
+    pub seq_start: u64,
+    pub seq_order: SeqOrder,
     pub multi_doc: MultiDocMode,
     pub strict: bool,
+    pub diag_order: DiagOrder,
     pub max_yaml_bytes: Option<u64>,
     pub root_mode: RootMode,
     pub root_file: Option<PathBuf>,
+    /// Root document text for file-root mode, used instead of `root_file` when set.
+    pub root_inline: Option<String>,
     pub merge_under: Option<String>,
     pub preserve: bool,
+    /// Explicit fragment paths relative to the root; replaces directory scanning when set.
+    pub file_list: Option<Vec<PathBuf>>,
+    /// Fail when contributing fragments differ from `fyaml.manifest.json`.
+    pub frozen: bool,
+    /// The file (or `--output-dir` directory) the packed document is written to. It is not
+    /// scanned when it lies inside the tree, so a pack never reads its previous output back in.
+    pub output: Option<PathBuf>,
+    /// Record where fragment nodes start, for `--source-map`.
+    pub source_positions: bool,
+    pub big_integers: BigIntegers,
+    pub dates: DatesMode,
+    pub tags: TagPolicy,
+    /// Worker threads reading and parsing fragments; 0 and 1 both mean the calling thread
+    /// only.
+    pub jobs: usize,
+    /// When non-empty, only entries matching one of these (or below a matching directory)
+    /// are scanned.
+    pub include: Vec<Glob>,
+    /// Entries matching any of these are not scanned; takes precedence over `include`.
+    pub exclude: Vec<Glob>,
+    /// Files matching any of these are read as bytes into `!!binary` scalars. A pattern
+    /// without `/` also matches the file name at any depth.
+    pub accept_binary: Vec<Glob>,
+    pub ascii_keys: AsciiKeys,
+    /// Merge `_.yml` and `index.yml` into their directory's mapping instead of deriving keys
+    /// from them.
+    pub index_files: bool,
+    /// A directory holding only `value.yml` or `_value.yml` takes that file's value.
+    pub value_files: bool,
+    /// `.txt` and `.md` files contribute their contents, unparsed, as a string.
+    pub text_files: bool,
+    /// Items of a directory-assembled sequence must all have the same type.
+    pub uniform_sequences: bool,
+    pub item_keys: Vec<ItemKeys>,
+    pub item_counts: Vec<ItemCount>,
+    /// When non-empty, only mapping entries on the way to, at, or below a match of one of
+    /// these key paths are read; the rest of the tree is not scanned.
+    pub only: Vec<PathPattern>,
+    /// Subtrees emitted sorted or in build order regardless of `preserve`; the last matching
+    /// rule wins, and the nearest rule above a mapping applies to it.
+    pub sort_rules: Vec<SortRule>,
+    /// Whether `<<` merge keys are kept as ordinary keys, expanded, or rejected.
+    pub merge_keys: MergeKeys,
+    /// Aliases may name anchors defined in a root-level `_anchors.yml`.
+    pub shared_anchors: bool,
+    /// `!include PATH` values are replaced by the parsed contents of `PATH`.
+    pub resolve_includes: bool,
+    /// `.md` files contribute their YAML front matter, with the body under `content_key`.
+    pub front_matter: bool,
+    pub content_key: String,
+    /// Strip leading `NN-` prefixes from entry names and emit those keys in prefix order.
+    pub ordered_prefixes: bool,
+    /// Turn dots in file names into nested keys (`a.b.yml` becomes `a: {b: ...}`).
+    pub expand_dotted_keys: bool,
+    pub on_collision: CollisionPolicy,
+    pub extension_conflict: ExtensionConflict,
+    /// Warn about fragments that use the other YAML extension.
+    pub require_extension: Option<YamlExtension>,
 }
 
 impl Default for BuildOptions {
@@ -62,13 +905,47 @@ impl Default for BuildOptions {
             allow_dotted_keys: false,
             allow_reserved_keys: false,
             seq_gaps: SeqGapMode::Warn,
+            seq_start: 0,
+            seq_order: SeqOrder::Numeric,
             multi_doc: MultiDocMode::Error,
             strict: false,
+            diag_order: DiagOrder::Sorted,
             max_yaml_bytes: None,
             root_mode: RootMode::MapRoot,
             root_file: None,
+            root_inline: None,
             merge_under: None,
             preserve: false,
+            file_list: None,
+            frozen: false,
+            output: None,
+            source_positions: false,
+            big_integers: BigIntegers::Error,
+            dates: DatesMode::KeepString,
+            tags: TagPolicy::Preserve,
+            jobs: 1,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            accept_binary: Vec::new(),
+            ascii_keys: AsciiKeys::Allow,
+            index_files: false,
+            value_files: false,
+            text_files: false,
+            resolve_includes: false,
+            shared_anchors: false,
+            merge_keys: MergeKeys::Keep,
+            uniform_sequences: false,
+            item_keys: Vec::new(),
+            item_counts: Vec::new(),
+            only: Vec::new(),
+            sort_rules: Vec::new(),
+            front_matter: false,
+            content_key: "content".to_string(),
+            ordered_prefixes: false,
+            expand_dotted_keys: false,
+            on_collision: CollisionPolicy::Error,
+            extension_conflict: ExtensionConflict::Error,
+            require_extension: None,
         }
     }
 }
@@ -0,0 +1,393 @@
+//! A long-running process that keeps a [`ParseCache`] warm between CLI invocations.
+//!
+//! Clients connect over a unix socket and send one JSON request per connection. Fragments
+//! are still read from disk on every request and the cache is keyed by content hash, so a
+//! stale result is impossible and no filesystem watching is needed; the daemon only saves
+//! re-parsing unchanged content. Any failure to reach the daemon makes the client build
+//! in-process instead.
+//!
+//! The socket is only usable by the user who started the daemon: it sits in
+//! `$XDG_RUNTIME_DIR` or a private per-user directory, is created with mode 0600, and a
+//! client refuses to talk to a socket another user owns.
+
+use crate::config::BuildOptions;
+use crate::diagnostics::{Category, Diagnostic, Severity, SourcePosition, Suggestion};
+use crate::engine::{build_with_cache, ParseCache, ParseStats};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, MetadataExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// Overrides the socket location; by default it lives in `$XDG_RUNTIME_DIR`, or in a
+/// private `fyaml-<uid>` directory under the temporary directory.
+pub const SOCKET_ENV: &str = "FYAML_DAEMON_SOCKET";
+
+/// Distinct fragment contents kept before the cache is dropped and rebuilt.
+const MAX_CACHED_FRAGMENTS: usize = 200_000;
+
+/// How long the daemon waits for a connected client to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a client waits for the daemon's reply, which includes the build.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(300);
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub fn socket_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(SOCKET_ENV) {
+        return PathBuf::from(path);
+    }
+    if let Some(runtime) = std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(runtime).join("fyaml.sock");
+    }
+    std::env::temp_dir()
+        .join(format!("fyaml-{}", current_uid()))
+        .join("fyaml.sock")
+}
+
+fn current_uid() -> u32 {
+    // SAFETY: `geteuid` has no preconditions and always succeeds.
+    unsafe { libc::geteuid() }
+}
+
+/// The sticky bit: only a file's owner may remove or rename it.
+const STICKY: u32 = 0o1000;
+
+fn permission_denied(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, message)
+}
+
+/// Creates the socket's directory, private to this user, when it does not exist yet.
+///
+/// An existing directory must belong to this user unless it is sticky (like `/tmp`), since
+/// whoever owns the directory can swap the socket for their own.
+fn prepare_socket_dir(socket: &Path) -> io::Result<()> {
+    let dir = match socket.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    match fs::DirBuilder::new().mode(0o700).create(dir) {
+        Err(err) if err.kind() != io::ErrorKind::AlreadyExists => return Err(err),
+        _ => {}
+    }
+    let metadata = fs::metadata(dir)?;
+    if metadata.uid() != current_uid() && metadata.mode() & STICKY == 0 {
+        return Err(permission_denied(format!(
+            "{} belongs to another user",
+            dir.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Binds `socket` with mode 0600 from the start, so other users never get a window to
+/// connect to it.
+fn bind_private(socket: &Path) -> io::Result<UnixListener> {
+    // SAFETY: `umask` has no preconditions and always succeeds. It is process-wide, which
+    // is fine here: the daemon starts no threads before it binds.
+    let previous = unsafe { libc::umask(0o177) };
+    let bound = UnixListener::bind(socket);
+    // SAFETY: as above.
+    unsafe { libc::umask(previous) };
+    bound
+}
+
+/// Connects to `socket` after checking that this user's daemon created it.
+fn connect(socket: &Path) -> io::Result<UnixStream> {
+    check_own_socket(socket)?;
+    UnixStream::connect(socket)
+}
+
+/// Fails unless `socket` is a socket (not a symlink to one) owned by this user.
+fn check_own_socket(socket: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(socket)?;
+    if !metadata.file_type().is_socket() {
+        return Err(permission_denied(format!(
+            "{} is not a socket",
+            socket.display()
+        )));
+    }
+    if metadata.uid() != current_uid() {
+        return Err(permission_denied(format!(
+            "{} belongs to another user",
+            socket.display()
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "kebab-case")]
+enum Request {
+    Validate {
+        version: String,
+        /// Absolute root directories.
+        dirs: Vec<PathBuf>,
+        options: Box<BuildOptions>,
+    },
+    Status,
+    Stop,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "response", rename_all = "kebab-case")]
+enum Response {
+    Validated {
+        /// Diagnostics per root, in request order.
+        diagnostics: Vec<Vec<WireDiagnostic>>,
+        stats: ParseStats,
+    },
+    Status(DaemonStatus),
+    Stopped,
+    Rejected {
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub version: String,
+    pub cached_fragments: usize,
+    pub stats: ParseStats,
+}
+
+/// `Diagnostic` with the fields its public JSON form leaves out.
+#[derive(Debug, Serialize, Deserialize)]
+struct WireDiagnostic {
+    code: String,
+    severity: Severity,
+    message: String,
+    paths: Vec<String>,
+    derived_key_path: Option<String>,
+    location: Option<String>,
+    cause: String,
+    action: String,
+    context: Option<String>,
+    category: Category,
+    position: Option<SourcePosition>,
+    #[serde(default)]
+    suggestions: Vec<Suggestion>,
+}
+
+impl From<Diagnostic> for WireDiagnostic {
+    fn from(diagnostic: Diagnostic) -> Self {
+        Self {
+            code: diagnostic.code,
+            severity: diagnostic.severity,
+            message: diagnostic.message,
+            paths: diagnostic.paths,
+            derived_key_path: diagnostic.derived_key_path,
+            location: diagnostic.location,
+            cause: diagnostic.cause,
+            action: diagnostic.action,
+            context: diagnostic.context,
+            category: diagnostic.category,
+            position: diagnostic.position,
+            suggestions: diagnostic.suggestions,
+        }
+    }
+}
+
+impl From<WireDiagnostic> for Diagnostic {
+    fn from(wire: WireDiagnostic) -> Self {
+        Self {
+            code: wire.code,
+            severity: wire.severity,
+            message: wire.message,
+            paths: wire.paths,
+            derived_key_path: wire.derived_key_path,
+            location: wire.location,
+            cause: wire.cause,
+            action: wire.action,
+            context: wire.context,
+            category: wire.category,
+            position: wire.position,
+            suggestions: wire.suggestions,
+        }
+    }
+}
+
+/// Serves requests on `socket` until a stop request arrives.
+///
+/// A leftover socket of this user's from a daemon that is no longer running is replaced; a
+/// live daemon on the same socket is an `AddrInUse` error, and anything else at that path
+/// is left alone and refused. Each client is served on its own
+/// thread, so one that is slow to send its request does not hold up the others; builds
+/// share the cache and take turns with it.
+pub fn serve(socket: &Path) -> io::Result<()> {
+    prepare_socket_dir(socket)?;
+    if fs::symlink_metadata(socket).is_ok() {
+        check_own_socket(socket)?;
+        if UnixStream::connect(socket).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                "a fyaml daemon is already listening on this socket",
+            ));
+        }
+        fs::remove_file(socket)?;
+    }
+
+    let listener = bind_private(socket)?;
+    let cache = Arc::new(Mutex::new(ParseCache::default()));
+    let stopping = Arc::new(AtomicBool::new(false));
+    for stream in listener.incoming() {
+        if stopping.load(Ordering::SeqCst) {
+            break;
+        }
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let cache = Arc::clone(&cache);
+        let stopping = Arc::clone(&stopping);
+        let socket = socket.to_path_buf();
+        std::thread::spawn(move || {
+            // A misbehaving client only loses its own request.
+            if let Ok(Response::Stopped) = handle(stream, &cache) {
+                stopping.store(true, Ordering::SeqCst);
+                // Wake the accept loop so it sees the flag.
+                let _ = UnixStream::connect(&socket);
+            }
+        });
+    }
+    fs::remove_file(socket)
+}
+
+fn handle(mut stream: UnixStream, cache: &Mutex<ParseCache>) -> io::Result<Response> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+
+    let response = match serde_json::from_str::<Request>(&line) {
+        Ok(Request::Validate {
+            version,
+            dirs,
+            options,
+        }) if version == VERSION => {
+            let mut cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+            if cache.len() > MAX_CACHED_FRAGMENTS {
+                cache.clear();
+            }
+            let before = cache.stats();
+            let diagnostics = dirs
+                .iter()
+                .map(|dir| {
+                    build_with_cache(dir, &options, &mut cache)
+                        .diagnostics
+                        .into_iter()
+                        .map(WireDiagnostic::from)
+                        .collect()
+                })
+                .collect();
+            let after = cache.stats();
+            Response::Validated {
+                diagnostics,
+                stats: ParseStats {
+                    parsed: after.parsed - before.parsed,
+                    reused: after.reused - before.reused,
+                },
+            }
+        }
+        Ok(Request::Validate { version, .. }) => Response::Rejected {
+            reason: format!("daemon runs fyaml {VERSION}, client is {version}"),
+        },
+        Ok(Request::Status) => {
+            let cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+            Response::Status(DaemonStatus {
+                version: VERSION.to_string(),
+                cached_fragments: cache.len(),
+                stats: cache.stats(),
+            })
+        }
+        Ok(Request::Stop) => Response::Stopped,
+        Err(err) => Response::Rejected {
+            reason: err.to_string(),
+        },
+    };
+
+    let mut encoded = serde_json::to_string(&response).map_err(io::Error::from)?;
+    encoded.push('\n');
+    stream.write_all(encoded.as_bytes())?;
+    Ok(response)
+}
+
+fn request(socket: &Path, request: &Request) -> io::Result<Response> {
+    let mut stream = connect(socket)?;
+    stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+    let mut encoded = serde_json::to_string(request).map_err(io::Error::from)?;
+    encoded.push('\n');
+    stream.write_all(encoded.as_bytes())?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    serde_json::from_str(&line).map_err(io::Error::from)
+}
+
+/// Validates `dirs` in the daemon, returning per-root diagnostics, or `None` when the daemon
+/// is not running or cannot serve this client.
+pub fn validate(
+    socket: &Path,
+    dirs: &[PathBuf],
+    options: &BuildOptions,
+) -> Option<(Vec<Vec<Diagnostic>>, ParseStats)> {
+    let cwd = std::env::current_dir().ok()?;
+    let absolute = dirs.iter().map(|dir| cwd.join(dir)).collect::<Vec<_>>();
+    let request_value = Request::Validate {
+        version: VERSION.to_string(),
+        dirs: absolute.clone(),
+        options: Box::new(options.clone()),
+    };
+    let Response::Validated { diagnostics, stats } = request(socket, &request_value).ok()? else {
+        return None;
+    };
+    if diagnostics.len() != dirs.len() {
+        return None;
+    }
+
+    let diagnostics = diagnostics
+        .into_iter()
+        .zip(dirs.iter().zip(&absolute))
+        .map(|(root, (dir, absolute))| {
+            root.into_iter()
+                .map(|wire| {
+                    let mut diagnostic = Diagnostic::from(wire);
+                    // Report root paths the way the user typed them, as an in-process build does.
+                    if let Some(location) = &mut diagnostic.location {
+                        if let Ok(rest) = Path::new(location.as_str()).strip_prefix(absolute) {
+                            *location = if rest.as_os_str().is_empty() {
+                                dir.display().to_string()
+                            } else {
+                                dir.join(rest).display().to_string()
+                            };
+                        }
+                    }
+                    diagnostic
+                })
+                .collect()
+        })
+        .collect();
+    Some((diagnostics, stats))
+}
+
+pub fn status(socket: &Path) -> io::Result<DaemonStatus> {
+    match request(socket, &Request::Status)? {
+        Response::Status(status) => Ok(status),
+        other => Err(unexpected(other)),
+    }
+}
+
+pub fn stop(socket: &Path) -> io::Result<()> {
+    match request(socket, &Request::Stop)? {
+        Response::Stopped => Ok(()),
+        other => Err(unexpected(other)),
+    }
+}
+
+fn unexpected(response: Response) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("unexpected daemon response: {response:?}"),
+    )
+}
@@ -1,6 +1,7 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 
-#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum Severity {
     Error,
@@ -8,15 +9,24 @@ pub enum Severity {
     Info,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum Category {
     InvalidInput,
     Parse,
     Write,
+    /// `!include` resolution (spec Appendix B).
+    Include,
     Internal,
 }
 
+/// One-based line and column reported by the YAML parser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourcePosition {
+    pub line: usize,
+    pub column: usize,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct Diagnostic {
     pub code: String,
@@ -30,6 +40,27 @@ pub struct Diagnostic {
     pub context: Option<String>,
     #[serde(skip_serializing)]
     pub category: Category,
+    #[serde(skip_serializing)]
+    pub position: Option<SourcePosition>,
+    /// Ways to resolve the diagnostic, best first.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<Suggestion>,
+}
+
+/// A concrete resolution, as `validate --fix collisions --apply-suggestion N` applies it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Suggestion {
+    /// What the edits do, e.g. `rename auth.yml to auth-base.yml`.
+    pub title: String,
+    pub edits: Vec<SuggestedEdit>,
+}
+
+/// A file system change, with paths as diagnostics show them (relative to the root).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum SuggestedEdit {
+    /// Renames or moves the file or directory `from` to `to`.
+    Rename { from: String, to: String },
 }
 
 impl Diagnostic {
@@ -50,9 +81,16 @@ impl Diagnostic {
             action: String::new(),
             context: None,
             category,
+            position: None,
+            suggestions: Vec::new(),
         }
     }
 
+    pub fn with_suggestions(mut self, suggestions: Vec<Suggestion>) -> Self {
+        self.suggestions = suggestions;
+        self
+    }
+
     pub fn with_paths(mut self, paths: Vec<String>) -> Self {
         self.paths = paths;
         self
@@ -83,6 +121,11 @@ impl Diagnostic {
         self
     }
 
+    pub fn with_position(mut self, line: usize, column: usize) -> Self {
+        self.position = Some(SourcePosition { line, column });
+        self
+    }
+
     pub fn error(code: impl Into<String>, message: impl Into<String>, category: Category) -> Self {
         Self::new(code, Severity::Error, message, category)
     }
@@ -103,6 +146,31 @@ impl Diagnostic {
         self.severity == Severity::Warn
     }
 
+    /// Orders diagnostics by location, then line and column, then code, then derived key
+    /// path. Locations compare by component with either separator, so the order is the same
+    /// on every platform; diagnostics without a position come first within their file.
+    pub fn cmp_sorted(&self, other: &Self) -> Ordering {
+        fn components(diagnostic: &Diagnostic) -> Option<Vec<&str>> {
+            let location = diagnostic
+                .location
+                .as_deref()
+                .or(diagnostic.paths.first().map(String::as_str))?;
+            Some(location.split(['/', '\\']).collect())
+        }
+        components(self)
+            .cmp(&components(other))
+            .then_with(|| {
+                let line_column = |diagnostic: &Self| {
+                    diagnostic
+                        .position
+                        .map(|position| (position.line, position.column))
+                };
+                line_column(self).cmp(&line_column(other))
+            })
+            .then_with(|| self.code.cmp(&other.code))
+            .then_with(|| self.derived_key_path.cmp(&other.derived_key_path))
+    }
+
     pub fn render_human(&self) -> String {
         let mut out = String::new();
         let sev = match self.severity {
@@ -131,6 +199,13 @@ impl Diagnostic {
             out.push_str(&format!("  Context: {}\n", context));
         }
 
+        if !self.suggestions.is_empty() {
+            out.push_str("  Suggestions:\n");
+            for (rank, suggestion) in self.suggestions.iter().enumerate() {
+                out.push_str(&format!("    {}. {}\n", rank + 1, suggestion.title));
+            }
+        }
+
         out
     }
 }
@@ -141,6 +216,7 @@ pub enum ExitCode {
     Internal = 1,
     InvalidInput = 2,
     ParseError = 3,
+    IncludeError = 4,
     WriteError = 5,
 }
 
@@ -160,6 +236,13 @@ impl ExitCode {
             return ExitCode::WriteError;
         }
 
+        let has_include = diags
+            .iter()
+            .any(|d| d.is_error() && d.category == Category::Include);
+        if has_include {
+            return ExitCode::IncludeError;
+        }
+
         let has_input = diags
             .iter()
             .any(|d| d.is_error() && d.category == Category::InvalidInput);
@@ -177,3 +260,50 @@ impl ExitCode {
         ExitCode::Success
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorted_order_breaks_ties_by_line_then_code_then_key_path() {
+        let at = |location: &str, code: &str| {
+            Diagnostic::error(code, "message", Category::InvalidInput).with_location(location)
+        };
+        let mut diagnostics = [
+            at("b/app.yml", "E001"),
+            at("a/app.yml", "E002").with_position(3, 1),
+            at("a/app.yml", "E002").with_derived_key_path("app.z"),
+            at("a\\app.yml", "E001").with_position(3, 1),
+            at("a/app.yml", "E002").with_derived_key_path("app.a"),
+            at("a/app.yml", "E001").with_position(10, 1),
+            at("a/app.yml", "E003").with_position(3, 1),
+        ];
+        diagnostics.sort_by(Diagnostic::cmp_sorted);
+
+        let order = diagnostics
+            .iter()
+            .map(|d| {
+                let line = d.position.map_or(0, |position| position.line);
+                let key = d.derived_key_path.as_deref().unwrap_or("-");
+                format!(
+                    "{} {line} {} {key}",
+                    d.location.as_deref().unwrap_or(""),
+                    d.code
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            [
+                "a/app.yml 0 E002 app.a",
+                "a/app.yml 0 E002 app.z",
+                "a\\app.yml 3 E001 -",
+                "a/app.yml 3 E002 -",
+                "a/app.yml 3 E003 -",
+                "a/app.yml 10 E001 -",
+                "b/app.yml 0 E001 -",
+            ]
+        );
+    }
+}
@@ -0,0 +1,347 @@
+use crate::config::Collation;
+use crate::engine::DirectoryMode;
+use crate::hash::SubtreeHashes;
+use crate::serializer::natural_cmp;
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Difference {
+    pub path: String,
+    pub reason: String,
+}
+
+/// A directory that both trees assemble, but in different modes (e.g. mapping vs sequence).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModeChange {
+    pub directory: String,
+    pub left: String,
+    pub right: String,
+}
+
+/// Returns the directories, relative to each root, whose mode decision differs between the
+/// two builds, sorted by directory. Directories present on only one side are value
+/// differences and are not listed.
+pub fn directory_mode_changes(left: &[DirectoryMode], right: &[DirectoryMode]) -> Vec<ModeChange> {
+    let right: std::collections::BTreeMap<&str, &str> = right
+        .iter()
+        .map(|decision| (decision.directory.as_str(), decision.mode.as_str()))
+        .collect();
+    let mut changes: Vec<ModeChange> = left
+        .iter()
+        .filter_map(|decision| {
+            let other = *right.get(decision.directory.as_str())?;
+            (other != decision.mode).then(|| ModeChange {
+                directory: decision.directory.clone(),
+                left: decision.mode.clone(),
+                right: other.to_string(),
+            })
+        })
+        .collect();
+    changes.sort_by(|a, b| a.directory.cmp(&b.directory));
+    changes
+}
+
+/// Returns the first semantic difference between two packed values, in canonical key order.
+/// Keys are visited in natural order under [`Collation::Natural`] and in byte order otherwise.
+pub fn first_difference(left: &Value, right: &Value, collation: &Collation) -> Option<Difference> {
+    let natural = *collation == Collation::Natural;
+    let mut found = Vec::new();
+    collect_differences(
+        left,
+        right,
+        "$".to_string(),
+        &mut found,
+        Some(1),
+        None,
+        natural,
+    );
+    found.into_iter().next()
+}
+
+/// Like [`first_difference`], but hashes both values first and skips every subtree whose
+/// structural hash matches on both sides. The result is the same unless two different
+/// subtrees share a SHA-256 hash.
+pub fn first_difference_fast(
+    left: &Value,
+    right: &Value,
+    collation: &Collation,
+) -> Option<Difference> {
+    let hashes = std::thread::scope(|scope| {
+        let right_hashes = scope.spawn(|| SubtreeHashes::new(right));
+        let left_hashes = SubtreeHashes::new(left);
+        (
+            left_hashes,
+            right_hashes.join().expect("hashing worker panicked"),
+        )
+    });
+    let mut found = Vec::new();
+    collect_differences(
+        left,
+        right,
+        "$".to_string(),
+        &mut found,
+        Some(1),
+        Some((&hashes.0, &hashes.1)),
+        *collation == Collation::Natural,
+    );
+    found.into_iter().next()
+}
+
+/// Returns every semantic difference between two packed values, in canonical key order.
+///
+/// Sequences of different lengths are reported once and not descended into.
+pub fn differences(left: &Value, right: &Value) -> Vec<Difference> {
+    let mut found = Vec::new();
+    collect_differences(left, right, "$".to_string(), &mut found, None, None, false);
+    found
+}
+
+type HashPair<'h, 'a> = Option<(&'h SubtreeHashes<'a>, &'h SubtreeHashes<'a>)>;
+
+fn collect_differences<'a>(
+    left: &'a Value,
+    right: &'a Value,
+    path: String,
+    found: &mut Vec<Difference>,
+    limit: Option<usize>,
+    hashes: HashPair<'_, 'a>,
+    natural: bool,
+) {
+    if limit.is_some_and(|limit| found.len() >= limit) {
+        return;
+    }
+    if let Some((left_hashes, right_hashes)) = hashes {
+        let left_hash = left_hashes.get(left);
+        if left_hash.is_some() && left_hash == right_hashes.get(right) {
+            return;
+        }
+    }
+
+    match (left, right) {
+        (Value::Null, Value::Null)
+        | (Value::Bool(_), Value::Bool(_))
+        | (Value::Number(_), Value::Number(_))
+        | (Value::String(_), Value::String(_)) => {
+            if left != right {
+                found.push(Difference {
+                    path,
+                    reason: "scalar value differs".to_string(),
+                });
+            }
+        }
+        (Value::Sequence(a), Value::Sequence(b)) => {
+            if a.len() != b.len() {
+                found.push(Difference {
+                    path,
+                    reason: format!("sequence length differs ({} vs {})", a.len(), b.len()),
+                });
+                return;
+            }
+
+            for (index, (left_item, right_item)) in a.iter().zip(b.iter()).enumerate() {
+                let child_path = format!("{path}[{index}]");
+                collect_differences(
+                    left_item, right_item, child_path, found, limit, hashes, natural,
+                );
+            }
+        }
+        (Value::Mapping(a), Value::Mapping(b)) => {
+            collect_map_differences(a, b, path, found, limit, hashes, natural);
+        }
+        (Value::Tagged(a), Value::Tagged(b)) => {
+            if a.tag != b.tag {
+                found.push(Difference {
+                    path,
+                    reason: format!("tag differs ({} vs {})", a.tag, b.tag),
+                });
+                return;
+            }
+            collect_differences(&a.value, &b.value, path, found, limit, hashes, natural);
+        }
+        _ => found.push(Difference {
+            path,
+            reason: "value type differs".to_string(),
+        }),
+    }
+}
+
+fn collect_map_differences<'a>(
+    left: &'a Mapping,
+    right: &'a Mapping,
+    path: String,
+    found: &mut Vec<Difference>,
+    limit: Option<usize>,
+    hashes: HashPair<'_, 'a>,
+    natural: bool,
+) {
+    let reached = |found: &Vec<Difference>| limit.is_some_and(|limit| found.len() >= limit);
+
+    let mut left_keys: Vec<&Value> = left.keys().collect();
+    let mut right_keys: Vec<&Value> = right.keys().collect();
+
+    left_keys.sort_by(|a, b| compare_yaml_key(a, b, natural));
+    right_keys.sort_by(|a, b| compare_yaml_key(a, b, natural));
+
+    for key in &left_keys {
+        if !right.contains_key(*key) {
+            found.push(Difference {
+                path: path.clone(),
+                reason: format!("key missing on right side: {}", yaml_key_text(key)),
+            });
+            if reached(found) {
+                return;
+            }
+        }
+    }
+
+    for key in &right_keys {
+        if !left.contains_key(*key) {
+            found.push(Difference {
+                path: path.clone(),
+                reason: format!("key missing on left side: {}", yaml_key_text(key)),
+            });
+            if reached(found) {
+                return;
+            }
+        }
+    }
+
+    for key in left_keys {
+        let (Some(left_value), Some(right_value)) = (left.get(key), right.get(key)) else {
+            continue;
+        };
+        let next_path = child_path(&path, key);
+        collect_differences(
+            left_value,
+            right_value,
+            next_path,
+            found,
+            limit,
+            hashes,
+            natural,
+        );
+        if reached(found) {
+            return;
+        }
+    }
+}
+
+/// Path of the entry under `key` in the mapping at `path`, e.g. `$.a.b`.
+pub(crate) fn child_path(path: &str, key: &Value) -> String {
+    format!("{path}.{}", yaml_key_text(key))
+}
+
+fn compare_yaml_key(a: &Value, b: &Value, natural: bool) -> Ordering {
+    let (a, b) = (yaml_sort_key(a), yaml_sort_key(b));
+    if natural {
+        natural_cmp(&a, &b).then_with(|| a.cmp(&b))
+    } else {
+        a.cmp(&b)
+    }
+}
+
+fn yaml_sort_key(value: &Value) -> Vec<u8> {
+    match value {
+        Value::String(s) => s.as_bytes().to_vec(),
+        _ => serde_yaml::to_string(value)
+            .unwrap_or_else(|_| format!("{value:?}"))
+            .into_bytes(),
+    }
+}
+
+fn yaml_key_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        _ => serde_yaml::to_string(value)
+            .unwrap_or_else(|_| format!("{value:?}"))
+            .trim()
+            .to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_difference_finds_nested_path() {
+        let left: Value = serde_yaml::from_str("a:\n  b: 1\n").expect("left parse");
+        let right: Value = serde_yaml::from_str("a:\n  b: 2\n").expect("right parse");
+
+        let diff =
+            first_difference(&left, &right, &Collation::Bytewise).expect("difference exists");
+        assert_eq!(diff.path, "$.a.b");
+    }
+
+    #[test]
+    fn natural_collation_reports_the_first_difference_in_natural_key_order() {
+        let left: Value = serde_yaml::from_str("item10: 1\nitem2: 1\n").expect("left parse");
+        let right: Value = serde_yaml::from_str("item10: 2\nitem2: 2\n").expect("right parse");
+
+        let bytewise = first_difference(&left, &right, &Collation::Bytewise).expect("differs");
+        assert_eq!(bytewise.path, "$.item10");
+        let natural = first_difference(&left, &right, &Collation::Natural).expect("differs");
+        assert_eq!(natural.path, "$.item2");
+    }
+
+    #[test]
+    fn differences_reports_every_changed_path() {
+        let left: Value = serde_yaml::from_str("a: 1\nb: 2\nc: 3\n").expect("left parse");
+        let right: Value = serde_yaml::from_str("a: 1\nb: 3\nd: 3\n").expect("right parse");
+
+        let found = differences(&left, &right);
+        let reasons = found.iter().map(|d| d.reason.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            reasons,
+            vec![
+                "key missing on right side: c",
+                "key missing on left side: d",
+                "scalar value differs"
+            ]
+        );
+        assert_eq!(found[2].path, "$.b");
+    }
+
+    #[test]
+    fn tags_are_part_of_the_comparison() {
+        let left: Value = serde_yaml::from_str("a: !Ref Bucket\nb: !Sub x\n").expect("left parse");
+        let right: Value =
+            serde_yaml::from_str("a: !GetAtt Bucket\nb: !Sub y\n").expect("right parse");
+
+        let found = differences(&left, &right);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].reason, "tag differs (!Ref vs !GetAtt)");
+        assert_eq!(found[1].path, "$.b");
+        assert_eq!(found[1].reason, "scalar value differs");
+    }
+
+    #[test]
+    fn directory_mode_changes_lists_flipped_directories() {
+        let decision = |directory: &str, mode: &str| DirectoryMode {
+            directory: directory.to_string(),
+            mode: mode.to_string(),
+            contributors: Vec::new(),
+        };
+        let left = vec![
+            decision(".", "mapping"),
+            decision("items", "sequence"),
+            decision("old", "mapping"),
+        ];
+        let right = vec![
+            decision(".", "mapping"),
+            decision("items", "mapping"),
+            decision("new", "sequence"),
+        ];
+
+        assert_eq!(
+            directory_mode_changes(&left, &right),
+            vec![ModeChange {
+                directory: "items".to_string(),
+                left: "sequence".to_string(),
+                right: "mapping".to_string(),
+            }]
+        );
+    }
+}
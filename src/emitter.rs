@@ -0,0 +1,942 @@
+//! Block-style YAML emitter for packed documents.
+//!
+//! The layout matches what `serde_yaml` produces (indentless sequences under mapping keys,
+//! two-space indentation, `{}`/`[]` for empty collections), but the structure and number
+//! formatting are owned here so canonical output does not drift with emitter versions.
+//! String scalars are still rendered by `serde_yaml`, which decides quoting and block style.
+//! Small collections of scalars can be written in flow style (`flow_max_items`), and long
+//! single-line strings as folded blocks wrapped at `max_line_width` (`fold_strings`).
+//!
+//! Output is written incrementally to any `io::Write`, so a large document never exists
+//! as a second full copy in memory.
+
+use crate::config::{
+    FloatFormat, FoldStrings, QuoteStrings, DEFAULT_MAX_LINE_WIDTH, DEFAULT_YAML_INDENT,
+};
+use crate::keypath::{push_index, push_key};
+use crate::preserve::Preserved;
+use serde_yaml::{Mapping, Number, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+/// Longest scalar (in bytes, including its tag) written as an implicit `key: value` key.
+const MAX_SIMPLE_KEY_BYTES: usize = 128;
+
+const NON_STRING_WORDS: &[&str] = &[
+    "null", "Null", "NULL", "true", "True", "TRUE", "false", "False", "FALSE",
+];
+
+/// YAML 1.1 booleans besides `true` and `false`, quoted under `--quote-strings ambiguous`.
+const YAML11_BOOL_WORDS: &[&str] = &[
+    "y", "Y", "yes", "Yes", "YES", "n", "N", "no", "No", "NO", "on", "On", "ON", "off", "Off",
+    "OFF",
+];
+
+#[derive(Debug, Clone, Copy)]
+pub struct EmitterOptions {
+    pub float_format: FloatFormat,
+    /// Spaces a mapping nested under a key is indented by. Sequences under a key stay
+    /// indentless, and content after `- ` stays aligned with it.
+    pub indent: usize,
+    pub quote_strings: QuoteStrings,
+    /// Non-empty collections of at most this many scalars are written in flow style;
+    /// 0 writes every collection in block style.
+    pub flow_max_items: usize,
+    pub fold_strings: FoldStrings,
+    /// Under `FoldStrings::Folded`, the column past which a string value's line is folded.
+    pub max_line_width: usize,
+}
+
+impl Default for EmitterOptions {
+    fn default() -> Self {
+        Self {
+            float_format: FloatFormat::default(),
+            indent: DEFAULT_YAML_INDENT,
+            quote_strings: QuoteStrings::default(),
+            flow_max_items: 0,
+            fold_strings: FoldStrings::default(),
+            max_line_width: DEFAULT_MAX_LINE_WIDTH,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EmitError {
+    #[error(transparent)]
+    Yaml(#[from] serde_yaml::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Writes `value` as a YAML document to `out`.
+///
+/// Writes are small; pass a buffered writer when `out` is a file or stdout.
+pub fn write_yaml<W: Write>(
+    out: W,
+    value: &Value,
+    options: &EmitterOptions,
+) -> Result<(), EmitError> {
+    let mut emitter = Emitter::new(out, options);
+    emitter.write_node(value, 0, Slot::Document)
+}
+
+/// Like [`write_yaml`], writing back the comments and anchors `--preserve` read from the
+/// fragments.
+pub fn write_yaml_preserved<W: Write>(
+    out: W,
+    value: &Value,
+    options: &EmitterOptions,
+    preserved: &Preserved,
+) -> Result<(), EmitError> {
+    let mut emitter = Emitter::new(out, options);
+    emitter.preserving = Some(Preserving {
+        notes: preserved,
+        path: String::new(),
+        referenced: preserved.aliases.values().map(String::as_str).collect(),
+        written: HashMap::new(),
+    });
+    emitter.write_head(value, false, 0)?;
+    emitter.write_node(value, 0, Slot::Document)
+}
+
+pub fn to_yaml_string(value: &Value, options: &EmitterOptions) -> Result<String, EmitError> {
+    let mut out = Vec::new();
+    write_yaml(&mut out, value, options)?;
+    Ok(String::from_utf8(out).expect("emitter writes UTF-8"))
+}
+
+/// The single-line text of a scalar as [`write_yaml`] writes it, or `None` for strings that
+/// need a block scalar. Collections are written as `{}` and `[]`.
+pub fn inline_scalar(
+    value: &Value,
+    options: &EmitterOptions,
+) -> Result<Option<String>, serde_yaml::Error> {
+    let emitter = Emitter::new(io::sink(), options);
+    Ok(match emitter.scalar(value)? {
+        Scalar::Inline(text) => Some(text),
+        Scalar::Block { .. } => None,
+    })
+}
+
+/// Where a node is being written, which decides how nested collections are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    /// The document root, written at column zero.
+    Document,
+    /// After `key:` in a block mapping whose entries sit at the given indent.
+    MappingValue,
+    /// After `- `, `? `, or `: ` at the given indent; nested content starts two columns in.
+    Item,
+}
+
+enum Scalar {
+    Inline(String),
+    Block { header: String, body: Vec<String> },
+}
+
+struct Emitter<'a, W> {
+    out: W,
+    options: &'a EmitterOptions,
+    /// Under `--preserve`, the comments and anchors to write back.
+    preserving: Option<Preserving<'a>>,
+    /// Characters written on the current line so far.
+    column: usize,
+}
+
+/// Where a `--preserve` emitter is in the document, and what it has anchored so far.
+struct Preserving<'a> {
+    notes: &'a Preserved,
+    /// Key path of the node being written.
+    path: String,
+    /// Key paths of the anchored nodes some alias repeats; other anchors are not written.
+    referenced: HashSet<&'a str>,
+    /// Anchored nodes already written, by key path.
+    written: HashMap<String, &'a Value>,
+}
+
+impl<'a, W: Write> Emitter<'a, W> {
+    fn new(out: W, options: &'a EmitterOptions) -> Self {
+        Self {
+            out,
+            options,
+            preserving: None,
+            column: 0,
+        }
+    }
+
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        self.column = match text.rfind('\n') {
+            Some(end) => text[end + 1..].chars().count(),
+            None => self.column + text.chars().count(),
+        };
+        self.out.write_all(text.as_bytes())
+    }
+
+    fn write_node(&mut self, value: &'a Value, indent: usize, slot: Slot) -> Result<(), EmitError> {
+        if slot != Slot::Document {
+            if let Some(alias) = self.alias(value) {
+                if slot == Slot::MappingValue {
+                    self.write(" ")?;
+                }
+                self.write("*")?;
+                self.write(alias)?;
+                self.end_line()?;
+                return Ok(());
+            }
+        }
+        let (tag, inner) = match value {
+            Value::Tagged(tagged) => (Some(tagged.tag.to_string()), &tagged.value),
+            other => (None, other),
+        };
+        let anchor = match slot {
+            Slot::Document => None,
+            _ => self.anchor(value),
+        };
+        // Node properties: an anchor, then the tag.
+        let properties = match (anchor, tag) {
+            (Some(anchor), Some(tag)) => Some(format!("&{anchor} {tag}")),
+            (Some(anchor), None) => Some(format!("&{anchor}")),
+            (None, tag) => tag,
+        };
+
+        if properties.is_none() {
+            if let Some(text) = self.flow(inner)? {
+                if slot == Slot::MappingValue {
+                    self.write(" ")?;
+                }
+                self.write(&text)?;
+                self.end_line()?;
+                return Ok(());
+            }
+        }
+
+        match inner {
+            Value::Mapping(map) if !map.is_empty() => {
+                let nested = match slot {
+                    Slot::Document => 0,
+                    Slot::MappingValue => indent + self.options.indent,
+                    Slot::Item => indent + 2,
+                };
+                self.write_collection_prefix(properties.as_deref(), slot)?;
+                let compact = slot == Slot::Item && properties.is_none();
+                self.write_mapping(map, nested, compact)
+            }
+            Value::Sequence(items) if !items.is_empty() => {
+                let nested = match slot {
+                    Slot::Document => 0,
+                    Slot::MappingValue => indent,
+                    Slot::Item => indent + 2,
+                };
+                self.write_collection_prefix(properties.as_deref(), slot)?;
+                let compact = slot == Slot::Item && properties.is_none();
+                self.write_sequence(items, nested, compact)
+            }
+            _ => {
+                if slot == Slot::MappingValue {
+                    self.write(" ")?;
+                }
+                if let Some(properties) = &properties {
+                    self.write(properties)?;
+                    self.write(" ")?;
+                }
+                let scalar = match self.scalar(inner)? {
+                    Scalar::Inline(text) => self.fold(inner, text, indent),
+                    block => block,
+                };
+                self.write_scalar(scalar, indent)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes what precedes a non-empty collection: a line break (or node properties and
+    /// line break), or nothing when the collection continues on the current `- ` line.
+    fn write_collection_prefix(&mut self, properties: Option<&str>, slot: Slot) -> io::Result<()> {
+        match (properties, slot) {
+            (Some(properties), Slot::MappingValue) => {
+                self.write(" ")?;
+                self.write(properties)?;
+                self.end_line()?;
+            }
+            (Some(properties), _) => {
+                self.write(properties)?;
+                self.end_line()?;
+            }
+            (None, Slot::MappingValue) => self.end_line()?,
+            (None, _) => {}
+        }
+        Ok(())
+    }
+
+    fn write_mapping(
+        &mut self,
+        map: &'a Mapping,
+        indent: usize,
+        compact: bool,
+    ) -> Result<(), EmitError> {
+        for (index, (key, value)) in map.iter().enumerate() {
+            let len = self.enter(|path| push_key(path, &key_text(key)));
+            if index > 0 || !compact {
+                self.write_head(value, false, indent)?;
+                self.push_indent(indent)?;
+            }
+
+            match self.simple_key(key)? {
+                Some(text) => {
+                    self.write(&text)?;
+                    self.write(":")?;
+                    self.write_node(value, indent, Slot::MappingValue)?;
+                }
+                None => {
+                    self.write("? ")?;
+                    // Comments and anchors belong to the value, not to a complex key.
+                    let preserving = self.preserving.take();
+                    self.write_node(key, indent, Slot::Item)?;
+                    self.preserving = preserving;
+                    self.push_indent(indent)?;
+                    self.write(": ")?;
+                    self.write_node(value, indent, Slot::Item)?;
+                }
+            }
+            self.leave(len);
+        }
+        Ok(())
+    }
+
+    fn write_sequence(
+        &mut self,
+        items: &'a [Value],
+        indent: usize,
+        compact: bool,
+    ) -> Result<(), EmitError> {
+        for (index, item) in items.iter().enumerate() {
+            let len = self.enter(|path| push_index(path, index));
+            if index > 0 || !compact {
+                self.write_head(item, true, indent)?;
+                self.push_indent(indent)?;
+            }
+            self.write("- ")?;
+            self.write_node(item, indent, Slot::Item)?;
+            self.leave(len);
+        }
+        Ok(())
+    }
+
+    fn write_scalar(&mut self, scalar: Scalar, indent: usize) -> io::Result<()> {
+        match scalar {
+            Scalar::Inline(text) => {
+                self.write(&text)?;
+                self.end_line()?;
+            }
+            Scalar::Block { header, body } => {
+                self.write(&header)?;
+                self.end_line()?;
+                for line in body {
+                    if !line.is_empty() {
+                        self.push_indent(indent)?;
+                        self.write(&line)?;
+                    }
+                    self.write("\n")?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Ends the current line, after the comment that ended the node's line in its fragment.
+    fn end_line(&mut self) -> io::Result<()> {
+        let trailing = self
+            .preserving
+            .as_ref()
+            .and_then(|preserving| preserving.notes.trailing.get(&preserving.path));
+        if let Some(comment) = trailing {
+            let comment = format!(" {comment}");
+            self.write(&comment)?;
+        }
+        self.write("\n")
+    }
+
+    /// Writes the comments above the node at the current path, at `indent`. An item whose
+    /// collection starts on its `- ` line brings the comments of its first entry along,
+    /// since they cannot be written inside that line.
+    fn write_head(&mut self, value: &'a Value, item: bool, indent: usize) -> Result<(), EmitError> {
+        let Some(preserving) = &self.preserving else {
+            return Ok(());
+        };
+        let notes = preserving.notes;
+        let mut path = preserving.path.clone();
+        let mut value = value;
+        let mut lines = Vec::new();
+        loop {
+            lines.extend(notes.head.get(&path).into_iter().flatten());
+            if !item || !self.is_compact(value, &path)? {
+                break;
+            }
+            match value {
+                Value::Mapping(map) => {
+                    if let Some(key) = map.keys().next() {
+                        push_key(&mut path, &key_text(key));
+                        lines.extend(notes.head.get(&path).into_iter().flatten());
+                    }
+                    break;
+                }
+                Value::Sequence(items) => {
+                    push_index(&mut path, 0);
+                    value = &items[0];
+                }
+                _ => break,
+            }
+        }
+        for line in lines {
+            self.push_indent(indent)?;
+            self.write(line)?;
+            self.write("\n")?;
+        }
+        Ok(())
+    }
+
+    /// Whether `value`, written as an item at `path`, continues on its `- ` line.
+    fn is_compact(&self, value: &Value, path: &str) -> Result<bool, EmitError> {
+        let collection = match value {
+            Value::Mapping(map) => !map.is_empty(),
+            Value::Sequence(items) => !items.is_empty(),
+            _ => false,
+        };
+        if !collection || self.flow(value)?.is_some() {
+            return Ok(false);
+        }
+        let Some(preserving) = &self.preserving else {
+            return Ok(true);
+        };
+        let anchored = preserving.referenced.contains(path);
+        let aliased = preserving
+            .notes
+            .aliases
+            .get(path)
+            .and_then(|anchor| preserving.written.get(anchor))
+            .is_some_and(|written| *written == value);
+        Ok(!anchored && !aliased)
+    }
+
+    /// The anchor to write `value` as an alias of, when it repeats the anchored node its
+    /// fragment's alias names and that node has been written.
+    fn alias(&self, value: &Value) -> Option<&'a str> {
+        let preserving = self.preserving.as_ref()?;
+        let notes = preserving.notes;
+        let anchor = notes.aliases.get(&preserving.path)?;
+        let written = preserving.written.get(anchor)?;
+        (*written == value)
+            .then(|| notes.anchors.get(anchor).map(String::as_str))
+            .flatten()
+    }
+
+    /// The anchor to write on `value`, recording it for the aliases that follow.
+    fn anchor(&mut self, value: &'a Value) -> Option<&'a str> {
+        let preserving = self.preserving.as_mut()?;
+        if !preserving.referenced.contains(preserving.path.as_str()) {
+            return None;
+        }
+        let notes = preserving.notes;
+        let name = notes.anchors.get(&preserving.path)?;
+        preserving.written.insert(preserving.path.clone(), value);
+        Some(name)
+    }
+
+    /// Appends a segment to the current path, returning the length to [`Self::leave`] with.
+    fn enter(&mut self, push: impl FnOnce(&mut String)) -> usize {
+        match &mut self.preserving {
+            Some(preserving) => {
+                let len = preserving.path.len();
+                push(&mut preserving.path);
+                len
+            }
+            None => 0,
+        }
+    }
+
+    fn leave(&mut self, len: usize) {
+        if let Some(preserving) = &mut self.preserving {
+            preserving.path.truncate(len);
+        }
+    }
+
+    /// Returns the inline text for keys that can be written as `key: value`.
+    fn simple_key(&self, key: &Value) -> Result<Option<String>, serde_yaml::Error> {
+        let (tag, inner) = match key {
+            Value::Tagged(tagged) => (Some(tagged.tag.to_string()), &tagged.value),
+            other => (None, other),
+        };
+
+        let length = match inner {
+            Value::Mapping(map) if !map.is_empty() => return Ok(None),
+            Value::Sequence(items) if !items.is_empty() => return Ok(None),
+            Value::String(s) => s.len(),
+            _ => 0,
+        };
+        if length + tag.as_ref().map_or(0, String::len) > MAX_SIMPLE_KEY_BYTES {
+            return Ok(None);
+        }
+
+        match self.scalar(inner)? {
+            Scalar::Inline(text) => Ok(Some(match tag {
+                Some(tag) => format!("{tag} {text}"),
+                None => text,
+            })),
+            Scalar::Block { .. } => Ok(None),
+        }
+    }
+
+    fn scalar(&self, value: &Value) -> Result<Scalar, serde_yaml::Error> {
+        let text = match value {
+            Value::Null => "null".to_string(),
+            Value::Bool(b) => b.to_string(),
+            Value::Number(n) => format_number(n, self.options.float_format),
+            Value::String(s) => {
+                return render_string(s, self.options.quote_strings, self.options.fold_strings)
+            }
+            Value::Mapping(_) => "{}".to_string(),
+            Value::Sequence(_) => "[]".to_string(),
+            Value::Tagged(_) => return render_with_serde_yaml(value),
+        };
+        Ok(Scalar::Inline(text))
+    }
+
+    /// `{a: 1}` or `[1, 2]` for a non-empty collection of at most `flow_max_items` untagged
+    /// scalars, or `None` when it is written in block style.
+    fn flow(&self, value: &Value) -> Result<Option<String>, serde_yaml::Error> {
+        let max = self.options.flow_max_items;
+        let (open, close, entries): (_, _, Vec<(Option<&Value>, &Value)>) = match value {
+            Value::Mapping(map) if !map.is_empty() && map.len() <= max => {
+                ("{", "}", map.iter().map(|(k, v)| (Some(k), v)).collect())
+            }
+            Value::Sequence(items) if !items.is_empty() && items.len() <= max => {
+                ("[", "]", items.iter().map(|item| (None, item)).collect())
+            }
+            _ => return Ok(None),
+        };
+        let mut parts = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            let Some(value) = self.flow_scalar(value)? else {
+                return Ok(None);
+            };
+            parts.push(match key {
+                Some(Value::String(s)) if s.len() > MAX_SIMPLE_KEY_BYTES => return Ok(None),
+                Some(key) => match self.flow_scalar(key)? {
+                    Some(key) => format!("{key}: {value}"),
+                    None => return Ok(None),
+                },
+                None => value,
+            });
+        }
+        Ok(Some(format!("{open}{}{close}", parts.join(", "))))
+    }
+
+    /// The text of a scalar inside `{}` or `[]`, where `,`, `[`, `]`, `{`, and `}` end a plain
+    /// scalar, so plain strings that could hold them are single-quoted.
+    fn flow_scalar(&self, value: &Value) -> Result<Option<String>, serde_yaml::Error> {
+        match value {
+            Value::Mapping(map) if !map.is_empty() => return Ok(None),
+            Value::Sequence(items) if !items.is_empty() => return Ok(None),
+            Value::Tagged(_) => return Ok(None),
+            _ => {}
+        }
+        Ok(match (self.scalar(value)?, value) {
+            (Scalar::Block { .. }, _) => None,
+            (Scalar::Inline(text), Value::String(s))
+                if !text.starts_with(['\'', '"']) && !is_plain_safe(s) =>
+            {
+                Some(single_quoted(s))
+            }
+            (Scalar::Inline(text), _) => Some(text),
+        })
+    }
+
+    /// `text` as a folded `>-` block wrapped at `max_line_width`, when the string value it
+    /// writes would run past that column of the current line and folds back to itself.
+    fn fold(&self, value: &Value, text: String, indent: usize) -> Scalar {
+        let width = self.options.max_line_width;
+        let Value::String(s) = value else {
+            return Scalar::Inline(text);
+        };
+        if self.options.fold_strings != FoldStrings::Folded
+            || self.column + text.chars().count() <= width
+            || text.starts_with('"')
+        {
+            return Scalar::Inline(text);
+        }
+        // Block content sits two columns in from the node.
+        let body = fold_lines(s, width.saturating_sub(indent + 2));
+        if body.len() < 2 {
+            return Scalar::Inline(text);
+        }
+        Scalar::Block {
+            header: ">-".to_string(),
+            body: body.into_iter().map(|line| format!("  {line}")).collect(),
+        }
+    }
+
+    fn push_indent(&mut self, indent: usize) -> io::Result<()> {
+        self.write(&" ".repeat(indent))
+    }
+}
+
+/// A mapping key as a key path segment. Keys that are not scalars get a segment no
+/// fragment text produces.
+fn key_text(key: &Value) -> String {
+    match key {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => "\0".to_string(),
+    }
+}
+
+fn render_string(
+    s: &str,
+    quote: QuoteStrings,
+    fold: FoldStrings,
+) -> Result<Scalar, serde_yaml::Error> {
+    let text = if is_plain_safe(s) {
+        s.to_string()
+    } else {
+        match render_with_serde_yaml(&Value::String(s.to_string()))? {
+            Scalar::Inline(text) => text,
+            Scalar::Block { .. } if fold == FoldStrings::Plain => {
+                serde_json::to_string(s).expect("strings always serialize as JSON")
+            }
+            block => return Ok(block),
+        }
+    };
+    let quoted = text.starts_with(['\'', '"']);
+    Ok(Scalar::Inline(match quote {
+        QuoteStrings::Ambiguous if !quoted && YAML11_BOOL_WORDS.contains(&s) => single_quoted(s),
+        // Text serde_yaml double-quotes holds characters only an escape can write.
+        QuoteStrings::Single if !text.starts_with('"') => single_quoted(s),
+        QuoteStrings::Double => serde_json::to_string(s).expect("strings always serialize as JSON"),
+        _ => text,
+    }))
+}
+
+/// `s` split at single spaces into lines of at most `width` characters where it can be, for a
+/// folded block that reads back as `s`. Empty when folding would change the string: it has
+/// line breaks, tabs, or other control characters, or starts or ends with a space.
+fn fold_lines(s: &str, width: usize) -> Vec<String> {
+    if s.starts_with(' ') || s.ends_with(' ') || s.chars().any(char::is_control) {
+        return Vec::new();
+    }
+    // A break folds back to one space only between two non-spaces, so runs of spaces stay
+    // inside a line.
+    let mut words = Vec::new();
+    let mut start = 0;
+    let bytes = s.as_bytes();
+    for (at, _) in s.match_indices(' ') {
+        if bytes[at - 1] != b' ' && bytes[at + 1] != b' ' {
+            words.push(&s[start..at]);
+            start = at + 1;
+        }
+    }
+    words.push(&s[start..]);
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut length = 0;
+    for word in words {
+        let count = word.chars().count();
+        match lines.last_mut() {
+            Some(line) if length + 1 + count <= width => {
+                line.push(' ');
+                line.push_str(word);
+                length += 1 + count;
+            }
+            _ => {
+                lines.push(word.to_string());
+                length = count;
+            }
+        }
+    }
+    lines
+}
+
+fn single_quoted(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+fn render_with_serde_yaml(value: &Value) -> Result<Scalar, serde_yaml::Error> {
+    let rendered = serde_yaml::to_string(value)?;
+    if rendered.starts_with('|') || rendered.starts_with('>') {
+        let mut lines = rendered
+            .strip_suffix('\n')
+            .unwrap_or(&rendered)
+            .split('\n')
+            .map(str::to_string);
+        let header = lines.next().unwrap_or_default();
+        return Ok(Scalar::Block {
+            header,
+            body: lines.collect(),
+        });
+    }
+    Ok(Scalar::Inline(rendered.trim_end_matches('\n').to_string()))
+}
+
+/// Conservative check for strings that `serde_yaml` would also emit as plain scalars.
+fn is_plain_safe(s: &str) -> bool {
+    let mut bytes = s.bytes();
+    let Some(first) = bytes.next() else {
+        return false;
+    };
+    first.is_ascii_alphabetic()
+        && bytes.all(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.' | b'/'))
+        && !NON_STRING_WORDS.contains(&s)
+}
+
+pub fn format_number(number: &Number, float_format: FloatFormat) -> String {
+    match (number.as_f64(), float_format) {
+        (Some(f), FloatFormat::Canonical) if number.is_f64() => format_float_canonical(f),
+        _ => number.to_string(),
+    }
+}
+
+/// Formats a float independently of any emitter library.
+///
+/// Shortest round-trip digits are written positionally for exponents in `-4..=15` (always with
+/// a fractional part) and otherwise as `d.ddde[+-]N`, which both YAML 1.1 and 1.2 read as a float.
+pub fn format_float_canonical(f: f64) -> String {
+    if f.is_nan() {
+        return ".nan".to_string();
+    }
+    if f.is_infinite() {
+        return if f > 0.0 { ".inf" } else { "-.inf" }.to_string();
+    }
+
+    let sign = if f.is_sign_negative() { "-" } else { "" };
+    if f == 0.0 {
+        return format!("{sign}0.0");
+    }
+
+    let scientific = format!("{:e}", f.abs());
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("`{:e}` output always has an exponent");
+    let exponent: i32 = exponent.parse().expect("`{:e}` exponent is an integer");
+    let digits = mantissa.replace('.', "");
+
+    if (-4..=15).contains(&exponent) {
+        let point = exponent + 1;
+        let text = if point <= 0 {
+            format!("0.{}{digits}", "0".repeat(point.unsigned_abs() as usize))
+        } else {
+            let point = point as usize;
+            if digits.len() > point {
+                format!("{}.{}", &digits[..point], &digits[point..])
+            } else {
+                format!("{digits}{}.0", "0".repeat(point - digits.len()))
+            }
+        };
+        return format!("{sign}{text}");
+    }
+
+    let (lead, rest) = digits.split_at(1);
+    let rest = if rest.is_empty() { "0" } else { rest };
+    let exponent_sign = if exponent < 0 { '-' } else { '+' };
+    format!(
+        "{sign}{lead}.{rest}e{exponent_sign}{}",
+        exponent.unsigned_abs()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_matches_serde_yaml() {
+        let fixtures = [
+            "a: [1, 2]\nb: {}\nc: []\nd: [{x: 1, y: [3]}, [4, 5], []]\ne: {f: {g: 1}}\n",
+            "- - 1\n  - 2\n- {a: 1}\n- - - 3\n",
+            "s: \"multi\\nline\\n\"\nt: \"multi\\nline\"\nu: \"trail\\n\\n\"\nv: \"  lead\\nx\"\n",
+            "a:\n  b:\n    s: \"x\\ny\\n\"\n    t: [\"p\\nq\", [\"r\\ns\"]]\n",
+            "? [1, 2]\n: v\n? [1]\n: {a: 1, b: [2]}\n1: int\n~: n\ntrue: b\n1.5: f\n",
+            "t: !Ref foo\nu: !GetAtt [a, b]\nv: !m {a: 1}\nw: !e {}\nx: !m \"a\\nb\"\n",
+            "- !t [[1]]\n- a: !t [1]\n  b: !t {c: !u [2]}\n",
+            "? \"multi\\nkey\"\n: v\n'': empty\n",
+            "x: ['', 'true', '1', 'a: b', 'it''s', \"tab\\tx\", null, 0.1, -0.0, .nan]\n",
+        ];
+
+        for fixture in fixtures {
+            let value: Value = serde_yaml::from_str(fixture).expect("fixture parses");
+            let expected = serde_yaml::to_string(&value).expect("serde_yaml emits");
+            let actual = to_yaml_string(&value, &EmitterOptions::default()).expect("emits");
+            assert_eq!(actual, expected, "fixture: {fixture:?}");
+        }
+    }
+
+    #[test]
+    fn write_errors_surface_as_io_errors() {
+        struct Full(usize);
+        impl Write for Full {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if self.0 < buf.len() {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero, "disk full"));
+                }
+                self.0 -= buf.len();
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let value: Value = serde_yaml::from_str("a: [1, 2]\nb: {c: text}\n").expect("fixture");
+        let expected = to_yaml_string(&value, &EmitterOptions::default()).expect("emits");
+        let result = write_yaml(Full(expected.len() - 1), &value, &EmitterOptions::default());
+        assert!(matches!(result, Err(EmitError::Io(_))));
+        assert!(write_yaml(Full(expected.len()), &value, &EmitterOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn indent_and_quoting_styles_keep_the_value() {
+        let value: Value = serde_yaml::from_str(
+            "a:\n  b: {on: yes, n: 1}\n  l: [x, \"it's\", \"t\\tab\", \"two\\nlines\\n\", [{c: d, e: f}]]\n",
+        )
+        .expect("fixture");
+        let emit = |indent, quote_strings| {
+            let options = EmitterOptions {
+                indent,
+                quote_strings,
+                ..EmitterOptions::default()
+            };
+            to_yaml_string(&value, &options).expect("emits")
+        };
+
+        assert_eq!(
+            emit(4, QuoteStrings::Ambiguous),
+            "a:\n    b:\n        'on': 'yes'\n        'n': 1\n    l:\n    - x\n    - it's\n    \
+             - \"t\\tab\"\n    - |\n      two\n      lines\n    - - c: d\n        e: f\n"
+        );
+        assert_eq!(
+            emit(2, QuoteStrings::Single),
+            "'a':\n  'b':\n    'on': 'yes'\n    'n': 1\n  'l':\n  - 'x'\n  - 'it''s'\n  \
+             - \"t\\tab\"\n  - |\n    two\n    lines\n  - - 'c': 'd'\n      'e': 'f'\n"
+        );
+        for indent in 2..=8 {
+            for quote in [
+                QuoteStrings::Auto,
+                QuoteStrings::Ambiguous,
+                QuoteStrings::Single,
+                QuoteStrings::Double,
+            ] {
+                let text = emit(indent, quote);
+                let parsed: Value = serde_yaml::from_str(&text).expect("emitted YAML parses");
+                assert_eq!(parsed, value, "indent={indent} {quote:?}:\n{text}");
+            }
+        }
+    }
+
+    #[test]
+    fn small_scalar_collections_use_flow_style() {
+        let value: Value = serde_yaml::from_str(
+            "a: {x: 1, y: \"p, q\"}\nb: [1, 2, 3, 4]\nc: [[1], {k: v}]\nd: [\"l\\nm\\n\", 2]\ne: [!t 1]\nf: {}\n",
+        )
+        .expect("fixture");
+        let options = EmitterOptions {
+            flow_max_items: 3,
+            ..EmitterOptions::default()
+        };
+        let text = to_yaml_string(&value, &options).expect("emits");
+        assert_eq!(
+            text,
+            "a: {x: 1, y: 'p, q'}\nb:\n- 1\n- 2\n- 3\n- 4\nc:\n- [1]\n- {k: v}\nd:\n- |\n  l\n  m\n- 2\ne:\n- !t 1\nf: {}\n"
+        );
+        let parsed: Value = serde_yaml::from_str(&text).expect("emitted YAML parses");
+        assert_eq!(parsed, value);
+    }
+
+    #[test]
+    fn long_strings_fold_at_the_line_width() {
+        let value: Value = serde_yaml::from_str(
+            "a:\n  text: the quick brown fox jumps over the lazy dog\n  spaced: keep  two  spaces here and there\n  short: fits\n  lead: \" leading space is kept on one line\"\n  notes: \"one\\ntwo\\n\"\n",
+        )
+        .expect("fixture");
+        let emit = |fold_strings| {
+            let options = EmitterOptions {
+                fold_strings,
+                max_line_width: 24,
+                ..EmitterOptions::default()
+            };
+            to_yaml_string(&value, &options).expect("emits")
+        };
+
+        let folded = emit(FoldStrings::Folded);
+        assert_eq!(
+            folded,
+            "a:\n  text: >-\n    the quick brown fox\n    jumps over the lazy\n    dog\n  spaced: >-\n    keep  two  spaces\n    here and there\n  short: fits\n  lead: ' leading space is kept on one line'\n  notes: |\n    one\n    two\n"
+        );
+        let plain = emit(FoldStrings::Plain);
+        assert!(plain.contains("  notes: \"one\\ntwo\\n\"\n"), "{plain}");
+        assert_eq!(
+            emit(FoldStrings::Literal),
+            to_yaml_string(&value, &EmitterOptions::default()).expect("emits")
+        );
+        for text in [folded, plain] {
+            let parsed: Value = serde_yaml::from_str(&text).expect("emitted YAML parses");
+            assert_eq!(parsed, value);
+        }
+    }
+
+    #[test]
+    fn preserved_comments_and_anchors_are_written_back() {
+        let text = "# top\n\nbase: &b {x: 1}\n\
+                    jobs:\n  # first\n  - name: a  # build\n    run: make\n\
+                    copy: *b\nother: *b\n";
+        let mut value: Value = serde_yaml::from_str(text).expect("fixture");
+        value["other"]["x"] = Value::from(2);
+        let mut preserved = Preserved::default();
+        preserved.scan(text, &[]);
+
+        let mut out = Vec::new();
+        write_yaml_preserved(&mut out, &value, &EmitterOptions::default(), &preserved)
+            .expect("emits");
+        let emitted = String::from_utf8(out).expect("UTF-8");
+        // `other` no longer repeats the anchored value, so it is written out in full.
+        assert_eq!(
+            emitted,
+            "# top\nbase: &b\n  x: 1\njobs:\n# first\n- name: a # build\n  run: make\n\
+             copy: *b\nother:\n  x: 2\n"
+        );
+        let parsed: Value = serde_yaml::from_str(&emitted).expect("emitted YAML parses");
+        assert_eq!(parsed, value);
+    }
+
+    #[test]
+    fn long_keys_use_explicit_key_syntax() {
+        let key = "k".repeat(MAX_SIMPLE_KEY_BYTES + 1);
+        let value: Value = serde_yaml::from_str(&format!("{key}: 1\n")).expect("parses");
+        let actual = to_yaml_string(&value, &EmitterOptions::default()).expect("emits");
+        assert_eq!(
+            actual,
+            serde_yaml::to_string(&value).expect("serde_yaml emits")
+        );
+    }
+
+    #[test]
+    fn canonical_floats_do_not_depend_on_magnitude_quirks() {
+        let cases = [
+            (1.0, "1.0"),
+            (1000.0, "1000.0"),
+            (1e16, "1.0e+16"),
+            (1e17, "1.0e+17"),
+            (1.5e-7, "1.5e-7"),
+            (0.0001, "0.0001"),
+            (123456.789, "123456.789"),
+            (-2.5, "-2.5"),
+            (-0.0, "-0.0"),
+            (1e300, "1.0e+300"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(format_float_canonical(input), expected);
+            assert_eq!(
+                format_float_canonical(input).parse::<f64>().ok(),
+                Some(input)
+            );
+        }
+    }
+}
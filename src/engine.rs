@@ -1,21 +1,90 @@
-use crate::config::{BuildOptions, MultiDocMode, RootMode, SeqGapMode};
-use crate::diagnostics::{Category, Diagnostic, Severity};
-use serde::Deserialize;
-use serde::Serialize;
+use crate::ascii::transliterate;
+use crate::config::{
+    AsciiKeys, BigIntegers, BuildOptions, CollisionPolicy, DatesMode, DiagOrder, ExtensionConflict,
+    MergeKeys, MultiDocMode, RootMode, SeqGapMode, SeqOrder, TagPolicy,
+};
+use crate::diagnostics::{Category, Diagnostic, Severity, SuggestedEdit, Suggestion};
+use crate::digest::{base64, sha256_hex};
+use crate::keymap::{Keymap, KEYMAP_FILE_NAME};
+use crate::keypath::Segment;
+use crate::loader::load_documents;
+use crate::manifest::{Manifest, MANIFEST_FILE_NAME};
+use crate::preserve::Preserved;
+use crate::snapshot;
+use crate::source::{EntryKind, FragmentRead, FsSource, ListSource, MemorySource, TreeSource};
+use crate::source_map::SourceLocation;
+use crate::timestamp::{Timestamp, TIMESTAMP_TAG};
+use serde::{Deserialize, Serialize};
+use serde_yaml::value::{Tag, TaggedValue};
 use serde_yaml::{Mapping, Value};
 use std::collections::{HashMap, HashSet};
 use std::ffi::OsStr;
-use std::fs;
-use std::path::{Path, PathBuf};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 
 const RESERVED_YAML_KEYS: &[&str] = &["true", "false", "yes", "no", "null", "on", "off"];
 const LARGE_FRAGMENT_WARN_BYTES: u64 = 1024 * 1024;
+/// Most fragments of one directory a prefetch worker reads in one batch.
+const PREFETCH_BATCH: usize = 64;
+const MANIFEST_IGNORE_RULE: &str = "fyaml manifest excluded from scanning";
+const KEYMAP_IGNORE_RULE: &str = "fyaml keymap excluded from scanning";
+const CACHE_IGNORE_RULE: &str = "fyaml parse cache excluded from scanning";
+const ROOT_FILE_IGNORE_RULE: &str = "root file excluded from normal scanning";
+const OUTPUT_IGNORE_RULE: &str = "pack output excluded from scanning";
+const ANCHORS_IGNORE_RULE: &str = "shared anchors file excluded from scanning";
+/// Root files file-root mode picks up when `--root-file` is not given.
+const ROOT_FILE_CANDIDATES: &[&str] = &["_root.yml", "_root.yaml", "root.yml", "root.yaml"];
+/// File stems merged into their directory's mapping under `--index-files`.
+const INDEX_FILE_STEMS: &[&str] = &["_", "index"];
+/// File stems that stand for their whole directory under `--value-files`.
+const VALUE_FILE_STEMS: &[&str] = &["value", "_value"];
+/// Extensions of files read as strings under `--text-files`.
+const TEXT_EXTENSIONS: &[&str] = &[".txt", ".md"];
+/// Root-level file of named values that `--shared-anchors` makes available as `*alias`.
+pub const ANCHORS_FILE_NAME: &str = "_anchors.yml";
+/// Stand-in for an alias to a shared anchor while its fragment is parsed.
+const SHARED_ALIAS_TAG: &str = "!fyaml-shared-alias";
+/// Mapping key whose value is merged into the mapping, with `--merge-keys expand`.
+const MERGE_KEY: &str = "<<";
+/// Tag whose string value names a file to splice in, with `--includes on`.
+const INCLUDE_TAG: &str = "!include";
+const INCLUDE_IGNORE_RULE: &str = "not matched by --include";
+const ONLY_IGNORE_RULE: &str = "outside every --only key path";
+const EXCLUDE_IGNORE_PREFIX: &str = "excluded by --exclude ";
+const SHADOWED_IGNORE_PREFIX: &str = "shadowed by ";
+
+/// Location reported for the root document given by `--root-inline` or `--root-stdin`.
+pub const ROOT_INLINE_LOCATION: &str = "<root-inline>";
+
+/// File in the input directory holding the parse cache saved by `--cache`.
+pub const CACHE_FILE_NAME: &str = ".fyaml-cache";
+const CACHE_HEADER: &[u8] =
+    concat!("fyaml parse cache v", env!("CARGO_PKG_VERSION"), "\n").as_bytes();
 
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct ExplainReport {
     pub derived_keys: Vec<DerivedKey>,
     pub ignored: Vec<IgnoredEntry>,
     pub directory_modes: Vec<DirectoryMode>,
+    /// Keys renamed by `--ascii-keys transliterate`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub transliterated_keys: Vec<TransliteratedKey>,
+    /// Keys whose `NN-` prefix was stripped by `--ordered-prefixes`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ordered_keys: Vec<OrderedKey>,
+    /// Fragments reported by `W020`, for `validate --fix unwrap`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub wrapped_fragments: Vec<WrappedFragment>,
+    /// The conventionally named root file picked in file-root mode without `--root-file`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_root_file: Option<String>,
+    /// `!include` tags resolved with `--includes on`, outermost first.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<IncludeEdge>,
+    /// The numbering of directory-assembled sequences, once one is built.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequence_numbering: Option<SequenceNumbering>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -24,6 +93,51 @@ pub struct DerivedKey {
     pub derived_key_path: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct TransliteratedKey {
+    pub source: String,
+    pub original: String,
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderedKey {
+    pub source: String,
+    /// Entry name as found on disk, prefix included.
+    pub name: String,
+    pub derived_key_path: String,
+    pub order: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WrappedFragment {
+    pub source: String,
+    /// The top-level key that repeats the file name.
+    pub key: String,
+    /// Key path the fragment contributes, without the repeated key.
+    pub derived_key_path: String,
+    /// Other entries contributing the same key path; the wrapper is not removed automatically
+    /// while there are any.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub shared_with: Vec<String>,
+}
+
+/// How the items of directory-assembled sequences are numbered and ordered.
+#[derive(Debug, Clone, Serialize)]
+pub struct SequenceNumbering {
+    pub start: u64,
+    pub order: SeqOrder,
+    pub gaps: SeqGapMode,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IncludeEdge {
+    /// The file holding the `!include` tag.
+    pub source: String,
+    pub target: String,
+    pub derived_key_path: String,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct IgnoredEntry {
     pub path: String,
@@ -37,17 +151,395 @@ pub struct DirectoryMode {
     pub contributors: Vec<String>,
 }
 
+/// A YAML file that was read as part of a build, with the checksum of its contents.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Fragment {
+    pub path: String,
+    pub sha256: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct BuildOutcome {
     pub value: Option<Value>,
     pub diagnostics: Vec<Diagnostic>,
     pub explain: ExplainReport,
+    pub fragments: Vec<Fragment>,
+    /// Emission order for mappings with `--ordered-prefixes` keys; see [`apply_key_orders`].
+    pub key_orders: Vec<KeyOrder>,
+    /// Comments and anchors read from fragments under `--preserve`.
+    pub preserved: Preserved,
+    /// Where each node written on its entry's line starts, by derived key path, when
+    /// [`BuildOptions::source_positions`] is set.
+    pub positions: HashMap<String, SourceLocation>,
+}
+
+/// Keys of one mapping that are emitted first, in this order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyOrder {
+    /// Mapping keys and sequence positions leading from the document root to the mapping.
+    pub path: Vec<Value>,
+    pub keys: Vec<String>,
+}
+
+/// Moves the keys named by each of `orders` to the front of their mapping, in order.
+///
+/// Canonical output sorts every mapping, so this runs after canonicalization; the remaining
+/// keys keep their order.
+pub fn apply_key_orders(value: &mut Value, orders: &[KeyOrder]) {
+    for order in orders {
+        let Some(Value::Mapping(map)) = value_at_mut(value, &order.path) else {
+            continue;
+        };
+        let mut rest = std::mem::take(map);
+        for key in &order.keys {
+            let key = Value::String(key.clone());
+            if let Some(child) = rest.shift_remove(&key) {
+                map.insert(key, child);
+            }
+        }
+        map.extend(rest);
+    }
+}
+
+fn value_at_mut<'a>(mut value: &'a mut Value, path: &[Value]) -> Option<&'a mut Value> {
+    for segment in path {
+        value = match (value, segment) {
+            (Value::Mapping(map), key) => map.get_mut(key)?,
+            (Value::Sequence(items), Value::Number(position)) => {
+                items.get_mut(usize::try_from(position.as_u64()?).ok()?)?
+            }
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+/// Loaded documents keyed by fragment content hash.
+///
+/// One cache can be shared by several builds in a run, so byte-identical fragments (overlay
+/// copies, vendored defaults) are parsed once. Policies that report per-file diagnostics
+/// (tags, dates, multi-doc) still run for every fragment.
+#[derive(Debug, Default)]
+pub struct ParseCache {
+    entries: HashMap<(BigIntegers, String), Result<Vec<Value>, CachedParseError>>,
+    stats: ParseStats,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParseStats {
+    /// Fragments parsed because their content had not been seen before.
+    pub parsed: usize,
+    /// Fragments whose content matched an earlier fragment and reused its documents.
+    pub reused: usize,
+}
+
+#[derive(Debug, Clone)]
+struct CachedParseError {
+    message: String,
+    position: Option<(usize, usize)>,
+}
+
+impl ParseCache {
+    pub fn stats(&self) -> ParseStats {
+        self.stats
+    }
+
+    /// Number of distinct fragment contents held.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drops every cached document; statistics are kept.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Adds the entries saved in `path` by [`ParseCache::write_file`]. A missing, unreadable,
+    /// or unrecognised file adds nothing, since the cache only saves parsing work; entries
+    /// are keyed by content hash, so they are only used for byte-identical fragments.
+    /// Returns whether the file was read.
+    pub fn read_file(&mut self, path: &Path) -> bool {
+        let Ok(bytes) = std::fs::read(path) else {
+            return false;
+        };
+        let Some(mut reader) = snapshot::Reader::new(&bytes, CACHE_HEADER) else {
+            return false;
+        };
+        let mut entries = Vec::new();
+        while !reader.is_empty() {
+            match read_cache_entry(&mut reader) {
+                Some(entry) => entries.push(entry),
+                None => return false,
+            }
+        }
+        self.entries.extend(entries);
+        true
+    }
+
+    /// Saves the cached documents of `fragments` to `path`, dropping entries no fragment
+    /// uses any more.
+    pub fn write_file(
+        &self,
+        path: &Path,
+        fragments: &[Fragment],
+        big_integers: BigIntegers,
+    ) -> io::Result<()> {
+        let mut writer = snapshot::Writer::new(CACHE_HEADER);
+        let mut written = HashSet::new();
+        for fragment in fragments {
+            let key = (big_integers, fragment.sha256.clone());
+            let Some(loaded) = self.entries.get(&key) else {
+                continue;
+            };
+            if written.insert(&fragment.sha256) {
+                write_cache_entry(&mut writer, &key, loaded);
+            }
+        }
+
+        let staging = path.with_file_name(format!("{CACHE_FILE_NAME}.{}.tmp", std::process::id()));
+        std::fs::write(&staging, writer.into_bytes())?;
+        std::fs::rename(&staging, path).map_err(|err| {
+            let _ = std::fs::remove_file(&staging);
+            err
+        })
+    }
+
+    fn contains(&self, sha256: &str, big_integers: BigIntegers) -> bool {
+        self.entries
+            .contains_key(&(big_integers, sha256.to_string()))
+    }
+
+    /// The documents of `contents`, parsing them unless they are cached or were already
+    /// `parsed` ahead of time.
+    fn load(
+        &mut self,
+        sha256: &str,
+        contents: &str,
+        big_integers: BigIntegers,
+        parsed: Option<Result<Vec<Value>, CachedParseError>>,
+    ) -> Result<Vec<Value>, CachedParseError> {
+        let key = (big_integers, sha256.to_string());
+        if let Some(cached) = self.entries.get(&key) {
+            self.stats.reused += 1;
+            return cached.clone();
+        }
+
+        self.stats.parsed += 1;
+        let loaded = parsed.unwrap_or_else(|| parse_documents(contents, big_integers));
+        self.entries.insert(key, loaded.clone());
+        loaded
+    }
+}
+
+fn parse_documents(
+    contents: &str,
+    big_integers: BigIntegers,
+) -> Result<Vec<Value>, CachedParseError> {
+    load_documents(contents, big_integers).map_err(|err| CachedParseError {
+        message: err.to_string(),
+        position: err
+            .location()
+            .map(|location| (location.line(), location.column())),
+    })
+}
+
+type CacheEntry = ((BigIntegers, String), Result<Vec<Value>, CachedParseError>);
+
+fn write_cache_entry(
+    writer: &mut snapshot::Writer,
+    (big_integers, sha256): &(BigIntegers, String),
+    loaded: &Result<Vec<Value>, CachedParseError>,
+) {
+    writer.u8(match big_integers {
+        BigIntegers::Error => 0,
+        BigIntegers::String => 1,
+    });
+    writer.str(sha256);
+    match loaded {
+        Ok(documents) => {
+            writer.u8(1);
+            writer.u64(documents.len() as u64);
+            documents.iter().for_each(|document| writer.value(document));
+        }
+        Err(err) => {
+            writer.u8(0);
+            writer.str(&err.message);
+            let (line, column) = err.position.unwrap_or((0, 0));
+            writer.u64(line as u64);
+            writer.u64(column as u64);
+        }
+    }
+}
+
+fn read_cache_entry(reader: &mut snapshot::Reader) -> Option<CacheEntry> {
+    let big_integers = match reader.u8()? {
+        0 => BigIntegers::Error,
+        1 => BigIntegers::String,
+        _ => return None,
+    };
+    let sha256 = reader.str()?;
+    let loaded = match reader.u8()? {
+        1 => {
+            let count = reader.u64()?;
+            let mut documents = Vec::new();
+            for _ in 0..count {
+                documents.push(reader.value()?);
+            }
+            Ok(documents)
+        }
+        0 => {
+            let message = reader.str()?;
+            let line = usize::try_from(reader.u64()?).ok()?;
+            let column = usize::try_from(reader.u64()?).ok()?;
+            Err(CachedParseError {
+                message,
+                position: (line > 0).then_some((line, column)),
+            })
+        }
+        _ => return None,
+    };
+    Some(((big_integers, sha256), loaded))
+}
+
+/// A fragment read, hashed, and parsed by a worker thread before assembly.
+struct Prefetched {
+    read: io::Result<FragmentRead>,
+    /// `None` when the contents were not read, or were already in the cache.
+    parsed: Option<(String, Result<Vec<Value>, CachedParseError>)>,
 }
 
 pub fn build(root: &Path, options: &BuildOptions) -> BuildOutcome {
-    let mut ctx = BuildContext::new(root, options.clone());
+    build_with_cache(root, options, &mut ParseCache::default())
+}
+
+/// Like [`build`], reusing documents already loaded into `cache` by earlier builds.
+pub fn build_with_cache(
+    root: &Path,
+    options: &BuildOptions,
+    cache: &mut ParseCache,
+) -> BuildOutcome {
+    let Some(files) = &options.file_list else {
+        return build_source_with_cache(root, options, &FsSource, cache);
+    };
+
+    let (source, list_diagnostics) = list_source(root, files, &FsSource);
+    let mut outcome = build_source_with_cache(root, options, &source, cache);
+    outcome.diagnostics.splice(0..0, list_diagnostics);
+    outcome
+}
+
+/// Restricts `inner` to the listed fragment paths, reporting entries that cannot take part.
+pub fn list_source<'a>(
+    root: &Path,
+    files: &[PathBuf],
+    inner: &'a dyn TreeSource,
+) -> (ListSource<'a>, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+    let mut accepted = Vec::new();
+
+    for file in files {
+        let confined = file
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !confined {
+            diagnostics.push(
+                Diagnostic::error(
+                    "E070",
+                    "listed fragment path is outside the FYAML root",
+                    Category::InvalidInput,
+                )
+                .with_location(file.display().to_string())
+                .with_cause("--from-list entries must be relative paths without `..` components.")
+                .with_action("List fragment paths relative to the input directory."),
+            );
+            continue;
+        }
+
+        let normalized = file.components().collect::<PathBuf>();
+        if inner.kind(&root.join(&normalized)) != Some(EntryKind::File) {
+            diagnostics.push(
+                Diagnostic::error(
+                    "E071",
+                    "listed fragment does not exist or is not a file",
+                    Category::InvalidInput,
+                )
+                .with_location(root.join(&normalized).display().to_string())
+                .with_cause("Every --from-list entry must name an existing file.")
+                .with_action("Regenerate the file list or remove stale entries."),
+            );
+            continue;
+        }
+
+        accepted.push(normalized);
+    }
+
+    (ListSource::new(inner, root, &accepted), diagnostics)
+}
+
+/// Assembles a tree given as `(path, contents)` pairs without touching the filesystem.
+///
+/// Paths are relative to the FYAML root, as a directory scan would find them, and go through
+/// the same key derivation, collision, and parsing rules as [`build`]. Options that name
+/// files on disk (`file_list`, `output`, the parse cache) do not apply; `root_file` is
+/// looked up among the entries. Diagnostic locations are relative to the root.
+pub fn build_from_entries(
+    entries: impl IntoIterator<Item = (PathBuf, String)>,
+    options: &BuildOptions,
+) -> BuildOutcome {
+    let root = Path::new(".");
+    let mut diagnostics = Vec::new();
+    let mut accepted = Vec::new();
+    for (path, contents) in entries {
+        let confined = path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        let normalized = path.components().collect::<PathBuf>();
+        if !confined || normalized.as_os_str().is_empty() {
+            diagnostics.push(
+                Diagnostic::error(
+                    "E070",
+                    "listed fragment path is outside the FYAML root",
+                    Category::InvalidInput,
+                )
+                .with_location(path.display().to_string())
+                .with_cause(
+                    "Entry paths must name a file relative to the root, without `..` components.",
+                )
+                .with_action("Give each entry a relative path such as `config/app.yml`."),
+            );
+            continue;
+        }
+        accepted.push((normalized, contents));
+    }
+
+    let source = MemorySource::new(root, accepted);
+    let mut outcome = build_with_source(root, options, &source);
+    outcome.diagnostics.splice(0..0, diagnostics);
+    outcome
+}
+
+/// Builds `root` as seen through `source` instead of the real filesystem.
+pub fn build_with_source(
+    root: &Path,
+    options: &BuildOptions,
+    source: &dyn TreeSource,
+) -> BuildOutcome {
+    build_source_with_cache(root, options, source, &mut ParseCache::default())
+}
 
-    if !root.exists() {
+pub fn build_source_with_cache(
+    root: &Path,
+    options: &BuildOptions,
+    source: &dyn TreeSource,
+    cache: &mut ParseCache,
+) -> BuildOutcome {
+    let mut ctx = BuildContext::new(root, options, source, cache);
+
+    let Some(root_kind) = source.kind(root) else {
         ctx.diag(
             Diagnostic::error(
                 "E000",
@@ -59,9 +551,9 @@ pub fn build(root: &Path, options: &BuildOptions) -> BuildOutcome {
             .with_action("Pass an existing directory to fyaml commands."),
         );
         return ctx.finish(None);
-    }
+    };
 
-    if !root.is_dir() {
+    if root_kind != EntryKind::Directory {
         ctx.diag(
             Diagnostic::error(
                 "E000",
@@ -75,6 +567,14 @@ pub fn build(root: &Path, options: &BuildOptions) -> BuildOutcome {
         return ctx.finish(None);
     }
 
+    ctx.load_keymap(root);
+    if options.shared_anchors {
+        ctx.load_shared_anchors(root);
+    }
+    // Prefetching reads every fragment, including those `--only` leaves out.
+    if options.jobs > 1 && options.only.is_empty() {
+        ctx.prefetch_fragments(root);
+    }
     let value = match options.root_mode {
         RootMode::MapRoot => Some(ctx.assemble_directory(root, "", true, None)),
         RootMode::SeqRoot => {
@@ -102,13 +602,32 @@ pub fn build(root: &Path, options: &BuildOptions) -> BuildOutcome {
         RootMode::FileRoot => ctx.assemble_file_root(root),
     };
 
-    if !ctx.explain.ignored.is_empty() {
-        let examples = ctx
-            .explain
-            .ignored
+    let ignored = ctx
+        .explain
+        .ignored
+        .iter()
+        .filter(|entry| {
+            ![
+                MANIFEST_IGNORE_RULE,
+                KEYMAP_IGNORE_RULE,
+                CACHE_IGNORE_RULE,
+                ROOT_FILE_IGNORE_RULE,
+                OUTPUT_IGNORE_RULE,
+                ANCHORS_IGNORE_RULE,
+                INCLUDE_IGNORE_RULE,
+                ONLY_IGNORE_RULE,
+            ]
+            .contains(&entry.rule.as_str())
+                && !entry.rule.starts_with(EXCLUDE_IGNORE_PREFIX)
+                && !entry.rule.starts_with(SHADOWED_IGNORE_PREFIX)
+        })
+        .map(|entry| entry.path.clone())
+        .collect::<Vec<_>>();
+    if !ignored.is_empty() {
+        let examples = ignored
             .iter()
             .take(3)
-            .map(|i| i.path.clone())
+            .cloned()
             .collect::<Vec<_>>()
             .join(", ");
         ctx.diag(
@@ -116,7 +635,7 @@ pub fn build(root: &Path, options: &BuildOptions) -> BuildOutcome {
                 "W050",
                 format!(
                     "ignored {} file(s)/directory(ies) while scanning",
-                    ctx.explain.ignored.len()
+                    ignored.len()
                 ),
             )
             .with_cause("Entries did not match FYAML inclusion rules.")
@@ -125,23 +644,57 @@ pub fn build(root: &Path, options: &BuildOptions) -> BuildOutcome {
         );
     }
 
+    if options.frozen {
+        ctx.verify_manifest(root);
+    }
+
     ctx.finish(value)
 }
 
-struct BuildContext {
+struct BuildContext<'a> {
     root: PathBuf,
-    options: BuildOptions,
+    options: &'a BuildOptions,
+    source: &'a dyn TreeSource,
+    cache: &'a mut ParseCache,
     diagnostics: Vec<Diagnostic>,
     explain: ExplainReport,
+    fragments: Vec<Fragment>,
+    keymap: Keymap,
+    prefetched: HashMap<PathBuf, Prefetched>,
+    /// Structural path of the value being assembled, for [`KeyOrder::path`].
+    segments: Vec<Value>,
+    key_orders: Vec<KeyOrder>,
+    /// Files whose `!include` tags are being resolved, outermost first.
+    include_stack: Vec<PathBuf>,
+    /// Values from `_anchors.yml` under `--shared-anchors`, by anchor name.
+    shared_anchors: HashMap<String, Value>,
+    preserved: Preserved,
+    positions: HashMap<String, SourceLocation>,
 }
 
-impl BuildContext {
-    fn new(root: &Path, options: BuildOptions) -> Self {
+impl<'a> BuildContext<'a> {
+    fn new(
+        root: &Path,
+        options: &'a BuildOptions,
+        source: &'a dyn TreeSource,
+        cache: &'a mut ParseCache,
+    ) -> Self {
         Self {
             root: root.to_path_buf(),
             options,
+            source,
+            cache,
             diagnostics: Vec::new(),
             explain: ExplainReport::default(),
+            fragments: Vec::new(),
+            keymap: Keymap::default(),
+            prefetched: HashMap::new(),
+            segments: Vec::new(),
+            key_orders: Vec::new(),
+            include_stack: Vec::new(),
+            shared_anchors: HashMap::new(),
+            preserved: Preserved::default(),
+            positions: HashMap::new(),
         }
     }
 
@@ -156,11 +709,18 @@ impl BuildContext {
                 }
             }
         }
+        if self.options.diag_order == DiagOrder::Sorted {
+            self.diagnostics.sort_by(Diagnostic::cmp_sorted);
+        }
 
         BuildOutcome {
             value,
             diagnostics: self.diagnostics,
             explain: self.explain,
+            fragments: self.fragments,
+            key_orders: self.key_orders,
+            preserved: self.preserved,
+            positions: self.positions,
         }
     }
 
@@ -182,6 +742,19 @@ impl BuildContext {
         });
     }
 
+    fn add_ordered_key(&mut self, source_path: &Path, derived_key_path: &str, order: u64) {
+        let name = source_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.explain.ordered_keys.push(OrderedKey {
+            source: self.display_path(source_path),
+            name,
+            derived_key_path: derived_key_path.to_string(),
+            order,
+        });
+    }
+
     fn add_directory_mode(&mut self, directory: &Path, mode: &str, contributors: &[Contributor]) {
         let contributor_names = contributors
             .iter()
@@ -194,92 +767,511 @@ impl BuildContext {
         });
     }
 
-    fn display_path(&self, path: &Path) -> String {
-        if let Ok(relative) = path.strip_prefix(&self.root) {
-            if relative.as_os_str().is_empty() {
-                ".".to_string()
-            } else {
-                relative.to_string_lossy().replace('\\', "/")
-            }
-        } else {
-            path.to_string_lossy().replace('\\', "/")
+    fn load_keymap(&mut self, root: &Path) {
+        let keymap_path = root.join(KEYMAP_FILE_NAME);
+        if self.source.kind(&keymap_path) != Some(EntryKind::File) {
+            return;
+        }
+        let keymap = self
+            .source
+            .read_to_string(&keymap_path)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| {
+                serde_json::from_str::<Keymap>(&contents).map_err(|err| err.to_string())
+            });
+        match keymap {
+            Ok(keymap) => self.keymap = keymap,
+            Err(cause) => self.diag(
+                Diagnostic::error(
+                    "E084",
+                    "unable to load fyaml keymap",
+                    Category::InvalidInput,
+                )
+                .with_location(self.display_path(&keymap_path))
+                .with_cause(cause)
+                .with_action(format!(
+                    "Fix {KEYMAP_FILE_NAME} or re-run `fyaml scaffold` to regenerate it."
+                )),
+            ),
         }
     }
 
-    fn assemble_file_root(&mut self, root: &Path) -> Option<Value> {
-        let root_file = match &self.options.root_file {
-            Some(file) => file,
-            None => {
-                self.diag(
-                    Diagnostic::error(
-                        "E041",
-                        "file-root mode requires --root-file",
-                        Category::InvalidInput,
-                    )
-                    .with_location(root.display().to_string())
-                    .with_cause("No root file was provided.")
-                    .with_action(
-                        "Pass --root-file <RELATIVE_PATH> when using --root-mode file-root.",
-                    ),
-                );
-                return None;
+    /// Reads `_anchors.yml` at the root, a mapping of anchor names to values, with the same
+    /// parsing policies as any fragment.
+    fn load_shared_anchors(&mut self, root: &Path) {
+        let anchors_path = root.join(ANCHORS_FILE_NAME);
+        if self.source.kind(&anchors_path) != Some(EntryKind::File) {
+            return;
+        }
+        let contents = match self.source.read_to_string(&anchors_path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                self.diag(shared_anchors_error(
+                    self.display_path(&anchors_path),
+                    err.to_string(),
+                ));
+                return;
             }
         };
+        let sha256 = sha256_hex(contents.as_bytes());
+        let Some(value) = self.parse_contents(&anchors_path, "", &contents, &sha256, None) else {
+            return;
+        };
+        let Value::Mapping(map) = value else {
+            self.diag(shared_anchors_error(
+                self.display_path(&anchors_path),
+                "The file is not a mapping of anchor names to values.".to_string(),
+            ));
+            return;
+        };
+        for (name, value) in map {
+            match name {
+                Value::String(name) => {
+                    self.shared_anchors.insert(name, value);
+                }
+                other => self.diag(shared_anchors_error(
+                    self.display_path(&anchors_path),
+                    format!(
+                        "Anchor names must be strings; found `{}`.",
+                        serde_yaml::to_string(&other).unwrap_or_default().trim_end()
+                    ),
+                )),
+            }
+        }
+    }
 
-        let root_file_abs = if root_file.is_absolute() {
-            root_file.clone()
+    /// The key recorded in the keymap for the entry `name` (without YAML extension) in
+    /// `directory`, if it was renamed.
+    fn mapped_key(&self, directory: &Path, name: &str) -> Option<String> {
+        if self.keymap.keys.is_empty() {
+            return None;
+        }
+        let entry = self.display_path(&directory.join(name));
+        self.keymap.get(&entry).map(str::to_string)
+    }
+
+    /// The key derived from the entry `name` (without YAML extension) in `directory`, and the
+    /// position given by its `NN-` prefix under `--ordered-prefixes`.
+    fn entry_key(&self, directory: &Path, name: String) -> (String, Option<u64>) {
+        let prefix = if self.options.ordered_prefixes {
+            split_order_prefix(&name)
         } else {
-            root.join(root_file)
+            None
         };
+        if let Some(key) = self.mapped_key(directory, &name) {
+            return (key, prefix.map(|(order, _)| order));
+        }
+        match prefix {
+            Some((order, key)) => (key.to_string(), Some(order)),
+            None => (name, None),
+        }
+    }
 
-        if !root_file_abs.exists() {
+    /// The name a file (without its extension) or directory derives its key from, reporting
+    /// trailing whitespace or dots and a YAML extension that is not lowercase.
+    fn normalized_name(&mut self, path: &Path, name: &str, file: bool) -> String {
+        let (normalized, removed) = normalize_entry_name(name, file);
+        let extension = Path::new(trimmed_entry_name(name))
+            .extension()
+            .map(|extension| extension.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if !removed.is_empty() {
+            let suggested = if file {
+                format!("{normalized}.{extension}")
+            } else {
+                normalized.clone()
+            };
+            let offending = removed
+                .iter()
+                .map(|(text, place)| format!("{} {place}", describe_bytes(text)))
+                .collect::<Vec<_>>()
+                .join(", ");
             self.diag(
-                Diagnostic::error("E042", "root file does not exist", Category::InvalidInput)
-                    .with_location(self.display_path(&root_file_abs))
-                    .with_cause("The --root-file path does not resolve to an existing file.")
-                    .with_action("Use a valid relative path under the FYAML root."),
+                Diagnostic::warn("W021", "entry name has trailing whitespace or dots")
+                    .with_location(self.display_path(path))
+                    .with_cause(
+                        "Trailing whitespace and dots are hard to see and are dropped by Windows, so the name aliases one without them.",
+                    )
+                    .with_action(format!("Rename the entry to `{suggested}`."))
+                    .with_context(format!("Offending bytes: {offending}")),
             );
-            return None;
         }
+        let lowercase = extension.to_ascii_lowercase();
+        if file && extension != lowercase && (lowercase == "yml" || lowercase == "yaml") {
+            self.diag(
+                Diagnostic::warn("W022", "YAML extension is not lowercase")
+                    .with_location(self.display_path(path))
+                    .with_cause(
+                        "The extension is matched case-insensitively, but mixed-case names alias on case-insensitive filesystems.",
+                    )
+                    .with_action(format!("Rename the file to end in .{lowercase}."))
+                    .with_context(format!("Offending bytes: {}", describe_bytes(&extension))),
+            );
+        }
+        normalized
+    }
 
-        let mut root_value = self.parse_yaml_file(&root_file_abs, "$root")?;
-
-        let dir_value = self.assemble_directory(root, "", true, Some(&root_file_abs));
-        let dir_map = match dir_value {
-            Value::Mapping(mapping) => mapping,
-            _ => {
+    /// Applies `--ascii-keys` to `key`, derived from the name of `path`.
+    fn ascii_key(&mut self, path: &Path, key_path: &str, key: String) -> String {
+        if key.is_ascii() {
+            return key;
+        }
+        let location = self.display_path(path);
+        let derived = join_key_path(key_path, &key);
+        match self.options.ascii_keys {
+            AsciiKeys::Allow => key,
+            AsciiKeys::Warn => {
+                self.diag(
+                    Diagnostic::warn("W017", "non-ASCII key derived from a file or directory name")
+                        .with_location(location)
+                        .with_derived_key_path(derived)
+                        .with_cause("Some YAML consumers cannot handle non-ASCII mapping keys.")
+                        .with_action(
+                            "Rename the entry, or pass --ascii-keys transliterate to spell it in ASCII.",
+                        ),
+                );
+                key
+            }
+            AsciiKeys::Error => {
                 self.diag(
                     Diagnostic::error(
-                        "E043",
-                        "internal mapping assembly failed in file-root mode",
-                        Category::Internal,
+                        "E023",
+                        "non-ASCII key derived from a file or directory name",
+                        Category::InvalidInput,
                     )
-                    .with_location(self.display_path(root))
-                    .with_cause("Directory assembly should produce a mapping when forced.")
-                    .with_action("Report this issue; this is an implementation bug."),
+                    .with_location(location)
+                    .with_derived_key_path(derived)
+                    .with_cause("--ascii-keys error rejects keys with non-ASCII characters.")
+                    .with_action(
+                        "Rename the entry, or pass --ascii-keys transliterate to spell it in ASCII.",
+                    ),
                 );
-                Mapping::new()
+                key
             }
-        };
+            AsciiKeys::Transliterate => match transliterate(&key) {
+                Ok(ascii) => {
+                    self.explain.transliterated_keys.push(TransliteratedKey {
+                        source: location,
+                        original: key,
+                        key: ascii.clone(),
+                    });
+                    ascii
+                }
+                Err(c) => {
+                    self.diag(
+                        Diagnostic::error(
+                            "E024",
+                            "derived key has no ASCII spelling",
+                            Category::InvalidInput,
+                        )
+                        .with_location(location)
+                        .with_derived_key_path(derived)
+                        .with_cause(format!(
+                            "`{c}` (U+{:04X}) has no ASCII transliteration.",
+                            u32::from(c)
+                        ))
+                        .with_action("Rename the entry using ASCII characters."),
+                    );
+                    key
+                }
+            },
+        }
+    }
 
-        if dir_map.is_empty() {
-            return Some(root_value);
+    /// Reads and parses the fragments under `root` on `options.jobs` worker threads.
+    ///
+    /// Assembly still walks the tree in order and reports every diagnostic itself; it only
+    /// takes the prepared result instead of reading the file, so the outcome matches a
+    /// serial build exactly. Fragments this walk misses are simply read during assembly.
+    ///
+    /// Workers take fragments in batches from one directory, so the source can read them
+    /// relative to the open directory instead of resolving each full path.
+    fn prefetch_fragments(&mut self, root: &Path) {
+        let mut paths = Vec::new();
+        self.collect_fragment_paths(root, &mut paths);
+        let mut batches = Vec::new();
+        let mut start = 0;
+        for end in 1..=paths.len() {
+            if end == paths.len()
+                || end - start == PREFETCH_BATCH
+                || paths[end].parent() != paths[start].parent()
+            {
+                batches.push(&paths[start..end]);
+                start = end;
+            }
         }
 
-        let merge_target = self.options.merge_under.clone();
+        let source = self.source;
+        let cache = &*self.cache;
+        let max_yaml_bytes = self.options.max_yaml_bytes;
+        let big_integers = self.options.big_integers;
+        let next = AtomicUsize::new(0);
+        let fetch = |read: io::Result<FragmentRead>| {
+            let parsed = match &read {
+                Ok(FragmentRead {
+                    contents: Some(Ok(contents)),
+                    ..
+                }) => {
+                    let sha256 = sha256_hex(contents.as_bytes());
+                    (!cache.contains(&sha256, big_integers)).then(|| {
+                        let documents = parse_documents(contents, big_integers);
+                        (sha256, documents)
+                    })
+                }
+                _ => None,
+            };
+            Prefetched { read, parsed }
+        };
+        let worker = || {
+            let mut fetched = Vec::new();
+            while let Some(batch) = batches.get(next.fetch_add(1, AtomicOrdering::Relaxed)) {
+                let directory = batch[0].parent().unwrap_or(Path::new(""));
+                let reads = source.read_fragments_in(directory, batch, max_yaml_bytes);
+                for (path, read) in batch.iter().zip(reads) {
+                    fetched.push((path.clone(), fetch(read)));
+                }
+            }
+            fetched
+        };
 
-        if let Some(target_key) = merge_target {
-            match &mut root_value {
-                Value::Mapping(root_map) => {
-                    let key = Value::String(target_key.clone());
-                    if let Some(existing) = root_map.get_mut(&key) {
-                        match existing {
-                            Value::Mapping(existing_map) => {
-                                self.merge_mappings(
-                                    existing_map,
-                                    dir_map,
+        let workers = self.options.jobs.min(batches.len().max(1));
+        self.prefetched = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers).map(|_| scope.spawn(worker)).collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("fragment parser panicked"))
+                .collect()
+        });
+    }
+
+    /// YAML files assembly is expected to read, found with the same inclusion rules but
+    /// without reporting anything.
+    fn collect_fragment_paths(&self, directory: &Path, paths: &mut Vec<PathBuf>) {
+        let Ok(entries) = self.source.read_dir(directory) else {
+            return;
+        };
+        for entry in entries.into_iter().flatten() {
+            if (!self.options.include_hidden && is_hidden_name(&entry.name))
+                || is_editor_junk(&entry.name)
+            {
+                continue;
+            }
+            let is_dir = matches!(entry.kind, Ok(EntryKind::Directory));
+            if self.filter_rule(&entry.path, is_dir).is_some() {
+                continue;
+            }
+            match entry.kind {
+                Ok(EntryKind::Directory) => self.collect_fragment_paths(&entry.path, paths),
+                Ok(EntryKind::File) if is_yaml_file(&entry.path) => paths.push(entry.path),
+                _ => {}
+            }
+        }
+    }
+
+    /// The ignore rule for an entry that `--exclude` or `--include` leaves out, if any. A
+    /// directory is kept while some `--include` pattern could match below it.
+    fn filter_rule(&self, path: &Path, is_dir: bool) -> Option<String> {
+        let relative = self.display_path(path);
+        if let Some(glob) = self.options.exclude.iter().find(|g| g.matches(&relative)) {
+            return Some(format!("{EXCLUDE_IGNORE_PREFIX}{glob}"));
+        }
+        if self.options.include.is_empty() {
+            return None;
+        }
+        // A matching directory includes everything below it.
+        let mut ancestors = relative
+            .match_indices('/')
+            .map(|(index, _)| &relative[..index])
+            .chain([relative.as_str()]);
+        let included = ancestors.any(|path| self.options.include.iter().any(|g| g.matches(path)));
+        if included
+            || (is_dir
+                && self
+                    .options
+                    .include
+                    .iter()
+                    .any(|g| g.may_match_below(&relative)))
+        {
+            return None;
+        }
+        Some(INCLUDE_IGNORE_RULE.to_string())
+    }
+
+    fn verify_manifest(&mut self, root: &Path) {
+        let manifest_path = root.join(MANIFEST_FILE_NAME);
+        let manifest = self
+            .source
+            .read_to_string(&manifest_path)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| {
+                serde_json::from_str::<Manifest>(&contents).map_err(|err| err.to_string())
+            });
+
+        match manifest {
+            Ok(manifest) => {
+                let location = self.display_path(&manifest_path);
+                let diagnostics = manifest.verify(&self.fragments, &location);
+                self.diagnostics.extend(diagnostics);
+            }
+            Err(cause) => self.diag(
+                Diagnostic::error(
+                    "E080",
+                    "unable to load fyaml manifest for --frozen build",
+                    Category::InvalidInput,
+                )
+                .with_location(self.display_path(&manifest_path))
+                .with_cause(cause)
+                .with_action("Generate the manifest with `fyaml manifest <DIR>` and commit it."),
+            ),
+        }
+    }
+
+    fn display_path(&self, path: &Path) -> String {
+        if let Ok(relative) = path.strip_prefix(&self.root) {
+            if relative.as_os_str().is_empty() {
+                ".".to_string()
+            } else {
+                relative.to_string_lossy().replace('\\', "/")
+            }
+        } else {
+            path.to_string_lossy().replace('\\', "/")
+        }
+    }
+
+    /// Picks the one conventionally named root file (`_root.yml`, `root.yaml`, ...) when
+    /// `--root-file` is omitted, recording the choice for `explain`.
+    fn discover_root_file(&mut self, root: &Path) -> Option<PathBuf> {
+        let candidates: Vec<&str> = ROOT_FILE_CANDIDATES
+            .iter()
+            .copied()
+            .filter(|name| self.source.kind(&root.join(name)) == Some(EntryKind::File))
+            .collect();
+        match candidates.as_slice() {
+            [] => {
+                self.diag(
+                    Diagnostic::error(
+                        "E041",
+                        "file-root mode requires --root-file",
+                        Category::InvalidInput,
+                    )
+                    .with_location(root.display().to_string())
+                    .with_cause("No root file or inline root document was provided.")
+                    .with_context(format!(
+                        "None of the conventional root files ({}) exists.",
+                        ROOT_FILE_CANDIDATES.join(", ")
+                    ))
+                    .with_action(
+                        "Pass --root-file <RELATIVE_PATH>, --root-inline <YAML>, or --root-stdin when using --root-mode file-root.",
+                    ),
+                );
+                None
+            }
+            [name] => {
+                self.explain.auto_root_file = Some((*name).to_string());
+                Some(PathBuf::from(name))
+            }
+            names => {
+                self.diag(
+                    Diagnostic::error(
+                        "E048",
+                        "several conventional root files found",
+                        Category::InvalidInput,
+                    )
+                    .with_location(root.display().to_string())
+                    .with_cause(format!(
+                        "Without --root-file, the root document is read from the one of {} that exists.",
+                        ROOT_FILE_CANDIDATES.join(", ")
+                    ))
+                    .with_action("Remove all but one of them, or pass --root-file.")
+                    .with_context(format!("Found: {}", names.join(", "))),
+                );
+                None
+            }
+        }
+    }
+
+    fn assemble_file_root(&mut self, root: &Path) -> Option<Value> {
+        let options = self.options;
+        if let Some(document) = &options.root_inline {
+            let location = Path::new(ROOT_INLINE_LOCATION);
+            let sha256 = sha256_hex(document.as_bytes());
+            let root_value = self.parse_contents(location, "$root", document, &sha256, None)?;
+            let dir_value = self.assemble_directory(root, "", true, None);
+            return self.merge_file_root(root, root_value, dir_value, ROOT_INLINE_LOCATION);
+        }
+
+        let root_file = match &self.options.root_file {
+            Some(file) => file.clone(),
+            None => self.discover_root_file(root)?,
+        };
+
+        let root_file_abs = if root_file.is_absolute() {
+            root_file
+        } else {
+            root.join(root_file)
+        };
+
+        if self.source.kind(&root_file_abs).is_none() {
+            self.diag(
+                Diagnostic::error("E042", "root file does not exist", Category::InvalidInput)
+                    .with_location(self.display_path(&root_file_abs))
+                    .with_cause("The --root-file path does not resolve to an existing file.")
+                    .with_action("Use a valid relative path under the FYAML root."),
+            );
+            return None;
+        }
+
+        let root_value = self.parse_yaml_file(&root_file_abs, "$root")?;
+
+        let dir_value = self.assemble_directory(root, "", true, Some(&root_file_abs));
+        let location = self.display_path(&root_file_abs);
+        self.merge_file_root(root, root_value, dir_value, &location)
+    }
+
+    /// Merges the assembled directory mapping into the root document read from `location`.
+    fn merge_file_root(
+        &mut self,
+        root: &Path,
+        mut root_value: Value,
+        dir_value: Value,
+        location: &str,
+    ) -> Option<Value> {
+        let dir_map = match dir_value {
+            Value::Mapping(mapping) => mapping,
+            _ => {
+                self.diag(
+                    Diagnostic::error(
+                        "E043",
+                        "internal mapping assembly failed in file-root mode",
+                        Category::Internal,
+                    )
+                    .with_location(self.display_path(root))
+                    .with_cause("Directory assembly should produce a mapping when forced.")
+                    .with_action("Report this issue; this is an implementation bug."),
+                );
+                Mapping::new()
+            }
+        };
+
+        if dir_map.is_empty() {
+            return Some(root_value);
+        }
+
+        if let Some(target_key) = self.options.merge_under.as_deref() {
+            for order in &mut self.key_orders {
+                order.path.insert(0, Value::String(target_key.to_string()));
+            }
+            match &mut root_value {
+                Value::Mapping(root_map) => {
+                    let key = Value::String(target_key.to_string());
+                    if let Some(existing) = root_map.get_mut(&key) {
+                        match existing {
+                            Value::Mapping(existing_map) => {
+                                self.merge_mappings(
+                                    existing_map,
+                                    dir_map,
                                     &format!("{target_key}."),
-                                    &self.display_path(&root_file_abs),
+                                    location,
                                 );
                             }
                             _ => {
@@ -289,8 +1281,8 @@ impl BuildContext {
                                         "merge target exists but is not a mapping",
                                         Category::InvalidInput,
                                     )
-                                    .with_location(self.display_path(&root_file_abs))
-                                    .with_derived_key_path(target_key.clone())
+                                    .with_location(location.to_string())
+                                    .with_derived_key_path(target_key)
                                     .with_cause(
                                         "--merge-under requires an existing mapping when the target key already exists.",
                                     )
@@ -309,7 +1301,7 @@ impl BuildContext {
                             "file-root merge requires root YAML to be a mapping",
                             Category::InvalidInput,
                         )
-                        .with_location(self.display_path(&root_file_abs))
+                        .with_location(location.to_string())
                         .with_cause("The root file parsed to a non-mapping value.")
                         .with_action("Use a mapping root YAML value when merging directory keys."),
                     );
@@ -320,7 +1312,7 @@ impl BuildContext {
 
         match &mut root_value {
             Value::Mapping(root_map) => {
-                self.merge_mappings(root_map, dir_map, "", &self.display_path(&root_file_abs));
+                self.merge_mappings(root_map, dir_map, "", location);
             }
             _ => {
                 self.diag(
@@ -329,7 +1321,7 @@ impl BuildContext {
                         "file-root root YAML is not a mapping",
                         Category::InvalidInput,
                     )
-                    .with_location(self.display_path(&root_file_abs))
+                    .with_location(location.to_string())
                     .with_cause("Directory keys cannot be merged into a non-mapping root value.")
                     .with_action(
                         "Use --merge-under with a mapping target or make the root file a mapping.",
@@ -349,23 +1341,92 @@ impl BuildContext {
         location: &str,
     ) {
         for (key, value) in source {
-            if let Some(existing) = target.get(&key) {
-                let key_name = key_as_string(&key);
-                let key_path = format!("{key_prefix}{key_name}");
+            if let Some(existing) = target.get_mut(&key) {
+                let key_path = format!("{key_prefix}{}", key_as_string(&key));
+                self.merge_value(existing, value, &key_path, location);
+            } else {
+                target.insert(key, value);
+            }
+        }
+    }
+
+    /// Merges two values for a key that an expanded dotted file name shares with a sibling,
+    /// descending through mappings so only keys both define collide.
+    fn merge_expanded(
+        &mut self,
+        existing: &mut Value,
+        incoming: Value,
+        key_path: &str,
+        location: &str,
+    ) {
+        match (existing, incoming) {
+            (Value::Mapping(existing), Value::Mapping(incoming)) => {
+                for (key, value) in incoming {
+                    match existing.get_mut(&key) {
+                        Some(child) => {
+                            let child_key_path = join_key_path(key_path, &key_as_string(&key));
+                            self.merge_expanded(child, value, &child_key_path, location);
+                        }
+                        None => {
+                            existing.insert(key, value);
+                        }
+                    }
+                }
+            }
+            (existing, incoming) => self.merge_value(existing, incoming, key_path, location),
+        }
+    }
+
+    /// Resolves a key that two sources define, following `--on-collision`. `existing` comes
+    /// first: the earlier contributor in path order, the root file, or the directory entries an
+    /// index file is merged into.
+    fn merge_value(
+        &mut self,
+        existing: &mut Value,
+        incoming: Value,
+        key_path: &str,
+        location: &str,
+    ) {
+        let policy = self.options.on_collision;
+        match (policy, existing, incoming) {
+            (CollisionPolicy::FirstWins | CollisionPolicy::LastWins, existing, incoming) => {
+                let kept = if policy == CollisionPolicy::FirstWins {
+                    "first"
+                } else {
+                    *existing = incoming;
+                    "last"
+                };
+                self.diag(
+                    Diagnostic::warn("W018", "key collision resolved by --on-collision")
+                        .with_location(location.to_string())
+                        .with_derived_key_path(key_path.to_string())
+                        .with_cause(format!(
+                            "Both sources define the key; --on-collision {kept}-wins keeps the {kept} value."
+                        ))
+                        .with_action("Rename one source if both values are needed."),
+                );
+            }
+            (CollisionPolicy::DeepMerge, Value::Mapping(existing), Value::Mapping(incoming)) => {
+                self.merge_mappings(existing, incoming, &format!("{key_path}."), location);
+            }
+            (policy, existing, incoming) => {
+                let cause = if policy == CollisionPolicy::DeepMerge {
+                    "Deep merge combines mappings only; both sides define a value here."
+                } else {
+                    "Both sides of a merge define the same key."
+                };
                 self.diag(
                     Diagnostic::error("E001", "key collision during merge", Category::InvalidInput)
                         .with_location(location.to_string())
-                        .with_derived_key_path(key_path.clone())
-                        .with_cause("Both sides of a merge define the same key.")
+                        .with_derived_key_path(key_path.to_string())
+                        .with_cause(cause)
                         .with_action("Rename one key or move content into a different subtree.")
                         .with_context(format!(
                             "Existing value kind: {}, incoming value kind: {}",
                             value_kind(existing),
-                            value_kind(&value)
+                            value_kind(&incoming)
                         )),
                 );
-            } else {
-                target.insert(key, value);
             }
         }
     }
@@ -377,7 +1438,7 @@ impl BuildContext {
         force_map: bool,
         excluded_file: Option<&Path>,
     ) -> Value {
-        let read_dir = match fs::read_dir(directory) {
+        let read_dir = match self.source.read_dir(directory) {
             Ok(rd) => rd,
             Err(err) => {
                 self.diag(
@@ -390,8 +1451,8 @@ impl BuildContext {
             }
         };
 
-        let excluded = excluded_file.and_then(|path| fs::canonicalize(path).ok());
         let mut contributors: Vec<Contributor> = Vec::new();
+        let mut index_files: Vec<PathBuf> = Vec::new();
 
         for entry in read_dir {
             let entry = match entry {
@@ -411,17 +1472,34 @@ impl BuildContext {
                 }
             };
 
-            let path = entry.path();
-            if excluded
-                .as_ref()
-                .is_some_and(|x| fs::canonicalize(&path).ok().as_ref() == Some(x))
-            {
-                self.add_ignored(&path, "root file excluded from normal scanning");
+            let path = entry.path;
+            if excluded_file.is_some_and(|excluded| self.source.same_entry(&path, excluded)) {
+                self.add_ignored(&path, ROOT_FILE_IGNORE_RULE);
                 continue;
             }
 
-            let name = entry.file_name();
-            let name = name.to_string_lossy();
+            let name = entry.name;
+
+            if directory == self.root && name == MANIFEST_FILE_NAME {
+                self.add_ignored(&path, MANIFEST_IGNORE_RULE);
+                continue;
+            }
+            if directory == self.root && name == KEYMAP_FILE_NAME {
+                self.add_ignored(&path, KEYMAP_IGNORE_RULE);
+                continue;
+            }
+            if directory == self.root && name == CACHE_FILE_NAME {
+                self.add_ignored(&path, CACHE_IGNORE_RULE);
+                continue;
+            }
+            if directory == self.root && self.options.shared_anchors && name == ANCHORS_FILE_NAME {
+                self.add_ignored(&path, ANCHORS_IGNORE_RULE);
+                continue;
+            }
+            if self.is_output(&path, &name) {
+                self.add_ignored(&path, OUTPUT_IGNORE_RULE);
+                continue;
+            }
 
             if !self.options.include_hidden && is_hidden_name(&name) {
                 self.add_ignored(&path, "hidden entry ignored (use --include-hidden)");
@@ -433,8 +1511,8 @@ impl BuildContext {
                 continue;
             }
 
-            let file_type = match entry.file_type() {
-                Ok(ft) => ft,
+            let kind = match entry.kind {
+                Ok(kind) => kind,
                 Err(err) => {
                     self.diag(
                         Diagnostic::error(
@@ -450,13 +1528,20 @@ impl BuildContext {
                 }
             };
 
-            if file_type.is_symlink() {
+            if kind == EntryKind::Symlink {
                 self.add_ignored(&path, "symlink ignored");
                 continue;
             }
 
-            if file_type.is_dir() {
-                let key = name.to_string();
+            if let Some(rule) = self.filter_rule(&path, kind == EntryKind::Directory) {
+                self.add_ignored(&path, &rule);
+                continue;
+            }
+
+            if kind == EntryKind::Directory {
+                let name = self.normalized_name(&path, &name, false);
+                let (key, order) = self.entry_key(directory, name);
+                let key = self.ascii_key(&path, key_path, key);
                 if !self.options.allow_reserved_keys && is_reserved_yaml_key(&key) {
                     self.diag(
                         Diagnostic::error(
@@ -479,17 +1564,64 @@ impl BuildContext {
                     key,
                     path,
                     kind: ContributorKind::Directory,
+                    order,
+                    nested: Vec::new(),
                 });
                 continue;
             }
 
-            if file_type.is_file() {
-                if !is_yaml_file(path.as_path()) {
+            if kind == EntryKind::File {
+                let binary = self.is_binary_file(&path);
+                let markdown = !binary && self.options.front_matter && is_markdown_file(&path);
+                let text =
+                    !binary && (markdown || (self.options.text_files && is_text_file(&path)));
+                if !binary && !text && !is_yaml_file(path.as_path()) {
                     self.add_ignored(&path, "non-YAML file ignored");
                     continue;
                 }
 
-                let key = strip_yaml_extension(&name);
+                let yaml = !binary && !text;
+                if let Some(required) = self.options.require_extension.filter(|_| yaml) {
+                    if yaml_extension(&path).as_deref() != Some(required.as_str()) {
+                        self.diag(
+                            Diagnostic::warn(
+                                "W019",
+                                format!(
+                                    "fragment does not use the .{} extension",
+                                    required.as_str()
+                                ),
+                            )
+                            .with_location(self.display_path(&path))
+                            .with_cause(format!(
+                                "--require-extension {} expects one extension across the tree.",
+                                required.as_str()
+                            ))
+                            .with_action(format!(
+                                "Rename the file to end in .{}.",
+                                required.as_str()
+                            )),
+                        );
+                    }
+                }
+
+                let stem = if binary {
+                    let name = self.normalized_name(&path, &name, false);
+                    match name.rsplit_once('.') {
+                        Some((stem, _)) if !stem.is_empty() => stem.to_string(),
+                        _ => name,
+                    }
+                } else {
+                    self.normalized_name(&path, &name, true)
+                };
+                if self.options.index_files && yaml && INDEX_FILE_STEMS.contains(&stem.as_str()) {
+                    index_files.push(path);
+                    continue;
+                }
+                // Keys restored through the keymap are taken literally, dots included.
+                let expand =
+                    self.options.expand_dotted_keys && self.mapped_key(directory, &stem).is_none();
+                let (key, order) = self.entry_key(directory, stem);
+                let key = self.ascii_key(&path, key_path, key);
                 if key.is_empty() {
                     self.diag(
                         Diagnostic::error(
@@ -504,7 +1636,32 @@ impl BuildContext {
                     continue;
                 }
 
-                if key.contains('.') && !self.options.allow_dotted_keys {
+                let nested = if expand {
+                    match split_dotted_key(&key) {
+                        Some(segments) if segments.iter().any(String::is_empty) => {
+                            self.diag(
+                                Diagnostic::error(
+                                    "E021",
+                                    "empty key derived from YAML filename",
+                                    Category::InvalidInput,
+                                )
+                                .with_location(self.display_path(&path))
+                                .with_derived_key_path(join_key_path(key_path, &key))
+                                .with_cause(
+                                    "--expand-dotted-keys found an empty segment before, between, or after the dots.",
+                                )
+                                .with_action("Remove the extra dot, e.g., server.port.yml."),
+                            );
+                            continue;
+                        }
+                        Some(segments) => segments,
+                        None => Vec::new(),
+                    }
+                } else {
+                    Vec::new()
+                };
+
+                if nested.is_empty() && key.contains('.') && !self.options.allow_dotted_keys {
                     self.diag(
                         Diagnostic::warn("W010", "dotted key derived from filename")
                             .with_location(self.display_path(&path))
@@ -516,7 +1673,12 @@ impl BuildContext {
                     );
                 }
 
-                if !self.options.allow_reserved_keys && is_reserved_yaml_key(&key) {
+                let reserved = if nested.is_empty() {
+                    is_reserved_yaml_key(&key)
+                } else {
+                    nested.iter().any(|segment| is_reserved_yaml_key(segment))
+                };
+                if !self.options.allow_reserved_keys && reserved {
                     self.diag(
                         Diagnostic::error(
                             "E022",
@@ -532,10 +1694,16 @@ impl BuildContext {
                     );
                 }
 
+                let (key, nested) = match nested.split_first() {
+                    Some((first, rest)) => (first.clone(), rest.to_vec()),
+                    None => (key, Vec::new()),
+                };
                 contributors.push(Contributor {
                     key,
                     path,
                     kind: ContributorKind::File,
+                    order,
+                    nested,
                 });
                 continue;
             }
@@ -550,18 +1718,73 @@ impl BuildContext {
                 .then(a.path.cmp(&b.path))
         });
 
+        self.resolve_extension_conflicts(directory, key_path, &mut contributors);
         self.detect_key_collisions(directory, key_path, &contributors);
 
+        if let [single] = contributors.as_slice() {
+            let is_value_file = matches!(single.kind, ContributorKind::File)
+                && VALUE_FILE_STEMS.contains(&single.entry_name().as_str());
+            if self.options.value_files && is_value_file && !force_map && index_files.is_empty() {
+                self.add_directory_mode(directory, "value", &contributors);
+                self.add_derived_key(&single.path, key_path);
+                return self
+                    .parse_yaml_file(&single.path, key_path)
+                    .unwrap_or(Value::Null);
+            }
+        }
+
+        // Index files contribute mapping keys, so their directory is never a sequence.
+        let force_map = force_map || !index_files.is_empty();
         let effective_mode =
             self.resolve_directory_mode(directory, key_path, force_map, &contributors);
 
-        match effective_mode {
+        let value = match effective_mode {
             DirectoryAssemblyMode::Sequence => {
                 self.assemble_sequence(directory, key_path, contributors, excluded_file)
             }
             DirectoryAssemblyMode::Mapping => {
-                self.assemble_mapping(directory, key_path, contributors, excluded_file)
+                let mut map = self.assemble_mapping(key_path, contributors, excluded_file);
+                index_files.sort();
+                for index_file in index_files {
+                    self.merge_index_file(&mut map, key_path, &index_file);
+                }
+                Value::Mapping(map)
+            }
+        };
+        if !self.options.item_counts.is_empty() {
+            self.check_item_count(directory, key_path, &value);
+        }
+        value
+    }
+
+    /// Merges the mapping in an index file (`_.yml`, `index.yml`) into the mapping of its
+    /// directory, reporting keys both define as E001.
+    fn merge_index_file(&mut self, map: &mut Mapping, key_path: &str, path: &Path) {
+        let location = self.display_path(path);
+        match self.parse_yaml_file(path, key_path) {
+            None | Some(Value::Null) => {}
+            Some(Value::Mapping(index)) => {
+                for key in index.keys() {
+                    let child_key_path = join_key_path(key_path, &key_as_string(key));
+                    self.add_derived_key(path, &child_key_path);
+                }
+                let key_prefix = if key_path.is_empty() {
+                    String::new()
+                } else {
+                    format!("{key_path}.")
+                };
+                self.merge_mappings(map, index, &key_prefix, &location);
             }
+            Some(other) => self.diag(
+                Diagnostic::error("E005", "index file is not a mapping", Category::InvalidInput)
+                    .with_location(location)
+                    .with_derived_key_path(key_path.to_string())
+                    .with_cause(format!(
+                        "--index-files merges the file into its directory's mapping, but it holds a {}.",
+                        value_kind(&other)
+                    ))
+                    .with_action("Make the index file a mapping, or rename it to contribute a key."),
+            ),
         }
     }
 
@@ -632,10 +1855,25 @@ impl BuildContext {
 
         numeric.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.path.cmp(&b.1.path)));
 
-        let mut expected = 0_u64;
+        let start = self.options.seq_start;
+        self.explain.sequence_numbering = Some(SequenceNumbering {
+            start,
+            order: self.options.seq_order,
+            gaps: self.options.seq_gaps,
+        });
+        let mut expected = start;
         let mut gaps = Vec::new();
+        let mut below_start = Vec::new();
 
-        for (index, _) in &numeric {
+        for (position, (index, _)) in numeric.iter().enumerate() {
+            // Entries sharing an index are a collision, not a gap.
+            if position > 0 && numeric[position - 1].0 == *index {
+                continue;
+            }
+            if *index < start {
+                below_start.push(index.to_string());
+                continue;
+            }
             if *index != expected {
                 gaps.push((expected, *index));
                 expected = *index;
@@ -643,12 +1881,25 @@ impl BuildContext {
             expected += 1;
         }
 
-        if !gaps.is_empty() {
-            let gap_text = gaps
-                .iter()
-                .map(|(start, end)| format!("{start}..{}", end.saturating_sub(1)))
-                .collect::<Vec<_>>()
-                .join(", ");
+        if !gaps.is_empty() || !below_start.is_empty() {
+            let mut context = Vec::new();
+            if !gaps.is_empty() {
+                let gap_text = gaps
+                    .iter()
+                    .map(|(start, end)| format!("{start}..{}", end.saturating_sub(1)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                context.push(format!("Missing ranges: {gap_text}"));
+            }
+            if !below_start.is_empty() {
+                context.push(format!(
+                    "Below --seq-start {start}: {}",
+                    below_start.join(", ")
+                ));
+            }
+            let context = context.join(". ");
+            let action =
+                format!("Rename indices to form a contiguous sequence starting at {start}.");
 
             match self.options.seq_gaps {
                 SeqGapMode::Error => {
@@ -661,8 +1912,8 @@ impl BuildContext {
                         .with_location(self.display_path(directory))
                         .with_derived_key_path(key_path.to_string())
                         .with_cause("Sequence contributors are not contiguous.")
-                        .with_action("Rename indices to form a contiguous sequence starting at 0.")
-                        .with_context(format!("Missing ranges: {gap_text}")),
+                        .with_action(action)
+                        .with_context(context),
                     );
                 }
                 SeqGapMode::Warn => {
@@ -671,70 +1922,436 @@ impl BuildContext {
                             .with_location(self.display_path(directory))
                             .with_derived_key_path(key_path.to_string())
                             .with_cause("Sequence contributors are not contiguous.")
-                            .with_action(
-                                "Rename indices to form a contiguous sequence starting at 0.",
-                            )
-                            .with_context(format!("Missing ranges: {gap_text}")),
+                            .with_action(action)
+                            .with_context(context),
                     );
                 }
                 SeqGapMode::Allow => {}
             }
         }
 
+        if self.options.seq_order == SeqOrder::Lexical {
+            numeric.sort_by(|a, b| a.1.path.file_name().cmp(&b.1.path.file_name()));
+        }
+
         let mut output = Vec::new();
+        let mut sources = Vec::new();
+        let mut previous = None;
         for (index, contributor) in numeric {
+            let item = index.saturating_sub(start);
             let child_key_path = if key_path.is_empty() {
-                format!("[{index}]")
+                format!("[{item}]")
             } else {
-                format!("{key_path}[{index}]")
+                format!("{key_path}[{item}]")
             };
             self.add_derived_key(&contributor.path, &child_key_path);
+            let merging =
+                previous == Some(index) && self.options.on_collision != CollisionPolicy::Error;
+            let position = output.len() - usize::from(merging);
+            self.segments.push(Value::Number(position.into()));
             let value = self.load_contributor_value(&contributor, &child_key_path, excluded_file);
-            output.push(value);
+            self.segments.pop();
+            match output.last_mut() {
+                Some(existing) if merging => {
+                    let location = self.display_path(&contributor.path);
+                    self.merge_value(existing, value, &child_key_path, &location);
+                }
+                _ => {
+                    output.push(value);
+                    sources.push((index, contributor.path));
+                }
+            }
+            previous = Some(index);
         }
 
-        Value::Sequence(output)
-    }
-
-    fn assemble_mapping(
-        &mut self,
-        _directory: &Path,
-        key_path: &str,
-        contributors: Vec<Contributor>,
-        excluded_file: Option<&Path>,
-    ) -> Value {
-        let mut map = Mapping::new();
-
-        for contributor in contributors {
-            let child_key_path = join_key_path(key_path, &contributor.key);
-            self.add_derived_key(&contributor.path, &child_key_path);
-            let value = self.load_contributor_value(&contributor, &child_key_path, excluded_file);
-            map.insert(Value::String(contributor.key), value);
+        if self.options.uniform_sequences || !self.options.item_keys.is_empty() {
+            self.check_sequence_items(directory, key_path, &output, &sources);
         }
-
-        Value::Mapping(map)
+        Value::Sequence(output)
     }
 
-    fn load_contributor_value(
+    /// `--uniform-sequences` and `--item-keys`: reports items whose type differs from the
+    /// most common one, and mapping items missing a required key. `sources` gives the
+    /// file index and path of each item.
+    fn check_sequence_items(
         &mut self,
-        contributor: &Contributor,
+        directory: &Path,
         key_path: &str,
-        excluded_file: Option<&Path>,
-    ) -> Value {
-        match contributor.kind {
-            ContributorKind::File => self
-                .parse_yaml_file(&contributor.path, key_path)
-                .unwrap_or(Value::Null),
-            ContributorKind::Directory => {
-                self.assemble_directory(&contributor.path, key_path, false, excluded_file)
+        items: &[Value],
+        sources: &[(u64, PathBuf)],
+    ) {
+        let sequence = if key_path.is_empty() { "." } else { key_path };
+        if self.options.uniform_sequences {
+            let mut counts: Vec<(&str, usize)> = Vec::new();
+            for item in items {
+                let kind = value_kind(item);
+                match counts.iter_mut().find(|(seen, _)| *seen == kind) {
+                    Some((_, count)) => *count += 1,
+                    None => counts.push((kind, 1)),
+                }
             }
-        }
-    }
-
-    fn parse_yaml_file(&mut self, path: &Path, key_path: &str) -> Option<Value> {
-        let metadata = match fs::metadata(path) {
-            Ok(metadata) => metadata,
-            Err(err) => {
+            // The first kind to reach the highest count wins ties.
+            let common = counts
+                .iter()
+                .fold(
+                    None,
+                    |best: Option<(&str, usize)>, &(kind, count)| match best {
+                        Some((_, most)) if most >= count => best,
+                        _ => Some((kind, count)),
+                    },
+                )
+                .map(|(kind, _)| kind);
+            for (item, (index, path)) in items.iter().zip(sources) {
+                let kind = value_kind(item);
+                let Some(common) = common.filter(|common| *common != kind) else {
+                    continue;
+                };
+                self.diag(
+                    Diagnostic::error(
+                        "E007",
+                        "sequence item differs in type from the other items",
+                        Category::InvalidInput,
+                    )
+                    .with_location(self.display_path(path))
+                    .with_derived_key_path(format!("{key_path}[{index}]"))
+                    .with_cause(format!(
+                        "Item {index} is a {kind}, but most items of `{sequence}` are a {common}."
+                    ))
+                    .with_action(
+                        "Check the file's name and contents; it may belong in another directory.",
+                    )
+                    .with_context(format!(
+                        "Sequence directory: {}",
+                        self.display_path(directory)
+                    )),
+                );
+            }
+        }
+
+        let segments = self.current_segments();
+        let required = self
+            .options
+            .item_keys
+            .iter()
+            .filter(|item_keys| item_keys.sequence.matches(&segments))
+            .flat_map(|item_keys| item_keys.keys.iter().cloned())
+            .collect::<Vec<_>>();
+        if required.is_empty() {
+            return;
+        }
+        for (item, (index, path)) in items.iter().zip(sources) {
+            let missing = match item {
+                Value::Mapping(map) => required
+                    .iter()
+                    .filter(|key| !map.contains_key(key.as_str()))
+                    .cloned()
+                    .collect::<Vec<_>>(),
+                _ => required.clone(),
+            };
+            if missing.is_empty() {
+                continue;
+            }
+            let cause = match item {
+                Value::Mapping(_) => format!("Item {index} of `{sequence}` lacks a required key."),
+                other => format!(
+                    "Item {index} of `{sequence}` is a {}, not a mapping with the required keys.",
+                    value_kind(other)
+                ),
+            };
+            self.diag(
+                Diagnostic::error(
+                    "E008",
+                    "sequence item lacks a required key",
+                    Category::InvalidInput,
+                )
+                .with_location(self.display_path(path))
+                .with_derived_key_path(format!("{key_path}[{index}]"))
+                .with_cause(cause)
+                .with_action("Add the keys to the item, or check the file's name and directory.")
+                .with_context(format!("Missing keys: {}", missing.join(", "))),
+            );
+        }
+    }
+
+    /// The structural path of the node being assembled, for matching `PathPattern`s.
+    fn current_segments(&self) -> Vec<Segment> {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Value::Number(number) => number
+                    .as_u64()
+                    .map(|index| Segment::Index(index as usize))
+                    .unwrap_or_else(|| Segment::Key(number.to_string())),
+                other => Segment::Key(key_as_string(other)),
+            })
+            .collect()
+    }
+
+    /// Whether the node being assembled is on the way to, at, or below an `--only` key path.
+    fn within_only(&self) -> bool {
+        if self.options.only.is_empty() {
+            return true;
+        }
+        let segments = self.current_segments();
+        self.options
+            .only
+            .iter()
+            .any(|pattern| pattern.overlaps(&segments))
+    }
+
+    /// `--item-count`: reports a directory at a matched path whose sequence has too few or
+    /// too many items. A directory left with no contributors counts as an empty sequence,
+    /// and its ignored entries are listed, since they are the usual reason.
+    fn check_item_count(&mut self, directory: &Path, key_path: &str, value: &Value) {
+        let segments = self.current_segments();
+        let Some(bounds) = self
+            .options
+            .item_counts
+            .iter()
+            .find(|item_count| item_count.sequence.matches(&segments))
+            .cloned()
+        else {
+            return;
+        };
+        let sequence = if key_path.is_empty() { "." } else { key_path };
+        let count = match value {
+            Value::Sequence(items) => items.len(),
+            Value::Mapping(map) if map.is_empty() => 0,
+            other => {
+                self.diag(
+                    Diagnostic::error(
+                        "E009",
+                        "sequence directory has the wrong number of items",
+                        Category::InvalidInput,
+                    )
+                    .with_location(self.display_path(directory))
+                    .with_derived_key_path(key_path.to_string())
+                    .with_cause(format!(
+                        "--item-count {bounds} expects a sequence at `{sequence}`, but the directory assembled a {}.",
+                        value_kind(other)
+                    ))
+                    .with_action("Name the directory's entries 0.yml, 1.yml, ... to build a sequence."),
+                );
+                return;
+            }
+        };
+        let problem = match (bounds.min, bounds.max) {
+            (Some(min), _) if count < min => format!("at least {min}"),
+            (_, Some(max)) if count > max => format!("at most {max}"),
+            _ => return,
+        };
+        let location = self.display_path(directory);
+        let parent = if location == "." {
+            ""
+        } else {
+            location.as_str()
+        };
+        let ignored = self
+            .explain
+            .ignored
+            .iter()
+            .filter(|entry| Path::new(&entry.path).parent() == Some(Path::new(parent)))
+            .map(|entry| format!("{} ({})", entry.path, entry.rule))
+            .collect::<Vec<_>>();
+        let mut diagnostic = Diagnostic::error(
+            "E009",
+            "sequence directory has the wrong number of items",
+            Category::InvalidInput,
+        )
+        .with_location(location)
+        .with_derived_key_path(key_path.to_string())
+        .with_cause(format!(
+            "`{sequence}` has {count} item(s); --item-count {bounds} requires {problem}."
+        ))
+        .with_action("Add or remove item files, or check for misnamed and ignored entries.");
+        if !ignored.is_empty() {
+            diagnostic =
+                diagnostic.with_context(format!("Ignored entries: {}", ignored.join(", ")));
+        }
+        self.diag(diagnostic);
+    }
+
+    fn assemble_mapping(
+        &mut self,
+        key_path: &str,
+        contributors: Vec<Contributor>,
+        excluded_file: Option<&Path>,
+    ) -> Mapping {
+        let mut map = Mapping::new();
+        let mut ordered = Vec::new();
+        let expanded: HashSet<String> = contributors
+            .iter()
+            .filter(|c| !c.nested.is_empty())
+            .map(|c| c.key.clone())
+            .collect();
+        // Contributors arrive sorted by key, so entries sharing a key path are adjacent.
+        let mut shared: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        for (index, contributor) in contributors.iter().enumerate() {
+            let same_key = |other: &Contributor| other.key == contributor.key;
+            let grouped = index
+                .checked_sub(1)
+                .is_some_and(|previous| same_key(&contributors[previous]))
+                || contributors.get(index + 1).is_some_and(same_key);
+            if grouped {
+                shared
+                    .entry(contributor.full_key())
+                    .or_default()
+                    .push(contributor.path.clone());
+            }
+        }
+
+        for contributor in contributors {
+            let depth = self.segments.len();
+            self.segments.push(Value::String(contributor.key.clone()));
+            self.segments
+                .extend(contributor.nested.iter().cloned().map(Value::String));
+            if !self.within_only() {
+                self.segments.truncate(depth);
+                self.add_ignored(&contributor.path, ONLY_IGNORE_RULE);
+                continue;
+            }
+            let child_key_path = join_key_path(key_path, &contributor.full_key());
+            self.add_derived_key(&contributor.path, &child_key_path);
+            if let Some(order) = contributor.order {
+                self.add_ordered_key(&contributor.path, &child_key_path, order);
+                ordered.push((order, contributor.key.clone()));
+            }
+            let value = self.load_contributor_value(&contributor, &child_key_path, excluded_file);
+            self.segments.truncate(depth);
+            if matches!(contributor.kind, ContributorKind::File) {
+                let siblings = shared
+                    .get(&contributor.full_key())
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                self.check_redundant_wrapper(&contributor, &child_key_path, &value, siblings);
+            }
+            let value = nest_value(&contributor.nested, value);
+            let key_path_of_key = join_key_path(key_path, &contributor.key);
+            let is_expanded = expanded.contains(&contributor.key);
+            let key = Value::String(contributor.key);
+            match map.get_mut(&key) {
+                Some(existing) if is_expanded => {
+                    let location = self.display_path(&contributor.path);
+                    self.merge_expanded(existing, value, &key_path_of_key, &location);
+                }
+                Some(existing) if self.options.on_collision != CollisionPolicy::Error => {
+                    let location = self.display_path(&contributor.path);
+                    self.merge_value(existing, value, &child_key_path, &location);
+                }
+                _ => {
+                    map.insert(key, value);
+                }
+            }
+        }
+
+        if !ordered.is_empty() {
+            ordered.sort();
+            let mut keys: Vec<String> = Vec::with_capacity(ordered.len());
+            for (_, key) in ordered {
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
+            self.key_orders.push(KeyOrder {
+                path: self.segments.clone(),
+                keys,
+            });
+        }
+
+        map
+    }
+
+    /// Reports a fragment whose only top-level key repeats the key derived from its name, as in
+    /// `database.yml` holding `database: {...}`, which packs to `database.database`.
+    fn check_redundant_wrapper(
+        &mut self,
+        contributor: &Contributor,
+        key_path: &str,
+        value: &Value,
+        siblings: &[PathBuf],
+    ) {
+        let key = contributor.nested.last().unwrap_or(&contributor.key);
+        let Value::Mapping(map) = value else {
+            return;
+        };
+        if map.len() != 1 || map.keys().next().and_then(Value::as_str) != Some(key.as_str()) {
+            return;
+        }
+        let location = self.display_path(&contributor.path);
+        let shared_with = siblings
+            .iter()
+            .filter(|path| **path != contributor.path)
+            .map(|path| self.display_path(path))
+            .collect::<Vec<_>>();
+        let mut diagnostic = Diagnostic::warn(
+            "W020",
+            "fragment's only top-level key repeats its file name",
+        )
+        .with_location(location.clone())
+        .with_derived_key_path(join_key_path(key_path, key))
+        .with_cause(format!(
+            "The file already contributes `{key}`, so its contents are packed one level deeper."
+        ))
+        .with_action(
+            "Remove the wrapper key and outdent its contents, or run `fyaml validate --fix unwrap`.",
+        );
+        if !shared_with.is_empty() {
+            diagnostic = diagnostic.with_context(format!(
+                "Also contributing `{key_path}`: {}",
+                shared_with.join(", ")
+            ));
+        }
+        self.diag(diagnostic);
+        self.explain.wrapped_fragments.push(WrappedFragment {
+            source: location,
+            key: key.clone(),
+            derived_key_path: key_path.to_string(),
+            shared_with,
+        });
+    }
+
+    fn load_contributor_value(
+        &mut self,
+        contributor: &Contributor,
+        key_path: &str,
+        excluded_file: Option<&Path>,
+    ) -> Value {
+        match contributor.kind {
+            ContributorKind::File => self
+                .parse_yaml_file(&contributor.path, key_path)
+                .unwrap_or(Value::Null),
+            ContributorKind::Directory => {
+                self.assemble_directory(&contributor.path, key_path, false, excluded_file)
+            }
+        }
+    }
+
+    /// Whether the entry `name` at `path` is the file the packed document is written to.
+    fn is_output(&self, path: &Path, name: &str) -> bool {
+        self.options.output.as_deref().is_some_and(|output| {
+            output.file_name() == Some(OsStr::new(name)) && self.source.same_entry(path, output)
+        })
+    }
+
+    /// Whether `--accept-binary` matches `path`, by its root-relative path or, for patterns
+    /// without `/`, by its file name.
+    fn is_binary_file(&self, path: &Path) -> bool {
+        if self.options.accept_binary.is_empty() {
+            return false;
+        }
+        let relative = self.display_path(path);
+        let name = relative.rsplit('/').next().unwrap_or_default();
+        self.options.accept_binary.iter().any(|glob| {
+            glob.matches(&relative) || (!glob.as_str().contains('/') && glob.matches(name))
+        })
+    }
+
+    /// Reads a file matched by `--accept-binary` into a `!!binary` base64 scalar. The
+    /// `--max-yaml-bytes` limit applies to the file's size.
+    fn read_binary_file(&mut self, path: &Path, key_path: &str) -> Option<Value> {
+        let file_len = match self.source.file_len(path) {
+            Ok(len) => len,
+            Err(err) => {
                 self.diag(
                     Diagnostic::error(
                         "E033",
@@ -748,41 +2365,117 @@ impl BuildContext {
                 return None;
             }
         };
+        if let Some(max_bytes) = self.options.max_yaml_bytes.filter(|max| file_len > *max) {
+            self.diag(
+                Diagnostic::error(
+                    "E034",
+                    "YAML fragment exceeds max size",
+                    Category::InvalidInput,
+                )
+                .with_location(self.display_path(path))
+                .with_derived_key_path(key_path.to_string())
+                .with_cause(format!(
+                    "Binary file is {file_len} bytes, which exceeds --max-yaml-bytes={max_bytes}."
+                ))
+                .with_action(
+                    "Shrink the file, raise --max-yaml-bytes, or leave it out of --accept-binary.",
+                ),
+            );
+            return None;
+        }
+
+        let bytes = match self.source.read_bytes(path) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                self.diag(
+                    Diagnostic::error("E035", "unable to read YAML file", Category::InvalidInput)
+                        .with_location(self.display_path(path))
+                        .with_cause(err.to_string())
+                        .with_action("Check file permissions and retry."),
+                );
+                return None;
+            }
+        };
+        self.fragments.push(Fragment {
+            path: self.display_path(path),
+            sha256: sha256_hex(&bytes),
+        });
+        let mut value = Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new("!!binary"),
+            value: Value::String(base64(&bytes)),
+        }));
+        if self.options.tags != TagPolicy::Preserve {
+            let errors_before = self.error_count();
+            self.apply_tag_policy(&mut value, path, key_path);
+            if self.error_count() > errors_before {
+                return None;
+            }
+        }
+        Some(value)
+    }
 
-        if let Some(max_bytes) = self.options.max_yaml_bytes {
-            if metadata.len() > max_bytes {
+    fn parse_yaml_file(&mut self, path: &Path, key_path: &str) -> Option<Value> {
+        if self.is_binary_file(path) {
+            return self.read_binary_file(path, key_path);
+        }
+        let (read, parsed) = match self.prefetched.remove(path) {
+            Some(prefetched) => (prefetched.read, prefetched.parsed),
+            None => (
+                self.source.read_fragment(path, self.options.max_yaml_bytes),
+                None,
+            ),
+        };
+        let read = match read {
+            Ok(read) => read,
+            Err(err) => {
                 self.diag(
                     Diagnostic::error(
-                        "E034",
-                        "YAML fragment exceeds max size",
+                        "E033",
+                        "unable to read file metadata",
                         Category::InvalidInput,
                     )
                     .with_location(self.display_path(path))
-                    .with_derived_key_path(key_path.to_string())
-                    .with_cause(format!(
-                        "File size is {} bytes, which exceeds --max-yaml-bytes={max_bytes}.",
-                        metadata.len()
-                    ))
-                    .with_action("Split the fragment or raise --max-yaml-bytes."),
+                    .with_cause(err.to_string())
+                    .with_action("Check file permissions and retry."),
                 );
                 return None;
             }
-        }
+        };
+        let file_len = read.len;
+
+        let Some(contents) = read.contents else {
+            let max_bytes = self.options.max_yaml_bytes.unwrap_or_default();
+            self.diag(
+                Diagnostic::error(
+                    "E034",
+                    "YAML fragment exceeds max size",
+                    Category::InvalidInput,
+                )
+                .with_location(self.display_path(path))
+                .with_derived_key_path(key_path.to_string())
+                .with_cause(format!(
+                    "File size is {} bytes, which exceeds --max-yaml-bytes={max_bytes}.",
+                    file_len
+                ))
+                .with_action("Split the fragment or raise --max-yaml-bytes."),
+            );
+            return None;
+        };
 
-        if metadata.len() > LARGE_FRAGMENT_WARN_BYTES {
+        if file_len > LARGE_FRAGMENT_WARN_BYTES {
             self.diag(
                 Diagnostic::warn("W012", "large YAML fragment detected")
                     .with_location(self.display_path(path))
                     .with_derived_key_path(key_path.to_string())
                     .with_cause(format!(
                         "Fragment is {} bytes; large fragments can reduce reviewability.",
-                        metadata.len()
+                        file_len
                     ))
                     .with_action("Consider splitting this YAML into smaller FYAML fragments."),
             );
         }
 
-        let contents = match fs::read_to_string(path) {
+        let contents = match contents {
             Ok(contents) => contents,
             Err(err) => {
                 self.diag(
@@ -795,6 +2488,103 @@ impl BuildContext {
             }
         };
 
+        let (sha256, parsed) = match parsed {
+            Some((sha256, documents)) => (sha256, Some(documents)),
+            None => (sha256_hex(contents.as_bytes()), None),
+        };
+        self.fragments.push(Fragment {
+            path: self.display_path(path),
+            sha256: sha256.clone(),
+        });
+        if self.options.front_matter && is_markdown_file(path) {
+            return self.parse_markdown(path, key_path, &contents);
+        }
+        if self.options.text_files && is_text_file(path) {
+            return Some(Value::String(contents));
+        }
+        self.preserve(path, &contents);
+        self.parse_contents(path, key_path, &contents, &sha256, parsed)
+    }
+
+    /// Under `--preserve`, records the comments and anchors of the fragment being loaded,
+    /// and for `--source-map` where its nodes start.
+    fn preserve(&mut self, path: &Path, contents: &str) {
+        if !self.options.preserve && !self.options.source_positions {
+            return;
+        }
+        let segments = self.current_segments();
+        let positions = if self.options.preserve {
+            self.preserved.scan(contents, &segments)
+        } else {
+            Preserved::default().scan(contents, &segments)
+        };
+        if self.options.source_positions {
+            let file = path.display().to_string();
+            for (key_path, position) in positions {
+                let location = SourceLocation {
+                    file: file.clone(),
+                    line: position.line,
+                    column: position.column,
+                };
+                self.positions.insert(key_path, location);
+            }
+        }
+    }
+
+    /// Reads a Markdown file under `--front-matter`: its front matter, parsed like a fragment,
+    /// with the body added under `--content-key`.
+    fn parse_markdown(&mut self, path: &Path, key_path: &str, contents: &str) -> Option<Value> {
+        let (front_matter, body) = split_front_matter(contents);
+        let sha256 = sha256_hex(front_matter.as_bytes());
+        let mut map = match self.parse_contents(path, key_path, front_matter, &sha256, None)? {
+            Value::Null => Mapping::new(),
+            Value::Mapping(map) => map,
+            _ => {
+                self.diag(
+                    Diagnostic::error("E104", "front matter is not a mapping", Category::Parse)
+                        .with_location(self.display_path(path))
+                        .with_derived_key_path(key_path.to_string())
+                        .with_cause(format!(
+                            "The body is stored under `{}` in the front matter mapping.",
+                            self.options.content_key
+                        ))
+                        .with_action("Write the front matter as a mapping, or leave it empty."),
+                );
+                return None;
+            }
+        };
+
+        let content_key = Value::String(self.options.content_key.clone());
+        if map.contains_key(&content_key) {
+            self.diag(
+                Diagnostic::error(
+                    "E105",
+                    "front matter already defines the content key",
+                    Category::Parse,
+                )
+                .with_location(self.display_path(path))
+                .with_derived_key_path(join_key_path(key_path, &self.options.content_key))
+                .with_cause("The Markdown body would overwrite the front matter value.")
+                .with_action(
+                    "Rename the front matter key, or pick another key with --content-key.",
+                ),
+            );
+            return None;
+        }
+        map.insert(content_key, Value::String(body.to_string()));
+        Some(Value::Mapping(map))
+    }
+
+    /// Parses fragment text read from `path` (or given inline) and applies the per-fragment
+    /// policies to its documents.
+    fn parse_contents(
+        &mut self,
+        path: &Path,
+        key_path: &str,
+        contents: &str,
+        sha256: &str,
+        parsed: Option<Result<Vec<Value>, CachedParseError>>,
+    ) -> Option<Value> {
         if !self.options.preserve && (contents.contains('&') || contents.contains('*')) {
             self.diag(
                 Diagnostic::warn("W013", "possible YAML anchors/aliases may not be preserved")
@@ -802,36 +2592,83 @@ impl BuildContext {
                     .with_derived_key_path(key_path.to_string())
                     .with_cause("Canonical mode may lose source style and anchor details.")
                     .with_action(
-                        "Use --preserve if supported behavior is acceptable for your workflow.",
+                        "Use --preserve to write anchors, aliases, and comments back into the output.",
                     ),
             );
         }
 
-        let mut documents = Vec::new();
-        for document in serde_yaml::Deserializer::from_str(&contents) {
-            match Value::deserialize(document) {
-                Ok(value) => documents.push(value),
-                Err(err) => {
-                    let mut diag =
-                        Diagnostic::error("E100", "invalid YAML fragment", Category::Parse)
-                            .with_location(self.display_path(path))
-                            .with_derived_key_path(key_path.to_string())
-                            .with_cause(err.to_string())
-                            .with_action("Fix YAML syntax (indentation, colons, and tabs/spaces).")
-                            .with_context("Run `fyaml validate` for full diagnostics.".to_string());
-
-                    if let Some(location) = err.location() {
-                        diag = diag.with_context(format!(
-                            "YAML parser location: line {}, column {}",
-                            location.line(),
-                            location.column()
-                        ));
-                    }
+        let loaded = self
+            .cache
+            .load(sha256, contents, self.options.big_integers, parsed);
 
-                    self.diag(diag);
-                    return None;
+        let loaded = match loaded {
+            Err(err) if self.options.shared_anchors && is_unknown_anchor(&err) => {
+                self.load_with_shared_anchors(path, key_path, contents)?
+            }
+            loaded => loaded,
+        };
+
+        let mut documents = match loaded {
+            Ok(documents) => documents,
+            Err(err) => {
+                let mut diag = Diagnostic::error("E100", "invalid YAML fragment", Category::Parse)
+                    .with_location(self.display_path(path))
+                    .with_derived_key_path(key_path.to_string())
+                    .with_cause(err.message)
+                    .with_action("Fix YAML syntax (indentation, colons, and tabs/spaces).")
+                    .with_context("Run `fyaml validate` for full diagnostics.".to_string());
+
+                if let Some((line, column)) = err.position {
+                    diag = diag.with_position(line, column).with_context(format!(
+                        "YAML parser location: line {line}, column {column}"
+                    ));
+                }
+
+                self.diag(diag);
+                return None;
+            }
+        };
+
+        if self.options.resolve_includes {
+            let errors_before = self.error_count();
+            self.include_stack.push(path.to_path_buf());
+            for document in &mut documents {
+                self.resolve_includes(document, path, key_path);
+            }
+            self.include_stack.pop();
+            if self.error_count() > errors_before {
+                return None;
+            }
+        }
+
+        if self.options.merge_keys != MergeKeys::Keep {
+            let errors_before = self.error_count();
+            for document in &mut documents {
+                if self.options.merge_keys == MergeKeys::Expand {
+                    self.resolve_merge_keys(document, path, key_path);
+                } else {
+                    self.reject_merge_keys(document, path, key_path);
                 }
             }
+            if self.error_count() > errors_before {
+                return None;
+            }
+        }
+
+        if self.options.tags != TagPolicy::Preserve {
+            let errors_before = self.error_count();
+            for document in &mut documents {
+                self.apply_tag_policy(document, path, key_path);
+            }
+            if self.error_count() > errors_before {
+                return None;
+            }
+        }
+
+        if self.options.dates != DatesMode::KeepString {
+            for document in &mut documents {
+                self.apply_dates_policy(document, path, key_path);
+            }
         }
 
         if documents.len() <= 1 {
@@ -872,40 +2709,549 @@ impl BuildContext {
         }
     }
 
-    fn detect_key_collisions(
-        &mut self,
-        directory: &Path,
-        key_path: &str,
-        contributors: &[Contributor],
-    ) {
-        let mut exact: HashMap<String, Vec<&Contributor>> = HashMap::new();
-        let mut case_folded: HashMap<String, Vec<&Contributor>> = HashMap::new();
-
-        for contributor in contributors {
-            exact
-                .entry(contributor.key.clone())
-                .or_default()
-                .push(contributor);
-            case_folded
-                .entry(contributor.key.to_lowercase())
-                .or_default()
-                .push(contributor);
-        }
+    /// Replaces every `!include PATH` value in `value` with the parsed contents of `PATH`,
+    /// relative to the directory of `path`. Included files go through the same parsing
+    /// policies, their own includes too.
+    fn resolve_includes(&mut self, value: &mut Value, path: &Path, key_path: &str) {
+        match value {
+            Value::Tagged(tagged) if tagged.tag == INCLUDE_TAG => {
+                let Value::String(target) = &tagged.value else {
+                    self.diag(
+                        Diagnostic::error(
+                            "E109",
+                            "!include needs a relative file path",
+                            Category::Include,
+                        )
+                        .with_location(self.display_path(path))
+                        .with_derived_key_path(key_path.to_string())
+                        .with_cause("The value tagged `!include` is not a string.")
+                        .with_action("Write the include as `!include path/to/file.yml`.")
+                        .with_context(format!("Key path: {key_path}")),
+                    );
+                    return;
+                };
+                if let Some(included) = self.read_include(path, key_path, target.clone()) {
+                    *value = included;
+                }
+            }
+            Value::Tagged(tagged) => self.resolve_includes(&mut tagged.value, path, key_path),
+            Value::Sequence(items) => {
+                for item in items {
+                    self.resolve_includes(item, path, key_path);
+                }
+            }
+            Value::Mapping(map) => {
+                for (_, item) in map.iter_mut() {
+                    self.resolve_includes(item, path, key_path);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_include(&mut self, path: &Path, key_path: &str, target: String) -> Option<Value> {
+        let base = path
+            .parent()
+            .and_then(|parent| parent.strip_prefix(&self.root).ok())
+            .unwrap_or(Path::new(""));
+        let outside = |cause: String| {
+            Diagnostic::error(
+                "E106",
+                "!include target is outside the FYAML root",
+                Category::Include,
+            )
+            .with_location(self.display_path(path))
+            .with_derived_key_path(key_path.to_string())
+            .with_cause(cause)
+            .with_action(
+                "Include files by a path relative to the including file, inside the input \
+                 directory and not through a symlink.",
+            )
+        };
+        let Some(relative) = confine_include(base, Path::new(&target)) else {
+            let diagnostic = outside(format!(
+                "`{target}` resolves outside the input directory, or is absolute."
+            ));
+            self.diag(diagnostic);
+            return None;
+        };
+        // A symlink could point anywhere, so, as in the tree walk, none is followed.
+        let mut resolved = self.root.clone();
+        for component in relative.components() {
+            resolved.push(component);
+            if self.source.is_symlink(&resolved) {
+                let diagnostic = outside(format!(
+                    "`{target}` passes through the symlink {}, and includes do not follow \
+                     symlinks.",
+                    self.display_path(&resolved)
+                ));
+                self.diag(diagnostic);
+                return None;
+            }
+        }
+
+        if self.include_stack.contains(&resolved) {
+            let chain = self
+                .include_stack
+                .iter()
+                .chain(std::iter::once(&resolved))
+                .map(|file| self.display_path(file))
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            self.diag(
+                Diagnostic::error("E108", "!include cycle", Category::Include)
+                    .with_location(self.display_path(path))
+                    .with_derived_key_path(key_path.to_string())
+                    .with_cause(format!("`{target}` is already being included."))
+                    .with_action("Break the cycle by inlining one of the values.")
+                    .with_context(format!("Include chain: {chain}")),
+            );
+            return None;
+        }
+
+        let read = match self.source.kind(&resolved) {
+            Some(EntryKind::File) => self
+                .source
+                .read_fragment(&resolved, self.options.max_yaml_bytes)
+                .and_then(|read| match read.contents {
+                    Some(contents) => contents,
+                    None => Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "the file is {} bytes, over --max-yaml-bytes={}",
+                            read.len,
+                            self.options.max_yaml_bytes.unwrap_or_default()
+                        ),
+                    )),
+                }),
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the path is not a file",
+            )),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no file exists at this path",
+            )),
+        };
+        let contents = match read {
+            Ok(contents) => contents,
+            Err(err) => {
+                self.diag(
+                    Diagnostic::error("E107", "unable to read !include target", Category::Include)
+                        .with_location(self.display_path(path))
+                        .with_derived_key_path(key_path.to_string())
+                        .with_cause(format!("{}: {err}", self.display_path(&resolved)))
+                        .with_action("Fix the include path or create the file."),
+                );
+                return None;
+            }
+        };
+
+        self.explain.includes.push(IncludeEdge {
+            source: self.display_path(path),
+            target: self.display_path(&resolved),
+            derived_key_path: key_path.to_string(),
+        });
+        let sha256 = sha256_hex(contents.as_bytes());
+        self.parse_contents(&resolved, key_path, &contents, &sha256, None)
+    }
+
+    /// Parses `contents` with its aliases to `_anchors.yml` names replaced by the shared
+    /// values. The parser reports each alias it cannot resolve locally, one at a time, so
+    /// only real alias positions are rewritten. `None` after reporting an unknown alias.
+    fn load_with_shared_anchors(
+        &mut self,
+        path: &Path,
+        key_path: &str,
+        contents: &str,
+    ) -> Option<Result<Vec<Value>, CachedParseError>> {
+        let mut text = contents.to_string();
+        loop {
+            let loaded = parse_documents(&text, self.options.big_integers);
+            let alias = match &loaded {
+                Err(err) if is_unknown_anchor(err) => err
+                    .position
+                    .and_then(|(line, column)| alias_at(&text, line, column)),
+                _ => None,
+            };
+            let Some((offset, name)) = alias else {
+                return Some(loaded.map(|mut documents| {
+                    for document in &mut documents {
+                        splice_shared_aliases(document, &self.shared_anchors);
+                    }
+                    documents
+                }));
+            };
+
+            if !self.shared_anchors.contains_key(&name) {
+                let (line, column) = position_of(&text, offset);
+                let mut known = self.shared_anchors.keys().cloned().collect::<Vec<_>>();
+                known.sort();
+                let known = if known.is_empty() {
+                    format!("No anchors are defined in {ANCHORS_FILE_NAME}.")
+                } else {
+                    format!("Shared anchors: {}", known.join(", "))
+                };
+                self.diag(
+                    Diagnostic::error("E122", "unknown alias", Category::Parse)
+                        .with_location(self.display_path(path))
+                        .with_derived_key_path(key_path.to_string())
+                        .with_position(line, column)
+                        .with_cause(format!(
+                            "`*{name}` names no anchor in this fragment or in {ANCHORS_FILE_NAME}."
+                        ))
+                        .with_action(format!(
+                            "Define `{name}` in {ANCHORS_FILE_NAME}, or fix the alias name."
+                        ))
+                        .with_context(known),
+                );
+                return None;
+            }
+            text.replace_range(
+                offset..offset + 1 + name.len(),
+                &format!("{SHARED_ALIAS_TAG} {name}"),
+            );
+        }
+    }
+
+    /// Expands `<<` merge keys: the entries of the merged mapping, or of each mapping in a
+    /// merged sequence, are added unless an explicit key or an earlier merged mapping already
+    /// has them.
+    fn resolve_merge_keys(&mut self, value: &mut Value, path: &Path, key_path: &str) {
+        match value {
+            Value::Mapping(map) => {
+                let mut explicit = Mapping::new();
+                let mut sources = Vec::new();
+                for (key, item) in std::mem::take(map) {
+                    if key.as_str() == Some(MERGE_KEY) {
+                        sources.push(item);
+                    } else {
+                        explicit.insert(key, item);
+                    }
+                }
+
+                let mut merged = explicit.clone();
+                for source in sources {
+                    let mappings = match source {
+                        Value::Sequence(items) => items,
+                        other => vec![other],
+                    };
+                    for mut mapping in mappings {
+                        self.resolve_merge_keys(&mut mapping, path, key_path);
+                        let Value::Mapping(mapping) = mapping else {
+                            self.diag(
+                                Diagnostic::error(
+                                    "E123",
+                                    "merge key value is not a mapping",
+                                    Category::Parse,
+                                )
+                                .with_location(self.display_path(path))
+                                .with_derived_key_path(key_path.to_string())
+                                .with_cause(format!(
+                                    "`<<` must name a mapping or a sequence of mappings; found a {}.",
+                                    value_kind(&mapping)
+                                ))
+                                .with_action("Point `<<` at an anchored mapping, as in `<<: *base`.")
+                                .with_context(format!(
+                                    "Key path: {}",
+                                    if key_path.is_empty() { "." } else { key_path }
+                                )),
+                            );
+                            continue;
+                        };
+                        for (key, item) in mapping {
+                            match explicit.get(&key) {
+                                Some(own) if *own != item => {
+                                    let child_path = join_key_path(key_path, &key_as_string(&key));
+                                    self.diag(
+                                        Diagnostic::warn(
+                                            "W024",
+                                            "explicit key overrides a merged value",
+                                        )
+                                        .with_location(self.display_path(path))
+                                        .with_derived_key_path(child_path.clone())
+                                        .with_cause(
+                                            "The mapping sets the key itself and also merges a different value for it with `<<`; the explicit value wins.",
+                                        )
+                                        .with_action(
+                                            "Remove one of the two values if the override is not intended.",
+                                        )
+                                        .with_context(format!("Key path: {child_path}")),
+                                    );
+                                }
+                                _ => {}
+                            }
+                            if !merged.contains_key(&key) {
+                                merged.insert(key, item);
+                            }
+                        }
+                    }
+                }
+
+                for (key, child) in merged.iter_mut() {
+                    let child_path = join_key_path(key_path, &key_as_string(key));
+                    self.resolve_merge_keys(child, path, &child_path);
+                }
+                *map = merged;
+            }
+            Value::Sequence(items) => {
+                for (index, child) in items.iter_mut().enumerate() {
+                    self.resolve_merge_keys(child, path, &format!("{key_path}[{index}]"));
+                }
+            }
+            Value::Tagged(tagged) => self.resolve_merge_keys(&mut tagged.value, path, key_path),
+            _ => {}
+        }
+    }
+
+    /// Reports every `<<` key under `--merge-keys error`.
+    fn reject_merge_keys(&mut self, value: &Value, path: &Path, key_path: &str) {
+        match value {
+            Value::Mapping(map) => {
+                for (key, child) in map {
+                    let child_path = join_key_path(key_path, &key_as_string(key));
+                    if key.as_str() == Some(MERGE_KEY) {
+                        self.diag(
+                            Diagnostic::error(
+                                "E135",
+                                "merge key is not allowed",
+                                Category::Parse,
+                            )
+                            .with_location(self.display_path(path))
+                            .with_derived_key_path(child_path)
+                            .with_cause("Found a `<<` merge key; --merge-keys=error rejects them.")
+                            .with_action(
+                                "Write the merged entries out, or use --merge-keys=expand or --merge-keys=keep.",
+                            ),
+                        );
+                        continue;
+                    }
+                    self.reject_merge_keys(child, path, &child_path);
+                }
+            }
+            Value::Sequence(items) => {
+                for (index, child) in items.iter().enumerate() {
+                    self.reject_merge_keys(child, path, &format!("{key_path}[{index}]"));
+                }
+            }
+            Value::Tagged(tagged) => self.reject_merge_keys(&tagged.value, path, key_path),
+            _ => {}
+        }
+    }
+
+    fn error_count(&self) -> usize {
+        self.diagnostics.iter().filter(|d| d.is_error()).count()
+    }
+
+    /// Strips tags from, or reports tags on, every value in a loaded document.
+    fn apply_tag_policy(&mut self, value: &mut Value, path: &Path, key_path: &str) {
+        if let Value::Tagged(tagged) = value {
+            if self.options.tags == TagPolicy::Error {
+                self.diag(
+                    Diagnostic::error("E102", "tagged value is not allowed", Category::Parse)
+                        .with_location(self.display_path(path))
+                        .with_derived_key_path(key_path.to_string())
+                        .with_cause(format!(
+                            "Found tag `{}`; --tags=error rejects YAML tags.",
+                            tagged.tag
+                        ))
+                        .with_action("Remove the tag, or use --tags=preserve or --tags=strip."),
+                );
+            }
+            let inner = std::mem::take(&mut tagged.value);
+            *value = inner;
+            self.apply_tag_policy(value, path, key_path);
+            return;
+        }
+
+        match value {
+            Value::Mapping(map) => {
+                let entries = std::mem::take(map);
+                for (mut key, mut child) in entries {
+                    self.apply_tag_policy(&mut key, path, key_path);
+                    let child_path = join_key_path(key_path, &key_as_string(&key));
+                    self.apply_tag_policy(&mut child, path, &child_path);
+                    if map.contains_key(&key) {
+                        self.diag(
+                            Diagnostic::error(
+                                "E103",
+                                "stripping tags produced a duplicate mapping key",
+                                Category::Parse,
+                            )
+                            .with_location(self.display_path(path))
+                            .with_derived_key_path(child_path)
+                            .with_cause("Two keys differ only by their YAML tags.")
+                            .with_action("Rename one of the keys, or use --tags=preserve."),
+                        );
+                        continue;
+                    }
+                    map.insert(key, child);
+                }
+            }
+            Value::Sequence(items) => {
+                for (index, child) in items.iter_mut().enumerate() {
+                    self.apply_tag_policy(child, path, &format!("{key_path}[{index}]"));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Tags or normalizes timestamp-looking string values, warning about naive datetimes.
+    ///
+    /// Mapping keys are left alone so that normalization cannot merge distinct keys.
+    fn apply_dates_policy(&mut self, value: &mut Value, path: &Path, key_path: &str) {
+        match value {
+            Value::Mapping(map) => {
+                for (key, child) in map.iter_mut() {
+                    let child_path = join_key_path(key_path, &key_as_string(key));
+                    self.apply_dates_policy(child, path, &child_path);
+                }
+            }
+            Value::Sequence(items) => {
+                for (index, child) in items.iter_mut().enumerate() {
+                    self.apply_dates_policy(child, path, &format!("{key_path}[{index}]"));
+                }
+            }
+            Value::String(text) => {
+                let Some(timestamp) = Timestamp::parse(text) else {
+                    return;
+                };
+                if timestamp.is_naive() {
+                    self.diag(
+                        Diagnostic::warn("W015", "naive datetime has no UTC offset")
+                            .with_location(self.display_path(path))
+                            .with_derived_key_path(key_path.to_string())
+                            .with_cause(format!(
+                                "`{text}` has no offset; consumers disagree on whether it is local time or UTC."
+                            ))
+                            .with_action("Add an explicit offset such as `Z` or `+02:00`."),
+                    );
+                }
+                match self.options.dates {
+                    DatesMode::KeepString => {}
+                    DatesMode::Tag => {
+                        *value = Value::Tagged(Box::new(TaggedValue {
+                            tag: Tag::new(TIMESTAMP_TAG),
+                            value: Value::String(std::mem::take(text)),
+                        }));
+                    }
+                    DatesMode::Parse => {
+                        if let Some(utc) = timestamp.to_utc_string() {
+                            *text = utc;
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Reports or resolves files that differ only by a `.yml` or `.yaml` extension, following
+    /// `--extension-conflict`, and drops the file that does not contribute.
+    fn resolve_extension_conflicts(
+        &mut self,
+        directory: &Path,
+        key_path: &str,
+        contributors: &mut Vec<Contributor>,
+    ) {
+        let extension = |contributor: &Contributor| match contributor.kind {
+            ContributorKind::File => yaml_extension(&contributor.path),
+            ContributorKind::Directory => None,
+        };
+        let pairs = contributors
+            .iter()
+            .filter(|c| extension(c).as_deref() == Some("yml"))
+            .filter_map(|yml| {
+                contributors
+                    .iter()
+                    .find(|c| c.key == yml.key && extension(c).as_deref() == Some("yaml"))
+                    .map(|yaml| (yml.path.clone(), yaml.path.clone()))
+            })
+            .collect::<Vec<_>>();
+
+        for (yml, yaml) in pairs {
+            let (kept, dropped) = match self.options.extension_conflict {
+                ExtensionConflict::Error => {
+                    let paths = vec![self.display_path(&yml), self.display_path(&yaml)];
+                    let key = contributors
+                        .iter()
+                        .find(|c| c.path == yml)
+                        .map_or("", |c| c.key.as_str());
+                    self.diag(
+                        Diagnostic::error(
+                            "E006",
+                            "same key from .yml and .yaml files",
+                            Category::InvalidInput,
+                        )
+                        .with_location(self.display_path(directory))
+                        .with_derived_key_path(join_key_path(key_path, key))
+                        .with_paths(paths.clone())
+                        .with_cause("Both files derive the same key and differ only by extension.")
+                        .with_action(
+                            "Delete one of them, or pass --extension-conflict prefer-yml or prefer-yaml.",
+                        )
+                        .with_context(format!("Sources: {}", paths.join(", "))),
+                    );
+                    (None, yaml)
+                }
+                ExtensionConflict::PreferYml => (Some(yml), yaml),
+                ExtensionConflict::PreferYaml => (Some(yaml), yml),
+            };
+            if let Some(kept) = kept {
+                let rule = format!(
+                    "{SHADOWED_IGNORE_PREFIX}{} (--extension-conflict)",
+                    self.display_path(&kept)
+                );
+                self.add_ignored(&dropped, &rule);
+            }
+            contributors.retain(|c| c.path != dropped);
+        }
+    }
+
+    fn detect_key_collisions(
+        &mut self,
+        directory: &Path,
+        key_path: &str,
+        contributors: &[Contributor],
+    ) {
+        let mut exact: HashMap<&str, Vec<&Contributor>> = HashMap::new();
+        // Folded by on-disk name: entries renamed through the keymap keep distinct names
+        // even when their keys differ only by case.
+        let mut case_folded: HashMap<String, Vec<(&Contributor, String)>> = HashMap::new();
+
+        for contributor in contributors {
+            exact
+                .entry(contributor.key.as_str())
+                .or_default()
+                .push(contributor);
+            let name = contributor.entry_name();
+            case_folded
+                .entry(name.to_lowercase())
+                .or_default()
+                .push((contributor, name));
+        }
 
         for (key, entries) in exact {
-            if entries.len() > 1 {
+            // Other policies, and expanded dotted keys, resolve the collision while the values
+            // are assembled.
+            let expanded = entries.iter().any(|entry| !entry.nested.is_empty());
+            if entries.len() > 1 && self.options.on_collision == CollisionPolicy::Error && !expanded
+            {
                 let paths = entries
                     .iter()
                     .map(|entry| self.display_path(&entry.path))
                     .collect::<Vec<_>>();
+                let suggestions = self.collision_suggestions(&entries, contributors, "base");
                 self.diag(
                     Diagnostic::error("E001", "key collision detected", Category::InvalidInput)
                         .with_location(self.display_path(directory))
-                        .with_derived_key_path(join_key_path(key_path, &key))
+                        .with_derived_key_path(join_key_path(key_path, key))
                         .with_paths(paths.clone())
                         .with_cause("Multiple inputs resolve to the same FYAML key.")
                         .with_action("Rename one source or move it into a different directory.")
-                        .with_context(format!("Sources: {}", paths.join(", "))),
+                        .with_context(format!("Sources: {}", paths.join(", ")))
+                        .with_suggestions(suggestions),
                 );
             }
         }
@@ -914,14 +3260,16 @@ impl BuildContext {
             if entries.len() > 1 {
                 let unique = entries
                     .iter()
-                    .map(|entry| entry.key.as_str())
+                    .map(|(_, name)| name.as_str())
                     .collect::<HashSet<_>>();
                 if unique.len() > 1 {
-                    let example_key = entries.first().map(|e| e.key.clone()).unwrap_or_default();
+                    let example_key = entries.first().map_or("", |(e, _)| e.key.as_str());
                     let paths = entries
                         .iter()
-                        .map(|entry| self.display_path(&entry.path))
+                        .map(|(entry, _)| self.display_path(&entry.path))
                         .collect::<Vec<_>>();
+                    let colliding = entries.iter().map(|(entry, _)| *entry).collect::<Vec<_>>();
+                    let suggestions = self.collision_suggestions(&colliding, contributors, "2");
                     self.diag(
                         Diagnostic::error(
                             "E004",
@@ -929,18 +3277,161 @@ impl BuildContext {
                             Category::InvalidInput,
                         )
                         .with_location(self.display_path(directory))
-                        .with_derived_key_path(join_key_path(key_path, &example_key))
+                        .with_derived_key_path(join_key_path(key_path, example_key))
                         .with_paths(paths.clone())
                         .with_cause(
                             "Case-insensitive filesystems can make these keys indistinguishable.",
                         )
                         .with_action("Rename keys so they are distinct even after lowercasing.")
-                        .with_context(format!("Sources: {}", paths.join(", "))),
+                        .with_context(format!("Sources: {}", paths.join(", ")))
+                        .with_suggestions(suggestions),
                     );
                 }
             }
         }
     }
+
+    /// Ways to resolve a collision between `entries`, best first. Moving a file into the
+    /// directory it collides with as its index file keeps every key path, so it comes first
+    /// under `--index-files` and last without it. Then each entry is kept in turn while the
+    /// others are renamed to their clean name plus a `-<suffix>` that no entry of the
+    /// directory uses, even after lowercasing. Directories are kept first, then entries whose
+    /// names have the fewest [`Self::name_oddities`], so odd names are what gets renamed.
+    fn collision_suggestions(
+        &self,
+        entries: &[&Contributor],
+        contributors: &[Contributor],
+        suffix: &str,
+    ) -> Vec<Suggestion> {
+        let names = contributors
+            .iter()
+            .map(|contributor| contributor.entry_name().to_lowercase())
+            .collect::<HashSet<_>>();
+        let rename = |entry: &Contributor, taken: &mut HashSet<String>| {
+            let stem = entry.entry_name();
+            let candidate = suffix_candidates(suffix)
+                .map(|suffix| format!("{stem}-{suffix}"))
+                .find(|candidate| !taken.contains(&candidate.to_lowercase()))
+                .expect("only finitely many names are taken");
+            taken.insert(candidate.to_lowercase());
+            let name = match entry.kind {
+                ContributorKind::File => format!("{candidate}.{}", self.clean_extension(entry)),
+                ContributorKind::Directory => candidate,
+            };
+            SuggestedEdit::Rename {
+                from: self.display_path(&entry.path),
+                to: self.display_path(&entry.path.with_file_name(name)),
+            }
+        };
+
+        let mut keep = (0..entries.len()).collect::<Vec<_>>();
+        keep.sort_by_key(|&index| {
+            let entry = entries[index];
+            (
+                matches!(entry.kind, ContributorKind::File),
+                self.name_oddities(entry),
+            )
+        });
+        let mut suggestions = keep
+            .into_iter()
+            .map(|kept| {
+                let mut taken = names.clone();
+                let edits = entries
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| *index != kept)
+                    .map(|(_, entry)| rename(entry, &mut taken))
+                    .collect::<Vec<_>>();
+                let title = edits
+                    .iter()
+                    .map(|SuggestedEdit::Rename { from, to }| format!("rename {from} to {to}"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                Suggestion { title, edits }
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(index_move) = self.index_file_move(entries) {
+            if self.options.index_files {
+                suggestions.insert(0, index_move);
+            } else {
+                suggestions.push(index_move);
+            }
+        }
+        suggestions
+    }
+
+    /// The YAML extension a file among colliding entries is given when renamed: the one
+    /// `--require-extension` asks for, or else its own in lowercase.
+    fn clean_extension(&self, entry: &Contributor) -> String {
+        match self.options.require_extension {
+            Some(required) => required.as_str().to_string(),
+            None => yaml_extension(&entry.path).unwrap_or_else(|| "yml".to_string()),
+        }
+    }
+
+    /// How far `entry`'s name is from a clean one: one each for trailing whitespace or dots,
+    /// a YAML extension that is not lowercase, and one other than `--require-extension`.
+    fn name_oddities(&self, entry: &Contributor) -> usize {
+        let name = entry
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if matches!(entry.kind, ContributorKind::Directory) {
+            return usize::from(name != entry.entry_name());
+        }
+        let extension = Path::new(trimmed_entry_name(&name))
+            .extension()
+            .map(|extension| extension.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let lowercase = extension.to_ascii_lowercase();
+        usize::from(name != format!("{}.{extension}", entry.entry_name()))
+            + usize::from(extension != lowercase)
+            + usize::from(
+                self.options
+                    .require_extension
+                    .is_some_and(|required| required.as_str() != lowercase),
+            )
+    }
+
+    /// Moving the one file among `entries` into the one directory as its `_` index file, if
+    /// the directory has no index file yet.
+    fn index_file_move(&self, entries: &[&Contributor]) -> Option<Suggestion> {
+        let [first, second] = entries else {
+            return None;
+        };
+        let (file, directory) = match (first.kind, second.kind) {
+            (ContributorKind::File, ContributorKind::Directory) => (first, second),
+            (ContributorKind::Directory, ContributorKind::File) => (second, first),
+            _ => return None,
+        };
+        let name = file.path.file_name()?.to_string_lossy().into_owned();
+        let extension = name.rsplit_once('.')?.1;
+        let has_index = INDEX_FILE_STEMS.iter().any(|stem| {
+            ["yml", "yaml"].iter().any(|yaml| {
+                self.source
+                    .kind(&directory.path.join(format!("{stem}.{yaml}")))
+                    == Some(EntryKind::File)
+            })
+        });
+        if has_index {
+            return None;
+        }
+        let edit = SuggestedEdit::Rename {
+            from: self.display_path(&file.path),
+            to: self.display_path(&directory.path.join(format!("_.{extension}"))),
+        };
+        let SuggestedEdit::Rename { from, to } = &edit;
+        let mut title = format!("move {from} into {to}");
+        if !self.options.index_files {
+            title.push_str(" and pack with --index-files");
+        }
+        Some(Suggestion {
+            title,
+            edits: vec![edit],
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -948,6 +3439,29 @@ struct Contributor {
     key: String,
     path: PathBuf,
     kind: ContributorKind,
+    /// Position from an `NN-` name prefix under `--ordered-prefixes`.
+    order: Option<u64>,
+    /// Keys below `key` from a dotted file name under `--expand-dotted-keys`, outermost first.
+    nested: Vec<String>,
+}
+
+impl Contributor {
+    /// The key path this contributor's value lands at, relative to its directory.
+    fn full_key(&self) -> String {
+        self.nested
+            .iter()
+            .fold(self.key.clone(), |path, key| join_key_path(&path, key))
+    }
+
+    /// The file or directory name this contributor was read from, without YAML extension.
+    fn entry_name(&self) -> String {
+        let name = self
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        normalize_entry_name(&name, matches!(self.kind, ContributorKind::File)).0
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -962,6 +3476,35 @@ enum DirectoryAssemblyMode {
     Sequence,
 }
 
+/// Splits a dotted key into segments for `--expand-dotted-keys`. Keys without dots, and keys
+/// whose first segment is numeric (sequence items), are not expanded.
+fn split_dotted_key(key: &str) -> Option<Vec<String>> {
+    let (first, _) = key.split_once('.')?;
+    if is_numeric_key(first) {
+        return None;
+    }
+    Some(key.split('.').map(str::to_string).collect())
+}
+
+/// Wraps `value` in one single-key mapping per entry of `keys`, innermost last.
+fn nest_value(keys: &[String], value: Value) -> Value {
+    keys.iter().rev().fold(value, |value, key| {
+        let mut map = Mapping::new();
+        map.insert(Value::String(key.clone()), value);
+        Value::Mapping(map)
+    })
+}
+
+/// Splits a leading `NN-` prefix from `name`, if the rest is not empty.
+fn split_order_prefix(name: &str) -> Option<(u64, &str)> {
+    let digits = name.bytes().take_while(u8::is_ascii_digit).count();
+    let rest = name[digits..].strip_prefix('-')?;
+    if digits == 0 || rest.is_empty() {
+        return None;
+    }
+    Some((name[..digits].parse().ok()?, rest))
+}
+
 fn join_key_path(parent: &str, child: &str) -> String {
     if parent.is_empty() {
         child.to_string()
@@ -970,40 +3513,251 @@ fn join_key_path(parent: &str, child: &str) -> String {
     }
 }
 
-fn is_yaml_file(path: &Path) -> bool {
-    matches!(
-        path.extension().and_then(OsStr::to_str).map(|s| s.to_ascii_lowercase()),
-        Some(ext) if ext == "yml" || ext == "yaml"
+fn shared_anchors_error(location: String, cause: String) -> Diagnostic {
+    Diagnostic::error(
+        "E121",
+        "unable to load shared anchors file",
+        Category::InvalidInput,
     )
+    .with_location(location)
+    .with_cause(cause)
+    .with_action(format!(
+        "Make {ANCHORS_FILE_NAME} a mapping of anchor names to values."
+    ))
 }
 
-fn strip_yaml_extension(name: &str) -> String {
-    let lower = name.to_ascii_lowercase();
-    if lower.ends_with(".yaml") {
-        name[..name.len() - 5].to_string()
-    } else if lower.ends_with(".yml") {
-        name[..name.len() - 4].to_string()
-    } else {
-        name.to_string()
-    }
+fn is_unknown_anchor(err: &CachedParseError) -> bool {
+    err.message.starts_with("unknown anchor")
 }
 
-fn is_hidden_name(name: &str) -> bool {
-    name.starts_with('.')
+/// Rename suffixes to try in turn: `suffix`, `suffix-2`, `suffix-3`, and so on, or for a
+/// numeric `suffix` the numbers counting up from it.
+fn suffix_candidates(suffix: &str) -> impl Iterator<Item = String> + '_ {
+    let start = suffix.parse::<u64>().ok();
+    (0..).map(move |step| match start {
+        Some(start) => (start + step).to_string(),
+        None if step == 0 => suffix.to_string(),
+        None => format!("{suffix}-{}", step + 1),
+    })
 }
 
-fn is_editor_junk(name: &str) -> bool {
-    name == ".DS_Store" || name.ends_with('~')
+/// The byte offset and name of the `*alias` at a 1-based parser position in `text`.
+fn alias_at(text: &str, line: usize, column: usize) -> Option<(usize, String)> {
+    let line_start = if line <= 1 {
+        0
+    } else {
+        text.match_indices('\n').nth(line - 2)?.0 + 1
+    };
+    let (offset, _) = text[line_start..]
+        .char_indices()
+        .nth(column.checked_sub(1)?)?;
+    let offset = line_start + offset;
+    let name = text[offset..]
+        .strip_prefix('*')?
+        .chars()
+        .take_while(|c| !c.is_whitespace() && !matches!(c, ',' | '[' | ']' | '{' | '}'))
+        .collect::<String>();
+    (!name.is_empty()).then_some((offset, name))
 }
 
-fn is_numeric_key(key: &str) -> bool {
-    !key.is_empty() && key.as_bytes().iter().all(|b| b.is_ascii_digit())
+/// The 1-based line and column of a byte offset in `text`.
+fn position_of(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
 }
 
-fn is_reserved_yaml_key(key: &str) -> bool {
-    RESERVED_YAML_KEYS
-        .iter()
-        .any(|reserved| reserved.eq_ignore_ascii_case(key))
+/// Replaces the stand-ins left by [`BuildContext::load_with_shared_anchors`] with the
+/// shared values, in mapping keys too.
+fn splice_shared_aliases(value: &mut Value, anchors: &HashMap<String, Value>) {
+    match value {
+        Value::Tagged(tagged) if tagged.tag == SHARED_ALIAS_TAG => {
+            if let Value::String(name) = &tagged.value {
+                if let Some(shared) = anchors.get(name) {
+                    *value = shared.clone();
+                }
+            }
+        }
+        Value::Tagged(tagged) => splice_shared_aliases(&mut tagged.value, anchors),
+        Value::Sequence(items) => {
+            for item in items {
+                splice_shared_aliases(item, anchors);
+            }
+        }
+        Value::Mapping(map) => {
+            *map = std::mem::take(map)
+                .into_iter()
+                .map(|(mut key, mut item)| {
+                    splice_shared_aliases(&mut key, anchors);
+                    splice_shared_aliases(&mut item, anchors);
+                    (key, item)
+                })
+                .collect();
+        }
+        _ => {}
+    }
+}
+
+/// Joins an `!include` path onto `base` (both relative to the root), or `None` when the
+/// result is absolute or climbs above the root.
+fn confine_include(base: &Path, target: &Path) -> Option<PathBuf> {
+    let mut resolved = PathBuf::new();
+    for component in base.components().chain(target.components()) {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(resolved)
+}
+
+pub fn is_yaml_file(path: &Path) -> bool {
+    yaml_extension(path).is_some()
+}
+
+/// The lowercase YAML extension (`yml` or `yaml`) of `path`, if it has one, ignoring
+/// trailing whitespace and dots.
+fn yaml_extension(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_string_lossy();
+    Path::new(trimmed_entry_name(&name))
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_ascii_lowercase)
+        .filter(|ext| ext == "yml" || ext == "yaml")
+}
+
+/// Whether `path` ends in `.txt` or `.md`, read as a string under `--text-files`.
+fn is_text_file(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| text_extension_len(trimmed_entry_name(&name.to_string_lossy())) > 0)
+        .unwrap_or(false)
+}
+
+/// Whether `path` ends in `.md`, read for its front matter under `--front-matter`.
+fn is_markdown_file(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| {
+            let name = trimmed_entry_name(&name.to_string_lossy()).to_ascii_lowercase();
+            name.len() > ".md".len() && name.ends_with(".md")
+        })
+        .unwrap_or(false)
+}
+
+/// Splits Markdown into its front matter, between a leading `---` line and the next `---`
+/// or `...` line, and the body after it. Text without front matter is all body.
+fn split_front_matter(contents: &str) -> (&str, &str) {
+    let Some(rest) = contents
+        .strip_prefix("---\n")
+        .or_else(|| contents.strip_prefix("---\r\n"))
+    else {
+        return ("", contents);
+    };
+    let mut end = 0;
+    for line in rest.split_inclusive('\n') {
+        if matches!(line.trim_end(), "---" | "...") {
+            return (&rest[..end], &rest[end + line.len()..]);
+        }
+        end += line.len();
+    }
+    ("", contents)
+}
+
+/// Length of a `.txt` or `.md` extension (with its dot) at the end of `name`, or 0.
+fn text_extension_len(name: &str) -> usize {
+    let lower = name.to_ascii_lowercase();
+    TEXT_EXTENSIONS
+        .iter()
+        .find(|ext| lower.ends_with(*ext) && lower.len() > ext.len())
+        .map_or(0, |ext| ext.len())
+}
+
+pub fn strip_yaml_extension(name: &str) -> String {
+    let lower = name.to_ascii_lowercase();
+    if lower.ends_with(".yaml") {
+        name[..name.len() - 5].to_string()
+    } else if lower.ends_with(".yml") {
+        name[..name.len() - 4].to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+fn is_trailing_junk(c: char) -> bool {
+    c.is_whitespace() || c == '.'
+}
+
+/// `name` without trailing whitespace and dots, unless nothing else would remain.
+fn trimmed_entry_name(name: &str) -> &str {
+    match name.trim_end_matches(is_trailing_junk) {
+        "" => name,
+        trimmed => trimmed,
+    }
+}
+
+/// The name a key is derived from: trailing whitespace and dots are removed, and for a file
+/// the YAML (or `.txt`/`.md`) extension too, then trailing whitespace and dots before it. Also returns each
+/// removed run with where it was.
+fn normalize_entry_name(name: &str, file: bool) -> (String, Vec<(String, &'static str)>) {
+    let mut removed = Vec::new();
+    let trimmed = trimmed_entry_name(name);
+    if trimmed.len() < name.len() {
+        removed.push((name[trimmed.len()..].to_string(), "at the end of the name"));
+    }
+    if !file {
+        return (trimmed.to_string(), removed);
+    }
+    let stem = match text_extension_len(trimmed) {
+        0 => strip_yaml_extension(trimmed),
+        len => trimmed[..trimmed.len() - len].to_string(),
+    };
+    let stem_trimmed = stem.trim_end_matches(is_trailing_junk);
+    if stem_trimmed.len() < stem.len() {
+        removed.insert(
+            0,
+            (
+                stem[stem_trimmed.len()..].to_string(),
+                "before the extension",
+            ),
+        );
+    }
+    (stem_trimmed.to_string(), removed)
+}
+
+/// Renders `text` quoted, with its UTF-8 bytes, e.g. `" ." (0x20 0x2E)`.
+fn describe_bytes(text: &str) -> String {
+    let bytes = text
+        .bytes()
+        .map(|byte| format!("0x{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("{text:?} ({bytes})")
+}
+
+fn is_hidden_name(name: &str) -> bool {
+    name.starts_with('.')
+}
+
+fn is_editor_junk(name: &str) -> bool {
+    name == ".DS_Store" || name.ends_with('~')
+}
+
+fn is_numeric_key(key: &str) -> bool {
+    !key.is_empty() && key.as_bytes().iter().all(|b| b.is_ascii_digit())
+}
+
+fn is_reserved_yaml_key(key: &str) -> bool {
+    RESERVED_YAML_KEYS
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(key))
 }
 
 fn key_as_string(key: &Value) -> String {
@@ -1016,7 +3770,7 @@ fn key_as_string(key: &Value) -> String {
     }
 }
 
-fn value_kind(value: &Value) -> &'static str {
+pub(crate) fn value_kind(value: &Value) -> &'static str {
     match value {
         Value::Null => "null",
         Value::Bool(_) => "bool",
@@ -1031,6 +3785,8 @@ fn value_kind(value: &Value) -> &'static str {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::YamlExtension;
+    use crate::serializer::canonicalize_yaml;
     use std::fs;
     use tempfile::tempdir;
 
@@ -1102,4 +3858,1003 @@ mod tests {
         let outcome = build(dir.path(), &options);
         assert!(outcome.diagnostics.iter().any(|d| d.code == "E001"));
     }
+
+    #[test]
+    fn collisions_suggest_renames_to_unused_names() {
+        let dir = tempdir().expect("temp dir");
+        write(&dir.path().join("Readme.yml"), "a\n");
+        write(&dir.path().join("README.yml"), "b\n");
+        write(&dir.path().join("readme-2.yml"), "c\n");
+
+        let outcome = build(dir.path(), &BuildOptions::default());
+        let collision = outcome
+            .diagnostics
+            .iter()
+            .find(|d| d.code == "E004")
+            .expect("E004");
+        let titles = collision
+            .suggestions
+            .iter()
+            .map(|s| s.title.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            titles,
+            vec![
+                "rename Readme.yml to Readme-3.yml",
+                "rename README.yml to README-3.yml",
+            ]
+        );
+        assert_eq!(
+            collision.suggestions[0].edits,
+            vec![SuggestedEdit::Rename {
+                from: "Readme.yml".to_string(),
+                to: "Readme-3.yml".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn collisions_keep_the_clean_name_and_rename_the_odd_one() {
+        let dir = tempdir().expect("temp dir");
+        write(&dir.path().join("auth.yml"), "a\n");
+        write(&dir.path().join("auth .yml"), "b\n");
+        write(&dir.path().join("auth.yml."), "c\n");
+
+        let outcome = build(dir.path(), &BuildOptions::default());
+        let collision = outcome
+            .diagnostics
+            .iter()
+            .find(|d| d.code == "E001")
+            .expect("E001");
+        assert_eq!(
+            collision.suggestions[0].title,
+            "rename auth .yml to auth-base.yml, rename auth.yml. to auth-base-2.yml"
+        );
+        assert!(collision.suggestions[1..]
+            .iter()
+            .all(|suggestion| suggestion.title.contains("rename auth.yml to auth-base")));
+    }
+
+    #[test]
+    fn index_files_merge_into_their_directory() {
+        let dir = tempdir().expect("temp dir");
+        write(
+            &dir.path().join("db/_.yml"),
+            "host: localhost\nport: 5432\n",
+        );
+        write(&dir.path().join("db/replicas/0.yml"), "a\n");
+        write(&dir.path().join("db/port.yml"), "6432\n");
+        write(&dir.path().join("index.yml"), "name: demo\n");
+
+        let options = BuildOptions {
+            index_files: true,
+            ..BuildOptions::default()
+        };
+        let outcome = build(dir.path(), &options);
+        let collisions = outcome
+            .diagnostics
+            .iter()
+            .filter(|d| d.code == "E001")
+            .map(|d| d.derived_key_path.clone().unwrap_or_default())
+            .collect::<Vec<_>>();
+        assert_eq!(collisions, vec!["db.port"]);
+
+        let expected: Value = serde_yaml::from_str(
+            "db:\n  port: 6432\n  replicas: [a]\n  host: localhost\nname: demo\n",
+        )
+        .expect("expected");
+        assert_eq!(outcome.value, Some(expected));
+
+        let outcome = build(dir.path(), &BuildOptions::default());
+        let root = outcome.value.expect("value exists");
+        assert!(root.get("index").is_some());
+    }
+
+    #[test]
+    fn collision_policy_picks_or_deep_merges_values() {
+        let dir = tempdir().expect("temp dir");
+        write(&dir.path().join("app/db/host.yml"), "a\n");
+        write(&dir.path().join("app/db/port.yml"), "1\n");
+        write(&dir.path().join("app/name.yml"), "x\n");
+        write(&dir.path().join("app.yml"), "db:\n  port: 2\n  user: u\n");
+
+        let with_policy = |on_collision| BuildOptions {
+            on_collision,
+            ..BuildOptions::default()
+        };
+        let codes = |outcome: &BuildOutcome| {
+            outcome
+                .diagnostics
+                .iter()
+                .map(|d| {
+                    (
+                        d.code.clone(),
+                        d.derived_key_path.clone().unwrap_or_default(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let outcome = build(dir.path(), &with_policy(CollisionPolicy::FirstWins));
+        let expected: Value =
+            serde_yaml::from_str("app:\n  db:\n    host: a\n    port: 1\n  name: x\n")
+                .expect("expected");
+        assert_eq!(outcome.value, Some(expected));
+        assert_eq!(
+            codes(&outcome),
+            vec![("W018".to_string(), "app".to_string())]
+        );
+
+        let outcome = build(dir.path(), &with_policy(CollisionPolicy::LastWins));
+        let expected: Value =
+            serde_yaml::from_str("app:\n  db:\n    port: 2\n    user: u\n").expect("expected");
+        assert_eq!(outcome.value, Some(expected));
+
+        let outcome = build(dir.path(), &with_policy(CollisionPolicy::DeepMerge));
+        let expected: Value =
+            serde_yaml::from_str("app:\n  db:\n    host: a\n    port: 1\n    user: u\n  name: x\n")
+                .expect("expected");
+        assert_eq!(outcome.value, Some(expected));
+        assert_eq!(
+            codes(&outcome),
+            vec![("E001".to_string(), "app.db.port".to_string())]
+        );
+    }
+
+    #[test]
+    fn ordered_prefixes_strip_names_and_order_keys() {
+        let dir = tempdir().expect("temp dir");
+        write(&dir.path().join("10-zeta.yml"), "1\n");
+        write(&dir.path().join("02-services/01-web.yml"), "a\n");
+        write(&dir.path().join("02-services/00-db.yml"), "b\n");
+        write(&dir.path().join("alpha.yml"), "2\n");
+        write(&dir.path().join("1-.yml"), "3\n");
+
+        let options = BuildOptions {
+            ordered_prefixes: true,
+            ..BuildOptions::default()
+        };
+        let outcome = build(dir.path(), &options);
+        assert!(outcome.diagnostics.is_empty());
+        let ordered = outcome
+            .explain
+            .ordered_keys
+            .iter()
+            .map(|k| format!("{} {} {}", k.name, k.derived_key_path, k.order))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ordered,
+            vec![
+                "02-services services 2",
+                "00-db.yml services.db 0",
+                "01-web.yml services.web 1",
+                "10-zeta.yml zeta 10",
+            ]
+        );
+
+        let mut value = canonicalize_yaml(&outcome.value.expect("value exists"));
+        apply_key_orders(&mut value, &outcome.key_orders);
+        let keys = |value: &Value| {
+            value
+                .as_mapping()
+                .expect("mapping")
+                .keys()
+                .map(key_as_string)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(keys(&value), vec!["services", "zeta", "1-", "alpha"]);
+        assert_eq!(keys(&value["services"]), vec!["db", "web"]);
+
+        let outcome = build(dir.path(), &BuildOptions::default());
+        assert!(outcome.key_orders.is_empty());
+        assert!(outcome
+            .value
+            .expect("value exists")
+            .get("10-zeta")
+            .is_some());
+    }
+
+    #[test]
+    fn expand_dotted_keys_nests_and_merges_with_siblings() {
+        let dir = tempdir().expect("temp dir");
+        write(&dir.path().join("server.http.port.yml"), "8080\n");
+        write(&dir.path().join("server/http/host.yml"), "localhost\n");
+        write(&dir.path().join("server.tls.yml"), "enabled: true\n");
+        write(&dir.path().join("0.5.yml"), "x\n");
+
+        let options = BuildOptions {
+            expand_dotted_keys: true,
+            ..BuildOptions::default()
+        };
+        let outcome = build(dir.path(), &options);
+        let codes = outcome
+            .diagnostics
+            .iter()
+            .map(|d| d.code.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(codes, vec!["W010"]);
+        let expected: Value = serde_yaml::from_str(
+            "\"0.5\": x\nserver:\n  http:\n    host: localhost\n    port: 8080\n  tls:\n    enabled: true\n",
+        )
+        .expect("expected");
+        assert_eq!(outcome.value, Some(expected));
+
+        write(&dir.path().join("server/http/port.yml"), "80\n");
+        write(&dir.path().join("a..b.yml"), "1\n");
+        let outcome = build(dir.path(), &options);
+        let mut errors = outcome
+            .diagnostics
+            .iter()
+            .filter(|d| d.is_error())
+            .map(|d| format!("{} {}", d.code, d.derived_key_path.as_deref().unwrap_or("")))
+            .collect::<Vec<_>>();
+        errors.sort();
+        assert_eq!(errors, vec!["E001 server.http.port", "E021 a..b"]);
+    }
+
+    #[test]
+    fn value_files_stand_for_their_directory() {
+        let dir = tempdir().expect("temp dir");
+        write(&dir.path().join("items/0/value.yml"), "a\n");
+        write(&dir.path().join("items/1/_value.yml"), "[b]\n");
+        write(&dir.path().join("db/value.yml"), "1\n");
+        write(&dir.path().join("db/host.yml"), "h\n");
+
+        let options = BuildOptions {
+            value_files: true,
+            ..BuildOptions::default()
+        };
+        let outcome = build(dir.path(), &options);
+        let expected: Value =
+            serde_yaml::from_str("db:\n  host: h\n  value: 1\nitems:\n- a\n- [b]\n")
+                .expect("expected");
+        assert_eq!(outcome.value, Some(expected));
+
+        let outcome = build(dir.path(), &BuildOptions::default());
+        let root = outcome.value.expect("value exists");
+        assert_eq!(root["items"][0]["value"], Value::from("a"));
+    }
+
+    #[test]
+    fn text_files_become_raw_strings() {
+        let dir = tempdir().expect("temp dir");
+        write(
+            &dir.path().join("motd.txt"),
+            "Welcome: {user}\n# not a comment\n",
+        );
+        write(&dir.path().join("docs/README.md"), "# Title\n");
+        write(&dir.path().join("docs/index.yml"), "a: 1\n");
+
+        let options = BuildOptions {
+            text_files: true,
+            index_files: true,
+            ..BuildOptions::default()
+        };
+        let outcome = build(dir.path(), &options);
+        let root = outcome.value.expect("value exists");
+        assert_eq!(
+            root["motd"],
+            Value::from("Welcome: {user}\n# not a comment\n")
+        );
+        assert_eq!(root["docs"]["README"], Value::from("# Title\n"));
+        assert_eq!(root["docs"]["a"], Value::from(1));
+        assert_eq!(outcome.fragments.len(), 3);
+
+        let outcome = build(dir.path(), &BuildOptions::default());
+        let root = outcome.value.expect("value exists");
+        assert!(root.get("motd").is_none());
+    }
+
+    #[test]
+    fn accepted_binary_files_become_base64_scalars() {
+        let dir = tempdir().expect("temp dir");
+        fs::create_dir_all(dir.path().join("img")).expect("create dir");
+        fs::write(
+            dir.path().join("img/logo.png"),
+            [0x89, b'P', b'N', b'G', 0x00, 0xff],
+        )
+        .expect("write binary");
+        write(&dir.path().join("img/alt.yml"), "logo\n");
+
+        let options = BuildOptions {
+            accept_binary: vec!["*.png".parse().expect("glob")],
+            ..BuildOptions::default()
+        };
+        let outcome = build(dir.path(), &options);
+        let root = outcome.value.expect("value exists");
+        let Value::Tagged(logo) = &root["img"]["logo"] else {
+            panic!("expected a tagged value, got {:?}", root["img"]["logo"]);
+        };
+        assert_eq!(logo.tag.to_string(), "!!binary");
+        assert_eq!(logo.value, Value::from("iVBORwD/"));
+        assert_eq!(root["img"]["alt"], Value::from("logo"));
+
+        let limited = BuildOptions {
+            max_yaml_bytes: Some(5),
+            ..options
+        };
+        let outcome = build(dir.path(), &limited);
+        assert!(outcome
+            .diagnostics
+            .iter()
+            .any(|d| d.code == "E034" && d.location.as_deref() == Some("img/logo.png")));
+    }
+
+    #[test]
+    fn front_matter_becomes_the_value_of_markdown_files() {
+        let dir = tempdir().expect("temp dir");
+        write(
+            &dir.path().join("posts/hello.md"),
+            "---\ntitle: Hello\n---\n# Hello\n",
+        );
+        write(&dir.path().join("posts/plain.md"), "Just text\n");
+        write(&dir.path().join("notes.txt"), "raw\n");
+
+        let options = BuildOptions {
+            front_matter: true,
+            text_files: true,
+            ..BuildOptions::default()
+        };
+        let outcome = build(dir.path(), &options);
+        let expected: Value = serde_yaml::from_str(
+            "notes: \"raw\\n\"\nposts:\n  hello:\n    title: Hello\n    content: \"# Hello\\n\"\n  plain:\n    content: \"Just text\\n\"\n",
+        )
+        .expect("expected");
+        assert_eq!(outcome.value, Some(expected));
+
+        write(
+            &dir.path().join("posts/hello.md"),
+            "---\ncontent: x\n---\nbody\n",
+        );
+        let outcome = build(dir.path(), &options);
+        assert!(outcome.diagnostics.iter().any(|d| d.code == "E105"));
+
+        assert_eq!(split_front_matter("---\na: 1\n...\nb\n"), ("a: 1\n", "b\n"));
+        assert_eq!(split_front_matter("---\na: 1\n"), ("", "---\na: 1\n"));
+    }
+
+    #[test]
+    fn includes_splice_files_and_report_cycles() {
+        let options = BuildOptions {
+            resolve_includes: true,
+            exclude: vec!["shared/**".parse().expect("glob")],
+            ..BuildOptions::default()
+        };
+        let entries = |files: &[(&str, &str)]| {
+            files
+                .iter()
+                .map(|(path, contents)| (PathBuf::from(path), contents.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        let outcome = build_from_entries(
+            entries(&[
+                ("db.yml", "host: h\ntls: !include ../x/../shared/tls.yml\n"),
+                ("shared/tls.yml", "cert: !include certs/ca.yml\n"),
+                ("shared/certs/ca.yml", "pem\n"),
+            ]),
+            &options,
+        );
+        assert!(outcome.diagnostics.iter().any(|d| d.code == "E106"));
+
+        let outcome = build_from_entries(
+            entries(&[
+                ("db.yml", "host: h\ntls: !include shared/tls.yml\n"),
+                ("shared/tls.yml", "cert: !include certs/ca.yml\n"),
+                ("shared/certs/ca.yml", "pem\n"),
+            ]),
+            &options,
+        );
+        assert!(outcome.diagnostics.is_empty(), "{:?}", outcome.diagnostics);
+        let expected: Value =
+            serde_yaml::from_str("db:\n  host: h\n  tls:\n    cert: pem\n").expect("expected");
+        assert_eq!(outcome.value, Some(expected));
+        let edges = outcome
+            .explain
+            .includes
+            .iter()
+            .map(|edge| (edge.source.as_str(), edge.target.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            edges,
+            vec![
+                ("db.yml", "shared/tls.yml"),
+                ("shared/tls.yml", "shared/certs/ca.yml"),
+            ]
+        );
+
+        let outcome = build_from_entries(
+            entries(&[
+                ("a.yml", "b: !include shared/b.yml\n"),
+                ("shared/b.yml", "a: !include ../a.yml\n"),
+            ]),
+            &options,
+        );
+        let cycle = outcome
+            .diagnostics
+            .iter()
+            .find(|d| d.code == "E108")
+            .expect("cycle reported");
+        assert!(cycle
+            .context
+            .iter()
+            .any(|line| line == "Include chain: a.yml -> shared/b.yml -> a.yml"));
+    }
+
+    #[test]
+    fn sequence_items_are_checked_for_shape() {
+        let dir = tempdir().expect("temp dir");
+        write(&dir.path().join("ci/steps/0.yml"), "name: build\n");
+        write(
+            &dir.path().join("ci/steps/1.yml"),
+            "name: test\nrun: make\n",
+        );
+        write(&dir.path().join("ci/steps/2.yml"), "run: deploy\n");
+        write(&dir.path().join("ci/steps/3.yml"), "make lint\n");
+
+        let options = BuildOptions {
+            uniform_sequences: true,
+            item_keys: vec!["*.steps=name".parse().expect("item keys")],
+            ..BuildOptions::default()
+        };
+        let outcome = build(dir.path(), &options);
+        let found = outcome
+            .diagnostics
+            .iter()
+            .map(|d| (d.code.as_str(), d.location.as_deref().unwrap_or("")))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                ("E008", "ci/steps/2.yml"),
+                ("E007", "ci/steps/3.yml"),
+                ("E008", "ci/steps/3.yml"),
+            ]
+        );
+
+        let outcome = build(dir.path(), &BuildOptions::default());
+        assert!(outcome.diagnostics.is_empty(), "{:?}", outcome.diagnostics);
+    }
+
+    #[test]
+    fn sequences_can_start_at_one_and_order_by_name() {
+        let dir = tempdir().expect("temp dir");
+        for name in ["1", "2", "3", "10"] {
+            write(
+                &dir.path().join(format!("steps/{name}.yml")),
+                &format!("s{name}\n"),
+            );
+        }
+        let steps = |options: &BuildOptions| {
+            let outcome = build(dir.path(), options);
+            let value = outcome.value.expect("value");
+            let steps = value["steps"].clone();
+            (steps, outcome.diagnostics, outcome.explain)
+        };
+
+        let options = BuildOptions {
+            seq_start: 1,
+            ..BuildOptions::default()
+        };
+        let (value, diagnostics, explain) = steps(&options);
+        assert_eq!(
+            value,
+            serde_yaml::from_str::<Value>("[s1, s2, s3, s10]").expect("yaml")
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].context.as_deref(),
+            Some("Missing ranges: 4..9")
+        );
+        let numbering = explain.sequence_numbering.expect("numbering");
+        assert_eq!((numbering.start, numbering.order), (1, SeqOrder::Numeric));
+        assert!(explain
+            .derived_keys
+            .iter()
+            .any(|key| key.derived_key_path == "steps[0]" && key.source == "steps/1.yml"));
+
+        let options = BuildOptions {
+            seq_order: SeqOrder::Lexical,
+            seq_gaps: SeqGapMode::Allow,
+            ..BuildOptions::default()
+        };
+        let (value, diagnostics, _) = steps(&options);
+        assert_eq!(
+            value,
+            serde_yaml::from_str::<Value>("[s1, s10, s2, s3]").expect("yaml")
+        );
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+        write(&dir.path().join("steps/0.yml"), "s0\n");
+        let options = BuildOptions {
+            seq_start: 1,
+            seq_gaps: SeqGapMode::Error,
+            ..BuildOptions::default()
+        };
+        let (_, diagnostics, _) = steps(&options);
+        assert_eq!(diagnostics[0].code, "E003");
+        assert_eq!(
+            diagnostics[0].context.as_deref(),
+            Some("Missing ranges: 4..9. Below --seq-start 1: 0")
+        );
+    }
+
+    #[test]
+    fn item_counts_bound_sequence_directories() {
+        let dir = tempdir().expect("temp dir");
+        write(&dir.path().join("workflows/ci/jobs/0.yml"), "build\n");
+        write(&dir.path().join("workflows/ci/jobs/1.yml"), "test\n");
+        write(
+            &dir.path().join("workflows/nightly/jobs/notes.txt"),
+            "todo\n",
+        );
+        write(&dir.path().join("workflows/release/jobs/0.yml"), "tag\n");
+
+        let options = BuildOptions {
+            item_counts: vec!["workflows.*.jobs:min=1,max=1".parse().expect("item count")],
+            ..BuildOptions::default()
+        };
+        let outcome = build(dir.path(), &options);
+        let found = outcome
+            .diagnostics
+            .iter()
+            .filter(|d| d.is_error())
+            .map(|d| (d.code.as_str(), d.location.as_deref().unwrap_or("")))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                ("E009", "workflows/ci/jobs"),
+                ("E009", "workflows/nightly/jobs"),
+            ]
+        );
+        let nightly = outcome
+            .diagnostics
+            .iter()
+            .find(|d| d.location.as_deref() == Some("workflows/nightly/jobs"))
+            .expect("nightly diagnostic");
+        assert_eq!(
+            nightly.context.as_deref(),
+            Some("Ignored entries: workflows/nightly/jobs/notes.txt (non-YAML file ignored)")
+        );
+
+        assert!("jobs:min=2,max=1"
+            .parse::<crate::config::ItemCount>()
+            .is_err());
+        assert!("jobs".parse::<crate::config::ItemCount>().is_err());
+    }
+
+    #[test]
+    fn merge_keys_expand_with_explicit_keys_winning() {
+        let options = BuildOptions {
+            merge_keys: MergeKeys::Expand,
+            ..BuildOptions::default()
+        };
+        let entries = [(
+            PathBuf::from("svc.yml"),
+            "base: &base {replicas: 2, image: app}\n\
+             extra: &extra {image: other, port: 80}\n\
+             api:\n  <<: [*base, *extra]\n  replicas: 3\n"
+                .to_string(),
+        )];
+        let outcome = build_from_entries(entries, &options);
+        let expected: Value = serde_yaml::from_str(
+            "svc:\n  base: {replicas: 2, image: app}\n  extra: {image: other, port: 80}\n\
+             \x20 api: {replicas: 3, image: app, port: 80}\n",
+        )
+        .expect("expected");
+        assert_eq!(outcome.value, Some(expected));
+        let warnings = outcome
+            .diagnostics
+            .iter()
+            .filter(|d| d.code == "W024")
+            .map(|d| d.derived_key_path.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(warnings, vec![Some("svc.api.replicas")]);
+
+        let entries = [(PathBuf::from("svc.yml"), "<<: base\n".to_string())];
+        let outcome = build_from_entries(entries, &options);
+        assert!(outcome.diagnostics.iter().any(|d| d.code == "E123"));
+
+        let reject = BuildOptions {
+            merge_keys: MergeKeys::Error,
+            ..BuildOptions::default()
+        };
+        let entries = [(
+            PathBuf::from("svc.yml"),
+            "base: &base {replicas: 2}\napi:\n  <<: *base\n".to_string(),
+        )];
+        let outcome = build_from_entries(entries, &reject);
+        let rejected = outcome
+            .diagnostics
+            .iter()
+            .filter(|d| d.code == "E135")
+            .collect::<Vec<_>>();
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].derived_key_path.as_deref(), Some("svc.api.<<"));
+    }
+
+    #[test]
+    fn shared_anchors_resolve_aliases_across_fragments() {
+        let options = BuildOptions {
+            shared_anchors: true,
+            ..BuildOptions::default()
+        };
+        let entries = |files: &[(&str, &str)]| {
+            files
+                .iter()
+                .map(|(path, contents)| (PathBuf::from(path), contents.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        let outcome = build_from_entries(
+            entries(&[
+                ("_anchors.yml", "limits: {cpu: 2}\nregion: eu-west-1\n"),
+                (
+                    "api.yml",
+                    "limits: *limits\nregions: [*region, us-east-1]\nnote: \"*region\"\n",
+                ),
+                ("web.yml", "region: &region local\nalso: *region\n"),
+            ]),
+            &options,
+        );
+        assert!(
+            outcome.diagnostics.iter().all(|d| d.code == "W013"),
+            "{:?}",
+            outcome.diagnostics
+        );
+        let expected: Value = serde_yaml::from_str(
+            "api:\n  limits: {cpu: 2}\n  regions: [eu-west-1, us-east-1]\n  note: '*region'\n\
+             web:\n  region: local\n  also: local\n",
+        )
+        .expect("expected");
+        assert_eq!(outcome.value, Some(expected));
+
+        let outcome = build_from_entries(
+            entries(&[
+                ("_anchors.yml", "limits: {cpu: 2}\n"),
+                ("api.yml", "a: *limits\nb: *limts\n"),
+            ]),
+            &options,
+        );
+        let diag = outcome
+            .diagnostics
+            .iter()
+            .find(|d| d.is_error())
+            .expect("unknown alias reported");
+        assert_eq!(diag.code, "E122");
+        assert_eq!(diag.location.as_deref(), Some("api.yml"));
+        assert_eq!(diag.context.as_deref(), Some("Shared anchors: limits"));
+    }
+
+    #[test]
+    fn entries_assemble_like_a_directory_tree() {
+        let entries = [
+            ("db/host.yml", "db.internal\n"),
+            ("db/port.yml", "5432\n"),
+            ("app.yml", "name: demo\n"),
+        ]
+        .map(|(path, contents)| (PathBuf::from(path), contents.to_string()));
+        let outcome = build_from_entries(entries, &BuildOptions::default());
+        assert!(outcome.diagnostics.is_empty(), "{:?}", outcome.diagnostics);
+        let expected: Value =
+            serde_yaml::from_str("app:\n  name: demo\ndb:\n  host: db.internal\n  port: 5432\n")
+                .expect("expected");
+        assert_eq!(outcome.value, Some(expected));
+
+        let entries = [
+            ("app.yml", "a: 1\n"),
+            ("app.yaml", "b: 2\n"),
+            ("../x.yml", "1\n"),
+        ]
+        .map(|(path, contents)| (PathBuf::from(path), contents.to_string()));
+        let outcome = build_from_entries(entries, &BuildOptions::default());
+        let codes = outcome
+            .diagnostics
+            .iter()
+            .map(|d| (d.code.as_str(), d.location.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(codes, vec![("E070", Some("../x.yml")), ("E006", Some("."))]);
+    }
+
+    #[test]
+    fn file_root_mode_discovers_a_conventional_root_file() {
+        let dir = tempdir().expect("temp dir");
+        write(&dir.path().join("_root.yml"), "name: app\n");
+        write(&dir.path().join("db/host.yml"), "h\n");
+
+        let options = BuildOptions {
+            root_mode: RootMode::FileRoot,
+            ..BuildOptions::default()
+        };
+        let outcome = build(dir.path(), &options);
+        assert!(outcome.diagnostics.is_empty(), "{:?}", outcome.diagnostics);
+        assert_eq!(outcome.explain.auto_root_file.as_deref(), Some("_root.yml"));
+        let expected: Value =
+            serde_yaml::from_str("db:\n  host: h\nname: app\n").expect("expected");
+        assert_eq!(outcome.value, Some(expected));
+
+        write(&dir.path().join("root.yaml"), "other: 1\n");
+        let outcome = build(dir.path(), &options);
+        let codes = outcome
+            .diagnostics
+            .iter()
+            .map(|d| d.code.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(codes, vec!["E048"]);
+    }
+
+    #[test]
+    fn trailing_junk_and_uppercase_extensions_normalize_names() {
+        let dir = tempdir().expect("temp dir");
+        write(&dir.path().join("db /host.yml"), "h\n");
+        write(&dir.path().join("CONFIG.YML"), "1\n");
+        write(&dir.path().join("app..yml"), "2\n");
+
+        let outcome = build(dir.path(), &BuildOptions::default());
+        let mut warnings = outcome
+            .diagnostics
+            .iter()
+            .map(|d| format!("{} {}", d.code, d.context.as_deref().unwrap_or("")))
+            .collect::<Vec<_>>();
+        warnings.sort();
+        assert_eq!(
+            warnings,
+            vec![
+                "W021 Offending bytes: \" \" (0x20) at the end of the name",
+                "W021 Offending bytes: \".\" (0x2E) before the extension",
+                "W022 Offending bytes: \"YML\" (0x59 0x4D 0x4C)",
+            ]
+        );
+        let expected: Value =
+            serde_yaml::from_str("CONFIG: 1\napp: 2\ndb:\n  host: h\n").expect("expected");
+        assert_eq!(outcome.value, Some(expected));
+
+        write(&dir.path().join("app.yml "), "3\n");
+        let outcome = build(dir.path(), &BuildOptions::default());
+        assert!(outcome.diagnostics.iter().any(|d| d.code == "E001"));
+    }
+
+    #[test]
+    fn extension_conflicts_are_reported_or_resolved() {
+        let dir = tempdir().expect("temp dir");
+        write(&dir.path().join("app.yml"), "from: yml\n");
+        write(&dir.path().join("app.yaml"), "from: yaml\n");
+        write(&dir.path().join("db.yaml"), "port: 1\n");
+
+        let outcome = build(dir.path(), &BuildOptions::default());
+        let codes = outcome
+            .diagnostics
+            .iter()
+            .map(|d| d.code.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(codes, vec!["E006"]);
+
+        let options = BuildOptions {
+            extension_conflict: ExtensionConflict::PreferYaml,
+            require_extension: Some(YamlExtension::Yaml),
+            ..BuildOptions::default()
+        };
+        let outcome = build(dir.path(), &options);
+        let linted = outcome
+            .diagnostics
+            .iter()
+            .map(|d| (d.code.as_str(), d.location.as_deref().unwrap_or_default()))
+            .collect::<Vec<_>>();
+        assert_eq!(linted, vec![("W019", "app.yml")]);
+        let expected: Value =
+            serde_yaml::from_str("app:\n  from: yaml\ndb:\n  port: 1\n").expect("expected");
+        assert_eq!(outcome.value, Some(expected));
+        assert_eq!(outcome.explain.ignored[0].path, "app.yml");
+        assert_eq!(
+            outcome.explain.ignored[0].rule,
+            "shadowed by app.yaml (--extension-conflict)"
+        );
+
+        let options = BuildOptions {
+            require_extension: Some(YamlExtension::Yml),
+            extension_conflict: ExtensionConflict::PreferYml,
+            ..BuildOptions::default()
+        };
+        let outcome = build(dir.path(), &options);
+        let mut linted = outcome
+            .diagnostics
+            .iter()
+            .filter(|d| d.code == "W019")
+            .filter_map(|d| d.location.as_deref())
+            .collect::<Vec<_>>();
+        linted.sort_unstable();
+        assert_eq!(linted, vec!["app.yaml", "db.yaml"]);
+    }
+
+    #[test]
+    fn file_list_limits_tree_to_listed_fragments() {
+        let dir = tempdir().expect("temp dir");
+        write(&dir.path().join("app/name.yml"), "demo\n");
+        write(&dir.path().join("app/extra.yml"), "skip\n");
+        write(&dir.path().join("other.yml"), "skip\n");
+
+        let options = BuildOptions {
+            file_list: Some(vec![
+                PathBuf::from("app/name.yml"),
+                PathBuf::from("missing.yml"),
+            ]),
+            ..BuildOptions::default()
+        };
+        let outcome = build(dir.path(), &options);
+        assert!(outcome.diagnostics.iter().any(|d| d.code == "E071"));
+
+        let expected: Value = serde_yaml::from_str("app:\n  name: demo\n").expect("expected");
+        assert_eq!(outcome.value, Some(expected));
+    }
+
+    #[test]
+    fn dates_policy_normalizes_values_and_warns_on_naive_datetimes() {
+        let dir = tempdir().expect("temp dir");
+        write(
+            &dir.path().join("release.yml"),
+            "cut: 2001-12-14t21:59:43.10-05:00\nday: 2001-12-14\nlocal: 2001-12-14 21:59:43\n2001-12-14: key\n",
+        );
+
+        let options = BuildOptions {
+            dates: DatesMode::Parse,
+            ..BuildOptions::default()
+        };
+        let outcome = build(dir.path(), &options);
+        let expected: Value = serde_yaml::from_str(
+            "release:\n  cut: 2001-12-15T02:59:43.1Z\n  day: 2001-12-14\n  local: 2001-12-14 21:59:43\n  2001-12-14: key\n",
+        )
+        .expect("expected");
+        assert_eq!(outcome.value, Some(expected));
+
+        let naive = outcome
+            .diagnostics
+            .iter()
+            .filter(|d| d.code == "W015")
+            .collect::<Vec<_>>();
+        assert_eq!(naive.len(), 1);
+        assert_eq!(naive[0].derived_key_path.as_deref(), Some("release.local"));
+    }
+
+    #[test]
+    fn tag_policy_strips_or_rejects_tags() {
+        let dir = tempdir().expect("temp dir");
+        write(
+            &dir.path().join("stack.yml"),
+            "bucket: !Ref Bucket\narn: !GetAtt [Role, Arn]\n",
+        );
+
+        let strip = BuildOptions {
+            tags: TagPolicy::Strip,
+            ..BuildOptions::default()
+        };
+        let outcome = build(dir.path(), &strip);
+        let expected: Value =
+            serde_yaml::from_str("stack:\n  arn: [Role, Arn]\n  bucket: Bucket\n")
+                .expect("expected");
+        assert_eq!(outcome.value, Some(expected));
+
+        let reject = BuildOptions {
+            tags: TagPolicy::Error,
+            ..BuildOptions::default()
+        };
+        let outcome = build(dir.path(), &reject);
+        let rejected = outcome
+            .diagnostics
+            .iter()
+            .filter(|d| d.code == "E102")
+            .map(|d| d.derived_key_path.clone().unwrap_or_default())
+            .collect::<Vec<_>>();
+        assert_eq!(rejected, vec!["stack.arn", "stack.bucket"]);
+        assert!(outcome.diagnostics[1].cause.contains("`!Ref`"));
+    }
+
+    #[test]
+    fn parse_cache_reuses_identical_fragments_across_builds() {
+        let first = tempdir().expect("temp dir");
+        let second = tempdir().expect("temp dir");
+        for dir in [first.path(), second.path()] {
+            write(&dir.join("defaults.yml"), "retries: 3\n");
+            write(&dir.join("broken.yml"), "a: [1\n");
+        }
+        write(&first.path().join("copy.yml"), "retries: 3\n");
+
+        let mut cache = ParseCache::default();
+        let outcomes = [first.path(), second.path()]
+            .map(|dir| build_with_cache(dir, &BuildOptions::default(), &mut cache));
+        assert_eq!(
+            cache.stats(),
+            ParseStats {
+                parsed: 2,
+                reused: 3
+            }
+        );
+        for outcome in &outcomes {
+            let parse_error = outcome
+                .diagnostics
+                .iter()
+                .find(|d| d.code == "E100")
+                .expect("cached parse errors are still reported");
+            assert_eq!(parse_error.location.as_deref(), Some("broken.yml"));
+            assert!(parse_error.position.is_some());
+        }
+    }
+
+    #[test]
+    fn parallel_parsing_matches_serial_build() {
+        let dir = tempdir().expect("temp dir");
+        for service in 0..12 {
+            let base = dir.path().join(format!("svc{service}"));
+            write(
+                &base.join("config.yml"),
+                &format!("port: {service}\nref: !Ref x\n"),
+            );
+            write(&base.join("hosts/0.yml"), "host: a\n");
+            write(&base.join("hosts/1.yml"), "host: b\n");
+        }
+        write(&dir.path().join("svc3/broken.yml"), "a: [1\n");
+        write(&dir.path().join(".hidden/skip.yml"), "a: 1\n");
+
+        let serial_options = BuildOptions {
+            tags: TagPolicy::Error,
+            ..BuildOptions::default()
+        };
+        let parallel_options = BuildOptions {
+            jobs: 4,
+            ..serial_options.clone()
+        };
+        let mut serial_cache = ParseCache::default();
+        let mut parallel_cache = ParseCache::default();
+        let serial = build_with_cache(dir.path(), &serial_options, &mut serial_cache);
+        let parallel = build_with_cache(dir.path(), &parallel_options, &mut parallel_cache);
+
+        assert_eq!(serial.value, parallel.value);
+        assert_eq!(serial.fragments, parallel.fragments);
+        assert_eq!(
+            serde_json::to_value(&serial.diagnostics).expect("json"),
+            serde_json::to_value(&parallel.diagnostics).expect("json")
+        );
+        assert!(serial.diagnostics.iter().any(|d| d.code == "E100"));
+        assert_eq!(serial_cache.stats(), parallel_cache.stats());
+    }
+
+    #[test]
+    fn parse_cache_file_round_trips_documents_and_parse_errors() {
+        let dir = tempdir().expect("temp dir");
+        write(
+            &dir.path().join("config.yml"),
+            "port: 80\nref: !Ref x\nalias: &a 1\n",
+        );
+        write(&dir.path().join("broken.yml"), "a: [1\n");
+        let cache_path = dir.path().join(CACHE_FILE_NAME);
+        let options = BuildOptions::default();
+
+        let mut cache = ParseCache::default();
+        let first = build_with_cache(dir.path(), &options, &mut cache);
+        cache
+            .write_file(&cache_path, &first.fragments, options.big_integers)
+            .expect("write cache");
+
+        let mut reloaded = ParseCache::default();
+        assert!(reloaded.read_file(&cache_path));
+        let second = build_with_cache(dir.path(), &options, &mut reloaded);
+        assert_eq!(
+            reloaded.stats(),
+            ParseStats {
+                parsed: 0,
+                reused: 2
+            }
+        );
+        assert_eq!(first.value, second.value);
+        assert_eq!(
+            serde_json::to_value(&first.diagnostics).expect("json"),
+            serde_json::to_value(&second.diagnostics).expect("json")
+        );
+        assert!(second.diagnostics.iter().all(|d| d.code != "W050"));
+
+        fs::write(&cache_path, b"not a cache").expect("corrupt cache");
+        let mut ignored = ParseCache::default();
+        assert!(!ignored.read_file(&cache_path));
+        assert!(ignored.is_empty());
+    }
 }
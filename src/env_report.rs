@@ -0,0 +1,215 @@
+//! `fyaml env-report`: a snapshot of the platform, file system, options, and tree shape,
+//! meant to be pasted into bug reports. It is only printed; nothing is sent anywhere.
+//!
+//! Values that may be private (inline root documents) are replaced
+//! by `<redacted>`; entry names only appear in the options the user passed.
+
+use crate::config::BuildOptions;
+use crate::engine::BuildOutcome;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+const REDACTED: &str = "<redacted>";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EnvReport {
+    pub version: String,
+    pub os: String,
+    pub arch: String,
+    /// Whether the tree's file system tells names apart by case; `None` when no entry
+    /// name has a letter to probe with.
+    pub case_sensitive: Option<bool>,
+    pub tree: TreeShape,
+    pub build: BuildShape,
+    /// Every option, as resolved from the command line.
+    pub options: serde_json::Value,
+    /// The options that differ from the defaults, by name.
+    pub changed_options: Vec<String>,
+}
+
+/// What is on disk below the root, whether or not a build reads it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TreeShape {
+    pub directories: usize,
+    pub files: usize,
+    pub yaml_files: usize,
+    pub symlinks: usize,
+    pub hidden_entries: usize,
+    pub total_bytes: u64,
+    pub largest_file_bytes: u64,
+    /// Directory levels below the root; a flat tree is 0.
+    pub max_depth: usize,
+}
+
+/// What a build of the tree with these options made of it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BuildShape {
+    pub fragments: usize,
+    pub derived_keys: usize,
+    pub ignored_entries: usize,
+    /// Directories by the mode they were assembled in, e.g. `mapping` or `sequence`.
+    pub directory_modes: BTreeMap<String, usize>,
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+/// Collects the report for the tree at `root`, built as `outcome` with `options`.
+pub fn env_report(
+    root: &Path,
+    options: &BuildOptions,
+    outcome: &BuildOutcome,
+    version: &str,
+) -> EnvReport {
+    let mut tree = TreeShape::default();
+    walk(root, 0, &mut tree);
+
+    let mut directory_modes = BTreeMap::new();
+    for decision in &outcome.explain.directory_modes {
+        *directory_modes.entry(decision.mode.clone()).or_insert(0) += 1;
+    }
+    let build = BuildShape {
+        fragments: outcome.fragments.len(),
+        derived_keys: outcome.explain.derived_keys.len(),
+        ignored_entries: outcome.explain.ignored.len(),
+        directory_modes,
+        errors: outcome.diagnostics.iter().filter(|d| d.is_error()).count(),
+        warnings: outcome.diagnostics.iter().filter(|d| !d.is_error()).count(),
+    };
+
+    let options = options_json(&redacted(options));
+    let defaults = options_json(&BuildOptions::default());
+    let changed_options = match (&options, &defaults) {
+        (serde_json::Value::Object(options), serde_json::Value::Object(defaults)) => options
+            .iter()
+            .filter(|(name, value)| defaults.get(name.as_str()) != Some(value))
+            .map(|(name, _)| name.clone())
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    EnvReport {
+        version: version.to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        case_sensitive: case_sensitive(root),
+        tree,
+        build,
+        options,
+        changed_options,
+    }
+}
+
+fn options_json(options: &BuildOptions) -> serde_json::Value {
+    serde_json::to_value(options).unwrap_or(serde_json::Value::Null)
+}
+
+fn redacted(options: &BuildOptions) -> BuildOptions {
+    let mut options = options.clone();
+    if options.root_inline.is_some() {
+        options.root_inline = Some(REDACTED.to_string());
+    }
+    options
+}
+
+fn walk(dir: &Path, depth: usize, tree: &mut TreeShape) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with('.') {
+            tree.hidden_entries += 1;
+        }
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_symlink() {
+            tree.symlinks += 1;
+        } else if file_type.is_dir() {
+            tree.directories += 1;
+            tree.max_depth = tree.max_depth.max(depth + 1);
+            walk(&entry.path(), depth + 1, tree);
+        } else {
+            tree.files += 1;
+            if name.ends_with(".yml") || name.ends_with(".yaml") {
+                tree.yaml_files += 1;
+            }
+            let bytes = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            tree.total_bytes += bytes;
+            tree.largest_file_bytes = tree.largest_file_bytes.max(bytes);
+        }
+    }
+}
+
+/// Looks up an entry of `dir` under its name with the case of its letters swapped: found
+/// means the file system ignores case. Nothing is created.
+fn case_sensitive(dir: &Path) -> Option<bool> {
+    let names = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect::<Vec<_>>();
+    names.iter().find_map(|name| {
+        let swapped = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_lowercase() {
+                    c.to_ascii_uppercase()
+                } else {
+                    c.to_ascii_lowercase()
+                }
+            })
+            .collect::<String>();
+        if swapped == *name || names.contains(&swapped) {
+            return None;
+        }
+        Some(fs::symlink_metadata(dir.join(&swapped)).is_err())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::build;
+    use tempfile::tempdir;
+
+    #[test]
+    fn reports_tree_shape_and_redacts_private_options() {
+        let dir = tempdir().expect("temp dir");
+        let root = dir.path();
+        fs::create_dir_all(root.join("jobs")).expect("mkdir");
+        fs::write(root.join("app.yml"), "name: demo\n").expect("write");
+        fs::write(root.join("jobs/0.yml"), "run: build\n").expect("write");
+        fs::write(root.join("jobs/1.yml"), "run: test\n").expect("write");
+        fs::write(root.join(".env"), "SECRET=1\n").expect("write");
+
+        let options = BuildOptions {
+            root_inline: Some("token: s3cr3t\n".to_string()),
+            ..BuildOptions::default()
+        };
+        let outcome = build(root, &options);
+        let report = env_report(root, &options, &outcome, "0.1.0");
+
+        assert_eq!(
+            report.tree,
+            TreeShape {
+                directories: 1,
+                files: 4,
+                yaml_files: 3,
+                symlinks: 0,
+                hidden_entries: 1,
+                total_bytes: 41,
+                largest_file_bytes: 11,
+                max_depth: 1,
+            }
+        );
+        assert_eq!(report.build.fragments, 3);
+        assert_eq!(report.build.directory_modes.get("sequence"), Some(&1));
+        assert_eq!(report.changed_options, vec!["root_inline"]);
+        assert_eq!(report.options["root_inline"], REDACTED);
+        assert!(report.case_sensitive.is_some());
+    }
+}
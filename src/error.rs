@@ -0,0 +1,232 @@
+//! Typed errors for library consumers.
+//!
+//! Diagnostics stay the complete record of a build; `FyamlError` classifies the error-level
+//! ones so callers can match on the kind of failure instead of parsing rendered text.
+
+use crate::config::BuildOptions;
+use crate::diagnostics::{Category, Diagnostic};
+use crate::engine::{build, BuildOutcome};
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FyamlError {
+    /// A file or directory could not be read or listed.
+    #[error("{code}: {message} ({path})")]
+    Io {
+        code: String,
+        path: String,
+        message: String,
+    },
+    /// A fragment is not acceptable YAML; `line` and `column` are one-based when known.
+    #[error("{code}: {message} ({file}{})", position_suffix(*.line, *.column))]
+    Parse {
+        code: String,
+        file: String,
+        line: Option<usize>,
+        column: Option<usize>,
+        message: String,
+    },
+    /// Several sources produce the same key path.
+    #[error("{code}: {message} at `{path}` ({})", .sources.join(", "))]
+    Collision {
+        code: String,
+        path: String,
+        sources: Vec<String>,
+        message: String,
+    },
+    /// The tree or options are invalid (naming, sequence, root mode, manifest, ...).
+    #[error("{code}: {message}{}", location_suffix(.location))]
+    InvalidInput {
+        code: String,
+        location: Option<String>,
+        message: String,
+    },
+    /// An `!include` target is outside the root, unreadable, cyclic, or not a path.
+    #[error("{code}: {message}{}", location_suffix(.location))]
+    Include {
+        code: String,
+        location: Option<String>,
+        message: String,
+    },
+    #[error("{code}: {message}{}", location_suffix(.location))]
+    Write {
+        code: String,
+        location: Option<String>,
+        message: String,
+    },
+    #[error("{code}: {message}")]
+    Internal { code: String, message: String },
+}
+
+impl FyamlError {
+    /// Classifies an error-level diagnostic; warnings and info return `None`.
+    pub fn from_diagnostic(diagnostic: &Diagnostic) -> Option<Self> {
+        if !diagnostic.is_error() {
+            return None;
+        }
+
+        let code = diagnostic.code.clone();
+        let message = diagnostic.message.clone();
+        let location = diagnostic
+            .location
+            .clone()
+            .or_else(|| diagnostic.paths.first().cloned());
+
+        let error = match diagnostic.category {
+            Category::Parse => Self::Parse {
+                code,
+                file: location.unwrap_or_default(),
+                line: diagnostic.position.map(|position| position.line),
+                column: diagnostic.position.map(|position| position.column),
+                message,
+            },
+            Category::Write => Self::Write {
+                code,
+                location,
+                message,
+            },
+            Category::Include => Self::Include {
+                code,
+                location,
+                message,
+            },
+            Category::Internal => Self::Internal { code, message },
+            Category::InvalidInput if code == "E001" || code == "E004" => Self::Collision {
+                code,
+                path: diagnostic.derived_key_path.clone().unwrap_or_default(),
+                sources: if diagnostic.paths.is_empty() {
+                    location.into_iter().collect()
+                } else {
+                    diagnostic.paths.clone()
+                },
+                message,
+            },
+            Category::InvalidInput if code.starts_with("E03") => Self::Io {
+                code,
+                path: location.unwrap_or_default(),
+                message,
+            },
+            Category::InvalidInput => Self::InvalidInput {
+                code,
+                location,
+                message,
+            },
+        };
+        Some(error)
+    }
+
+    /// The stable diagnostic code behind this error.
+    pub fn code(&self) -> &str {
+        match self {
+            Self::Io { code, .. }
+            | Self::Parse { code, .. }
+            | Self::Collision { code, .. }
+            | Self::InvalidInput { code, .. }
+            | Self::Include { code, .. }
+            | Self::Write { code, .. }
+            | Self::Internal { code, .. } => code,
+        }
+    }
+}
+
+/// A failed build: the typed errors plus every diagnostic (warnings included).
+#[derive(Debug, Clone)]
+pub struct BuildError {
+    pub errors: Vec<FyamlError>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.errors.as_slice() {
+            [] => f.write_str("FYAML build failed"),
+            [only] => write!(f, "{only}"),
+            [first, rest @ ..] => write!(f, "{first} (and {} more error(s))", rest.len()),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+impl BuildOutcome {
+    /// Typed view of the error-level diagnostics, in report order.
+    pub fn errors(&self) -> Vec<FyamlError> {
+        self.diagnostics
+            .iter()
+            .filter_map(FyamlError::from_diagnostic)
+            .collect()
+    }
+}
+
+/// Like [`build`], but fails with a [`BuildError`] when any error-level diagnostic is
+/// reported. A successful outcome may still carry warnings.
+pub fn try_build(root: &Path, options: &BuildOptions) -> Result<BuildOutcome, BuildError> {
+    let outcome = build(root, options);
+    let errors = outcome.errors();
+    if errors.is_empty() && outcome.value.is_some() {
+        return Ok(outcome);
+    }
+    Err(BuildError {
+        errors,
+        diagnostics: outcome.diagnostics,
+    })
+}
+
+fn position_suffix(line: Option<usize>, column: Option<usize>) -> String {
+    match (line, column) {
+        (Some(line), Some(column)) => format!(":{line}:{column}"),
+        (Some(line), None) => format!(":{line}"),
+        _ => String::new(),
+    }
+}
+
+fn location_suffix(location: &Option<String>) -> String {
+    location
+        .as_ref()
+        .map(|location| format!(" ({location})"))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn try_build_classifies_collisions_and_parse_errors() {
+        let dir = tempdir().expect("temp dir");
+        fs::write(dir.path().join("auth.yml"), "x\n").expect("write");
+        fs::create_dir(dir.path().join("auth")).expect("mkdir");
+        fs::write(dir.path().join("auth/provider.yml"), "ok: true\n").expect("write");
+        fs::write(dir.path().join("broken.yml"), "a: [1\n").expect("write");
+
+        let err = try_build(dir.path(), &BuildOptions::default()).expect_err("build fails");
+        assert!(err.errors.iter().any(|error| matches!(
+            error,
+            FyamlError::Collision { path, sources, .. } if path == "auth" && sources.len() == 2
+        )));
+        let parse = err
+            .errors
+            .iter()
+            .find(|error| error.code() == "E100")
+            .expect("parse error");
+        let FyamlError::Parse { file, line, .. } = parse else {
+            panic!("E100 is a parse error: {parse:?}");
+        };
+        assert!(file.ends_with("broken.yml"));
+        assert!(line.is_some());
+        assert!(err.diagnostics.len() >= err.errors.len());
+    }
+
+    #[test]
+    fn try_build_returns_outcome_without_errors() {
+        let dir = tempdir().expect("temp dir");
+        fs::write(dir.path().join("app.yml"), "name: demo\n").expect("write");
+
+        let outcome = try_build(dir.path(), &BuildOptions::default()).expect("build succeeds");
+        assert!(outcome.errors().is_empty());
+        assert!(outcome.value.is_some());
+    }
+}
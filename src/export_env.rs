@@ -0,0 +1,317 @@
+//! `fyaml export-env`: a subtree of the packed document as `export NAME='value'` lines for a
+//! POSIX shell to `source` or `eval`.
+//!
+//! A variable's name is its key path below the subtree, upper-cased, with each run of
+//! characters other than ASCII letters and digits replaced by `_`, after `--prefix`. Values
+//! are always single-quoted, so the shell expands nothing in them.
+
+use crate::config::{EnvLists, EnvMaps};
+use crate::diagnostics::{Category, Diagnostic};
+use crate::emitter::{inline_scalar, EmitterOptions};
+use crate::keypath::{push_index, push_key, ROOT_PATH};
+use serde_yaml::Value;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub prefix: String,
+    pub lists: EnvLists,
+    /// Placed between items with `--lists join`.
+    pub list_separator: String,
+    pub maps: EnvMaps,
+}
+
+/// One exported variable, with the key path it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvVar {
+    pub name: String,
+    pub value: String,
+    pub derived_key_path: String,
+}
+
+/// The variables for `value`, in document order. `base` is the key path of `value` in the
+/// packed document, empty for the root.
+pub fn env_vars(
+    value: &Value,
+    base: &str,
+    options: &ExportOptions,
+) -> Result<Vec<EnvVar>, Box<Diagnostic>> {
+    let mut export = Export {
+        options,
+        vars: Vec::new(),
+        names: HashMap::new(),
+    };
+    export.node(value, &mut base.to_string(), &mut Vec::new())?;
+    Ok(export.vars)
+}
+
+/// `export NAME='value'` lines for `vars`.
+pub fn render(vars: &[EnvVar]) -> String {
+    vars.iter()
+        .map(|var| format!("export {}={}\n", var.name, shell_quote(&var.value)))
+        .collect()
+}
+
+/// `text` in single quotes, each `'` in it written as `'\''`.
+pub fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+struct Export<'a> {
+    options: &'a ExportOptions,
+    vars: Vec<EnvVar>,
+    /// Names given out so far, with the key path that took each.
+    names: HashMap<String, String>,
+}
+
+impl Export<'_> {
+    fn node(
+        &mut self,
+        value: &Value,
+        path: &mut String,
+        words: &mut Vec<String>,
+    ) -> Result<(), Box<Diagnostic>> {
+        let len = path.len();
+        match value {
+            Value::Tagged(tagged) => self.node(&tagged.value, path, words),
+            // The subtree itself is always flattened; --maps is for the mappings inside it.
+            Value::Mapping(map) if self.options.maps == EnvMaps::Flatten || words.is_empty() => {
+                for (key, child) in map {
+                    let key = match key {
+                        Value::String(text) => text.clone(),
+                        other => scalar_text(other),
+                    };
+                    push_key(path, &key);
+                    words.push(word(&key));
+                    self.node(child, path, words)?;
+                    words.pop();
+                    path.truncate(len);
+                }
+                Ok(())
+            }
+            Value::Sequence(items) => match self.options.lists {
+                EnvLists::Json => self.json(value, path, words),
+                EnvLists::Index => {
+                    for (index, child) in items.iter().enumerate() {
+                        push_index(path, index);
+                        words.push(index.to_string());
+                        self.node(child, path, words)?;
+                        words.pop();
+                        path.truncate(len);
+                    }
+                    Ok(())
+                }
+                EnvLists::Join => {
+                    let texts = items
+                        .iter()
+                        .map(|item| match untagged(item) {
+                            Value::Mapping(_) | Value::Sequence(_) => Err(unexportable(
+                                path,
+                                "--lists join only joins scalars, but an item is a collection."
+                                    .to_string(),
+                            )),
+                            scalar => Ok(scalar_text(scalar)),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let joined = texts.join(&self.options.list_separator);
+                    self.push(words, path, joined)
+                }
+                EnvLists::Error => Err(unexportable(
+                    path,
+                    "The value is a sequence, and --lists error exports none.".to_string(),
+                )),
+            },
+            Value::Mapping(_) => self.json(value, path, words),
+            scalar => self.push(words, path, scalar_text(scalar)),
+        }
+    }
+
+    fn json(&mut self, value: &Value, path: &str, words: &[String]) -> Result<(), Box<Diagnostic>> {
+        let json =
+            serde_json::to_string(value).map_err(|err| unexportable(path, err.to_string()))?;
+        self.push(words, path, json)
+    }
+
+    fn push(&mut self, words: &[String], path: &str, value: String) -> Result<(), Box<Diagnostic>> {
+        let derived_key_path = if path.is_empty() { ROOT_PATH } else { path }.to_string();
+        let name = if words.is_empty() {
+            self.options.prefix.trim_end_matches('_').to_string()
+        } else {
+            format!("{}{}", self.options.prefix, words.join("_"))
+        };
+        if !is_name(&name) {
+            let cause = if name.is_empty() {
+                "The whole subtree is one value, and there is no --prefix to name it.".to_string()
+            } else {
+                format!(
+                    "`{name}` is not a shell variable name, which must start with a letter or `_`."
+                )
+            };
+            return Err(Box::new(
+                Diagnostic::error(
+                    "E131",
+                    "key path does not give a valid environment variable name",
+                    Category::InvalidInput,
+                )
+                .with_derived_key_path(derived_key_path)
+                .with_cause(cause)
+                .with_action("Pass a --prefix such as `APP_`."),
+            ));
+        }
+        if let Some(taken) = self.names.get(&name) {
+            return Err(Box::new(
+                Diagnostic::error(
+                    "E132",
+                    "two key paths export the same environment variable",
+                    Category::InvalidInput,
+                )
+                .with_derived_key_path(derived_key_path.clone())
+                .with_cause(format!(
+                    "`{taken}` and `{derived_key_path}` are both exported as `{name}`."
+                ))
+                .with_action(
+                    "Rename one of the keys, or export the subtrees separately with --path.",
+                ),
+            ));
+        }
+        self.names.insert(name.clone(), derived_key_path.clone());
+        self.vars.push(EnvVar {
+            name,
+            value,
+            derived_key_path,
+        });
+        Ok(())
+    }
+}
+
+fn untagged(value: &Value) -> &Value {
+    match value {
+        Value::Tagged(tagged) => untagged(&tagged.value),
+        other => other,
+    }
+}
+
+/// A key as part of a variable name: upper-cased, other runs of characters as `_`.
+fn word(key: &str) -> String {
+    let mut word = String::new();
+    for c in key.chars() {
+        if c.is_ascii_alphanumeric() {
+            word.push(c.to_ascii_uppercase());
+        } else if !word.ends_with('_') {
+            word.push('_');
+        }
+    }
+    word
+}
+
+fn is_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Strings as they are, `null` as empty, other scalars as YAML writes them.
+fn scalar_text(value: &Value) -> String {
+    match untagged(value) {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        other => inline_scalar(other, &EmitterOptions::default())
+            .ok()
+            .flatten()
+            .unwrap_or_default(),
+    }
+}
+
+fn unexportable(path: &str, cause: String) -> Box<Diagnostic> {
+    let derived_key_path = if path.is_empty() { ROOT_PATH } else { path };
+    Box::new(
+        Diagnostic::error(
+            "E133",
+            "value cannot be exported as an environment variable",
+            Category::InvalidInput,
+        )
+        .with_derived_key_path(derived_key_path)
+        .with_cause(cause)
+        .with_action("Choose another --lists policy, or export a narrower --path."),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(text: &str) -> Value {
+        serde_yaml::from_str(text).expect("valid YAML")
+    }
+
+    fn options(lists: EnvLists, maps: EnvMaps) -> ExportOptions {
+        ExportOptions {
+            prefix: "APP_".to_string(),
+            lists,
+            list_separator: ",".to_string(),
+            maps,
+        }
+    }
+
+    #[test]
+    fn flattens_mappings_and_applies_list_policies() {
+        let value = yaml(
+            "database:\n  host: db.internal\n  port: 5432\n  max-conns: ~\n\
+             hosts: [a, b]\nmotd: \"it's up\"\n",
+        );
+        let vars = env_vars(
+            &value,
+            "env.prod",
+            &options(EnvLists::Json, EnvMaps::Flatten),
+        )
+        .expect("exported");
+        assert_eq!(
+            render(&vars),
+            "export APP_DATABASE_HOST='db.internal'\nexport APP_DATABASE_PORT='5432'\n\
+             export APP_DATABASE_MAX_CONNS=''\nexport APP_HOSTS='[\"a\",\"b\"]'\n\
+             export APP_MOTD='it'\\''s up'\n"
+        );
+        assert_eq!(vars[0].derived_key_path, "env.prod.database.host");
+
+        let names = |lists, maps| {
+            env_vars(&value, "", &options(lists, maps))
+                .expect("exported")
+                .into_iter()
+                .map(|var| format!("{}={}", var.name, var.value))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(EnvLists::Index, EnvMaps::Json),
+            vec![
+                "APP_DATABASE={\"host\":\"db.internal\",\"port\":5432,\"max-conns\":null}",
+                "APP_HOSTS_0=a",
+                "APP_HOSTS_1=b",
+                "APP_MOTD=it's up",
+            ]
+        );
+        assert_eq!(names(EnvLists::Join, EnvMaps::Flatten)[3], "APP_HOSTS=a,b");
+
+        let refused = env_vars(&value, "", &options(EnvLists::Error, EnvMaps::Flatten))
+            .expect_err("sequences refused");
+        assert_eq!(refused.code, "E133");
+        assert_eq!(refused.derived_key_path.as_deref(), Some("hosts"));
+    }
+
+    #[test]
+    fn rejects_invalid_and_colliding_names() {
+        let unprefixed = ExportOptions {
+            prefix: String::new(),
+            ..options(EnvLists::Index, EnvMaps::Flatten)
+        };
+        let invalid = env_vars(&yaml("[a]"), "", &unprefixed).expect_err("invalid name");
+        assert_eq!(invalid.code, "E131");
+
+        let colliding = env_vars(
+            &yaml("db-host: a\ndb_host: b\n"),
+            "",
+            &options(EnvLists::Json, EnvMaps::Flatten),
+        )
+        .expect_err("collision");
+        assert_eq!(colliding.code, "E132");
+        assert_eq!(colliding.derived_key_path.as_deref(), Some("db_host"));
+    }
+}
@@ -0,0 +1,275 @@
+//! Flat `key.path: value` views of packed documents.
+//!
+//! `flatten` writes one line per leaf, in document order, with the value as a single-line
+//! YAML scalar; empty collections and tagged values are leaves too. `unflatten` reads such
+//! lines back into a document. Mapping keys that are not strings come back as strings.
+
+use crate::diagnostics::{Category, Diagnostic};
+use crate::emitter::{inline_scalar, EmitError, EmitterOptions};
+use crate::keypath::{format_path, parse_path, push_index, push_key, Segment, ROOT_PATH};
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Writes the leaves of `value` as `path: value` lines.
+pub fn write_flat<W: Write>(
+    mut out: W,
+    value: &Value,
+    options: &EmitterOptions,
+) -> Result<(), EmitError> {
+    let mut path = String::new();
+    write_node(&mut out, value, &mut path, options)
+}
+
+fn write_node<W: Write>(
+    out: &mut W,
+    value: &Value,
+    path: &mut String,
+    options: &EmitterOptions,
+) -> Result<(), EmitError> {
+    let len = path.len();
+    match value {
+        Value::Mapping(map) if !map.is_empty() => {
+            for (key, child) in map {
+                push_key(path, &key_text(key, options)?);
+                write_node(out, child, path, options)?;
+                path.truncate(len);
+            }
+        }
+        Value::Sequence(items) if !items.is_empty() => {
+            for (index, child) in items.iter().enumerate() {
+                push_index(path, index);
+                write_node(out, child, path, options)?;
+                path.truncate(len);
+            }
+        }
+        leaf => {
+            let shown = if path.is_empty() { ROOT_PATH } else { path };
+            writeln!(out, "{shown}: {}", leaf_text(leaf, options)?)?;
+        }
+    }
+    Ok(())
+}
+
+fn key_text(key: &Value, options: &EmitterOptions) -> Result<String, EmitError> {
+    match key {
+        Value::String(text) => Ok(text.clone()),
+        other => leaf_text(other, options),
+    }
+}
+
+/// Top-level scalars are written as the block emitter writes them; block scalars become
+/// double-quoted strings so every leaf stays on one line.
+pub(crate) fn leaf_text(value: &Value, options: &EmitterOptions) -> Result<String, EmitError> {
+    Ok(match value {
+        Value::Tagged(tagged) => format!("{} {}", tagged.tag, leaf_text(&tagged.value, options)?),
+        Value::String(text) => match inline_scalar(value, options)? {
+            Some(inline) => inline,
+            None => quoted(text),
+        },
+        other => flow_text(other, options)?,
+    })
+}
+
+/// Flow-style text; strings are always quoted, since `,` and brackets end plain scalars in
+/// flow collections.
+fn flow_text(value: &Value, options: &EmitterOptions) -> Result<String, EmitError> {
+    Ok(match value {
+        Value::Mapping(map) => {
+            let entries = map
+                .iter()
+                .map(|(key, child)| {
+                    Ok(format!(
+                        "{}: {}",
+                        flow_text(key, options)?,
+                        flow_text(child, options)?
+                    ))
+                })
+                .collect::<Result<Vec<_>, EmitError>>()?;
+            format!("{{{}}}", entries.join(", "))
+        }
+        Value::Sequence(items) => {
+            let items = items
+                .iter()
+                .map(|item| flow_text(item, options))
+                .collect::<Result<Vec<_>, EmitError>>()?;
+            format!("[{}]", items.join(", "))
+        }
+        Value::Tagged(tagged) => format!("{} {}", tagged.tag, flow_text(&tagged.value, options)?),
+        Value::String(text) => quoted(text),
+        scalar => inline_scalar(scalar, options)?.unwrap_or_default(),
+    })
+}
+
+/// A JSON string, which YAML reads as the same double-quoted scalar.
+fn quoted(text: &str) -> String {
+    serde_json::to_string(text).expect("a string serializes")
+}
+
+/// Rebuilds a document from `path: value` lines. Blank lines and `#` comments are skipped.
+pub fn unflatten(text: &str, location: &str) -> Result<Value, Box<Diagnostic>> {
+    let mut root = Node::Empty;
+    for (number, line) in text.lines().enumerate().map(|(i, line)| (i + 1, line)) {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let (segments, value) = parse_line(line).map_err(|cause| {
+            Box::new(
+                Diagnostic::error("E111", "malformed flattened line", Category::Parse)
+                    .with_location(location.to_string())
+                    .with_position(number, 1)
+                    .with_cause(cause)
+                    .with_action("Write each line as `key.path: value`, as `fyaml flatten` does."),
+            )
+        })?;
+        root.insert(&segments, 0, value).map_err(|cause| {
+            Box::new(
+                Diagnostic::error("E112", "flattened paths conflict", Category::Parse)
+                    .with_location(location.to_string())
+                    .with_position(number, 1)
+                    .with_derived_key_path(format_path(&segments))
+                    .with_cause(cause)
+                    .with_action(
+                        "Give each path one value, and number sequence items from 0 without gaps.",
+                    ),
+            )
+        })?;
+    }
+    Ok(root.into_value())
+}
+
+fn parse_line(line: &str) -> Result<(Vec<Segment>, Value), String> {
+    let (segments, rest) = parse_path(line)?;
+    let Some(value) = rest.strip_prefix(':') else {
+        return Err("expected `:` after the key path".to_string());
+    };
+    if !value.is_empty() && !value.starts_with(' ') {
+        return Err("expected a space after `:`".to_string());
+    }
+    let value = serde_yaml::from_str::<Value>(value).map_err(|err| err.to_string())?;
+    Ok((segments, value))
+}
+
+/// A document under construction, which tells unassigned slots apart from `null` values.
+enum Node {
+    Empty,
+    Leaf(Value),
+    Map(Vec<(String, Node)>, HashMap<String, usize>),
+    Seq(Vec<Node>),
+}
+
+impl Node {
+    fn insert(&mut self, segments: &[Segment], depth: usize, value: Value) -> Result<(), String> {
+        let Some(segment) = segments.get(depth) else {
+            return match self {
+                Node::Empty => {
+                    *self = Node::Leaf(value);
+                    Ok(())
+                }
+                Node::Leaf(_) => Err("the path already has a value".to_string()),
+                _ => Err("the path already has entries below it".to_string()),
+            };
+        };
+
+        if matches!(self, Node::Empty) {
+            *self = match segment {
+                Segment::Key(_) => Node::Map(Vec::new(), HashMap::new()),
+                Segment::Index(_) => Node::Seq(Vec::new()),
+            };
+        }
+        let parent = || format_path(&segments[..depth]);
+        let child = match (self, segment) {
+            (Node::Map(entries, index), Segment::Key(key)) => {
+                let position = *index.entry(key.clone()).or_insert_with(|| {
+                    entries.push((key.clone(), Node::Empty));
+                    entries.len() - 1
+                });
+                &mut entries[position].1
+            }
+            (Node::Seq(items), Segment::Index(position)) => {
+                if *position > items.len() {
+                    return Err(format!(
+                        "`{}` has {} item(s), so the next index is {}, not {position}",
+                        parent(),
+                        items.len(),
+                        items.len()
+                    ));
+                }
+                if *position == items.len() {
+                    items.push(Node::Empty);
+                }
+                &mut items[*position]
+            }
+            (Node::Leaf(_), _) => return Err(format!("`{}` already has a value", parent())),
+            (Node::Map(..), Segment::Index(_)) => {
+                return Err(format!("`{}` is a mapping, not a sequence", parent()))
+            }
+            (_, _) => return Err(format!("`{}` is a sequence, not a mapping", parent())),
+        };
+        child.insert(segments, depth + 1, value)
+    }
+
+    fn into_value(self) -> Value {
+        match self {
+            Node::Empty => Value::Null,
+            Node::Leaf(value) => value,
+            Node::Map(entries, _) => Value::Mapping(
+                entries
+                    .into_iter()
+                    .map(|(key, node)| (Value::String(key), node.into_value()))
+                    .collect::<Mapping>(),
+            ),
+            Node::Seq(items) => Value::Sequence(items.into_iter().map(Node::into_value).collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flatten(value: &Value) -> String {
+        let mut out = Vec::new();
+        write_flat(&mut out, value, &EmitterOptions::default()).expect("flatten");
+        String::from_utf8(out).expect("utf8")
+    }
+
+    #[test]
+    fn flatten_and_unflatten_round_trip() {
+        let value: Value = serde_yaml::from_str(
+            "a:\n  b: [1, {c: 'x, y'}]\n  'd.e': \"two\\nlines\"\nempty: {}\nnone: []\nn: null\n\
+             ref: !Ref Bucket\nsub: !Sub {z: '1', a: [b]}\n",
+        )
+        .expect("valid yaml");
+        let flat = flatten(&value);
+        assert_eq!(
+            flat,
+            "a.b[0]: 1\na.b[1].c: x, y\na[\"d.e\"]: \"two\\nlines\"\nempty: {}\nnone: []\n\
+             n: null\nref: !Ref Bucket\nsub: !Sub {\"z\": \"1\", \"a\": [\"b\"]}\n"
+        );
+        assert_eq!(unflatten(&flat, "flat.txt").expect("unflatten"), value);
+
+        let scalar = Value::String("only".to_string());
+        assert_eq!(flatten(&scalar), "$: only\n");
+        assert_eq!(
+            unflatten(&flatten(&scalar), "flat.txt").expect("unflatten"),
+            scalar
+        );
+    }
+
+    #[test]
+    fn unflatten_rejects_conflicts_and_gaps() {
+        let conflict = unflatten("a: 1\na.b: 2\n", "flat.txt").expect_err("conflict");
+        assert_eq!(conflict.code, "E112");
+        assert_eq!(conflict.position.map(|p| p.line), Some(2));
+
+        let gap = unflatten("a[0]: 1\na[2]: 3\n", "flat.txt").expect_err("gap");
+        assert_eq!(gap.code, "E112");
+        assert!(gap.cause.contains("next index is 1"));
+
+        let malformed = unflatten("# comment\n\na b\n", "flat.txt").expect_err("malformed");
+        assert_eq!(malformed.code, "E111");
+        assert_eq!(malformed.position.map(|p| p.line), Some(3));
+    }
+}
@@ -0,0 +1,144 @@
+//! `--gha-output`: the key results of a run as GitHub Actions step outputs, appended to the
+//! file `$GITHUB_OUTPUT` names, and a job summary table, appended to `$GITHUB_STEP_SUMMARY`.
+//!
+//! Outputs are always written, so later steps can read them with
+//! `steps.<id>.outputs.error_count` and the like; values FYAML has none for are empty.
+
+use crate::diagnostics::{Diagnostic, Severity};
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+pub const OUTPUT_ENV: &str = "GITHUB_OUTPUT";
+pub const SUMMARY_ENV: &str = "GITHUB_STEP_SUMMARY";
+
+#[derive(Debug, Clone)]
+pub struct GhaReport<'a> {
+    pub command: &'static str,
+    pub diagnostics: &'a [Diagnostic],
+    /// Where the packed document was written; `None` for stdout or commands that write none.
+    pub output_path: Option<String>,
+    /// The fingerprint of the packed document, as `fyaml verify` computes it.
+    pub content_hash: Option<String>,
+}
+
+impl GhaReport<'_> {
+    fn count(&self, severity: Severity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .count()
+    }
+
+    /// `name=value` lines in the `$GITHUB_OUTPUT` format.
+    pub fn outputs(&self) -> String {
+        format!(
+            "error_count={}\nwarning_count={}\noutput_path={}\ncontent_hash={}\n",
+            self.count(Severity::Error),
+            self.count(Severity::Warn),
+            one_line(self.output_path.as_deref().unwrap_or("")),
+            self.content_hash.as_deref().unwrap_or(""),
+        )
+    }
+
+    /// A Markdown table of the results, followed by one of the diagnostics if there are any.
+    pub fn summary(&self) -> String {
+        let errors = self.count(Severity::Error);
+        let mut text = format!(
+            "### fyaml {}\n\n| Result | Value |\n| --- | --- |\n| Status | {} |\n| Errors | {errors} |\n| Warnings | {} |\n",
+            self.command,
+            if errors == 0 { "passed" } else { "failed" },
+            self.count(Severity::Warn),
+        );
+        if let Some(path) = &self.output_path {
+            writeln!(text, "| Output | `{}` |", cell(path)).expect("writing to a String succeeds");
+        }
+        if let Some(hash) = &self.content_hash {
+            writeln!(text, "| Content hash | `{hash}` |").expect("writing to a String succeeds");
+        }
+        let listed = self
+            .diagnostics
+            .iter()
+            .filter(|d| d.severity != Severity::Info)
+            .collect::<Vec<_>>();
+        if !listed.is_empty() {
+            text.push_str("\n| Code | Location | Message |\n| --- | --- | --- |\n");
+            for diagnostic in listed {
+                writeln!(
+                    text,
+                    "| {} | {} | {} |",
+                    diagnostic.code,
+                    cell(diagnostic.location.as_deref().unwrap_or("")),
+                    cell(&diagnostic.message)
+                )
+                .expect("writing to a String succeeds");
+            }
+        }
+        text.push('\n');
+        text
+    }
+
+    /// Appends the outputs and the summary to the files the Actions runner names. Fails if
+    /// `$GITHUB_OUTPUT` is not set; the summary is skipped when `$GITHUB_STEP_SUMMARY` is not.
+    pub fn write(&self) -> Result<(), String> {
+        let outputs = std::env::var_os(OUTPUT_ENV).ok_or_else(|| {
+            format!("{OUTPUT_ENV} is not set; --gha-output only works in a GitHub Actions step.")
+        })?;
+        append(Path::new(&outputs), &self.outputs())
+            .map_err(|err| format!("{}: {err}", Path::new(&outputs).display()))?;
+        if let Some(summary) = std::env::var_os(SUMMARY_ENV) {
+            append(Path::new(&summary), &self.summary())
+                .map_err(|err| format!("{}: {err}", Path::new(&summary).display()))?;
+        }
+        Ok(())
+    }
+}
+
+fn append(path: &Path, text: &str) -> std::io::Result<()> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(text.as_bytes())
+}
+
+/// An output value must stay on its line.
+fn one_line(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
+
+/// Table cells must not end the row or the cell early.
+fn cell(text: &str) -> String {
+    one_line(text).replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_outputs_and_a_summary_table() {
+        let diagnostics = [
+            Diagnostic::warn("W050", "entries ignored while scanning").with_location("."),
+            Diagnostic::info("I201", "not listed"),
+        ];
+        let report = GhaReport {
+            command: "pack",
+            diagnostics: &diagnostics,
+            output_path: Some("out|put.yml".to_string()),
+            content_hash: Some("sha256:00ff".to_string()),
+        };
+        assert_eq!(
+            report.outputs(),
+            "error_count=0\nwarning_count=1\noutput_path=out|put.yml\ncontent_hash=sha256:00ff\n"
+        );
+        assert_eq!(
+            report.summary(),
+            "### fyaml pack\n\n| Result | Value |\n| --- | --- |\n| Status | passed |\n\
+             | Errors | 0 |\n| Warnings | 1 |\n| Output | `out\\|put.yml` |\n\
+             | Content hash | `sha256:00ff` |\n\n| Code | Location | Message |\n| --- | --- | --- |\n\
+             | W050 | . | entries ignored while scanning |\n\n"
+        );
+    }
+}
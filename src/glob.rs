@@ -0,0 +1,170 @@
+//! Path globs for `--include` and `--exclude`.
+//!
+//! Patterns match `/`-separated paths relative to the FYAML root, one segment at a time:
+//! `*` matches any run of characters within a segment, `?` matches one character, and a
+//! `**` segment matches any number of segments, including none.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Glob {
+    pattern: String,
+    segments: Vec<String>,
+}
+
+impl Glob {
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether the root-relative `path` matches the whole pattern.
+    pub fn matches(&self, path: &str) -> bool {
+        let path: Vec<&str> = path.split('/').collect();
+        match_segments(&self.segments, &path)
+    }
+
+    /// Whether some path below the directory `dir` could match, so the directory is worth
+    /// scanning.
+    pub fn may_match_below(&self, dir: &str) -> bool {
+        let dir: Vec<&str> = dir.split('/').collect();
+        match_prefix(&self.segments, &dir)
+    }
+}
+
+fn match_segments(pattern: &[String], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| match_segments(rest, &path[skip..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((segment, path)) => match_segment(first, segment) && match_segments(rest, path),
+            None => false,
+        },
+    }
+}
+
+fn match_prefix(pattern: &[String], dir: &[&str]) -> bool {
+    match (pattern.split_first(), dir.split_first()) {
+        (Some(_), None) => true,
+        (None, _) => false,
+        (Some((first, _)), Some(_)) if first == "**" => true,
+        (Some((first, rest)), Some((segment, dir))) => {
+            match_segment(first, segment) && match_prefix(rest, dir)
+        }
+    }
+}
+
+/// Matches one segment against `*` and `?` wildcards.
+fn match_segment(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    // Backtracking to the most recent `*` is enough, since `*` cannot cross segments.
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+impl FromStr for Glob {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let trimmed = value.strip_prefix("./").unwrap_or(value);
+        let trimmed = trimmed.strip_suffix('/').unwrap_or(trimmed);
+        if trimmed.is_empty() {
+            return Err("a glob cannot be empty".to_string());
+        }
+        if trimmed.starts_with('/') {
+            return Err(format!(
+                "glob `{value}` must be relative to the FYAML root, not absolute"
+            ));
+        }
+        let segments: Vec<String> = trimmed.split('/').map(str::to_string).collect();
+        if let Some(bad) = segments
+            .iter()
+            .find(|segment| segment.is_empty() || segment.as_str() == "..")
+        {
+            return Err(format!("glob `{value}` has an invalid segment `{bad}`"));
+        }
+        Ok(Self {
+            pattern: value.to_string(),
+            segments,
+        })
+    }
+}
+
+impl TryFrom<String> for Glob {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Glob> for String {
+    fn from(glob: Glob) -> Self {
+        glob.pattern
+    }
+}
+
+impl fmt::Display for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(pattern: &str) -> Glob {
+        pattern.parse().expect("valid glob")
+    }
+
+    #[test]
+    fn globs_match_segments_wildcards_and_double_stars() {
+        assert!(glob("env/dev/**").matches("env/dev/db.yml"));
+        assert!(glob("env/dev/**").matches("env/dev"));
+        assert!(!glob("env/dev/**").matches("env/prod/db.yml"));
+        assert!(glob("**/*.yml").matches("a.yml"));
+        assert!(glob("**/*.yml").matches("a/b/c.yml"));
+        assert!(!glob("*.yml").matches("a/b.yml"));
+        assert!(glob("app/*-?.yaml").matches("app/db-1.yaml"));
+        assert!(!glob("app/*-?.yaml").matches("app/db-10.yaml"));
+        assert!(glob("./services/").matches("services"));
+
+        assert!(glob("env/*/db.yml").may_match_below("env"));
+        assert!(glob("env/*/db.yml").may_match_below("env/dev"));
+        assert!(!glob("env/*/db.yml").may_match_below("app"));
+        assert!(!glob("env/*/db.yml").may_match_below("env/dev/db.yml"));
+        assert!(glob("**/db.yml").may_match_below("anything/at/all"));
+
+        assert!("".parse::<Glob>().is_err());
+        assert!("/abs/*.yml".parse::<Glob>().is_err());
+        assert!("a//b".parse::<Glob>().is_err());
+        assert!("../up".parse::<Glob>().is_err());
+    }
+}
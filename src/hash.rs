@@ -0,0 +1,207 @@
+//! Structural hashes of packed values.
+//!
+//! A hash covers a node's type, scalar text, tag, and children. Mapping entries are combined
+//! independently of their order, so a hash does not depend on collation or on whether the
+//! value was canonicalized; number rules still apply, since they change scalar text.
+
+use crate::diff::child_path;
+use crate::digest::hex;
+use serde::Serialize;
+use serde_yaml::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+pub type StructuralHash = [u8; 32];
+
+pub fn structural_hash(value: &Value) -> StructuralHash {
+    hash_node(value, &mut |_, _| {})
+}
+
+/// `sha256:<hex>` of the structural hash of `value`, as `pack` records it in the header and
+/// `verify` recomputes it.
+pub fn fingerprint(value: &Value) -> String {
+    format!("sha256:{}", hex(&structural_hash(value)))
+}
+
+/// The hash of every node in one value, looked up by node.
+pub struct SubtreeHashes<'a> {
+    /// Keyed by node address, which stays fixed while the value is borrowed.
+    by_node: HashMap<usize, StructuralHash>,
+    root: PhantomData<&'a Value>,
+}
+
+impl<'a> SubtreeHashes<'a> {
+    pub fn new(root: &'a Value) -> Self {
+        let mut by_node = HashMap::new();
+        hash_node(root, &mut |node, hash| {
+            by_node.insert(node as *const Value as usize, hash);
+        });
+        Self {
+            by_node,
+            root: PhantomData,
+        }
+    }
+
+    /// `None` for scalars and tagged values, which are cheaper to compare directly, and for
+    /// values outside the hashed tree.
+    pub fn get(&self, node: &'a Value) -> Option<&StructuralHash> {
+        self.by_node.get(&(node as *const Value as usize))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PathHash {
+    pub path: String,
+    pub hash: String,
+}
+
+/// Hashes of `value` and its subtrees down to `depth` levels, in the value's key order.
+pub fn path_hashes(value: &Value, depth: usize) -> Vec<PathHash> {
+    let hashes = SubtreeHashes::new(value);
+    let mut out = Vec::new();
+    collect_path_hashes(value, &hashes, "$".to_string(), depth, &mut out);
+    out
+}
+
+fn collect_path_hashes<'a>(
+    value: &'a Value,
+    hashes: &SubtreeHashes<'a>,
+    path: String,
+    depth: usize,
+    out: &mut Vec<PathHash>,
+) {
+    let hash = hashes
+        .get(value)
+        .copied()
+        .unwrap_or_else(|| structural_hash(value));
+    out.push(PathHash {
+        path: path.clone(),
+        hash: hex(&hash),
+    });
+    if depth == 0 {
+        return;
+    }
+    match value {
+        Value::Mapping(map) => {
+            for (key, child) in map {
+                collect_path_hashes(child, hashes, child_path(&path, key), depth - 1, out);
+            }
+        }
+        Value::Sequence(items) => {
+            for (index, child) in items.iter().enumerate() {
+                let child_path = format!("{path}[{index}]");
+                collect_path_hashes(child, hashes, child_path, depth - 1, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Hashes `value`, recording the hash of every mapping and sequence inside it. Scalars and
+/// tags are written into their parent's hash instead of being hashed on their own.
+fn hash_node(value: &Value, record: &mut impl FnMut(&Value, StructuralHash)) -> StructuralHash {
+    let mut hasher = Sha256::new();
+    match value {
+        Value::Sequence(items) => {
+            hasher.update([5]);
+            hasher.update((items.len() as u64).to_le_bytes());
+            for item in items {
+                feed(item, &mut hasher, record);
+            }
+        }
+        Value::Mapping(map) => {
+            let mut entries: Vec<StructuralHash> = map
+                .iter()
+                .map(|(key, child)| {
+                    let mut entry = Sha256::new();
+                    feed(key, &mut entry, record);
+                    feed(child, &mut entry, record);
+                    entry.finalize().into()
+                })
+                .collect();
+            entries.sort_unstable();
+            hasher.update([6]);
+            hasher.update((entries.len() as u64).to_le_bytes());
+            for entry in entries {
+                hasher.update(entry);
+            }
+        }
+        _ => feed(value, &mut hasher, record),
+    }
+    let hash = hasher.finalize().into();
+    if matches!(value, Value::Sequence(_) | Value::Mapping(_)) {
+        record(value, hash);
+    }
+    hash
+}
+
+/// Writes an unambiguous encoding of `value`: collections by their hash, everything else
+/// inline with a type byte and a length prefix.
+fn feed(value: &Value, hasher: &mut Sha256, record: &mut impl FnMut(&Value, StructuralHash)) {
+    match value {
+        Value::Sequence(_) | Value::Mapping(_) => {
+            hasher.update([8]);
+            hasher.update(hash_node(value, record));
+        }
+        Value::Null => hasher.update([0]),
+        Value::Bool(flag) => hasher.update([1, u8::from(*flag)]),
+        Value::Number(number) => {
+            hasher.update([if number.is_f64() { 3 } else { 2 }]);
+            feed_bytes(number.to_string().as_bytes(), hasher);
+        }
+        Value::String(text) => {
+            hasher.update([4]);
+            feed_bytes(text.as_bytes(), hasher);
+        }
+        Value::Tagged(tagged) => {
+            hasher.update([7]);
+            feed_bytes(tagged.tag.to_string().as_bytes(), hasher);
+            feed(&tagged.value, hasher, record);
+        }
+    }
+}
+
+fn feed_bytes(bytes: &[u8], hasher: &mut Sha256) {
+    hasher.update((bytes.len() as u64).to_le_bytes());
+    hasher.update(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Value {
+        serde_yaml::from_str(text).expect("valid yaml")
+    }
+
+    #[test]
+    fn hashes_ignore_key_order_but_not_content() {
+        let base = structural_hash(&parse("a: 1\nb: [x, !Ref y]\n"));
+        assert_eq!(base, structural_hash(&parse("b: [x, !Ref y]\na: 1\n")));
+        for changed in [
+            "a: 1\nb: [y, !Ref x]\n",
+            "a: 1.0\nb: [x, !Ref y]\n",
+            "a: '1'\nb: [x, !Ref y]\n",
+            "a: 1\nb: [x, !Sub y]\n",
+            "a: 1\nb: [x, y]\n",
+            "a: 1\nc: [x, !Ref y]\n",
+        ] {
+            assert_ne!(base, structural_hash(&parse(changed)), "{changed}");
+        }
+    }
+
+    #[test]
+    fn path_hashes_list_subtrees_to_depth() {
+        let value = parse("a: {x: 1}\nb: [1, 2]\n");
+        let paths = path_hashes(&value, 1)
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect::<Vec<_>>();
+        assert_eq!(paths, vec!["$", "$.a", "$.b"]);
+
+        let nested = path_hashes(&value, 2);
+        assert_eq!(nested.len(), 6);
+        assert_eq!(nested[0].hash, hex(&structural_hash(&value)));
+    }
+}
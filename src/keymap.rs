@@ -0,0 +1,73 @@
+//! Keys whose file or directory name had to differ from the key itself.
+//!
+//! `scaffold` writes a keymap when sibling keys differ only by letter case, since such
+//! siblings would overwrite each other on case-insensitive filesystems (macOS, Windows).
+//! The renamed entry keeps the key in the keymap, and `pack` reads it back from there.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+pub const KEYMAP_FILE_NAME: &str = "fyaml.keymap.json";
+pub const KEYMAP_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Keymap {
+    pub version: u32,
+    /// Entry path relative to the root, with `/` separators and without the YAML
+    /// extension, mapped to the key it stands for.
+    pub keys: BTreeMap<String, String>,
+}
+
+impl Keymap {
+    pub fn new() -> Self {
+        Self {
+            version: KEYMAP_VERSION,
+            keys: BTreeMap::new(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self).map(|json| json + "\n")
+    }
+
+    pub fn get(&self, entry: &str) -> Option<&str> {
+        self.keys.get(entry).map(String::as_str)
+    }
+}
+
+/// Filesystem names for sibling `keys`, which must be sorted. Keys that differ only by case
+/// from an earlier key get the first free `~2`, `~3`, ... suffix, compared case-insensitively.
+pub fn case_safe_names(keys: &[String]) -> Vec<String> {
+    let all: HashSet<String> = keys.iter().map(|key| key.to_lowercase()).collect();
+    let mut taken = HashSet::new();
+    keys.iter()
+        .map(|key| {
+            if taken.insert(key.to_lowercase()) {
+                return key.clone();
+            }
+            (2..)
+                .map(|n| format!("{key}~{n}"))
+                .find(|name| {
+                    let folded = name.to_lowercase();
+                    !all.contains(&folded) && taken.insert(folded)
+                })
+                .expect("an unused suffix exists")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn case_safe_names_suffix_case_variants_in_order() {
+        let keys = ["API", "Api", "Api~2", "api", "other"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(
+            case_safe_names(&keys),
+            vec!["API", "Api~3", "Api~2", "api~4", "other"]
+        );
+    }
+}
@@ -0,0 +1,389 @@
+//! Key paths such as `a.b[0].c` that name one node of a packed document.
+//!
+//! Keys made of ordinary characters are written bare; any other key is written as a JSON
+//! string in brackets (`a["b.c"]`), so every path parses back to the segments it came from.
+//! The root itself is written `$`.
+
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use std::fmt::Write as _;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+pub const ROOT_PATH: &str = "$";
+
+/// Appends `key` to `path`.
+pub fn push_key(path: &mut String, key: &str) {
+    if is_bare_key(key) {
+        if !path.is_empty() {
+            path.push('.');
+        }
+        path.push_str(key);
+    } else {
+        path.push('[');
+        path.push_str(&serde_json::to_string(key).expect("a string serializes"));
+        path.push(']');
+    }
+}
+
+/// Appends the sequence position `index` to `path`.
+pub fn push_index(path: &mut String, index: usize) {
+    write!(path, "[{index}]").expect("writing to a String succeeds");
+}
+
+pub fn format_path(segments: &[Segment]) -> String {
+    let mut path = String::new();
+    for segment in segments {
+        match segment {
+            Segment::Key(key) => push_key(&mut path, key),
+            Segment::Index(index) => push_index(&mut path, *index),
+        }
+    }
+    if path.is_empty() {
+        path.push_str(ROOT_PATH);
+    }
+    path
+}
+
+/// Parses the key path at the start of `text`, returning its segments and the text after it.
+pub fn parse_path(text: &str) -> Result<(Vec<Segment>, &str), String> {
+    if let Some(rest) = text.strip_prefix(ROOT_PATH) {
+        if !rest.starts_with(|c: char| is_bare_char(c) || c == '.' || c == '[') {
+            return Ok((Vec::new(), rest));
+        }
+    }
+
+    let mut segments = Vec::new();
+    let mut rest = text;
+    loop {
+        let after_dot = match rest.strip_prefix('.') {
+            Some(after) if !segments.is_empty() => Some(after),
+            Some(_) => return Err("a key path cannot start with `.`".to_string()),
+            None => None,
+        };
+        if let Some(after) = rest.strip_prefix('[') {
+            let (segment, after) = parse_bracket(after)?;
+            segments.push(segment);
+            rest = after;
+        } else if segments.is_empty() || after_dot.is_some() {
+            let start = after_dot.unwrap_or(rest);
+            let len = start
+                .find(|c: char| !is_bare_char(c))
+                .unwrap_or(start.len());
+            if len == 0 {
+                return Err(format!("expected a key at `{}`", preview(start)));
+            }
+            segments.push(Segment::Key(start[..len].to_string()));
+            rest = &start[len..];
+        } else {
+            return Ok((segments, rest));
+        }
+    }
+}
+
+/// A key path naming one node, such as `services.api` or `jobs[0]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyPath {
+    text: String,
+    segments: Vec<Segment>,
+}
+
+impl KeyPath {
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// The node of `value` this path names. A key segment also matches a number or boolean
+    /// key written the same way.
+    pub fn select<'a>(&self, value: &'a Value) -> Result<&'a Value, Missing<'a>> {
+        let mut node = value;
+        for (depth, segment) in self.segments.iter().enumerate() {
+            let child = match (segment, node) {
+                (Segment::Key(key), Value::Mapping(map)) => map
+                    .iter()
+                    .find(|(candidate, _)| key_matches(candidate, key))
+                    .map(|(_, child)| child),
+                (Segment::Index(index), Value::Sequence(items)) => items.get(*index),
+                _ => None,
+            };
+            node = child.ok_or(Missing {
+                found: format_path(&self.segments[..depth]),
+                parent: node,
+            })?;
+        }
+        Ok(node)
+    }
+
+    /// Parses a path of keys only, such as `spec.config`, for nesting a document under it.
+    pub fn parse_keys(text: &str) -> Result<Self, String> {
+        let path: Self = text.parse()?;
+        if path.segments.is_empty() {
+            return Err("the key path names the document root".to_string());
+        }
+        if path
+            .segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Index(_)))
+        {
+            return Err(format!(
+                "`{text}` has a sequence index; only keys can be nested"
+            ));
+        }
+        Ok(path)
+    }
+
+    /// Nests `value` under this path's keys, innermost last; index segments are skipped.
+    pub fn wrap(&self, value: Value) -> Value {
+        self.segments
+            .iter()
+            .rev()
+            .fold(value, |inner, segment| match segment {
+                Segment::Key(key) => {
+                    let mut map = serde_yaml::Mapping::new();
+                    map.insert(Value::String(key.clone()), inner);
+                    Value::Mapping(map)
+                }
+                Segment::Index(_) => inner,
+            })
+    }
+}
+
+/// Where [`KeyPath::select`] stopped: the longest prefix of the path that exists, and the
+/// node there.
+#[derive(Debug)]
+pub struct Missing<'a> {
+    pub found: String,
+    pub parent: &'a Value,
+}
+
+fn key_matches(candidate: &Value, key: &str) -> bool {
+    match candidate {
+        Value::String(text) => text == key,
+        Value::Number(number) => number.to_string() == key,
+        Value::Bool(flag) => flag.to_string() == key,
+        _ => false,
+    }
+}
+
+impl std::str::FromStr for KeyPath {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match parse_path(text)? {
+            (segments, "") => Ok(Self {
+                text: text.to_string(),
+                segments,
+            }),
+            (_, rest) => Err(format!("unexpected `{}` in key path", preview(rest))),
+        }
+    }
+}
+
+impl std::fmt::Display for KeyPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// A key path in which a bare `*` segment stands for any one key or sequence index, such as
+/// `workflows.*.jobs`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PathPattern {
+    text: String,
+    segments: Vec<Segment>,
+}
+
+impl PathPattern {
+    pub fn matches(&self, path: &[Segment]) -> bool {
+        self.segments.len() == path.len()
+            && self
+                .segments
+                .iter()
+                .zip(path)
+                .all(|(pattern, segment)| match pattern {
+                    Segment::Key(key) if key == "*" => true,
+                    pattern => pattern == segment,
+                })
+    }
+
+    /// Whether `path` leads to a match, is one, or lies below one.
+    pub fn overlaps(&self, path: &[Segment]) -> bool {
+        self.segments
+            .iter()
+            .zip(path)
+            .all(|(pattern, segment)| match pattern {
+                Segment::Key(key) if key == "*" => true,
+                pattern => pattern == segment,
+            })
+    }
+}
+
+impl std::str::FromStr for PathPattern {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let KeyPath { text, segments } = text.parse()?;
+        Ok(Self { text, segments })
+    }
+}
+
+impl TryFrom<String> for PathPattern {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<PathPattern> for String {
+    fn from(pattern: PathPattern) -> Self {
+        pattern.text
+    }
+}
+
+impl std::fmt::Display for PathPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+fn parse_bracket(text: &str) -> Result<(Segment, &str), String> {
+    if text.starts_with('"') {
+        let mut strings = serde_json::Deserializer::from_str(text).into_iter::<String>();
+        let key = match strings.next() {
+            Some(Ok(key)) => key,
+            _ => return Err(format!("invalid quoted key at `{}`", preview(text))),
+        };
+        let rest = &text[strings.byte_offset()..];
+        return match rest.strip_prefix(']') {
+            Some(rest) => Ok((Segment::Key(key), rest)),
+            None => Err(format!(
+                "expected `]` after quoted key at `{}`",
+                preview(rest)
+            )),
+        };
+    }
+
+    let len = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let index = text[..len]
+        .parse::<usize>()
+        .map_err(|_| format!("expected an index or quoted key at `{}`", preview(text)))?;
+    match text[len..].strip_prefix(']') {
+        Some(rest) => Ok((Segment::Index(index), rest)),
+        None => Err(format!(
+            "expected `]` after index at `{}`",
+            preview(&text[len..])
+        )),
+    }
+}
+
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty() && key != ROOT_PATH && key.chars().all(is_bare_char)
+}
+
+fn is_bare_char(c: char) -> bool {
+    !c.is_whitespace() && !c.is_control() && !matches!(c, '.' | '[' | ']' | '"' | ':' | '\\')
+}
+
+fn preview(text: &str) -> String {
+    text.chars().take(20).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_match_any_one_segment_at_a_star() {
+        let pattern: PathPattern = "workflows.*.jobs".parse().expect("pattern");
+        let path = |segments: &[Segment]| pattern.matches(segments);
+        assert!(path(&[
+            Segment::Key("workflows".to_string()),
+            Segment::Key("build".to_string()),
+            Segment::Key("jobs".to_string()),
+        ]));
+        assert!(path(&[
+            Segment::Key("workflows".to_string()),
+            Segment::Index(0),
+            Segment::Key("jobs".to_string()),
+        ]));
+        assert!(!path(&[Segment::Key("workflows".to_string())]));
+        assert!("a b".parse::<PathPattern>().is_err());
+    }
+
+    #[test]
+    fn patterns_overlap_paths_above_at_and_below_a_match() {
+        let pattern: PathPattern = "env.*.db".parse().expect("pattern");
+        let key = |key: &str| Segment::Key(key.to_string());
+        assert!(pattern.overlaps(&[]));
+        assert!(pattern.overlaps(&[key("env")]));
+        assert!(pattern.overlaps(&[key("env"), key("prod")]));
+        assert!(pattern.overlaps(&[key("env"), key("prod"), key("db"), key("host")]));
+        assert!(!pattern.overlaps(&[key("workflows")]));
+        assert!(!pattern.overlaps(&[key("env"), key("prod"), key("cache")]));
+    }
+
+    #[test]
+    fn key_paths_select_a_node_or_say_where_they_stopped() {
+        let value: Value =
+            serde_yaml::from_str("services:\n  api: {ports: [80, 443]}\n  1: one\n").expect("yaml");
+        let select = |text: &str| {
+            text.parse::<KeyPath>()
+                .expect("key path")
+                .select(&value)
+                .ok()
+                .cloned()
+        };
+        assert_eq!(select("services.api.ports[1]"), Some(Value::from(443)));
+        assert_eq!(select("services.1"), Some(Value::from("one")));
+        let missing = "services.web.port".parse::<KeyPath>().expect("key path");
+        let missing = missing.select(&value).expect_err("missing");
+        assert_eq!(missing.found, "services");
+
+        let prefix = KeyPath::parse_keys("spec[\"config.yml\"]").expect("keys");
+        assert_eq!(
+            prefix.wrap(Value::from(1)),
+            serde_yaml::from_str::<Value>("spec: {config.yml: 1}").expect("yaml")
+        );
+        assert!(KeyPath::parse_keys("jobs[0]").is_err());
+        assert!(KeyPath::parse_keys("$").is_err());
+    }
+
+    #[test]
+    fn paths_round_trip_through_quoting() {
+        let segments = vec![
+            Segment::Key("services".to_string()),
+            Segment::Key("api.v2".to_string()),
+            Segment::Index(3),
+            Segment::Key("$".to_string()),
+            Segment::Key("port".to_string()),
+            Segment::Key(String::new()),
+        ];
+        let path = format_path(&segments);
+        assert_eq!(path, r#"services["api.v2"][3]["$"].port[""]"#);
+        assert_eq!(parse_path(&path), Ok((segments, "")));
+
+        assert_eq!(parse_path("$: 1"), Ok((Vec::new(), ": 1")));
+        assert_eq!(
+            parse_path("$ref: 1"),
+            Ok((vec![Segment::Key("$ref".into())], ": 1"))
+        );
+        assert_eq!(
+            parse_path("a.b: x"),
+            Ok((
+                vec![Segment::Key("a".into()), Segment::Key("b".into())],
+                ": x"
+            ))
+        );
+        assert!(parse_path(".a").is_err());
+        assert!(parse_path("a[x]").is_err());
+        assert!(parse_path("a.").is_err());
+    }
+}
@@ -1,7 +1,45 @@
+pub mod anonymize;
 pub mod app;
+pub mod ascii;
+pub mod catalog;
 pub mod cli;
+pub mod codes;
+pub mod compress;
 pub mod config;
+#[cfg(unix)]
+pub mod daemon;
 pub mod diagnostics;
+pub mod diff;
+pub mod digest;
+pub mod emitter;
 pub mod engine;
+pub mod env_report;
+pub mod error;
+pub mod export_env;
+pub mod flat;
+pub mod gha;
+pub mod glob;
+pub mod hash;
+pub mod keymap;
+pub mod keypath;
+pub mod loader;
+pub mod manifest;
+pub mod metrics;
+pub mod orphans;
+pub mod overlay;
+pub mod plan;
+pub mod preserve;
 pub mod scaffold;
 pub mod serializer;
+pub mod snapshot;
+pub mod source;
+pub mod source_map;
+pub mod split;
+pub mod table;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod timestamp;
+pub mod unwrap;
+pub mod watch;
+
+pub use engine::build_from_entries;
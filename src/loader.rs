@@ -0,0 +1,185 @@
+//! YAML document loading with control over values `serde_yaml::Value` cannot represent.
+//!
+//! The visitor mirrors `serde_yaml`'s own `Value` deserializer (including duplicate-key
+//! rejection and tag handling) and only differs for integers wider than 64 bits.
+
+use crate::config::BigIntegers;
+use serde::de::{
+    self, DeserializeSeed, Deserializer, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor,
+};
+use serde_yaml::value::{Tag, TaggedValue};
+use serde_yaml::{Mapping, Number, Value};
+use std::fmt;
+
+/// Loads every document in `contents`, stopping at the first parse error.
+pub fn load_documents(
+    contents: &str,
+    big_integers: BigIntegers,
+) -> Result<Vec<Value>, serde_yaml::Error> {
+    serde_yaml::Deserializer::from_str(contents)
+        .map(|document| ValueSeed { big_integers }.deserialize(document))
+        .collect()
+}
+
+#[derive(Clone, Copy)]
+struct ValueSeed {
+    big_integers: BigIntegers,
+}
+
+impl<'de> DeserializeSeed<'de> for ValueSeed {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl ValueSeed {
+    fn big_integer<E: de::Error>(self, text: String) -> Result<Value, E> {
+        match self.big_integers {
+            BigIntegers::String => Ok(Value::String(text)),
+            BigIntegers::Error => Err(E::custom(format!(
+                "integer `{text}` does not fit in 64 bits (use --big-integers=string to keep it as a string)"
+            ))),
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for ValueSeed {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any YAML value")
+    }
+
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<Value, E> {
+        Ok(Value::Bool(b))
+    }
+
+    fn visit_i64<E: de::Error>(self, i: i64) -> Result<Value, E> {
+        Ok(Value::Number(i.into()))
+    }
+
+    fn visit_u64<E: de::Error>(self, u: u64) -> Result<Value, E> {
+        Ok(Value::Number(u.into()))
+    }
+
+    fn visit_i128<E: de::Error>(self, i: i128) -> Result<Value, E> {
+        match i64::try_from(i) {
+            Ok(i) => self.visit_i64(i),
+            Err(_) => self.big_integer(i.to_string()),
+        }
+    }
+
+    fn visit_u128<E: de::Error>(self, u: u128) -> Result<Value, E> {
+        match u64::try_from(u) {
+            Ok(u) => self.visit_u64(u),
+            Err(_) => self.big_integer(u.to_string()),
+        }
+    }
+
+    fn visit_f64<E: de::Error>(self, f: f64) -> Result<Value, E> {
+        Ok(Value::Number(Number::from(f)))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Value, E> {
+        Ok(Value::String(s.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, s: String) -> Result<Value, E> {
+        Ok(Value::String(s))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut access: A) -> Result<Value, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = access.next_element_seed(self)? {
+            items.push(item);
+        }
+        Ok(Value::Sequence(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Value, A::Error> {
+        let mut mapping = Mapping::new();
+        while let Some(key) = access.next_key_seed(self)? {
+            if mapping.contains_key(&key) {
+                return Err(de::Error::custom(duplicate_key_message(&key)));
+            }
+            let value = access.next_value_seed(self)?;
+            mapping.insert(key, value);
+        }
+        Ok(Value::Mapping(mapping))
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, access: A) -> Result<Value, A::Error> {
+        let (tag, contents) = access.variant::<String>()?;
+        let value = contents.newtype_variant_seed(self)?;
+        Ok(Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new(tag),
+            value,
+        })))
+    }
+}
+
+fn duplicate_key_message(key: &Value) -> String {
+    match key {
+        Value::Null => "duplicate entry with null key".to_string(),
+        Value::Bool(b) => format!("duplicate entry with key {b}"),
+        Value::Number(n) => format!("duplicate entry with key {n}"),
+        Value::String(s) => format!("duplicate entry with key {s:?}"),
+        _ => "duplicate entry in YAML map".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[test]
+    fn loader_matches_serde_yaml_values() {
+        let fixtures = [
+            "a: 1\nb: -2\nc: 1.5\nd: ~\ne: [true, 'x', {f: .nan}]\n",
+            "t: !Ref foo\nu: !GetAtt [a, b]\nv: !m {a: !n null}\n",
+            "? [1, 2]\n: v\n18446744073709551615: max\n-9223372036854775808: min\n",
+            "a: 1\n---\nb: 2\n",
+        ];
+
+        for fixture in fixtures {
+            let expected = serde_yaml::Deserializer::from_str(fixture)
+                .map(Value::deserialize)
+                .collect::<Result<Vec<_>, _>>()
+                .expect("serde_yaml loads");
+            let actual = load_documents(fixture, BigIntegers::Error).expect("loads");
+            assert_eq!(actual, expected, "fixture: {fixture:?}");
+        }
+
+        assert!(load_documents("a: 1\na: 2\n", BigIntegers::Error).is_err());
+    }
+
+    #[test]
+    fn big_integers_follow_policy() {
+        let source = "id: 123456789012345678901234567890\nneg: -99999999999999999999999\n";
+
+        let err = load_documents(source, BigIntegers::Error).expect_err("rejected");
+        assert!(err.to_string().contains("--big-integers=string"));
+
+        let documents = load_documents(source, BigIntegers::String).expect("loads");
+        let expected: Value = serde_yaml::from_str(
+            "id: '123456789012345678901234567890'\nneg: '-99999999999999999999999'\n",
+        )
+        .expect("expected");
+        assert_eq!(documents, vec![expected]);
+    }
+}
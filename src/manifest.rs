@@ -0,0 +1,137 @@
+use crate::diagnostics::{Category, Diagnostic};
+use crate::engine::Fragment;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const MANIFEST_FILE_NAME: &str = "fyaml.manifest.json";
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// The recorded set of contributing fragments for a tree, used by `--frozen` builds.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Manifest {
+    pub version: u32,
+    pub files: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub sha256: String,
+}
+
+impl Manifest {
+    pub fn from_fragments(fragments: &[Fragment]) -> Self {
+        let mut files = fragments
+            .iter()
+            .map(|fragment| ManifestEntry {
+                path: fragment.path.clone(),
+                sha256: fragment.sha256.clone(),
+            })
+            .collect::<Vec<_>>();
+        files.sort_by(|a, b| a.path.as_bytes().cmp(b.path.as_bytes()));
+        files.dedup();
+        Self {
+            version: MANIFEST_VERSION,
+            files,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self).map(|json| json + "\n")
+    }
+
+    /// Reports every fragment that was added, removed, or changed relative to this manifest.
+    pub fn verify(&self, fragments: &[Fragment], location: &str) -> Vec<Diagnostic> {
+        let recorded = self
+            .files
+            .iter()
+            .map(|entry| (entry.path.as_str(), entry.sha256.as_str()))
+            .collect::<BTreeMap<_, _>>();
+        let scanned = fragments
+            .iter()
+            .map(|fragment| (fragment.path.as_str(), fragment.sha256.as_str()))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut diagnostics = Vec::new();
+
+        for (path, hash) in &scanned {
+            match recorded.get(path) {
+                None => diagnostics.push(
+                    Diagnostic::error(
+                        "E081",
+                        "fragment is not recorded in the manifest",
+                        Category::InvalidInput,
+                    )
+                    .with_location(path.to_string())
+                    .with_paths(vec![location.to_string()])
+                    .with_cause("--frozen requires the scanned tree to match the manifest exactly.")
+                    .with_action(
+                        "Remove the file or regenerate the manifest with `fyaml manifest`.",
+                    ),
+                ),
+                Some(expected) if expected != hash => diagnostics.push(
+                    Diagnostic::error(
+                        "E083",
+                        "fragment content differs from the manifest",
+                        Category::InvalidInput,
+                    )
+                    .with_location(path.to_string())
+                    .with_paths(vec![location.to_string()])
+                    .with_cause("The fragment's SHA-256 does not match the recorded checksum.")
+                    .with_action(
+                        "Revert the change or regenerate the manifest with `fyaml manifest`.",
+                    )
+                    .with_context(format!("Recorded: {expected}, scanned: {hash}")),
+                ),
+                Some(_) => {}
+            }
+        }
+
+        for path in recorded.keys() {
+            if !scanned.contains_key(path) {
+                diagnostics.push(
+                    Diagnostic::error(
+                        "E082",
+                        "manifest fragment is missing from the tree",
+                        Category::InvalidInput,
+                    )
+                    .with_location(path.to_string())
+                    .with_paths(vec![location.to_string()])
+                    .with_cause(
+                        "A fragment recorded in the manifest did not contribute to this build.",
+                    )
+                    .with_action(
+                        "Restore the file or regenerate the manifest with `fyaml manifest`.",
+                    ),
+                );
+            }
+        }
+
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fragment(path: &str, sha256: &str) -> Fragment {
+        Fragment {
+            path: path.to_string(),
+            sha256: sha256.to_string(),
+        }
+    }
+
+    #[test]
+    fn verify_reports_new_missing_and_changed_fragments() {
+        let manifest = Manifest::from_fragments(&[fragment("a.yml", "1"), fragment("b.yml", "2")]);
+        let scanned = [fragment("a.yml", "9"), fragment("c.yml", "3")];
+
+        let codes = manifest
+            .verify(&scanned, MANIFEST_FILE_NAME)
+            .into_iter()
+            .map(|d| d.code)
+            .collect::<Vec<_>>();
+        assert_eq!(codes, vec!["E083", "E081", "E082"]);
+    }
+}
@@ -0,0 +1,147 @@
+//! Run metrics in the Prometheus textfile format, for `--metrics-out`.
+//!
+//! The file is written to a temporary sibling and renamed into place, so a collector such
+//! as node_exporter's textfile collector never reads a half-written file.
+
+use crate::diagnostics::Diagnostic;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunMetrics {
+    pub command: &'static str,
+    pub fragments: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub duration: Duration,
+    /// Bytes of packed output, for commands that write one.
+    pub output_bytes: Option<u64>,
+}
+
+impl RunMetrics {
+    pub fn new(
+        command: &'static str,
+        fragments: usize,
+        diagnostics: &[Diagnostic],
+        duration: Duration,
+    ) -> Self {
+        Self {
+            command,
+            fragments,
+            errors: diagnostics.iter().filter(|d| d.is_error()).count(),
+            warnings: diagnostics.iter().filter(|d| d.is_warning()).count(),
+            duration,
+            output_bytes: None,
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        let mut gauge = |name: &str, help: &str, value: String| {
+            writeln!(text, "# HELP fyaml_{name} {help}").expect("writing to a String succeeds");
+            writeln!(text, "# TYPE fyaml_{name} gauge").expect("writing to a String succeeds");
+            writeln!(text, "fyaml_{name}{{command=\"{}\"}} {value}", self.command)
+                .expect("writing to a String succeeds");
+        };
+        gauge(
+            "fragments",
+            "YAML fragments read by the last run.",
+            self.fragments.to_string(),
+        );
+        gauge(
+            "errors",
+            "Error diagnostics reported by the last run.",
+            self.errors.to_string(),
+        );
+        gauge(
+            "warnings",
+            "Warning diagnostics reported by the last run.",
+            self.warnings.to_string(),
+        );
+        gauge(
+            "duration_seconds",
+            "Wall-clock duration of the last run.",
+            format!("{:.6}", self.duration.as_secs_f64()),
+        );
+        if let Some(bytes) = self.output_bytes {
+            gauge(
+                "output_bytes",
+                "Bytes of packed output written by the last run.",
+                bytes.to_string(),
+            );
+        }
+        text
+    }
+
+    pub fn write_textfile(&self, path: &Path) -> io::Result<()> {
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+        let mut temp_name = file_name.to_os_string();
+        temp_name.push(format!(".{}.tmp", std::process::id()));
+        let temp = path.with_file_name(temp_name);
+        fs::write(&temp, self.render())
+            .and_then(|()| fs::rename(&temp, path))
+            .map_err(|err| {
+                let _ = fs::remove_file(&temp);
+                err
+            })
+    }
+}
+
+/// Passes writes through to `inner` and counts the bytes.
+pub struct CountingWriter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W: Write> CountingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner, count: 0 }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_writes_one_gauge_per_metric() {
+        let diagnostics = [
+            Diagnostic::warn("W010", "dotted key derived from filename"),
+            Diagnostic::warn("W050", "entries ignored while scanning"),
+        ];
+        let mut metrics = RunMetrics::new("pack", 3, &diagnostics, Duration::from_millis(1500));
+        metrics.output_bytes = Some(42);
+        let text = metrics.render();
+
+        assert!(text.starts_with(
+            "# HELP fyaml_fragments YAML fragments read by the last run.\n\
+             # TYPE fyaml_fragments gauge\n\
+             fyaml_fragments{command=\"pack\"} 3\n"
+        ));
+        assert!(text.contains("fyaml_errors{command=\"pack\"} 0\n"));
+        assert!(text.contains("fyaml_warnings{command=\"pack\"} 2\n"));
+        assert!(text.contains("fyaml_duration_seconds{command=\"pack\"} 1.500000\n"));
+        assert!(text.ends_with("fyaml_output_bytes{command=\"pack\"} 42\n"));
+    }
+}
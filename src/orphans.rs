@@ -0,0 +1,273 @@
+//! `fyaml orphans`: YAML files outside every FYAML tree that look like misplaced fragments.
+//!
+//! A file resembles a fragment when its name matches an entry that contributes a key to one
+//! of the trees, or when its parent directory does (so the file would have become a child
+//! key there). Hidden entries and the trees themselves are not scanned.
+
+use crate::config::BuildOptions;
+use crate::diagnostics::{Category, Diagnostic};
+use crate::engine::{build, is_yaml_file, strip_yaml_extension};
+use crate::glob::Glob;
+use crate::source::{EntryKind, FsSource, TreeSource};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrphanReport {
+    pub trees: Vec<String>,
+    pub orphans: Vec<Orphan>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Orphan {
+    /// Path relative to the repository root.
+    pub path: String,
+    pub resembles: Vec<Resemblance>,
+}
+
+/// A key the orphan would plausibly have contributed had it been placed in `tree`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Resemblance {
+    pub tree: String,
+    pub key_path: String,
+}
+
+/// Entry names that contribute keys, across all trees.
+#[derive(Default)]
+struct KeyIndex {
+    entries: BTreeMap<String, Vec<Resemblance>>,
+    directories: BTreeMap<String, Vec<Resemblance>>,
+}
+
+/// Scans `repo_root` for YAML files outside `trees` (relative to `repo_root`) that look
+/// like fragments of one of them. Entries matching `skip` (relative to `repo_root`) are not
+/// scanned.
+///
+/// Returns the diagnostics of any tree that fails to build, since its keys cannot be
+/// trusted for matching.
+pub fn find_orphans(
+    repo_root: &Path,
+    trees: &[PathBuf],
+    skip: &[Glob],
+    options: &BuildOptions,
+) -> Result<OrphanReport, Vec<Diagnostic>> {
+    if FsSource.kind(repo_root) != Some(EntryKind::Directory) {
+        return Err(vec![Diagnostic::error(
+            "E000",
+            "input directory is missing or not a directory",
+            Category::InvalidInput,
+        )
+        .with_location(repo_root.display().to_string())
+        .with_cause("`fyaml orphans` scans a directory for misplaced fragments.")
+        .with_action("Pass the repository root as the first argument.")]);
+    }
+
+    let mut index = KeyIndex::default();
+    let mut errors = Vec::new();
+    let tree_roots: Vec<PathBuf> = trees.iter().map(|tree| repo_root.join(tree)).collect();
+    for (tree, tree_root) in trees.iter().zip(&tree_roots) {
+        let outcome = build(tree_root, options);
+        let tree_name = display_relative(tree);
+        let failed = outcome.diagnostics.iter().any(Diagnostic::is_error);
+        if failed {
+            errors.extend(
+                outcome
+                    .diagnostics
+                    .into_iter()
+                    .filter(Diagnostic::is_error)
+                    .map(|mut diagnostic| {
+                        if let Some(location) = &mut diagnostic.location {
+                            *location = display_relative(&tree.join(&*location));
+                        }
+                        diagnostic
+                    }),
+            );
+            continue;
+        }
+        for derived in outcome.explain.derived_keys {
+            let source = tree_root.join(&derived.source);
+            let resemblance = Resemblance {
+                tree: tree_name.clone(),
+                key_path: derived.derived_key_path,
+            };
+            if FsSource.kind(&source) == Some(EntryKind::Directory) {
+                index
+                    .directories
+                    .entry(file_name(&source))
+                    .or_default()
+                    .push(resemblance.clone());
+            }
+            index
+                .entries
+                .entry(strip_yaml_extension(&file_name(&source)))
+                .or_default()
+                .push(resemblance);
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let tree_roots: Vec<PathBuf> = tree_roots
+        .into_iter()
+        .map(|tree| tree.canonicalize().unwrap_or(tree))
+        .collect();
+    let mut scan = Scan {
+        repo_root,
+        tree_roots: &tree_roots,
+        skip,
+        index: &index,
+        orphans: Vec::new(),
+        errors: Vec::new(),
+    };
+    scan.directory(repo_root);
+    if !scan.errors.is_empty() {
+        return Err(scan.errors);
+    }
+
+    Ok(OrphanReport {
+        trees: trees.iter().map(|tree| display_relative(tree)).collect(),
+        orphans: scan.orphans,
+    })
+}
+
+struct Scan<'a> {
+    repo_root: &'a Path,
+    tree_roots: &'a [PathBuf],
+    skip: &'a [Glob],
+    index: &'a KeyIndex,
+    orphans: Vec<Orphan>,
+    errors: Vec<Diagnostic>,
+}
+
+impl Scan<'_> {
+    fn directory(&mut self, directory: &Path) {
+        let entries = match FsSource.read_dir(directory) {
+            Ok(entries) => entries,
+            Err(err) => {
+                self.errors.push(
+                    Diagnostic::error("E030", "unable to read directory", Category::InvalidInput)
+                        .with_location(directory.display().to_string())
+                        .with_cause(err.to_string())
+                        .with_action("Check directory permissions, or --skip the directory."),
+                );
+                return;
+            }
+        };
+        let mut entries: Vec<_> = entries.into_iter().filter_map(Result::ok).collect();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for entry in entries {
+            if entry.name.starts_with('.') {
+                continue;
+            }
+            let relative = self.relative(&entry.path);
+            if self.skip.iter().any(|glob| glob.matches(&relative)) {
+                continue;
+            }
+            match entry.kind {
+                Ok(EntryKind::Directory) => {
+                    let path = entry.path.canonicalize().unwrap_or(entry.path.clone());
+                    if !self.tree_roots.contains(&path) {
+                        self.directory(&entry.path);
+                    }
+                }
+                Ok(EntryKind::File) if is_yaml_file(&entry.path) => self.file(&entry.path),
+                _ => {}
+            }
+        }
+    }
+
+    fn file(&mut self, path: &Path) {
+        let stem = strip_yaml_extension(&file_name(path));
+        let mut resembles: Vec<Resemblance> =
+            self.index.entries.get(&stem).cloned().unwrap_or_default();
+        let parent = path.parent().map(file_name).unwrap_or_default();
+        if let Some(directories) = self.index.directories.get(&parent) {
+            resembles.extend(directories.iter().map(|directory| Resemblance {
+                tree: directory.tree.clone(),
+                key_path: format!("{}.{stem}", directory.key_path),
+            }));
+        }
+        if resembles.is_empty() {
+            return;
+        }
+        resembles.sort();
+        resembles.dedup();
+        self.orphans.push(Orphan {
+            path: self.relative(path),
+            resembles,
+        });
+    }
+
+    fn relative(&self, path: &Path) -> String {
+        display_relative(path.strip_prefix(self.repo_root).unwrap_or(path))
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn display_relative(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn write(path: &Path, content: &str) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("create parent dirs");
+        }
+        fs::write(path, content).expect("write file");
+    }
+
+    #[test]
+    fn reports_files_named_like_tree_entries() {
+        let repo = tempdir().expect("temp dir");
+        let root = repo.path();
+        write(&root.join("config/services/web.yml"), "port: 80\n");
+        write(&root.join("config/database.yml"), "host: db\n");
+        write(&root.join("database.yml"), "host: old\n");
+        write(&root.join("deploy/services/worker.yaml"), "replicas: 2\n");
+        write(&root.join("deploy/unrelated.yml"), "x: 1\n");
+        write(&root.join(".github/database.yml"), "x: 1\n");
+        write(&root.join("vendor/database.yml"), "x: 1\n");
+
+        let skip = vec!["vendor".parse::<Glob>().expect("glob")];
+        let report = find_orphans(
+            root,
+            &[PathBuf::from("config")],
+            &skip,
+            &BuildOptions::default(),
+        )
+        .expect("scan succeeds");
+
+        let found = report
+            .orphans
+            .iter()
+            .map(|orphan| {
+                let keys = orphan
+                    .resembles
+                    .iter()
+                    .map(|r| format!("{}:{}", r.tree, r.key_path))
+                    .collect::<Vec<_>>();
+                format!("{} -> {}", orphan.path, keys.join(","))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                "database.yml -> config:database",
+                "deploy/services/worker.yaml -> config:services.worker",
+            ]
+        );
+    }
+}
@@ -0,0 +1,284 @@
+//! `fyaml pack BASE OVERLAY...`: each root is packed on its own, then laid over the layers
+//! before it, so a per-environment directory only holds what differs from the base.
+//!
+//! Locations in the combined diagnostics and explain report are prefixed with the layer's
+//! root, and a value an overlay replaces with one of another type is reported as `W025`,
+//! naming the file in each layer.
+
+use crate::config::OverlayMerge;
+use crate::diagnostics::Diagnostic;
+use crate::engine::{value_kind, BuildOutcome, DerivedKey, ROOT_INLINE_LOCATION};
+use crate::keypath::push_index;
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Lays the packed `overlays` over `base`, packed from `root`, in order. Each layer's
+/// diagnostics are kept; the combined value is `None` if any layer has none.
+pub fn overlay(
+    root: &Path,
+    base: BuildOutcome,
+    overlays: Vec<(PathBuf, BuildOutcome)>,
+    strategy: OverlayMerge,
+) -> BuildOutcome {
+    let base_root = root.display().to_string();
+    let mut combined = relocate(root, base);
+    let mut merger = Merger {
+        strategy,
+        owners: sources(&combined.explain.derived_keys),
+        layer: HashMap::new(),
+        diagnostics: Vec::new(),
+    };
+
+    for (root, outcome) in overlays {
+        let mut outcome = relocate(&root, outcome);
+        merger.layer = sources(&outcome.explain.derived_keys);
+        combined.value = match (combined.value.take(), outcome.value.take()) {
+            (Some(mut value), Some(incoming)) => {
+                let fallback = root.display().to_string();
+                merger.merge(
+                    &mut value,
+                    incoming,
+                    &mut String::new(),
+                    &base_root,
+                    &fallback,
+                );
+                Some(value)
+            }
+            _ => None,
+        };
+        combined.diagnostics.append(&mut outcome.diagnostics);
+        combined.diagnostics.append(&mut merger.diagnostics);
+        combined.fragments.append(&mut outcome.fragments);
+        combined.key_orders.append(&mut outcome.key_orders);
+        combined.positions.extend(outcome.positions);
+        let explain = &mut combined.explain;
+        explain
+            .derived_keys
+            .append(&mut outcome.explain.derived_keys);
+        explain.ignored.append(&mut outcome.explain.ignored);
+        explain
+            .directory_modes
+            .append(&mut outcome.explain.directory_modes);
+    }
+    combined
+}
+
+/// Prefixes the relative locations of `outcome` with the layer's root.
+fn relocate(root: &Path, mut outcome: BuildOutcome) -> BuildOutcome {
+    let within = |location: &str| -> String {
+        if location == "." {
+            root.display().to_string()
+        } else if location == ROOT_INLINE_LOCATION || Path::new(location).is_absolute() {
+            location.to_string()
+        } else {
+            root.join(location).display().to_string()
+        }
+    };
+    for diagnostic in &mut outcome.diagnostics {
+        diagnostic.location = diagnostic.location.as_deref().map(within);
+    }
+    for derived in &mut outcome.explain.derived_keys {
+        derived.source = within(&derived.source);
+    }
+    for ignored in &mut outcome.explain.ignored {
+        ignored.path = within(&ignored.path);
+    }
+    for mode in &mut outcome.explain.directory_modes {
+        mode.directory = within(&mode.directory);
+    }
+    outcome
+}
+
+/// The file each derived key path came from.
+fn sources(derived_keys: &[DerivedKey]) -> HashMap<String, String> {
+    derived_keys
+        .iter()
+        .map(|key| (key.derived_key_path.clone(), key.source.clone()))
+        .collect()
+}
+
+struct Merger {
+    strategy: OverlayMerge,
+    /// The file that set each derived key path of the combined value so far.
+    owners: HashMap<String, String>,
+    /// The files of the layer being merged.
+    layer: HashMap<String, String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Merger {
+    /// Merges `incoming` into `existing` at `key_path`. `owner` and `source` are the files
+    /// the nearest enclosing derived keys came from, in the combined value and in the layer.
+    fn merge(
+        &mut self,
+        existing: &mut Value,
+        incoming: Value,
+        key_path: &mut String,
+        owner: &str,
+        source: &str,
+    ) {
+        let owner = self
+            .owners
+            .get(key_path.as_str())
+            .cloned()
+            .unwrap_or_else(|| owner.to_string());
+        let source = self
+            .layer
+            .get(key_path.as_str())
+            .cloned()
+            .unwrap_or_else(|| source.to_string());
+        let descend = self.strategy != OverlayMerge::Shallow || key_path.is_empty();
+        match (existing, incoming) {
+            (Value::Mapping(existing), Value::Mapping(incoming)) if descend => {
+                for (key, value) in incoming {
+                    let len = key_path.len();
+                    if !key_path.is_empty() {
+                        key_path.push('.');
+                    }
+                    key_path.push_str(&key_text(&key));
+                    match existing.get_mut(&key) {
+                        Some(child) => self.merge(child, value, key_path, &owner, &source),
+                        None => {
+                            self.adopt(key_path, &source);
+                            existing.insert(key, value);
+                        }
+                    }
+                    key_path.truncate(len);
+                }
+            }
+            (Value::Sequence(existing), Value::Sequence(incoming))
+                if self.strategy == OverlayMerge::Append =>
+            {
+                for item in incoming {
+                    let len = key_path.len();
+                    push_index(key_path, existing.len());
+                    self.owners.insert(key_path.clone(), source.clone());
+                    key_path.truncate(len);
+                    existing.push(item);
+                }
+            }
+            (existing, incoming) => {
+                let (was, now) = (value_kind(existing), value_kind(&incoming));
+                if was != now {
+                    let shown = if key_path.is_empty() {
+                        "."
+                    } else {
+                        key_path.as_str()
+                    };
+                    self.diagnostics.push(
+                        Diagnostic::warn("W025", "overlay replaces a value of another type")
+                            .with_location(source.clone())
+                            .with_derived_key_path(key_path.clone())
+                            .with_cause(format!(
+                                "`{shown}` is a {was} in an earlier layer and a {now} in this one; the later layer wins."
+                            ))
+                            .with_action(
+                                "Check that the overlay's key path matches the base, or give the value the base's type.",
+                            )
+                            .with_context(format!("Layers: {owner} ({was}) -> {source} ({now})")),
+                    );
+                }
+                self.owners
+                    .retain(|path, _| !is_within(path, key_path.as_str()));
+                self.adopt(key_path, &source);
+                *existing = incoming;
+            }
+        }
+    }
+
+    /// Records the layer's files for `key_path` and everything under it as the owners of the
+    /// combined value there.
+    fn adopt(&mut self, key_path: &str, source: &str) {
+        self.owners.insert(key_path.to_string(), source.to_string());
+        for (path, file) in &self.layer {
+            if is_within(path, key_path) {
+                self.owners.insert(path.clone(), file.clone());
+            }
+        }
+    }
+}
+
+/// Whether derived key path `path` is `ancestor` or lies under it.
+fn is_within(path: &str, ancestor: &str) -> bool {
+    if ancestor.is_empty() {
+        return true;
+    }
+    path.strip_prefix(ancestor)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.') || rest.starts_with('['))
+}
+
+fn key_text(key: &Value) -> String {
+    match key {
+        Value::String(text) => text.clone(),
+        other => serde_yaml::to_string(other)
+            .map(|text| text.trim_end().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BuildOptions;
+    use crate::engine::build_from_entries;
+
+    fn pack(files: &[(&str, &str)]) -> BuildOutcome {
+        let entries = files
+            .iter()
+            .map(|(path, contents)| (PathBuf::from(path), contents.to_string()));
+        build_from_entries(entries, &BuildOptions::default())
+    }
+
+    fn layers(strategy: OverlayMerge) -> BuildOutcome {
+        let base = pack(&[
+            ("db.yml", "host: localhost\nport: 5432\n"),
+            ("hosts.yml", "[a, b]\n"),
+            ("log.yml", "level: info\n"),
+        ]);
+        let prod = pack(&[
+            ("db/host.yml", "db.internal\n"),
+            ("hosts.yml", "[c]\n"),
+            ("log.yml", "warn\n"),
+        ]);
+        overlay(
+            Path::new("base"),
+            base,
+            vec![(PathBuf::from("prod"), prod)],
+            strategy,
+        )
+    }
+
+    fn yaml(text: &str) -> Value {
+        serde_yaml::from_str(text).expect("valid YAML")
+    }
+
+    #[test]
+    fn later_layers_override_and_type_changes_name_both_files() {
+        let outcome = layers(OverlayMerge::Deep);
+        assert_eq!(
+            outcome.value,
+            Some(yaml(
+                "db: {host: db.internal, port: 5432}\nhosts: [c]\nlog: warn\n"
+            ))
+        );
+        let found = outcome
+            .diagnostics
+            .iter()
+            .map(|d| (d.code.as_str(), d.context.as_deref().unwrap_or("")))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![(
+                "W025",
+                "Layers: base/log.yml (mapping) -> prod/log.yml (string)"
+            )]
+        );
+
+        let value = layers(OverlayMerge::Append).value.expect("value");
+        assert_eq!(value["hosts"], yaml("[a, b, c]"));
+
+        let value = layers(OverlayMerge::Shallow).value.expect("value");
+        assert_eq!(value["db"], yaml("{host: db.internal}"));
+    }
+}
@@ -0,0 +1,306 @@
+use crate::config::BuildOptions;
+use crate::diagnostics::{Category, Diagnostic};
+use crate::diff::{differences, Difference};
+use crate::engine::{build_with_source, list_source, BuildOutcome};
+use crate::serializer::canonicalize_yaml;
+use crate::source::{EntryKind, FsSource, MovedSource, TreeSource};
+use serde::Serialize;
+use serde_yaml::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MovePlan {
+    pub from: String,
+    pub to: String,
+    pub key_changes: Vec<KeyChange>,
+    pub output_differences: Vec<Difference>,
+    pub new_diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyChange {
+    pub source_before: String,
+    pub source_after: String,
+    pub key_path_before: String,
+    pub key_path_after: Option<String>,
+}
+
+impl MovePlan {
+    pub fn introduces_errors(&self) -> bool {
+        self.new_diagnostics.iter().any(Diagnostic::is_error)
+    }
+}
+
+/// Previews `mv <from> <to>` inside `root` without touching the filesystem.
+///
+/// Both paths are relative to `root`. As with `mv`, moving onto an existing directory moves
+/// the entry into it.
+pub fn plan_move(
+    root: &Path,
+    from: &Path,
+    to: &Path,
+    options: &BuildOptions,
+) -> Result<MovePlan, Box<Diagnostic>> {
+    let fs_source = FsSource;
+    let listed;
+    let base: &dyn TreeSource = match &options.file_list {
+        Some(files) => {
+            listed = list_source(root, files, &fs_source).0;
+            &listed
+        }
+        None => &fs_source,
+    };
+
+    let from_rel = relative_move_path(from, "source")?;
+    let mut to_rel = relative_move_path(to, "destination")?;
+
+    let from_abs = root.join(&from_rel);
+    if base.kind(&from_abs).is_none() {
+        return Err(Box::new(
+            Diagnostic::error("E060", "move source does not exist", Category::InvalidInput)
+                .with_location(from_abs.display().to_string())
+                .with_cause("The <FROM> path does not resolve to an entry under the FYAML root.")
+                .with_action("Pass a path relative to <DIR> that exists."),
+        ));
+    }
+
+    if base.kind(&root.join(&to_rel)) == Some(EntryKind::Directory) {
+        if let Some(name) = from_rel.file_name() {
+            to_rel = to_rel.join(name);
+        }
+    }
+
+    let to_abs = root.join(&to_rel);
+    if to_rel.starts_with(&from_rel) {
+        return Err(Box::new(
+            Diagnostic::error(
+                "E061",
+                "move destination is inside the move source",
+                Category::InvalidInput,
+            )
+            .with_location(to_abs.display().to_string())
+            .with_cause("An entry cannot be moved into itself.")
+            .with_action("Choose a destination outside of <FROM>."),
+        ));
+    }
+
+    let destination_parent_is_dir = to_abs
+        .parent()
+        .is_some_and(|parent| base.kind(parent) == Some(EntryKind::Directory));
+    if base.kind(&to_abs).is_some() || !destination_parent_is_dir {
+        return Err(Box::new(
+            Diagnostic::error(
+                "E062",
+                "move destination is not available",
+                Category::InvalidInput,
+            )
+            .with_location(to_abs.display().to_string())
+            .with_cause("The destination already exists or its parent directory does not exist.")
+            .with_action("Pick a destination whose parent directory exists and that is unused."),
+        ));
+    }
+
+    let before = build_with_source(root, options, base);
+    let moved = MovedSource::new(base, from_abs, to_abs);
+    let after = build_with_source(root, options, &moved);
+
+    Ok(MovePlan {
+        from: display_relative(&from_rel),
+        to: display_relative(&to_rel),
+        key_changes: key_changes(&before, &after, &from_rel, &to_rel),
+        output_differences: differences(&packed(&before), &packed(&after)),
+        new_diagnostics: new_diagnostics(&before, &after),
+    })
+}
+
+fn relative_move_path(path: &Path, role: &str) -> Result<PathBuf, Box<Diagnostic>> {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => out.push(part),
+            Component::CurDir => {}
+            _ => {
+                return Err(Box::new(
+                    Diagnostic::error(
+                        "E063",
+                        format!("move {role} must be a relative path inside the FYAML root"),
+                        Category::InvalidInput,
+                    )
+                    .with_location(path.display().to_string())
+                    .with_cause("Absolute paths and `..` components cannot be previewed.")
+                    .with_action("Pass paths relative to <DIR>, as you would after `cd <DIR>`."),
+                ));
+            }
+        }
+    }
+
+    if out.as_os_str().is_empty() {
+        return Err(Box::new(
+            Diagnostic::error(
+                "E063",
+                format!("move {role} must name an entry inside the FYAML root"),
+                Category::InvalidInput,
+            )
+            .with_location(path.display().to_string())
+            .with_cause("The FYAML root itself cannot be moved.")
+            .with_action("Pass a file or directory below <DIR>."),
+        ));
+    }
+
+    Ok(out)
+}
+
+fn packed(outcome: &BuildOutcome) -> Value {
+    canonicalize_yaml(outcome.value.as_ref().unwrap_or(&Value::Null))
+}
+
+fn key_changes(
+    before: &BuildOutcome,
+    after: &BuildOutcome,
+    from_rel: &Path,
+    to_rel: &Path,
+) -> Vec<KeyChange> {
+    let from_display = display_relative(from_rel);
+    let to_display = display_relative(to_rel);
+
+    let after_by_source = after
+        .explain
+        .derived_keys
+        .iter()
+        .map(|entry| {
+            (
+                original_source(&entry.source, &from_display, &to_display),
+                (entry.source.clone(), entry.derived_key_path.clone()),
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let mut changes = before
+        .explain
+        .derived_keys
+        .iter()
+        .filter_map(|entry| {
+            let moved = after_by_source.get(&entry.source);
+            let key_path_after = moved.map(|(_, key_path)| key_path.clone());
+            if key_path_after.as_deref() == Some(entry.derived_key_path.as_str()) {
+                return None;
+            }
+            Some(KeyChange {
+                source_before: entry.source.clone(),
+                source_after: moved
+                    .map(|(source, _)| source.clone())
+                    .unwrap_or_else(|| moved_source(&entry.source, &from_display, &to_display)),
+                key_path_before: entry.derived_key_path.clone(),
+                key_path_after,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    changes.sort_by(|a, b| a.source_before.cmp(&b.source_before));
+    changes
+}
+
+fn original_source(source_after: &str, from: &str, to: &str) -> String {
+    replace_prefix(source_after, to, from).unwrap_or_else(|| source_after.to_string())
+}
+
+fn moved_source(source_before: &str, from: &str, to: &str) -> String {
+    replace_prefix(source_before, from, to).unwrap_or_else(|| source_before.to_string())
+}
+
+fn replace_prefix(path: &str, prefix: &str, replacement: &str) -> Option<String> {
+    if path == prefix {
+        return Some(replacement.to_string());
+    }
+    path.strip_prefix(prefix)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .map(|rest| format!("{replacement}/{rest}"))
+}
+
+fn new_diagnostics(before: &BuildOutcome, after: &BuildOutcome) -> Vec<Diagnostic> {
+    let existing = before
+        .diagnostics
+        .iter()
+        .map(diagnostic_identity)
+        .collect::<HashSet<_>>();
+
+    after
+        .diagnostics
+        .iter()
+        .filter(|diagnostic| !existing.contains(&diagnostic_identity(diagnostic)))
+        .cloned()
+        .collect()
+}
+
+fn diagnostic_identity(diagnostic: &Diagnostic) -> (String, Option<String>, String) {
+    (
+        diagnostic.code.clone(),
+        diagnostic.derived_key_path.clone(),
+        diagnostic.message.clone(),
+    )
+}
+
+fn display_relative(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn write(path: &Path, content: &str) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("create parent dirs");
+        }
+        fs::write(path, content).expect("write file");
+    }
+
+    #[test]
+    fn plan_move_reports_key_changes_and_new_collisions() {
+        let dir = tempdir().expect("temp dir");
+        write(&dir.path().join("auth/provider.yml"), "kind: oidc\n");
+        write(&dir.path().join("identity.yml"), "kind: file\n");
+
+        let plan = plan_move(
+            dir.path(),
+            Path::new("auth"),
+            Path::new("identity"),
+            &BuildOptions::default(),
+        )
+        .expect("plan computed");
+
+        assert!(plan
+            .key_changes
+            .iter()
+            .any(|change| change.key_path_before == "auth.provider"
+                && change.key_path_after.as_deref() == Some("identity.provider")));
+        assert!(plan.new_diagnostics.iter().any(|d| d.code == "E001"));
+        assert!(plan.introduces_errors());
+        assert!(dir.path().join("auth/provider.yml").exists());
+    }
+
+    #[test]
+    fn plan_move_into_existing_directory_keeps_name() {
+        let dir = tempdir().expect("temp dir");
+        write(&dir.path().join("cache.yml"), "ttl: 60\n");
+        write(&dir.path().join("prod/database.yml"), "host: db\n");
+
+        let plan = plan_move(
+            dir.path(),
+            Path::new("cache.yml"),
+            Path::new("prod"),
+            &BuildOptions::default(),
+        )
+        .expect("plan computed");
+
+        assert_eq!(plan.to, "prod/cache.yml");
+        assert!(!plan.introduces_errors());
+        assert!(plan
+            .output_differences
+            .iter()
+            .any(|d| d.reason == "key missing on right side: cache"));
+    }
+}
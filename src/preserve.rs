@@ -0,0 +1,464 @@
+//! What `--preserve` keeps from fragment text besides key order: full-line comments, the
+//! comment ending a line, and anchors with the aliases that repeat them.
+//!
+//! `serde_yaml` drops all of these while parsing, so a line scanner reads them from the
+//! text, following block-style indentation, and files each under the key path of the node
+//! it belongs to in the packed document. The emitter writes them back where that node is
+//! written. Comments inside flow collections and after the last node of a fragment, and
+//! anything in a multi-document fragment, are not kept; what cannot be placed is dropped
+//! rather than guessed.
+//!
+//! The same scan finds where each node written on its entry's line starts, which
+//! `--source-map` reports; a flow collection is one such node.
+
+use crate::diagnostics::SourcePosition;
+use crate::keypath::{push_index, push_key, Segment};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preserved {
+    /// Full-line comments written above a node, `#` included, by the node's key path (empty
+    /// for the document root).
+    pub head: BTreeMap<String, Vec<String>>,
+    /// The comment at the end of a node's line.
+    pub trailing: BTreeMap<String, String>,
+    /// Anchor names by the key path of the anchored node. A name used by an earlier
+    /// fragment gets a `-2`, `-3`, ... suffix.
+    pub anchors: BTreeMap<String, String>,
+    /// For each alias, the key path of the anchored node it repeats.
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl Preserved {
+    pub fn is_empty(&self) -> bool {
+        self.head.is_empty()
+            && self.trailing.is_empty()
+            && self.anchors.is_empty()
+            && self.aliases.is_empty()
+    }
+
+    /// Reads the comments, anchors, and aliases of `text`, a fragment whose value lands at
+    /// `base` in the packed document. Returns the position of each scalar, alias, and flow
+    /// collection written after its key or `- `, by key path.
+    pub fn scan(&mut self, text: &str, base: &[Segment]) -> Vec<(String, SourcePosition)> {
+        if is_multi_document(text) {
+            return Vec::new();
+        }
+        let mut base_path = String::new();
+        for segment in base {
+            push_segment(&mut base_path, segment);
+        }
+        let mut scanner = Scanner {
+            notes: self,
+            base: base_path,
+            levels: Vec::new(),
+            pending: Vec::new(),
+            anchors: HashMap::new(),
+            skip_deeper: None,
+            current: "",
+            line_number: 0,
+            positions: Vec::new(),
+        };
+        scanner.run(text);
+        scanner.positions
+    }
+
+    fn unique_anchor(&self, name: &str) -> String {
+        let used = self.anchors.values().collect::<HashSet<_>>();
+        let mut unique = name.to_string();
+        let mut n = 2;
+        while used.contains(&unique) {
+            unique = format!("{name}-{n}");
+            n += 1;
+        }
+        unique
+    }
+}
+
+/// Appends `segment` to `path` as [`push_key`] and [`push_index`] write it.
+pub fn push_segment(path: &mut String, segment: &Segment) {
+    match segment {
+        Segment::Key(key) => push_key(path, key),
+        Segment::Index(index) => push_index(path, *index),
+    }
+}
+
+/// A mapping or sequence the scanner is inside, with the entry it is at.
+struct Level {
+    indent: usize,
+    sequence: bool,
+    segment: Segment,
+}
+
+struct Scanner<'a> {
+    notes: &'a mut Preserved,
+    base: String,
+    levels: Vec<Level>,
+    /// Full-line comments waiting for the node below them.
+    pending: Vec<String>,
+    /// Anchor names as written in this fragment, to the key path each now names.
+    anchors: HashMap<String, String>,
+    /// Lines indented deeper than this belong to the value above them: a block scalar, or a
+    /// scalar or flow collection continued over several lines.
+    skip_deeper: Option<usize>,
+    /// The line being read, and its one-based number.
+    current: &'a str,
+    line_number: usize,
+    positions: Vec<(String, SourcePosition)>,
+}
+
+impl<'a> Scanner<'a> {
+    fn run(&mut self, text: &'a str) {
+        let mut started = false;
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            self.current = line;
+            self.line_number = index + 1;
+            let trimmed = line.trim_start_matches(' ');
+            let indent = line.len() - trimmed.len();
+            if let Some(limit) = self.skip_deeper {
+                if trimmed.is_empty() || indent > limit {
+                    continue;
+                }
+                self.skip_deeper = None;
+            }
+            if trimmed.is_empty() {
+                // A comment block set off from the first node describes the whole fragment.
+                if !started && !self.pending.is_empty() {
+                    let pending = std::mem::take(&mut self.pending);
+                    self.notes
+                        .head
+                        .entry(self.base.clone())
+                        .or_default()
+                        .extend(pending);
+                }
+                continue;
+            }
+            if trimmed.starts_with('#') {
+                self.pending.push(trimmed.trim_end().to_string());
+                continue;
+            }
+            if indent == 0 && (is_document_start(trimmed) || trimmed.starts_with('%')) {
+                continue;
+            }
+            started = true;
+            self.line(indent, trimmed);
+        }
+    }
+
+    /// Reads one line holding content, starting at column `col`.
+    fn line(&mut self, mut col: usize, mut rest: &str) {
+        loop {
+            if rest == "-" || rest.starts_with("- ") {
+                let dash = col;
+                while self.levels.last().is_some_and(|level| level.indent > col) {
+                    self.levels.pop();
+                }
+                match self.levels.last_mut() {
+                    Some(level) if level.indent == col && level.sequence => {
+                        if let Segment::Index(index) = &mut level.segment {
+                            *index += 1;
+                        }
+                    }
+                    _ => self.levels.push(Level {
+                        indent: col,
+                        sequence: true,
+                        segment: Segment::Index(0),
+                    }),
+                }
+                let path = self.path();
+                self.attach_pending(&path);
+                let after = &rest[1..];
+                rest = after.trim_start_matches(' ');
+                col += 1 + after.len() - rest.len();
+                if rest.starts_with("- ") || rest == "-" || split_key(rest).is_some() {
+                    continue;
+                }
+                self.value(rest, &path, dash, col);
+                return;
+            }
+            if let Some((key, after)) = split_key(rest) {
+                while self.levels.last().is_some_and(|level| {
+                    level.indent > col || (level.indent == col && level.sequence)
+                }) {
+                    self.levels.pop();
+                }
+                match self.levels.last_mut() {
+                    Some(level) if level.indent == col => level.segment = Segment::Key(key),
+                    _ => self.levels.push(Level {
+                        indent: col,
+                        sequence: false,
+                        segment: Segment::Key(key),
+                    }),
+                }
+                let path = self.path();
+                self.attach_pending(&path);
+                let start = col + rest.len() - after.len();
+                self.value(after, &path, col, start);
+                return;
+            }
+            // Text that is neither an entry nor an item, such as a root scalar.
+            if self.levels.is_empty() {
+                let path = self.path();
+                self.record(path, col);
+            }
+            self.pending.clear();
+            self.skip_deeper = Some(col);
+            return;
+        }
+    }
+
+    /// Reads the value written after `key:` or `- ` for the node at `path`, whose entry
+    /// starts at column `col` and whose value text at column `start`.
+    fn value(&mut self, text: &str, path: &str, col: usize, start: usize) {
+        let mut rest = text.trim_start();
+        loop {
+            if let Some(anchor) = rest.strip_prefix('&') {
+                let (name, after) = token(anchor);
+                let unique = self.notes.unique_anchor(name);
+                self.notes.anchors.insert(path.to_string(), unique);
+                self.anchors.insert(name.to_string(), path.to_string());
+                rest = after.trim_start();
+            } else if rest.starts_with('!') {
+                rest = token(rest).1.trim_start();
+            } else {
+                break;
+            }
+        }
+        let value_start = start + text.len() - rest.len();
+        if rest.starts_with('*') || !split_comment(rest).0.is_empty() {
+            self.record(path.to_string(), value_start);
+        }
+        if let Some(alias) = rest.strip_prefix('*') {
+            let (name, after) = token(alias);
+            if let Some(anchor) = self.anchors.get(name) {
+                self.notes.aliases.insert(path.to_string(), anchor.clone());
+            }
+            rest = after.trim_start();
+        }
+        let (value, comment) = split_comment(rest);
+        if let Some(comment) = comment {
+            self.notes
+                .trailing
+                .insert(path.to_string(), comment.trim_end().to_string());
+        }
+        if !value.is_empty() {
+            // Block scalars, continued plain scalars, and multi-line quoted strings and flow
+            // collections all go on in lines indented deeper than their entry.
+            self.skip_deeper = Some(col);
+        }
+    }
+
+    /// Notes that the node at `path` starts at byte `col` of the current line.
+    fn record(&mut self, path: String, col: usize) {
+        let column = self
+            .current
+            .get(..col)
+            .map_or(col, |text| text.chars().count())
+            + 1;
+        self.positions.push((
+            path,
+            SourcePosition {
+                line: self.line_number,
+                column,
+            },
+        ));
+    }
+
+    fn path(&self) -> String {
+        let mut path = self.base.clone();
+        for level in &self.levels {
+            push_segment(&mut path, &level.segment);
+        }
+        path
+    }
+
+    fn attach_pending(&mut self, path: &str) {
+        if !self.pending.is_empty() {
+            let pending = std::mem::take(&mut self.pending);
+            self.notes
+                .head
+                .entry(path.to_string())
+                .or_default()
+                .extend(pending);
+        }
+    }
+}
+
+fn is_document_start(line: &str) -> bool {
+    line == "---" || line.starts_with("--- ")
+}
+
+/// Whether a `---` or `...` marker follows content, so the text holds several documents.
+fn is_multi_document(text: &str) -> bool {
+    let mut content = false;
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        if is_document_start(line) || line == "..." {
+            if content {
+                return true;
+            }
+        } else if !line.trim().is_empty() && !line.trim_start().starts_with('#') {
+            content = true;
+        }
+    }
+    false
+}
+
+/// Splits a node property or alias name from what follows it.
+fn token(text: &str) -> (&str, &str) {
+    let end = text.find([' ', '\t']).unwrap_or(text.len());
+    (&text[..end], &text[end..])
+}
+
+/// Splits `key: rest` into the key's text and `rest`; `None` when `line` is not a block
+/// mapping entry with a plain or quoted key.
+fn split_key(line: &str) -> Option<(String, &str)> {
+    if line.starts_with(['"', '\'']) {
+        let end = quoted_end(line)?;
+        let after = line[end..].trim_start_matches(' ');
+        let after = after.strip_prefix(':')?;
+        if !(after.is_empty() || after.starts_with(' ')) {
+            return None;
+        }
+        let key = serde_yaml::from_str::<String>(&line[..end]).ok()?;
+        return Some((key, after));
+    }
+    if line.starts_with([
+        '?', '[', '{', '&', '*', '!', '|', '>', '#', '%', '@', '`', ',', ']', '}',
+    ]) {
+        return None;
+    }
+    let colon = line
+        .match_indices(':')
+        .map(|(index, _)| index)
+        .find(|&index| line[index + 1..].is_empty() || line[index + 1..].starts_with(' '))?;
+    let key = &line[..colon];
+    if key.contains(" #") || key.trim().is_empty() {
+        return None;
+    }
+    Some((key.trim_end().to_string(), &line[colon + 1..]))
+}
+
+/// The index just past the closing quote of the quoted scalar `text` starts with.
+fn quoted_end(text: &str) -> Option<usize> {
+    let quote = text.chars().next()?;
+    let mut chars = text.char_indices().skip(1);
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' if quote == '"' => {
+                chars.next();
+            }
+            '\'' if quote == '\'' => {
+                if text[index + 1..].starts_with('\'') {
+                    chars.next();
+                } else {
+                    return Some(index + 1);
+                }
+            }
+            '"' if quote == '"' => return Some(index + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits a value from the `#` comment ending its line. Quotes are only followed in quoted
+/// scalars and flow collections, so apostrophes in plain text do not hide a comment.
+fn split_comment(text: &str) -> (&str, Option<&str>) {
+    let quoting = text.starts_with(['"', '\'', '[', '{']);
+    let mut quote = None;
+    let mut previous = ' ';
+    let mut chars = text.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match (quote, c) {
+            (Some('"'), '\\') => {
+                chars.next();
+            }
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') if quoting => quote = Some(c),
+            (None, '#') if previous == ' ' || previous == '\t' => {
+                return (text[..index].trim_end(), Some(&text[index..]));
+            }
+            _ => {}
+        }
+        previous = c;
+    }
+    (text.trim_end(), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scanned(text: &str, base: &[Segment]) -> Preserved {
+        let mut preserved = Preserved::default();
+        preserved.scan(text, base);
+        preserved
+    }
+
+    #[test]
+    fn files_comments_and_anchors_under_key_paths() {
+        let text = "\
+# Database settings
+
+# primary
+host: db.internal  # the writer
+port: 5432
+notes: |
+  # not a comment
+  text
+replicas:
+  # first
+  - name: a
+    # its zone
+    zone: x
+  - &main
+    name: b
+retry: {count: 3}  # flow
+fallback: *main
+\"odd key\": 'it''s' # quoted
+";
+        let preserved = scanned(text, &[Segment::Key("db".to_string())]);
+        assert_eq!(
+            preserved.head,
+            BTreeMap::from([
+                ("db".to_string(), vec!["# Database settings".to_string()]),
+                ("db.host".to_string(), vec!["# primary".to_string()]),
+                ("db.replicas[0]".to_string(), vec!["# first".to_string()]),
+                (
+                    "db.replicas[0].zone".to_string(),
+                    vec!["# its zone".to_string()]
+                ),
+            ])
+        );
+        assert_eq!(
+            preserved.trailing,
+            BTreeMap::from([
+                ("db.host".to_string(), "# the writer".to_string()),
+                ("db.retry".to_string(), "# flow".to_string()),
+                ("db[\"odd key\"]".to_string(), "# quoted".to_string()),
+            ])
+        );
+        assert_eq!(
+            preserved.anchors,
+            BTreeMap::from([("db.replicas[1]".to_string(), "main".to_string())])
+        );
+        assert_eq!(
+            preserved.aliases,
+            BTreeMap::from([("db.fallback".to_string(), "db.replicas[1]".to_string())])
+        );
+    }
+
+    #[test]
+    fn renames_anchors_repeated_across_fragments() {
+        let mut preserved = Preserved::default();
+        preserved.scan("a: &x 1\nb: *x\n", &[Segment::Key("one".to_string())]);
+        preserved.scan("a: &x 2\nb: *x\n", &[Segment::Key("two".to_string())]);
+        assert_eq!(preserved.anchors["two.a"], "x-2");
+        assert_eq!(preserved.aliases["two.b"], "two.a");
+
+        assert!(scanned("a: 1 # c\n---\nb: 2\n", &[]).is_empty());
+    }
+}
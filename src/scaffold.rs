@@ -1,9 +1,14 @@
 use crate::diagnostics::{Category, Diagnostic};
+use crate::keymap::{case_safe_names, Keymap, KEYMAP_FILE_NAME};
+use crate::keypath::{push_index, push_key, ROOT_PATH};
+use crate::watch::resolve;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_yaml::Value;
 use std::fs;
-use std::path::Path;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering};
 
 #[derive(Debug, Clone, Copy, clap::ValueEnum, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "kebab-case")]
@@ -20,11 +25,42 @@ pub enum SequenceLayout {
     Files,
 }
 
+/// What `scaffold` does with an input holding several YAML documents.
+#[derive(Debug, Clone, Copy, clap::ValueEnum, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScaffoldMultiDoc {
+    /// Reject the input (E202).
+    Error,
+    /// Scaffold the first document and warn about the rest (W014).
+    First,
+    /// Write document N into `<DIR>/N/`, as `pack --multi-doc all` reads them back.
+    Split,
+}
+
+/// How much of the scaffolded tree is flushed to disk before `scaffold` returns.
+#[derive(Debug, Clone, Copy, clap::ValueEnum, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FsyncMode {
+    /// Leave flushing to the operating system.
+    None,
+    /// Sync every created directory, so the entries survive a crash.
+    Dir,
+    /// Sync every file as well as every directory.
+    All,
+}
+
 #[derive(Debug, Clone)]
 pub struct ScaffoldOptions {
     pub layout: ScaffoldLayout,
     pub seq: SequenceLayout,
     pub split_threshold_bytes: Option<usize>,
+    pub fsync: FsyncMode,
+    /// Threads writing fragment files; the written tree is the same for any value.
+    pub jobs: usize,
+    pub multi_doc: ScaffoldMultiDoc,
+    /// A nested mapping with fewer than this many entries, all one-line scalars, is written
+    /// as one `<key>.yml` file instead of a directory of one-line files.
+    pub group_leaves: Option<usize>,
 }
 
 impl Default for ScaffoldOptions {
@@ -33,6 +69,10 @@ impl Default for ScaffoldOptions {
             layout: ScaffoldLayout::Hybrid,
             seq: SequenceLayout::Files,
             split_threshold_bytes: None,
+            fsync: FsyncMode::None,
+            jobs: 1,
+            multi_doc: ScaffoldMultiDoc::Error,
+            group_leaves: None,
         }
     }
 }
@@ -40,6 +80,102 @@ impl Default for ScaffoldOptions {
 #[derive(Debug, Clone)]
 pub struct ScaffoldOutcome {
     pub diagnostics: Vec<Diagnostic>,
+    /// Every directory and file written, in plan order; empty when nothing was written.
+    pub entries: Vec<ScaffoldEntry>,
+}
+
+/// One directory or file a scaffold run created, as listed by `scaffold --report`.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ScaffoldEntry {
+    /// Relative to the output directory, with `/` separators.
+    pub path: String,
+    pub kind: EntryKind,
+    /// The input node the entry holds; `None` for the keymap file.
+    pub key_path: Option<String>,
+    pub rule: ScaffoldRule,
+    /// How the input key was changed to name the entry.
+    pub transformations: Vec<KeyTransformation>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum EntryKind {
+    Directory,
+    File,
+}
+
+/// Why an entry was written the way it was.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScaffoldRule {
+    /// A mapping written as a directory of its entries.
+    MappingDirectory,
+    /// A sequence written as a directory of numbered items.
+    SequenceDirectory,
+    /// A sequence item written as a directory (`--seq dir`), or a document (`--multi-doc split`).
+    SequenceItemDirectory,
+    /// A scalar written as one file.
+    ScalarFile,
+    /// A sequence item written as one file, whatever its type (`--seq files`).
+    SequenceItemFile,
+    /// A mapping or sequence written as one file by `--layout flat`.
+    FlatLayout,
+    /// A small mapping of one-line scalars written as one file by `--group-leaves`.
+    GroupedLeaves,
+    /// The directory holding a string longer than `--split-threshold-bytes`.
+    SplitDirectory,
+    /// The `value.yml` holding a string longer than `--split-threshold-bytes`.
+    SplitValue,
+    /// The keymap recording keys renamed for case-insensitive filesystems.
+    Keymap,
+}
+
+/// A change made to an input key to name its entry.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum KeyTransformation {
+    /// Suffixed because a sibling key differs only by letter case (W016).
+    CaseRename { key: String, name: String },
+}
+
+/// The `scaffold --report` artifact: the options that shaped the layout and every entry.
+#[derive(Debug, Serialize)]
+pub struct ScaffoldReport<'a> {
+    pub input: String,
+    pub output: String,
+    pub layout: ScaffoldLayout,
+    pub seq: SequenceLayout,
+    pub split_threshold_bytes: Option<usize>,
+    pub group_leaves: Option<usize>,
+    pub multi_doc: ScaffoldMultiDoc,
+    pub entries: &'a [ScaffoldEntry],
+}
+
+impl<'a> ScaffoldReport<'a> {
+    pub fn new(
+        input: &Path,
+        output: &Path,
+        options: &ScaffoldOptions,
+        entries: &'a [ScaffoldEntry],
+    ) -> Self {
+        Self {
+            input: input.display().to_string(),
+            output: output.display().to_string(),
+            layout: options.layout,
+            seq: options.seq,
+            split_threshold_bytes: options.split_threshold_bytes,
+            group_leaves: options.group_leaves,
+            multi_doc: options.multi_doc,
+            entries,
+        }
+    }
+
+    /// Writes the report to `path` as pretty-printed JSON.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut text = serde_json::to_string_pretty(self)?;
+        text.push('\n');
+        fs::write(path, text)
+    }
 }
 
 type ScaffoldResult<T> = Result<T, Box<Diagnostic>>;
@@ -64,10 +200,34 @@ pub fn scaffold(
                 .with_cause(err.to_string())
                 .with_action("Pass a readable YAML file to `fyaml scaffold`."),
             );
-            return ScaffoldOutcome { diagnostics };
+            return ScaffoldOutcome {
+                diagnostics,
+                entries: Vec::new(),
+            };
         }
     };
 
+    if resolve(input_file).starts_with(resolve(output_dir)) {
+        diagnostics.push(
+            Diagnostic::error(
+                "E215",
+                "scaffold input is inside the output directory",
+                Category::InvalidInput,
+            )
+            .with_location(input_file.display().to_string())
+            .with_cause(format!(
+                "The generated fragments would land next to the input in {}, and later packs \
+                 would read the input as a fragment.",
+                output_dir.display()
+            ))
+            .with_action("Scaffold into a directory that does not contain the input file."),
+        );
+        return ScaffoldOutcome {
+            diagnostics,
+            entries: Vec::new(),
+        };
+    }
+
     let mut docs = Vec::new();
     for document in serde_yaml::Deserializer::from_str(&contents) {
         match Value::deserialize(document) {
@@ -79,92 +239,200 @@ pub fn scaffold(
                         .with_cause(err.to_string())
                         .with_action("Fix YAML syntax before scaffolding."),
                 );
-                return ScaffoldOutcome { diagnostics };
+                return ScaffoldOutcome {
+                    diagnostics,
+                    entries: Vec::new(),
+                };
             }
         }
     }
 
-    if docs.len() > 1 {
-        diagnostics.push(
-            Diagnostic::error(
-                "E202",
-                "scaffold input must be a single YAML document",
-                Category::Parse,
-            )
-            .with_location(input_file.display().to_string())
-            .with_cause("Multiple documents were found in scaffold input.")
-            .with_action("Provide a single YAML document for deterministic scaffold output."),
-        );
-        return ScaffoldOutcome { diagnostics };
+    let mut plan = ScaffoldPlan {
+        keymap: Keymap::new(),
+        ..ScaffoldPlan::default()
+    };
+    let planned = if docs.len() > 1 {
+        match options.multi_doc {
+            ScaffoldMultiDoc::Error => {
+                diagnostics.push(
+                    Diagnostic::error(
+                        "E202",
+                        "scaffold input must be a single YAML document",
+                        Category::Parse,
+                    )
+                    .with_location(input_file.display().to_string())
+                    .with_cause("Multiple documents were found in scaffold input.")
+                    .with_action(
+                        "Pass --multi-doc split to write one directory per document, or --multi-doc first.",
+                    ),
+                );
+                return ScaffoldOutcome {
+                    diagnostics,
+                    entries: Vec::new(),
+                };
+            }
+            ScaffoldMultiDoc::First => {
+                diagnostics.push(
+                    Diagnostic::warn(
+                        "W014",
+                        "multi-document YAML: using first document and ignoring the rest",
+                    )
+                    .with_location(input_file.display().to_string())
+                    .with_cause("Configured with --multi-doc=first.")
+                    .with_action("Use --multi-doc=split to scaffold every document."),
+                );
+                plan_value(None, &docs[0], Path::new(""), options, &mut plan)
+            }
+            ScaffoldMultiDoc::Split => docs.iter().enumerate().try_for_each(|(index, doc)| {
+                plan.key_path.clear();
+                push_index(&mut plan.key_path, index);
+                plan_sequence_item(&index.to_string(), doc, Path::new(""), options, &mut plan)
+            }),
+        }
+    } else {
+        let value = docs.into_iter().next().unwrap_or(Value::Null);
+        plan_value(None, &value, Path::new(""), options, &mut plan)
+    };
+
+    let written = planned.and_then(|()| {
+        plan_keymap(&mut plan);
+        write_plan(&plan, output_dir, options)
+    });
+    diagnostics.append(&mut plan.diagnostics);
+    if let Err(diagnostic) = written {
+        diagnostics.push(*diagnostic);
+        return ScaffoldOutcome {
+            diagnostics,
+            entries: Vec::new(),
+        };
     }
 
-    let value = docs.into_iter().next().unwrap_or(Value::Null);
+    let mut report = Diagnostic::info(
+        "I200",
+        "scaffold generated a deterministic FYAML layout (non-invertible helper)",
+    )
+    .with_location(output_dir.display().to_string())
+    .with_cause("Scaffold is intentionally one-way and not a reverse of pack.")
+    .with_action("Validate with `fyaml pack <DIR>` and compare semantic output in CI.");
+    if let (Some(limit), false) = (options.group_leaves, plan.grouped.is_empty()) {
+        report = report.with_context(format!(
+            "--group-leaves {limit} wrote each mapping of fewer than {limit} one-line scalars \
+             as one file: {}",
+            plan.grouped
+                .iter()
+                .map(|(path, count)| {
+                    let keys = if *count == 1 { "key" } else { "keys" };
+                    format!("{} ({count} {keys})", path.display())
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    diagnostics.push(report);
 
-    if let Err(err) = fs::create_dir_all(output_dir) {
-        diagnostics.push(
-            Diagnostic::error(
-                "E203",
-                "unable to create scaffold output directory",
-                Category::Write,
-            )
-            .with_location(output_dir.display().to_string())
-            .with_cause(err.to_string())
-            .with_action("Check write permissions for the output path."),
-        );
-        return ScaffoldOutcome { diagnostics };
+    ScaffoldOutcome {
+        diagnostics,
+        entries: plan.entries,
     }
+}
 
-    if let Err(diagnostic) = write_value(None, &value, output_dir, options) {
-        diagnostics.push(*diagnostic);
+/// Everything a scaffold run writes, collected before the filesystem is touched so that
+/// invalid keys or unserializable values leave no partial output behind.
+#[derive(Debug, Default)]
+struct ScaffoldPlan {
+    /// Paths relative to the output directory, parents before children.
+    dirs: Vec<(PathBuf, DirKind)>,
+    files: Vec<PlannedFile>,
+    /// Entries renamed because their key differs from a sibling's only by case.
+    keymap: Keymap,
+    /// Files written for mappings grouped by `--group-leaves`, with their entry counts.
+    grouped: Vec<(PathBuf, usize)>,
+    /// The key path of the node being planned; empty at the root.
+    key_path: String,
+    entries: Vec<ScaffoldEntry>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl ScaffoldPlan {
+    fn add_dir(&mut self, path: PathBuf, kind: DirKind) {
+        let rule = match kind {
+            DirKind::Mapping => ScaffoldRule::MappingDirectory,
+            DirKind::Sequence => ScaffoldRule::SequenceDirectory,
+            DirKind::SequenceItem => ScaffoldRule::SequenceItemDirectory,
+            DirKind::Split => ScaffoldRule::SplitDirectory,
+        };
+        self.record(&path, EntryKind::Directory, rule);
+        self.dirs.push((path, kind));
     }
 
-    diagnostics.push(
-        Diagnostic::info(
-            "I200",
-            "scaffold generated a deterministic FYAML layout (non-invertible helper)",
-        )
-        .with_location(output_dir.display().to_string())
-        .with_cause("Scaffold is intentionally one-way and not a reverse of pack.")
-        .with_action("Validate with `fyaml pack <DIR>` and compare semantic output in CI."),
-    );
+    fn add_file(&mut self, file: PlannedFile, rule: ScaffoldRule) {
+        self.record(&file.path, EntryKind::File, rule);
+        self.files.push(file);
+    }
+
+    fn record(&mut self, path: &Path, kind: EntryKind, rule: ScaffoldRule) {
+        let key_path = match rule {
+            ScaffoldRule::Keymap => None,
+            _ if self.key_path.is_empty() => Some(ROOT_PATH.to_string()),
+            _ => Some(self.key_path.clone()),
+        };
+        self.entries.push(ScaffoldEntry {
+            path: path.to_string_lossy().replace('\\', "/"),
+            kind,
+            key_path,
+            rule,
+            transformations: Vec::new(),
+        });
+    }
+}
 
-    ScaffoldOutcome { diagnostics }
+#[derive(Debug, Clone, Copy)]
+enum DirKind {
+    Mapping,
+    Sequence,
+    SequenceItem,
+    Split,
 }
 
-fn write_value(
+#[derive(Debug)]
+struct PlannedFile {
+    path: PathBuf,
+    yaml: String,
+    split: bool,
+}
+
+fn plan_value(
     key: Option<&str>,
     value: &Value,
     directory: &Path,
     options: &ScaffoldOptions,
+    plan: &mut ScaffoldPlan,
 ) -> ScaffoldResult<()> {
     match value {
-        Value::Mapping(map) => write_mapping(key, map, directory, options),
-        Value::Sequence(sequence) => write_sequence(key, sequence, directory, options),
-        _ => write_scalar_file(key.unwrap_or("root"), value, directory, options),
+        Value::Mapping(map) => plan_mapping(key, map, directory, options, plan),
+        Value::Sequence(sequence) => plan_sequence(key, sequence, directory, options, plan),
+        _ => plan_scalar_file(
+            key.unwrap_or("root"),
+            value,
+            directory,
+            ScaffoldRule::ScalarFile,
+            options,
+            plan,
+        ),
     }
 }
 
-fn write_mapping(
+fn plan_mapping(
     key: Option<&str>,
     map: &serde_yaml::Mapping,
     directory: &Path,
     options: &ScaffoldOptions,
+    plan: &mut ScaffoldPlan,
 ) -> ScaffoldResult<()> {
     let target_directory = if let Some(key) = key {
         let key = normalize_path_key(key)?;
         let next = directory.join(key);
-        fs::create_dir_all(&next).map_err(|err| {
-            Box::new(
-                Diagnostic::error(
-                    "E204",
-                    "unable to create mapping directory",
-                    Category::Write,
-                )
-                .with_location(next.display().to_string())
-                .with_cause(err.to_string())
-                .with_action("Check write permissions and path validity."),
-            )
-        })?;
+        plan.add_dir(next.clone(), DirKind::Mapping);
         next
     } else {
         directory.to_path_buf()
@@ -190,103 +458,214 @@ fn write_mapping(
 
     entries.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
 
-    for (child_key, child_value) in entries {
+    let keys = entries
+        .iter()
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<_>>();
+    let names = case_safe_names(&keys);
+
+    for ((key, child_value), child_key) in entries.into_iter().zip(names) {
+        let parent_path = plan.key_path.len();
+        push_key(&mut plan.key_path, &key);
+        let first_entry = plan.entries.len();
+        if child_key != key {
+            let entry = target_directory.join(&child_key);
+            plan.keymap
+                .keys
+                .insert(entry.to_string_lossy().replace('\\', "/"), key.clone());
+            plan.diagnostics.push(
+                Diagnostic::warn("W016", "sibling keys differ only by letter case")
+                    .with_location(entry.display().to_string())
+                    .with_derived_key_path(key.clone())
+                    .with_cause(
+                        "Case-insensitive filesystems cannot hold both names, so this entry was renamed.",
+                    )
+                    .with_action(format!(
+                        "Keep {KEYMAP_FILE_NAME} with the scaffolded tree; pack reads the original key from it."
+                    )),
+            );
+        }
+
         match child_value {
+            Value::Mapping(map) if is_groupable(map, options) => {
+                plan_scalar_file(
+                    &child_key,
+                    child_value,
+                    &target_directory,
+                    ScaffoldRule::GroupedLeaves,
+                    options,
+                    plan,
+                )?;
+                let path = target_directory.join(format!("{child_key}.yml"));
+                plan.grouped.push((path, map.len()));
+            }
             Value::Mapping(_) => {
                 let as_file = matches!(options.layout, ScaffoldLayout::Flat);
                 if as_file {
-                    write_scalar_file(&child_key, child_value, &target_directory, options)?;
+                    plan_scalar_file(
+                        &child_key,
+                        child_value,
+                        &target_directory,
+                        ScaffoldRule::FlatLayout,
+                        options,
+                        plan,
+                    )?;
                 } else {
-                    write_mapping(
+                    plan_mapping(
                         Some(&child_key),
                         child_value.as_mapping().expect("mapping"),
                         &target_directory,
                         options,
+                        plan,
                     )?;
                 }
             }
             Value::Sequence(_) => {
                 let as_file = matches!(options.layout, ScaffoldLayout::Flat);
                 if as_file {
-                    write_scalar_file(&child_key, child_value, &target_directory, options)?;
+                    plan_scalar_file(
+                        &child_key,
+                        child_value,
+                        &target_directory,
+                        ScaffoldRule::FlatLayout,
+                        options,
+                        plan,
+                    )?;
                 } else {
-                    write_sequence(
+                    plan_sequence(
                         Some(&child_key),
                         child_value.as_sequence().expect("sequence"),
                         &target_directory,
                         options,
+                        plan,
                     )?;
                 }
             }
-            _ => write_scalar_file(&child_key, child_value, &target_directory, options)?,
+            _ => plan_scalar_file(
+                &child_key,
+                child_value,
+                &target_directory,
+                ScaffoldRule::ScalarFile,
+                options,
+                plan,
+            )?,
         }
+        // The first entry planned for a key is the one its name is given to.
+        if child_key != key {
+            if let Some(entry) = plan.entries.get_mut(first_entry) {
+                entry.transformations.push(KeyTransformation::CaseRename {
+                    key,
+                    name: child_key,
+                });
+            }
+        }
+        plan.key_path.truncate(parent_path);
     }
 
     Ok(())
 }
 
-fn write_sequence(
+/// Whether `--group-leaves` writes `map` as one file: a non-empty mapping below the limit
+/// whose values are all scalars that fit on one line (and are not split by
+/// `--split-threshold-bytes`). Flat layouts already write every mapping as a file.
+fn is_groupable(map: &serde_yaml::Mapping, options: &ScaffoldOptions) -> bool {
+    let Some(limit) = options.group_leaves else {
+        return false;
+    };
+    if options.layout == ScaffoldLayout::Flat || map.is_empty() || map.len() >= limit {
+        return false;
+    }
+    map.values().all(|value| {
+        if matches!(
+            value,
+            Value::Mapping(_) | Value::Sequence(_) | Value::Tagged(_)
+        ) {
+            return false;
+        }
+        let Ok(yaml) = serde_yaml::to_string(value) else {
+            return false;
+        };
+        let yaml = yaml.trim_end_matches('\n');
+        !yaml.contains('\n')
+            && options
+                .split_threshold_bytes
+                .map_or(true, |threshold| yaml.len() < threshold)
+    })
+}
+
+fn plan_sequence(
     key: Option<&str>,
     sequence: &[Value],
     directory: &Path,
     options: &ScaffoldOptions,
+    plan: &mut ScaffoldPlan,
 ) -> ScaffoldResult<()> {
     let base_directory = if let Some(key) = key {
         let key = normalize_path_key(key)?;
         let next = directory.join(key);
-        fs::create_dir_all(&next).map_err(|err| {
-            Box::new(
-                Diagnostic::error(
-                    "E206",
-                    "unable to create sequence directory",
-                    Category::Write,
-                )
-                .with_location(next.display().to_string())
-                .with_cause(err.to_string())
-                .with_action("Check write permissions and path validity."),
-            )
-        })?;
+        plan.add_dir(next.clone(), DirKind::Sequence);
         next
     } else {
         directory.to_path_buf()
     };
 
     for (index, item) in sequence.iter().enumerate() {
+        let parent_path = plan.key_path.len();
+        push_index(&mut plan.key_path, index);
         let key = index.to_string();
         match options.seq {
-            SequenceLayout::Files => write_scalar_file(&key, item, &base_directory, options)?,
+            SequenceLayout::Files => {
+                plan_scalar_file(
+                    &key,
+                    item,
+                    &base_directory,
+                    ScaffoldRule::SequenceItemFile,
+                    options,
+                    plan,
+                )?;
+            }
             SequenceLayout::Dir => {
-                let item_dir = base_directory.join(&key);
-                fs::create_dir_all(&item_dir).map_err(|err| {
-                    Box::new(
-                        Diagnostic::error(
-                            "E207",
-                            "unable to create sequence item directory",
-                            Category::Write,
-                        )
-                        .with_location(item_dir.display().to_string())
-                        .with_cause(err.to_string())
-                        .with_action("Check write permissions and path validity."),
-                    )
-                })?;
-
-                match item {
-                    Value::Mapping(map) => write_mapping(None, map, &item_dir, options)?,
-                    Value::Sequence(seq) => write_sequence(None, seq, &item_dir, options)?,
-                    _ => write_scalar_file("value", item, &item_dir, options)?,
-                }
+                plan_sequence_item(&key, item, &base_directory, options, plan)?;
             }
         }
+        plan.key_path.truncate(parent_path);
     }
 
     Ok(())
 }
 
-fn write_scalar_file(
+/// Plans `item` as the directory `<directory>/<key>/`, with a scalar in `value.yml`.
+fn plan_sequence_item(
+    key: &str,
+    item: &Value,
+    directory: &Path,
+    options: &ScaffoldOptions,
+    plan: &mut ScaffoldPlan,
+) -> ScaffoldResult<()> {
+    let item_dir = directory.join(key);
+    plan.add_dir(item_dir.clone(), DirKind::SequenceItem);
+
+    match item {
+        Value::Mapping(map) => plan_mapping(None, map, &item_dir, options, plan),
+        Value::Sequence(seq) => plan_sequence(None, seq, &item_dir, options, plan),
+        _ => plan_scalar_file(
+            "value",
+            item,
+            &item_dir,
+            ScaffoldRule::ScalarFile,
+            options,
+            plan,
+        ),
+    }
+}
+
+fn plan_scalar_file(
     key: &str,
     value: &Value,
     directory: &Path,
+    rule: ScaffoldRule,
     options: &ScaffoldOptions,
+    plan: &mut ScaffoldPlan,
 ) -> ScaffoldResult<()> {
     let key = normalize_path_key(key)?;
     let output_path = directory.join(format!("{key}.yml"));
@@ -307,43 +686,251 @@ fn write_scalar_file(
     if let Some(threshold) = options.split_threshold_bytes {
         if yaml.len() > threshold && matches!(value, Value::String(_)) {
             let nested_path = directory.join(&key);
-            fs::create_dir_all(&nested_path).map_err(|err| {
-                Box::new(
-                    Diagnostic::error("E209", "unable to create split directory", Category::Write)
-                        .with_location(nested_path.display().to_string())
-                        .with_cause(err.to_string())
-                        .with_action("Check write permissions and path validity."),
-                )
-            })?;
-            let fallback = nested_path.join("value.yml");
-            fs::write(&fallback, yaml).map_err(|err| {
-                Box::new(
-                    Diagnostic::error(
-                        "E210",
-                        "unable to write split YAML fragment",
-                        Category::Write,
-                    )
-                    .with_location(fallback.display().to_string())
-                    .with_cause(err.to_string())
-                    .with_action("Check write permissions and available disk space."),
-                )
-            })?;
+            plan.add_dir(nested_path.clone(), DirKind::Split);
+            plan.add_file(
+                PlannedFile {
+                    path: nested_path.join("value.yml"),
+                    yaml,
+                    split: true,
+                },
+                ScaffoldRule::SplitValue,
+            );
             return Ok(());
         }
     }
 
-    fs::write(&output_path, yaml).map_err(|err| {
+    plan.add_file(
+        PlannedFile {
+            path: output_path,
+            yaml,
+            split: false,
+        },
+        rule,
+    );
+    Ok(())
+}
+
+fn plan_keymap(plan: &mut ScaffoldPlan) {
+    if plan.keymap.keys.is_empty() {
+        return;
+    }
+    let yaml = plan.keymap.to_json().expect("a string map serializes");
+    plan.add_file(
+        PlannedFile {
+            path: PathBuf::from(KEYMAP_FILE_NAME),
+            yaml,
+            split: false,
+        },
+        ScaffoldRule::Keymap,
+    );
+}
+
+/// Writes `plan` into a staging directory next to `output_dir` and moves it into place only
+/// once every directory and file exists, so a failed run leaves `output_dir` untouched.
+fn write_plan(
+    plan: &ScaffoldPlan,
+    output_dir: &Path,
+    options: &ScaffoldOptions,
+) -> ScaffoldResult<()> {
+    let staging = staging_directory(output_dir);
+    let _ = fs::remove_dir_all(&staging);
+    let staged = fs::create_dir_all(&staging)
+        .map_err(|err| output_dir_error(output_dir, &err))
+        .and_then(|()| write_staged(plan, &staging, output_dir, options))
+        .and_then(|()| commit_staged(plan, &staging, output_dir, options));
+    if staged.is_err() {
+        let _ = fs::remove_dir_all(&staging);
+    }
+    staged
+}
+
+fn staging_directory(output_dir: &Path) -> PathBuf {
+    let name = output_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "scaffold".to_string());
+    let staging_name = format!(".{name}.fyaml-staging-{}", std::process::id());
+    match output_dir.parent() {
+        Some(parent) => parent.join(staging_name),
+        None => PathBuf::from(staging_name),
+    }
+}
+
+fn output_dir_error(output_dir: &Path, err: &io::Error) -> Box<Diagnostic> {
+    Box::new(
+        Diagnostic::error(
+            "E203",
+            "unable to create scaffold output directory",
+            Category::Write,
+        )
+        .with_location(output_dir.display().to_string())
+        .with_cause(err.to_string())
+        .with_action("Check write permissions for the output path."),
+    )
+}
+
+fn dir_error(kind: DirKind, location: &Path, err: &io::Error) -> Box<Diagnostic> {
+    let (code, message) = match kind {
+        DirKind::Mapping => ("E204", "unable to create mapping directory"),
+        DirKind::Sequence => ("E206", "unable to create sequence directory"),
+        DirKind::SequenceItem => ("E207", "unable to create sequence item directory"),
+        DirKind::Split => ("E209", "unable to create split directory"),
+    };
+    Box::new(
+        Diagnostic::error(code, message, Category::Write)
+            .with_location(location.display().to_string())
+            .with_cause(err.to_string())
+            .with_action("Check write permissions and path validity."),
+    )
+}
+
+fn file_error(file: &PlannedFile, location: &Path, err: &io::Error) -> Box<Diagnostic> {
+    let (code, message) = if file.split {
+        ("E210", "unable to write split YAML fragment")
+    } else {
+        ("E211", "unable to write YAML fragment")
+    };
+    Box::new(
+        Diagnostic::error(code, message, Category::Write)
+            .with_location(location.display().to_string())
+            .with_cause(err.to_string())
+            .with_action("Check write permissions and available disk space."),
+    )
+}
+
+/// Creates a directory whose parent already exists; planned directories arrive parents first.
+fn create_dir(path: &Path) -> io::Result<()> {
+    match fs::create_dir(path) {
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists && path.is_dir() => Ok(()),
+        result => result,
+    }
+}
+
+fn write_staged(
+    plan: &ScaffoldPlan,
+    staging: &Path,
+    output_dir: &Path,
+    options: &ScaffoldOptions,
+) -> ScaffoldResult<()> {
+    for (dir, kind) in &plan.dirs {
+        create_dir(&staging.join(dir))
+            .map_err(|err| dir_error(*kind, &output_dir.join(dir), &err))?;
+    }
+
+    let workers = options.jobs.clamp(1, plan.files.len().max(1));
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let write_one = |file: &PlannedFile| -> io::Result<()> {
+        let path = staging.join(&file.path);
+        if options.fsync == FsyncMode::All {
+            let mut handle = fs::File::create(&path)?;
+            handle.write_all(file.yaml.as_bytes())?;
+            handle.sync_all()
+        } else {
+            fs::write(&path, &file.yaml)
+        }
+    };
+    // Files are claimed in plan order; the lowest failing index is reported so the error
+    // does not depend on scheduling.
+    let worker = || -> Option<(usize, io::Error)> {
+        while !failed.load(AtomicOrdering::Relaxed) {
+            let index = next.fetch_add(1, AtomicOrdering::Relaxed);
+            let file = plan.files.get(index)?;
+            if let Err(err) = write_one(file) {
+                failed.store(true, AtomicOrdering::Relaxed);
+                return Some((index, err));
+            }
+        }
+        None
+    };
+    let failure = if workers == 1 {
+        worker()
+    } else {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers).map(|_| scope.spawn(worker)).collect();
+            handles
+                .into_iter()
+                .filter_map(|handle| handle.join().expect("scaffold writer panicked"))
+                .min_by_key(|(index, _)| *index)
+        })
+    };
+    if let Some((index, err)) = failure {
+        let file = &plan.files[index];
+        return Err(file_error(file, &output_dir.join(&file.path), &err));
+    }
+
+    if options.fsync != FsyncMode::None {
+        sync_dir(staging).map_err(|err| output_dir_error(output_dir, &err))?;
+        for (dir, kind) in &plan.dirs {
+            sync_dir(&staging.join(dir))
+                .map_err(|err| dir_error(*kind, &output_dir.join(dir), &err))?;
+        }
+    }
+    Ok(())
+}
+
+/// Moves the staged tree into `output_dir`: with one rename when the output directory is
+/// new or empty, or entry by entry into an existing directory.
+fn commit_staged(
+    plan: &ScaffoldPlan,
+    staging: &Path,
+    output_dir: &Path,
+    options: &ScaffoldOptions,
+) -> ScaffoldResult<()> {
+    let commit_error = |location: &Path, err: io::Error| {
         Box::new(
-            Diagnostic::error("E211", "unable to write YAML fragment", Category::Write)
-                .with_location(output_path.display().to_string())
-                .with_cause(err.to_string())
-                .with_action("Check write permissions and available disk space."),
+            Diagnostic::error(
+                "E214",
+                "unable to move scaffold output into place",
+                Category::Write,
+            )
+            .with_location(location.display().to_string())
+            .with_cause(err.to_string())
+            .with_action("Check write permissions for the output path and its parent directory."),
         )
-    })?;
+    };
 
+    let is_empty_dir = fs::read_dir(output_dir).is_ok_and(|mut entries| entries.next().is_none());
+    if is_empty_dir {
+        fs::remove_dir(output_dir).map_err(|err| commit_error(output_dir, err))?;
+    }
+    if !output_dir.exists() {
+        fs::rename(staging, output_dir).map_err(|err| commit_error(output_dir, err))?;
+    } else {
+        for (dir, kind) in &plan.dirs {
+            let target = output_dir.join(dir);
+            create_dir(&target).map_err(|err| dir_error(*kind, &target, &err))?;
+        }
+        for file in &plan.files {
+            let target = output_dir.join(&file.path);
+            fs::rename(staging.join(&file.path), &target)
+                .map_err(|err| commit_error(&target, err))?;
+        }
+        let _ = fs::remove_dir_all(staging);
+    }
+
+    if options.fsync != FsyncMode::None {
+        let parent = output_dir
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        sync_dir(output_dir)
+            .and_then(|()| sync_dir(parent))
+            .map_err(|err| commit_error(output_dir, err))?;
+    }
     Ok(())
 }
 
+/// Flushes a directory's entries to disk. Windows cannot open directories for syncing, so
+/// this is a no-op there.
+fn sync_dir(path: &Path) -> io::Result<()> {
+    if cfg!(unix) {
+        fs::File::open(path)?.sync_all()
+    } else {
+        Ok(())
+    }
+}
+
 fn normalize_path_key(key: &str) -> ScaffoldResult<String> {
     if key.contains('/') || key.contains('\\') {
         return Err(Box::new(
@@ -391,4 +978,166 @@ mod tests {
         assert!(out.join("a.yml").exists());
         assert!(out.join("b.yml").exists());
     }
+
+    fn tree(root: &Path) -> Vec<(String, String)> {
+        let mut out = Vec::new();
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in fs::read_dir(&dir).expect("read dir") {
+                let path = entry.expect("entry").path();
+                let relative = path.strip_prefix(root).expect("inside root").display();
+                if path.is_dir() {
+                    out.push((format!("{relative}/"), String::new()));
+                    pending.push(path);
+                } else {
+                    out.push((
+                        relative.to_string(),
+                        fs::read_to_string(&path).expect("read"),
+                    ));
+                }
+            }
+        }
+        out.sort();
+        out
+    }
+
+    #[test]
+    fn group_leaves_writes_small_mappings_as_one_file() {
+        let dir = tempdir().expect("temp dir");
+        let input = dir.path().join("input.yml");
+        fs::write(
+            &input,
+            "db: {host: a, port: 5432}\nbig: {a: 1, b: 2, c: 3}\n\
+             nested: {inner: {x: 1}}\nnotes: {text: \"one\\ntwo\"}\n",
+        )
+        .expect("write input");
+
+        let out = dir.path().join("out");
+        let options = ScaffoldOptions {
+            group_leaves: Some(3),
+            ..ScaffoldOptions::default()
+        };
+        let outcome = scaffold(&input, &out, &options);
+
+        assert!(outcome.diagnostics.iter().all(|d| !d.is_error()));
+        assert_eq!(
+            fs::read_to_string(out.join("db.yml")).expect("grouped"),
+            "host: a\nport: 5432\n"
+        );
+        assert!(out.join("big/a.yml").exists());
+        assert!(out.join("nested/inner.yml").exists());
+        assert!(out.join("notes/text.yml").exists());
+        let report = outcome
+            .diagnostics
+            .iter()
+            .find(|d| d.code == "I200")
+            .expect("report");
+        assert_eq!(
+            report.context,
+            Some(format!(
+                "--group-leaves 3 wrote each mapping of fewer than 3 one-line scalars as one \
+                 file: {} (2 keys), {} (1 key)",
+                Path::new("db.yml").display(),
+                Path::new("nested/inner.yml").display()
+            ))
+        );
+    }
+
+    #[test]
+    fn entries_record_the_key_path_and_rule_of_every_entry() {
+        let dir = tempdir().expect("temp dir");
+        let input = dir.path().join("input.yml");
+        fs::write(
+            &input,
+            "API: 1\napi: 2\ndb: {host: a}\nitems: [x, {y: 1}]\n",
+        )
+        .expect("write input");
+
+        let out = dir.path().join("out");
+        let options = ScaffoldOptions {
+            group_leaves: Some(2),
+            ..ScaffoldOptions::default()
+        };
+        let outcome = scaffold(&input, &out, &options);
+
+        assert!(outcome.diagnostics.iter().all(|d| !d.is_error()));
+        let entries = outcome
+            .entries
+            .iter()
+            .map(|entry| {
+                let rule = serde_json::to_value(entry.rule).expect("rule serializes");
+                format!(
+                    "{} {} {}",
+                    entry.path,
+                    entry.key_path.as_deref().unwrap_or("-"),
+                    rule.as_str().expect("rule name")
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                "API.yml API scalar-file",
+                "api~2.yml api scalar-file",
+                "db.yml db grouped-leaves",
+                "items items sequence-directory",
+                "items/0.yml items[0] sequence-item-file",
+                "items/1.yml items[1] sequence-item-file",
+                "fyaml.keymap.json - keymap",
+            ]
+        );
+        assert_eq!(
+            outcome.entries[1].transformations,
+            vec![KeyTransformation::CaseRename {
+                key: "api".to_string(),
+                name: "api~2".to_string(),
+            }]
+        );
+        assert!(outcome.entries[0].transformations.is_empty());
+    }
+
+    #[test]
+    fn parallel_synced_writes_match_serial_and_failures_leave_no_output() {
+        let dir = tempdir().expect("temp dir");
+        let input = dir.path().join("input.yml");
+        fs::write(
+            &input,
+            "a: {b: [1, {c: 2}], d: x}\ne: [y, z]\nf: {g: {h: 3}}\n",
+        )
+        .expect("write input");
+
+        let serial = dir.path().join("serial");
+        let serial_options = ScaffoldOptions {
+            seq: SequenceLayout::Dir,
+            ..ScaffoldOptions::default()
+        };
+        let outcome = scaffold(&input, &serial, &serial_options);
+        assert!(outcome.diagnostics.iter().all(|d| !d.is_error()));
+
+        let parallel = dir.path().join("parallel");
+        fs::create_dir(&parallel).expect("empty output dir");
+        let options = ScaffoldOptions {
+            fsync: FsyncMode::All,
+            jobs: 4,
+            ..serial_options
+        };
+        let outcome = scaffold(&input, &parallel, &options);
+        assert!(outcome.diagnostics.iter().all(|d| !d.is_error()));
+        assert_eq!(tree(&parallel), tree(&serial));
+        assert!(tree(&serial).len() > 10);
+
+        fs::write(&input, "a: 1\nz: {\"bad/key\": 2}\n").expect("write input");
+        let failed = dir.path().join("failed");
+        let outcome = scaffold(&input, &failed, &options);
+        assert!(outcome.diagnostics.iter().any(|d| d.code == "E212"));
+        assert!(!failed.exists());
+        let leftovers = fs::read_dir(dir.path())
+            .expect("read dir")
+            .filter(|entry| {
+                let name = entry.as_ref().expect("entry").file_name();
+                name.to_string_lossy().contains("staging")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+    }
 }
@@ -1,65 +1,785 @@
-use serde_yaml::{Mapping, Value};
+use crate::config::{
+    BuildOptions, CanonicalOptions, Collation, IntegralFloats, NegativeZero, SortRule,
+};
+use crate::emitter::{self, EmitError, EmitterOptions};
+use crate::keypath::Segment;
+use crate::preserve::Preserved;
+use crate::timestamp::Timestamp;
+use icu_collator::{Collator, CollatorOptions};
+use serde_yaml::value::TaggedValue;
+use serde_yaml::{Mapping, Number, Value};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::{self, Write};
 
 pub fn canonicalize_yaml(value: &Value) -> Value {
-    match value {
-        Value::Sequence(items) => Value::Sequence(items.iter().map(canonicalize_yaml).collect()),
-        Value::Mapping(map) => {
-            let mut items: Vec<(Value, Value)> = map
-                .iter()
-                .map(|(k, v)| (canonicalize_yaml(k), canonicalize_yaml(v)))
+    canonicalize_yaml_with(value, &CanonicalOptions::default())
+}
+
+pub fn canonicalize_yaml_with(value: &Value, options: &CanonicalOptions) -> Value {
+    canonicalize_yaml_into(value.clone(), options)
+}
+
+/// Like [`canonicalize_yaml_with`], but reuses the nodes of `value` instead of copying them.
+///
+/// `Mapping` already keeps insertion order, so canonical order is applied here once, just
+/// before output; building and merging never sort. With `options.jobs > 1` the subtrees of
+/// the first [`PARALLEL_DEPTH`] levels are canonicalized on worker threads; the result is
+/// the same value either way.
+pub fn canonicalize_yaml_into(value: Value, options: &CanonicalOptions) -> Value {
+    let context = Canonicalizer::new(options);
+    if options.jobs > 1 {
+        context.value_parallel(value, PARALLEL_DEPTH)
+    } else {
+        context.value(value, false)
+    }
+}
+
+/// Puts a packed `value` in the order it is emitted in: canonical unless `--preserve`, with
+/// `--sort` rules overriding either for their subtrees.
+pub fn emission_order(value: Value, build: &BuildOptions, options: &CanonicalOptions) -> Value {
+    match (build.sort_rules.as_slice(), build.preserve) {
+        ([], true) => value,
+        ([], false) => canonicalize_yaml_into(value, options),
+        (rules, preserve) => canonicalize_yaml_regions(value, options, !preserve, rules),
+    }
+}
+
+/// Like [`canonicalize_yaml_into`] under `--sort` rules: the mappings in each subtree a rule
+/// names are sorted or left in build order as it says, and those outside every rule as
+/// `canonical` says. Numbers are normalized only when `canonical` is set; `--preserve` leaves
+/// them as written even where keys are sorted.
+pub fn canonicalize_yaml_regions(
+    value: Value,
+    options: &CanonicalOptions,
+    canonical: bool,
+    rules: &[SortRule],
+) -> Value {
+    Regions {
+        context: Canonicalizer::new(options),
+        rules,
+        normalize: canonical,
+    }
+    .value(value, &mut Vec::new(), canonical)
+}
+
+struct Regions<'a> {
+    context: Canonicalizer<'a>,
+    rules: &'a [SortRule],
+    normalize: bool,
+}
+
+impl Regions<'_> {
+    fn value(&self, value: Value, path: &mut Vec<Segment>, sorted: bool) -> Value {
+        let sorted = self
+            .rules
+            .iter()
+            .rev()
+            .find(|rule| rule.path.matches(path))
+            .map_or(sorted, |rule| rule.sorted);
+        match value {
+            Value::Mapping(map) => {
+                let entries = map.into_iter().map(|(key, value)| {
+                    path.push(Segment::Key(key_segment(&key)));
+                    let value = self.value(value, path, sorted);
+                    path.pop();
+                    let key = if self.normalize {
+                        self.context.value(key, true)
+                    } else {
+                        key
+                    };
+                    (key, value)
+                });
+                if sorted {
+                    self.context.sorted_mapping(entries)
+                } else {
+                    Value::Mapping(entries.collect())
+                }
+            }
+            Value::Sequence(items) => Value::Sequence(
+                items
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, item)| {
+                        path.push(Segment::Index(index));
+                        let item = self.value(item, path, sorted);
+                        path.pop();
+                        item
+                    })
+                    .collect(),
+            ),
+            Value::Tagged(tagged) => {
+                let TaggedValue { tag, value } = *tagged;
+                Value::Tagged(Box::new(TaggedValue {
+                    tag,
+                    value: self.value(value, path, sorted),
+                }))
+            }
+            other if self.normalize => self.context.value(other, false),
+            other => other,
+        }
+    }
+}
+
+fn key_segment(key: &Value) -> String {
+    match key {
+        Value::String(text) => text.clone(),
+        other => serde_yaml::to_string(other)
+            .map(|text| text.trim_end().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+/// Document levels whose children may be handed to worker threads.
+const PARALLEL_DEPTH: usize = 2;
+
+struct Canonicalizer<'a> {
+    order: KeyOrder,
+    options: &'a CanonicalOptions,
+}
+
+impl<'a> Canonicalizer<'a> {
+    fn new(options: &'a CanonicalOptions) -> Self {
+        Self {
+            order: KeyOrder::new(&options.collation),
+            options,
+        }
+    }
+
+    fn value_parallel(&self, value: Value, depth: usize) -> Value {
+        match value {
+            Value::Mapping(map) if depth > 0 => {
+                let (keys, values): (Vec<_>, Vec<_>) = map.into_iter().unzip();
+                let values = self.children_parallel(values, depth);
+                self.sorted_mapping(keys.into_iter().map(|k| self.value(k, true)).zip(values))
+            }
+            Value::Sequence(items) if depth > 0 => {
+                Value::Sequence(self.children_parallel(items, depth))
+            }
+            other => self.value(other, false),
+        }
+    }
+
+    /// A level with fewer children than jobs looks one level deeper for more work.
+    fn children_parallel(&self, values: Vec<Value>, depth: usize) -> Vec<Value> {
+        if values.len() < self.options.jobs && depth > 1 {
+            return values
+                .into_iter()
+                .map(|value| self.value_parallel(value, depth - 1))
                 .collect();
-            items.sort_by(|(a, _), (b, _)| sort_key_for_yaml(a).cmp(&sort_key_for_yaml(b)));
+        }
+        if values.len() < 2 {
+            return values
+                .into_iter()
+                .map(|value| self.value(value, false))
+                .collect();
+        }
 
-            let mut out = Mapping::new();
-            for (k, v) in items {
-                out.insert(k, v);
+        // Contiguous chunks, joined in order, keep the output independent of scheduling.
+        let chunk_size = values.len().div_ceil(self.options.jobs);
+        let mut values = values.into_iter();
+        let mut chunks = Vec::new();
+        loop {
+            let chunk: Vec<Value> = values.by_ref().take(chunk_size).collect();
+            if chunk.is_empty() {
+                break;
             }
-            Value::Mapping(out)
+            chunks.push(chunk);
         }
-        _ => value.clone(),
+
+        let options = self.options;
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = chunks
+                .into_iter()
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        let context = Canonicalizer::new(options);
+                        chunk
+                            .into_iter()
+                            .map(|value| context.value(value, false))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("canonicalization worker panicked"))
+                .collect()
+        })
+    }
+
+    /// Number rules are not applied inside mapping keys, where they could merge distinct keys.
+    fn value(&self, value: Value, in_key: bool) -> Value {
+        match value {
+            Value::Sequence(items) => Value::Sequence(
+                items
+                    .into_iter()
+                    .map(|item| self.value(item, in_key))
+                    .collect(),
+            ),
+            Value::Mapping(map) => self.sorted_mapping(
+                map.into_iter()
+                    .map(|(k, v)| (self.value(k, true), self.value(v, in_key))),
+            ),
+            Value::Tagged(tagged) => {
+                let TaggedValue { tag, value } = *tagged;
+                // A tag names its string's type, so boolean words under one stay strings.
+                let value = match value {
+                    Value::String(_) => value,
+                    other => self.value(other, in_key),
+                };
+                Value::Tagged(Box::new(TaggedValue { tag, value }))
+            }
+            Value::Number(number) if !in_key => Value::Number(self.number(&number)),
+            Value::String(text) if !in_key => self.string(text),
+            other => other,
+        }
+    }
+
+    /// Builds a mapping from canonicalized entries in canonical key order.
+    fn sorted_mapping(&self, entries: impl Iterator<Item = (Value, Value)>) -> Value {
+        let mut items: Vec<(Option<Vec<u8>>, Value, Value)> = entries
+            .map(|(key, value)| (non_string_sort_key(&key), key, value))
+            .collect();
+        let numeric =
+            self.options.numeric_keys && items.iter().all(|(_, key, _)| integer_key(key).is_some());
+        items.sort_by(|(a_bytes, a, _), (b_bytes, b, _)| {
+            let by_value = if numeric {
+                integer_key(a).cmp(&integer_key(b))
+            } else {
+                Ordering::Equal
+            };
+            by_value.then_with(|| {
+                self.order
+                    .compare(sort_key(a_bytes, a), sort_key(b_bytes, b))
+            })
+        });
+
+        let mut out = Mapping::with_capacity(items.len());
+        for (_, k, v) in items {
+            out.insert(k, v);
+        }
+        Value::Mapping(out)
+    }
+
+    fn string(&self, text: String) -> Value {
+        if self.options.yaml11_booleans {
+            if let Some(flag) = yaml11_boolean(&text) {
+                return Value::Bool(flag);
+            }
+        }
+        if self.options.utc_timestamps {
+            // Dates and naive datetimes have no instant to convert, so they are kept as written.
+            let utc = Timestamp::parse(&text)
+                .filter(|timestamp| timestamp.has_offset())
+                .and_then(|timestamp| timestamp.to_utc_string());
+            if let Some(utc) = utc {
+                return Value::String(utc);
+            }
+        }
+        Value::String(text)
+    }
+
+    fn number(&self, number: &Number) -> Number {
+        let Some(mut f) = number.as_f64().filter(|_| number.is_f64()) else {
+            return number.clone();
+        };
+
+        if self.options.negative_zero == NegativeZero::Zero && f == 0.0 {
+            f = 0.0;
+        }
+        if self.options.integral_floats == IntegralFloats::Int && f.is_finite() && f.fract() == 0.0
+        {
+            // Bounds are exact powers of two, so every float inside them converts without loss.
+            if (-9_223_372_036_854_775_808.0..9_223_372_036_854_775_808.0).contains(&f) {
+                return Number::from(f as i64);
+            }
+            if (0.0..18_446_744_073_709_551_616.0).contains(&f) {
+                return Number::from(f as u64);
+            }
+        }
+        Number::from(f)
     }
 }
 
-fn sort_key_for_yaml(key: &Value) -> Vec<u8> {
+/// The boolean a YAML 1.1 parser reads `text` as, for the words YAML 1.2 reads as strings.
+/// The single letters `y` and `n` are left out, as most YAML 1.1 parsers do.
+fn yaml11_boolean(text: &str) -> Option<bool> {
+    match text {
+        "yes" | "Yes" | "YES" | "on" | "On" | "ON" => Some(true),
+        "no" | "No" | "NO" | "off" | "Off" | "OFF" => Some(false),
+        _ => None,
+    }
+}
+
+/// String keys sort by their own bytes; other keys by their YAML rendering, computed once.
+fn non_string_sort_key(key: &Value) -> Option<Vec<u8>> {
     match key {
-        Value::String(s) => s.as_bytes().to_vec(),
-        _ => serde_yaml::to_string(key)
-            .unwrap_or_else(|_| format!("{key:?}"))
-            .into_bytes(),
+        Value::String(_) => None,
+        _ => Some(
+            serde_yaml::to_string(key)
+                .unwrap_or_else(|_| format!("{key:?}"))
+                .into_bytes(),
+        ),
+    }
+}
+
+/// The value of an integer key, or of a string key that is an integer written in decimal
+/// digits (with an optional `-`), for `numeric_keys`. Keys out of `i128` range are not
+/// integers here.
+fn integer_key(key: &Value) -> Option<i128> {
+    match key {
+        Value::Number(number) => number
+            .as_i64()
+            .map(i128::from)
+            .or_else(|| number.as_u64().map(i128::from)),
+        Value::String(text) => integer_text(text),
+        _ => None,
+    }
+}
+
+fn integer_text(text: &str) -> Option<i128> {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+fn sort_key<'a>(rendered: &'a Option<Vec<u8>>, key: &'a Value) -> &'a [u8] {
+    match (rendered, key) {
+        (Some(bytes), _) => bytes,
+        (None, Value::String(s)) => s.as_bytes(),
+        (None, _) => &[],
+    }
+}
+
+/// Comparator for canonical key sort keys.
+///
+/// Collated orders fall back to byte order for keys the collator considers equal, so the
+/// result is always a total order.
+enum KeyOrder {
+    Bytewise,
+    Natural,
+    Collated(Box<Collator>),
+}
+
+impl KeyOrder {
+    fn new(collation: &Collation) -> Self {
+        let locale = match collation {
+            Collation::Bytewise => return Self::Bytewise,
+            Collation::Natural => return Self::Natural,
+            Collation::Unicode => icu_locid::Locale::UND,
+            Collation::Cldr(locale) => locale.clone(),
+        };
+        // Compiled collation data falls back to the root order, so every locale loads.
+        let collator = Collator::try_new(&locale.into(), CollatorOptions::new())
+            .expect("compiled collation data covers every locale");
+        Self::Collated(Box::new(collator))
+    }
+
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        match self {
+            Self::Bytewise => a.cmp(b),
+            Self::Natural => natural_cmp(a, b).then_with(|| a.cmp(b)),
+            Self::Collated(collator) => collator.compare_utf8(a, b).then_with(|| a.cmp(b)),
+        }
+    }
+}
+
+/// Compares `a` and `b` byte by byte, except that runs of ASCII digits compare by their
+/// numeric value, so `item2` sorts before `item10`. Runs differing only in leading zeros
+/// compare equal.
+pub fn natural_cmp(a: &[u8], b: &[u8]) -> Ordering {
+    // The digits of the run at `start` without leading zeros, and where the run ends.
+    fn run(text: &[u8], start: usize) -> (&[u8], usize) {
+        let end = text[start..]
+            .iter()
+            .position(|byte| !byte.is_ascii_digit())
+            .map_or(text.len(), |len| start + len);
+        let digits = &text[start..end];
+        let zeros = digits.iter().take_while(|&&byte| byte == b'0').count();
+        (&digits[zeros..], end)
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i].is_ascii_digit() && b[j].is_ascii_digit() {
+            let (left, left_end) = run(a, i);
+            let (right, right_end) = run(b, j);
+            let order = left.len().cmp(&right.len()).then_with(|| left.cmp(right));
+            if order != Ordering::Equal {
+                return order;
+            }
+            (i, j) = (left_end, right_end);
+        } else {
+            if a[i] != b[j] {
+                return a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+    }
+    (a.len() - i).cmp(&(b.len() - j))
+}
+
+/// The comment block YAML output starts with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Header {
+    /// Comment lines, without `# `, written before the canonicalization notes. No lines
+    /// means no header at all (`--no-header`).
+    pub lines: Vec<String>,
+}
+
+/// Values for the placeholders of `--header-template`.
+#[derive(Debug, Clone, Default)]
+pub struct HeaderFields {
+    pub version: String,
+    pub git_sha: String,
+    pub source_dir: String,
+    /// `None` unless `--header-timestamp` is given, so output stays deterministic.
+    pub timestamp: Option<String>,
+}
+
+/// Header line that records the fingerprint of the document below it.
+const FINGERPRINT_LINE: &str = "fingerprint: ";
+
+/// The fingerprint recorded in the leading comment block of packed YAML `text`, if any.
+pub fn header_fingerprint(text: &str) -> Option<&str> {
+    text.lines()
+        .map_while(|line| line.strip_prefix('#'))
+        .find_map(|line| line.trim().strip_prefix(FINGERPRINT_LINE))
+        .map(str::trim)
+}
+
+/// Placeholders `--header-template` fills in.
+pub const HEADER_PLACEHOLDERS: &[&str] = &["version", "git_sha", "source_dir", "timestamp"];
+
+impl Header {
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Adds `# fingerprint: <fingerprint>` after the other lines; no header stays none.
+    pub fn with_fingerprint(mut self, fingerprint: &str) -> Self {
+        if !self.lines.is_empty() {
+            self.lines.push(format!("{FINGERPRINT_LINE}{fingerprint}"));
+        }
+        self
+    }
+
+    /// `# packed by fyaml v<version>`.
+    pub fn packed_by(version: &str) -> Self {
+        Self {
+            lines: vec![format!("packed by fyaml v{version}")],
+        }
+    }
+
+    /// Fills the `{name}` placeholders of `template`; `\n`, written out or as a line
+    /// break, starts a new comment line. Errors name the placeholder that cannot be filled.
+    pub fn from_template(template: &str, fields: &HeaderFields) -> Result<Self, String> {
+        let mut text = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            text.push_str(&rest[..start]);
+            let Some(end) = rest[start..].find('}') else {
+                return Err(format!("unclosed `{{` in `{template}`"));
+            };
+            let name = &rest[start + 1..start + end];
+            text.push_str(match name {
+                "version" => &fields.version,
+                "git_sha" => &fields.git_sha,
+                "source_dir" => &fields.source_dir,
+                "timestamp" => fields.timestamp.as_deref().ok_or_else(|| {
+                    "{timestamp} is only filled in with --header-timestamp".to_string()
+                })?,
+                other => {
+                    return Err(format!(
+                        "unknown placeholder `{{{other}}}`; expected one of {}",
+                        HEADER_PLACEHOLDERS
+                            .iter()
+                            .map(|name| format!("{{{name}}}"))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ))
+                }
+            });
+            rest = &rest[start + end + 1..];
+        }
+        text.push_str(rest);
+        Ok(Self {
+            lines: text
+                .replace("\\n", "\n")
+                .lines()
+                .map(|line| line.trim_end().to_string())
+                .collect(),
+        })
+    }
+}
+
+fn write_header<W: Write>(
+    out: &mut W,
+    header: &Header,
+    options: &CanonicalOptions,
+) -> io::Result<()> {
+    if header.lines.is_empty() {
+        return Ok(());
     }
+    for line in &header.lines {
+        if line.is_empty() {
+            writeln!(out, "#")?;
+        } else {
+            writeln!(out, "# {line}")?;
+        }
+    }
+    for note in options.header_notes() {
+        writeln!(out, "# {note}")?;
+    }
+    Ok(())
 }
 
 pub fn emit_yaml(
     value: &Value,
-    include_header: bool,
-    version: &str,
-) -> Result<String, serde_yaml::Error> {
-    let mut out = String::new();
-    if include_header {
-        out.push_str(&format!("# packed by fyaml v{version}\n"));
+    header: &Header,
+    options: &CanonicalOptions,
+) -> Result<String, EmitError> {
+    let mut out = Vec::new();
+    write_yaml(&mut out, value, header, options)?;
+    Ok(String::from_utf8(out).expect("emitter writes UTF-8"))
+}
+
+/// Streams YAML output to `out`, header first.
+pub fn write_yaml<W: Write>(
+    mut out: W,
+    value: &Value,
+    header: &Header,
+    options: &CanonicalOptions,
+) -> Result<(), EmitError> {
+    write_header(&mut out, header, options)?;
+    let emitter_options = yaml_emitter_options(options);
+    emitter::write_yaml(out, value, &emitter_options)
+}
+
+/// Like [`write_yaml`], writing back the comments and anchors `--preserve` kept.
+pub fn write_yaml_preserved<W: Write>(
+    mut out: W,
+    value: &Value,
+    header: &Header,
+    options: &CanonicalOptions,
+    preserved: &Preserved,
+) -> Result<(), EmitError> {
+    write_header(&mut out, header, options)?;
+    let emitter_options = yaml_emitter_options(options);
+    emitter::write_yaml_preserved(out, value, &emitter_options, preserved)
+}
+
+fn yaml_emitter_options(options: &CanonicalOptions) -> EmitterOptions {
+    EmitterOptions {
+        float_format: options.float_format,
+        indent: options.yaml_indent,
+        quote_strings: options.quote_strings,
+        flow_max_items: options.flow_max_items,
+        fold_strings: options.fold_strings,
+        max_line_width: options.max_line_width,
     }
-    out.push_str(&serde_yaml::to_string(value)?);
-    Ok(out)
 }
 
-pub fn emit_json(value: &Value) -> Result<String, serde_json::Error> {
-    let json = serde_json::to_value(value)?;
-    let canonical = canonicalize_json(json);
-    serde_json::to_string_pretty(&canonical)
+/// Streams each of `items` as its own YAML document, each starting with `---`, header first.
+pub fn write_yaml_documents<W: Write>(
+    mut out: W,
+    items: &[Value],
+    header: &Header,
+    options: &CanonicalOptions,
+) -> Result<(), EmitError> {
+    write_header(&mut out, header, options)?;
+    let emitter_options = yaml_emitter_options(options);
+    for item in items {
+        writeln!(out, "---")?;
+        emitter::write_yaml(&mut out, item, &emitter_options)?;
+    }
+    Ok(())
+}
+
+/// A node of a packed value that JSON output would reject or silently change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonLoss {
+    /// Dotted key path of the node, with sequence indexes as segments (`ports.0`).
+    pub key_path: String,
+    pub reason: String,
+}
+
+/// Finds the YAML-only constructs in `value` that `--format json` cannot carry: mapping keys
+/// that are null, collections, or tagged; keys that become the same JSON string (`1` and
+/// `"1"`); and NaN or infinite floats, which JSON would write as `null`.
+pub fn json_losses(value: &Value) -> Vec<JsonLoss> {
+    let mut losses = Vec::new();
+    collect_json_losses(value, "", &mut losses);
+    losses
 }
 
-fn canonicalize_json(value: serde_json::Value) -> serde_json::Value {
+fn collect_json_losses(value: &Value, key_path: &str, losses: &mut Vec<JsonLoss>) {
     match value {
-        serde_json::Value::Array(items) => {
-            serde_json::Value::Array(items.into_iter().map(canonicalize_json).collect())
+        Value::Number(number) if number.as_f64().is_some_and(|f| !f.is_finite()) => {
+            losses.push(JsonLoss {
+                key_path: key_path.to_string(),
+                reason: format!(
+                    "`{}` has no JSON number; it would be written as null.",
+                    yaml_text(value)
+                ),
+            });
         }
+        Value::Sequence(items) => {
+            for (index, item) in items.iter().enumerate() {
+                collect_json_losses(item, &join_path(key_path, &index.to_string()), losses);
+            }
+        }
+        Value::Mapping(map) => {
+            let mut seen: HashMap<String, &Value> = HashMap::new();
+            for (key, child) in map {
+                let child_path = join_path(key_path, &yaml_text(key));
+                match json_key(key) {
+                    None => losses.push(JsonLoss {
+                        key_path: key_path.to_string(),
+                        reason: format!(
+                            "Mapping key `{}` is not a string, number, or boolean, so JSON has no key for it.",
+                            quoted_text(key)
+                        ),
+                    }),
+                    Some(json) => {
+                        if let Some(previous) = seen.insert(json.clone(), key) {
+                            losses.push(JsonLoss {
+                                key_path: child_path.clone(),
+                                reason: format!(
+                                    "Mapping keys `{}` and `{}` both become the JSON key \"{json}\", so one value would be lost.",
+                                    quoted_text(previous),
+                                    quoted_text(key)
+                                ),
+                            });
+                        }
+                    }
+                }
+                collect_json_losses(child, &child_path, losses);
+            }
+        }
+        Value::Tagged(tagged) => collect_json_losses(&tagged.value, key_path, losses),
+        _ => {}
+    }
+}
+
+/// The JSON object key serde_json writes for `key`, or `None` when it rejects the key.
+fn json_key(key: &Value) -> Option<String> {
+    let mut map = Mapping::new();
+    map.insert(key.clone(), Value::Null);
+    match serde_json::to_value(&map) {
+        Ok(serde_json::Value::Object(object)) => object.into_iter().next().map(|(key, _)| key),
+        _ => None,
+    }
+}
+
+fn yaml_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Sequence(_) | Value::Mapping(_) => {
+            serde_json::to_string(value).unwrap_or_else(|_| format!("{value:?}"))
+        }
+        _ => serde_yaml::to_string(value)
+            .unwrap_or_else(|_| format!("{value:?}"))
+            .trim()
+            .to_string(),
+    }
+}
+
+/// Like [`yaml_text`], but strings are quoted when YAML would read them as another type.
+fn quoted_text(value: &Value) -> String {
+    match value {
+        Value::String(_) => serde_yaml::to_string(value)
+            .map(|text| text.trim().to_string())
+            .unwrap_or_else(|_| yaml_text(value)),
+        _ => yaml_text(value),
+    }
+}
+
+fn join_path(parent: &str, child: &str) -> String {
+    if parent.is_empty() {
+        child.to_string()
+    } else {
+        format!("{parent}.{child}")
+    }
+}
+
+/// Renders JSON output; a tagged value becomes a single-entry object keyed by its tag,
+/// e.g. `{"!Ref": "Bucket"}`.
+pub fn emit_json(value: &Value, options: &CanonicalOptions) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&json_value(value, options)?)
+}
+
+/// Streams JSON output to `out`; see [`emit_json`].
+pub fn write_json<W: Write>(
+    out: W,
+    value: &Value,
+    options: &CanonicalOptions,
+) -> Result<(), serde_json::Error> {
+    serde_json::to_writer_pretty(out, &json_value(value, options)?)
+}
+
+/// Streams JSON output on a single line to `out`; see [`emit_json`].
+pub fn write_json_compact<W: Write>(
+    out: W,
+    value: &Value,
+    options: &CanonicalOptions,
+) -> Result<(), serde_json::Error> {
+    serde_json::to_writer(out, &json_value(value, options)?)
+}
+
+/// Streams each of `items` to `out` as one line of JSON (NDJSON); see [`emit_json`].
+pub fn write_ndjson<W: Write>(
+    mut out: W,
+    items: &[Value],
+    options: &CanonicalOptions,
+) -> Result<(), serde_json::Error> {
+    for item in items {
+        serde_json::to_writer(&mut out, &json_value(item, options)?)?;
+        out.write_all(b"\n").map_err(serde_json::Error::io)?;
+    }
+    Ok(())
+}
+
+fn json_value(
+    value: &Value,
+    options: &CanonicalOptions,
+) -> Result<serde_json::Value, serde_json::Error> {
+    let json = serde_json::to_value(value)?;
+    let order = KeyOrder::new(&options.collation);
+    Ok(canonicalize_json(json, &order, options.numeric_keys))
+}
+
+fn canonicalize_json(
+    value: serde_json::Value,
+    order: &KeyOrder,
+    numeric_keys: bool,
+) -> serde_json::Value {
+    match value {
+        serde_json::Value::Array(items) => serde_json::Value::Array(
+            items
+                .into_iter()
+                .map(|item| canonicalize_json(item, order, numeric_keys))
+                .collect(),
+        ),
         serde_json::Value::Object(map) => {
+            let mut entries: Vec<(String, serde_json::Value)> = map.into_iter().collect();
+            let numeric =
+                numeric_keys && entries.iter().all(|(key, _)| integer_text(key).is_some());
+            entries.sort_by(|(a, _), (b, _)| {
+                let by_value = if numeric {
+                    integer_text(a).cmp(&integer_text(b))
+                } else {
+                    Ordering::Equal
+                };
+                by_value.then_with(|| order.compare(a.as_bytes(), b.as_bytes()))
+            });
             let mut out = serde_json::Map::new();
-            let mut keys: Vec<String> = map.keys().cloned().collect();
-            keys.sort();
-            for key in keys {
-                let value = map.get(&key).cloned().unwrap_or(serde_json::Value::Null);
-                out.insert(key, canonicalize_json(value));
+            for (key, value) in entries {
+                out.insert(key, canonicalize_json(value, order, numeric_keys));
             }
             serde_json::Value::Object(out)
         }
@@ -70,23 +790,305 @@ fn canonicalize_json(value: serde_json::Value) -> serde_json::Value {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::FloatFormat;
 
     #[test]
     fn canonicalize_yaml_orders_map_keys() {
         let value: Value = serde_yaml::from_str("z: 1\na: 2\n").expect("valid yaml");
         let canonical = canonicalize_yaml(&value);
-        let emitted = emit_yaml(&canonical, false, "0.1.0").expect("emit yaml");
+        let emitted = emit_yaml(&canonical, &Header::none(), &CanonicalOptions::default())
+            .expect("emit yaml");
         let a_pos = emitted.find("a:").expect("a present");
         let z_pos = emitted.find("z:").expect("z present");
         assert!(a_pos < z_pos);
     }
 
+    #[test]
+    fn owned_canonicalization_sorts_mixed_keys_like_borrowed() {
+        let value: Value =
+            serde_yaml::from_str("b: 1\n10: x\n2: y\na: !Ref {z: 1, y: 2}\ntrue: t\n")
+                .expect("valid yaml");
+        let borrowed = canonicalize_yaml(&value);
+        let owned = canonicalize_yaml_into(value, &CanonicalOptions::default());
+        assert_eq!(owned, borrowed);
+
+        let keys = owned
+            .as_mapping()
+            .expect("mapping")
+            .keys()
+            .map(|k| serde_yaml::to_string(k).expect("key").trim().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(keys, vec!["10", "2", "a", "b", "true"]);
+    }
+
+    #[test]
+    fn parallel_canonicalization_matches_serial() {
+        // One wide level and one narrow level that forces the search one level deeper.
+        let mut text = String::from("narrow:\n");
+        for i in (0..40).rev() {
+            text.push_str(&format!(
+                "  k{i}: {{z: {i}.0, a: [-0.0, {{y: 1, b: 2}}]}}\n"
+            ));
+        }
+        for i in (0..25).rev() {
+            text.push_str(&format!("top{i}: {{c: {i}, b: [x, {{q: 1, p: 2}}]}}\n"));
+        }
+        let value: Value = serde_yaml::from_str(&text).expect("valid yaml");
+
+        let serial_options = CanonicalOptions {
+            collation: Collation::Unicode,
+            integral_floats: IntegralFloats::Int,
+            negative_zero: NegativeZero::Zero,
+            ..CanonicalOptions::default()
+        };
+        let serial = canonicalize_yaml_with(&value, &serial_options);
+        for jobs in [2, 3, 8, 64] {
+            let options = CanonicalOptions {
+                jobs,
+                ..serial_options.clone()
+            };
+            let parallel = canonicalize_yaml_into(value.clone(), &options);
+            assert_eq!(
+                emit_yaml(&parallel, &Header::none(), &options).expect("emit"),
+                emit_yaml(&serial, &Header::none(), &serial_options).expect("emit"),
+                "jobs={jobs}"
+            );
+        }
+    }
+
     #[test]
     fn canonicalize_json_orders_keys() {
         let value: Value = serde_yaml::from_str("z: 1\na: 2\n").expect("valid yaml");
-        let json = emit_json(&value).expect("emit json");
+        let json = emit_json(&value, &CanonicalOptions::default()).expect("emit json");
         let a_pos = json.find("\"a\"").expect("a present");
         let z_pos = json.find("\"z\"").expect("z present");
         assert!(a_pos < z_pos);
     }
+
+    #[test]
+    fn cldr_collation_orders_keys_by_locale() {
+        let value: Value = serde_yaml::from_str("zebra: 1\nÄpfel: 2\nbanana: 3\n").expect("yaml");
+        let keys = |collation: Collation| {
+            let options = CanonicalOptions {
+                collation,
+                ..CanonicalOptions::default()
+            };
+            canonicalize_yaml_with(&value, &options)
+                .as_mapping()
+                .expect("mapping")
+                .keys()
+                .map(|k| k.as_str().expect("string key").to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(keys(Collation::Bytewise), vec!["banana", "zebra", "Äpfel"]);
+        assert_eq!(
+            keys("cldr:de".parse().expect("known locale")),
+            vec!["Äpfel", "banana", "zebra"]
+        );
+    }
+
+    #[test]
+    fn numeric_keys_sort_integer_keyed_mappings_by_value() {
+        let value: Value = serde_yaml::from_str(
+            "ports: {\"10\": a, \"2\": b, -1: c, \"02\": d}\nmixed: {\"10\": a, \"2\": b, x: c}\n",
+        )
+        .expect("yaml");
+        let options = CanonicalOptions {
+            numeric_keys: true,
+            ..CanonicalOptions::default()
+        };
+        let canonical = canonicalize_yaml_with(&value, &options);
+        let keys = |name: &str| {
+            canonical[name]
+                .as_mapping()
+                .expect("mapping")
+                .keys()
+                .map(|key| serde_yaml::to_string(key).expect("key").trim().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(keys("ports"), vec!["-1", "'02'", "'2'", "'10'"]);
+        assert_eq!(keys("mixed"), vec!["'10'", "'2'", "x"]);
+
+        let mut json = Vec::new();
+        write_ndjson(
+            &mut json,
+            &[canonical["mixed"].clone(), canonical["ports"].clone()],
+            &options,
+        )
+        .expect("ndjson");
+        assert_eq!(
+            String::from_utf8(json).expect("utf8"),
+            "{\"10\":\"a\",\"2\":\"b\",\"x\":\"c\"}\n{\"-1\":\"c\",\"02\":\"d\",\"2\":\"b\",\"10\":\"a\"}\n"
+        );
+    }
+
+    #[test]
+    fn yaml11_booleans_normalize_values_but_not_keys() {
+        let value: Value = serde_yaml::from_str(
+            "on: {push: yes, pr: Off, name: 'no', tag: !word YES, list: [ON, n, 'yes please']}\n",
+        )
+        .expect("yaml");
+        let options = CanonicalOptions {
+            yaml11_booleans: true,
+            ..CanonicalOptions::default()
+        };
+        let expected: Value = serde_yaml::from_str(
+            "on: {list: [true, n, 'yes please'], name: false, pr: false, push: true, tag: !word YES}\n",
+        )
+        .expect("yaml");
+        assert_eq!(canonicalize_yaml_with(&value, &options), expected);
+    }
+
+    #[test]
+    fn utc_timestamps_make_the_same_instant_compare_equal() {
+        let left: Value = serde_yaml::from_str(
+            "at: 2024-03-01T10:00:00+02:00\nday: 2024-03-01\nlocal: 2024-03-01T10:00:00\n",
+        )
+        .expect("yaml");
+        let right: Value = serde_yaml::from_str(
+            "at: 2024-03-01 08:00:00.000 Z\nday: 2024-03-01\nlocal: 2024-03-01T10:00:00\n",
+        )
+        .expect("yaml");
+        let options = CanonicalOptions {
+            utc_timestamps: true,
+            ..CanonicalOptions::default()
+        };
+        let left = canonicalize_yaml_with(&left, &options);
+        assert_eq!(left, canonicalize_yaml_with(&right, &options));
+        assert_eq!(
+            left["at"],
+            Value::String("2024-03-01T08:00:00Z".to_string())
+        );
+        assert_eq!(
+            left["local"],
+            Value::String("2024-03-01T10:00:00".to_string())
+        );
+    }
+
+    #[test]
+    fn natural_collation_orders_digit_runs_by_value() {
+        let value: Value =
+            serde_yaml::from_str("item10: 1\nitem2: 2\nitem1b: 3\nitem02: 4\nitem: 5\n")
+                .expect("yaml");
+        let options = CanonicalOptions {
+            collation: Collation::Natural,
+            ..CanonicalOptions::default()
+        };
+        let keys = canonicalize_yaml_with(&value, &options)
+            .as_mapping()
+            .expect("mapping")
+            .keys()
+            .map(|k| k.as_str().expect("string key").to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(keys, vec!["item", "item1b", "item02", "item2", "item10"]);
+    }
+
+    #[test]
+    fn number_rules_normalize_values_but_not_keys() {
+        let value: Value =
+            serde_yaml::from_str("1.0: key\nwhole: 3.0\nneg: -0.0\nfrac: 2.5\nbig: 1.0e300\n")
+                .expect("yaml");
+        let options = CanonicalOptions {
+            integral_floats: IntegralFloats::Int,
+            negative_zero: NegativeZero::Zero,
+            float_format: FloatFormat::Canonical,
+            ..CanonicalOptions::default()
+        };
+        let emitted = emit_yaml(
+            &canonicalize_yaml_with(&value, &options),
+            &Header::packed_by("0.1.0"),
+            &options,
+        )
+        .expect("emit yaml");
+
+        assert_eq!(
+            emitted,
+            "# packed by fyaml v0.1.0\n\
+             # numbers: float-format=canonical, integral-floats=int, negative-zero=zero\n\
+             1.0: key\nbig: 1.0e+300\nfrac: 2.5\nneg: 0\nwhole: 3\n"
+        );
+    }
+
+    #[test]
+    fn sort_rules_mix_sorted_and_build_order_subtrees() {
+        let value: Value = serde_yaml::from_str(
+            "z: {b: 1, a: 2}\nservices: {web: {z: 1, a: 2}, db: {y: 1, x: 2}}\na: 1\n",
+        )
+        .expect("valid yaml");
+        let rules = vec![
+            "services=keys".parse().expect("rule"),
+            "services.db=none".parse().expect("rule"),
+        ];
+        let keys = |value: &Value| {
+            value
+                .as_mapping()
+                .expect("mapping")
+                .keys()
+                .map(|key| key.as_str().expect("string key").to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let options = CanonicalOptions::default();
+        let preserved = canonicalize_yaml_regions(value.clone(), &options, false, &rules);
+        assert_eq!(keys(&preserved), vec!["z", "services", "a"]);
+        assert_eq!(keys(&preserved["z"]), vec!["b", "a"]);
+        assert_eq!(keys(&preserved["services"]), vec!["db", "web"]);
+        assert_eq!(keys(&preserved["services"]["web"]), vec!["a", "z"]);
+        assert_eq!(keys(&preserved["services"]["db"]), vec!["y", "x"]);
+
+        let canonical = canonicalize_yaml_regions(value, &options, true, &rules);
+        assert_eq!(keys(&canonical), vec!["a", "services", "z"]);
+        assert_eq!(keys(&canonical["services"]["db"]), vec!["y", "x"]);
+    }
+
+    #[test]
+    fn json_losses_name_the_offending_paths() {
+        let value: Value = serde_yaml::from_str(
+            "ok: 1\nports:\n  1: a\n  \"1\": b\nratios: [.nan, 0.5]\nnested:\n  ? [x]\n  : y\n",
+        )
+        .expect("valid yaml");
+        let losses = json_losses(&value)
+            .into_iter()
+            .map(|loss| loss.key_path)
+            .collect::<Vec<_>>();
+        assert_eq!(losses, vec!["ports.1", "ratios.0", "nested"]);
+
+        let fine: Value = serde_yaml::from_str("1: a\ntrue: b\nc: !Ref d\n").expect("valid yaml");
+        assert!(json_losses(&fine).is_empty());
+    }
+
+    #[test]
+    fn header_templates_fill_placeholders_and_split_lines() {
+        let fields = HeaderFields {
+            version: "0.1.0".to_string(),
+            git_sha: "abc123".to_string(),
+            source_dir: "config".to_string(),
+            timestamp: None,
+        };
+        let header =
+            Header::from_template("{source_dir} @ {git_sha}\\n\\nfyaml {version}", &fields)
+                .expect("valid template");
+        let out = emit_yaml(&Value::Null, &header, &CanonicalOptions::default()).expect("emit");
+        assert!(out.starts_with("# config @ abc123\n#\n# fyaml 0.1.0\n"));
+
+        assert!(Header::from_template("{timestamp}", &fields)
+            .unwrap_err()
+            .contains("--header-timestamp"));
+        assert!(Header::from_template("{branch}", &fields)
+            .unwrap_err()
+            .contains("{branch}"));
+
+        let header = Header::packed_by("0.1.0").with_fingerprint("sha256:00ff");
+        let out = emit_yaml(&Value::Null, &header, &CanonicalOptions::default()).expect("emit");
+        assert_eq!(header_fingerprint(&out), Some("sha256:00ff"));
+        assert_eq!(
+            header_fingerprint("a: 1\n# fingerprint: sha256:00ff\n"),
+            None
+        );
+        assert_eq!(
+            Header::none().with_fingerprint("sha256:00ff"),
+            Header::none()
+        );
+    }
 }
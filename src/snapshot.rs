@@ -0,0 +1,195 @@
+//! Compact binary encoding of parsed values, used by the on-disk parse cache.
+//!
+//! Decoding is a straight copy of lengths and bytes, several times faster than parsing the
+//! YAML again. The format is private to one fyaml version; [`Reader`] returns `None` for
+//! anything it does not recognise, and callers treat that as an empty cache.
+
+use serde_yaml::value::{Tag, TaggedValue};
+use serde_yaml::{Mapping, Number, Value};
+
+pub struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    pub fn new(header: &[u8]) -> Self {
+        Self {
+            bytes: header.to_vec(),
+        }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn str(&mut self, text: &str) {
+        self.u64(text.len() as u64);
+        self.bytes.extend_from_slice(text.as_bytes());
+    }
+
+    pub fn value(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.u8(0),
+            Value::Bool(flag) => {
+                self.u8(1);
+                self.u8(u8::from(*flag));
+            }
+            Value::Number(number) => {
+                if let Some(int) = number.as_i64() {
+                    self.u8(2);
+                    self.u64(int as u64);
+                } else if let Some(uint) = number.as_u64() {
+                    self.u8(3);
+                    self.u64(uint);
+                } else {
+                    self.u8(4);
+                    self.u64(number.as_f64().unwrap_or(f64::NAN).to_bits());
+                }
+            }
+            Value::String(text) => {
+                self.u8(5);
+                self.str(text);
+            }
+            Value::Sequence(items) => {
+                self.u8(6);
+                self.u64(items.len() as u64);
+                items.iter().for_each(|item| self.value(item));
+            }
+            Value::Mapping(map) => {
+                self.u8(7);
+                self.u64(map.len() as u64);
+                for (key, child) in map {
+                    self.value(key);
+                    self.value(child);
+                }
+            }
+            Value::Tagged(tagged) => {
+                self.u8(8);
+                self.str(&tagged.tag.to_string());
+                self.value(&tagged.value);
+            }
+        }
+    }
+}
+
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// A reader positioned after `header`, or `None` when `bytes` does not start with it.
+    pub fn new(bytes: &'a [u8], header: &[u8]) -> Option<Self> {
+        Some(Self {
+            bytes: bytes.strip_prefix(header)?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        let (&first, rest) = self.bytes.split_first()?;
+        self.bytes = rest;
+        Some(first)
+    }
+
+    pub fn u64(&mut self) -> Option<u64> {
+        let bytes = self.take(8)?;
+        Some(u64::from_le_bytes(bytes.try_into().ok()?))
+    }
+
+    pub fn str(&mut self) -> Option<String> {
+        let len = usize::try_from(self.u64()?).ok()?;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+
+    pub fn value(&mut self) -> Option<Value> {
+        Some(match self.u8()? {
+            0 => Value::Null,
+            1 => Value::Bool(self.u8()? != 0),
+            2 => Value::Number(Number::from(self.u64()? as i64)),
+            3 => Value::Number(Number::from(self.u64()?)),
+            4 => Value::Number(Number::from(f64::from_bits(self.u64()?))),
+            5 => Value::String(self.str()?),
+            6 => {
+                let len = self.len()?;
+                let mut items = Vec::with_capacity(len);
+                for _ in 0..len {
+                    items.push(self.value()?);
+                }
+                Value::Sequence(items)
+            }
+            7 => {
+                let len = self.len()?;
+                let mut map = Mapping::with_capacity(len);
+                for _ in 0..len {
+                    let key = self.value()?;
+                    map.insert(key, self.value()?);
+                }
+                Value::Mapping(map)
+            }
+            8 => {
+                let tag = self.str()?;
+                Value::Tagged(Box::new(TaggedValue {
+                    tag: Tag::new(tag),
+                    value: self.value()?,
+                }))
+            }
+            _ => return None,
+        })
+    }
+
+    /// A collection length, bounded by the bytes left so corrupt input cannot reserve
+    /// unbounded memory.
+    fn len(&mut self) -> Option<usize> {
+        let len = usize::try_from(self.u64()?).ok()?;
+        (len <= self.bytes.len()).then_some(len)
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.bytes.len() {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Some(taken)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_round_trip_and_truncation_is_rejected() {
+        let value: Value = serde_yaml::from_str(
+            "a: [1, -2, 18446744073709551615, 1.5, .nan, true, null]\n\
+             !Ref b: !Sub {x: 'y'}\n3: text\n",
+        )
+        .expect("valid yaml");
+        let mut writer = Writer::new(b"test");
+        writer.value(&value);
+        let bytes = writer.into_bytes();
+
+        let mut reader = Reader::new(&bytes, b"test").expect("header");
+        let decoded = reader.value().expect("decodes");
+        assert!(reader.is_empty());
+        assert_eq!(
+            serde_yaml::to_string(&decoded).expect("yaml"),
+            serde_yaml::to_string(&value).expect("yaml")
+        );
+
+        assert!(Reader::new(&bytes, b"other").is_none());
+        let mut truncated = Reader::new(&bytes[..bytes.len() - 1], b"test").expect("header");
+        assert!(truncated.value().is_none());
+    }
+}
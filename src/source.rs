@@ -0,0 +1,609 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Directory,
+    Symlink,
+    Other,
+}
+
+#[derive(Debug)]
+pub struct SourceEntry {
+    pub name: String,
+    pub path: PathBuf,
+    pub kind: io::Result<EntryKind>,
+}
+
+/// Read-only view of a FYAML tree.
+///
+/// The engine only reaches the filesystem through this trait, so callers can assemble a
+/// tree that differs from what is on disk (for example, to preview a directory move).
+/// Sources are shared with the threads that parse fragments ahead of assembly.
+pub trait TreeSource: Sync {
+    /// Returns the entry kind at `path` (following symlinks), or `None` when nothing exists
+    /// there.
+    fn kind(&self, path: &Path) -> Option<EntryKind>;
+
+    fn read_dir(&self, directory: &Path) -> io::Result<Vec<io::Result<SourceEntry>>>;
+
+    fn file_len(&self, path: &Path) -> io::Result<u64>;
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+
+    fn read_bytes(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Reads a fragment for parsing: its length, then its contents unless the length exceeds
+    /// `limit`. An `Err` means the length could not be determined.
+    ///
+    /// The default makes the two calls above; sources backed by the filesystem answer both
+    /// from one open file, which matters for trees of thousands of small fragments.
+    fn read_fragment(&self, path: &Path, limit: Option<u64>) -> io::Result<FragmentRead> {
+        read_fragment_in_steps(self, path, limit)
+    }
+
+    /// [`TreeSource::read_fragment`] for several files of `directory`, in order.
+    ///
+    /// The default reads them one by one; on unix [`FsSource`] opens the directory once and
+    /// each file relative to it, so the directory's path is not looked up again per file.
+    fn read_fragments_in(
+        &self,
+        _directory: &Path,
+        paths: &[PathBuf],
+        limit: Option<u64>,
+    ) -> Vec<io::Result<FragmentRead>> {
+        paths
+            .iter()
+            .map(|path| self.read_fragment(path, limit))
+            .collect()
+    }
+
+    /// Whether two paths refer to the same entry in this view.
+    fn same_entry(&self, a: &Path, b: &Path) -> bool;
+
+    /// Whether `path` itself is a symlink, without following it.
+    fn is_symlink(&self, _path: &Path) -> bool {
+        false
+    }
+}
+
+/// Result of [`TreeSource::read_fragment`].
+#[derive(Debug)]
+pub struct FragmentRead {
+    pub len: u64,
+    /// `None` when `len` exceeded the limit and the file was not read.
+    pub contents: Option<io::Result<String>>,
+}
+
+fn read_fragment_in_steps<S: TreeSource + ?Sized>(
+    source: &S,
+    path: &Path,
+    limit: Option<u64>,
+) -> io::Result<FragmentRead> {
+    let len = source.file_len(path)?;
+    let within_limit = limit.map_or(true, |limit| len <= limit);
+    let contents = within_limit.then(|| source.read_to_string(path));
+    Ok(FragmentRead { len, contents })
+}
+
+/// The on-disk tree, read through `std::fs`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FsSource;
+
+impl TreeSource for FsSource {
+    fn kind(&self, path: &Path) -> Option<EntryKind> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(kind_from_file_type(metadata.file_type()))
+    }
+
+    fn read_dir(&self, directory: &Path) -> io::Result<Vec<io::Result<SourceEntry>>> {
+        let entries = fs::read_dir(directory)?
+            .map(|entry| {
+                entry.map(|entry| SourceEntry {
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    path: entry.path(),
+                    kind: entry.file_type().map(kind_from_file_type),
+                })
+            })
+            .collect();
+        Ok(entries)
+    }
+
+    fn file_len(&self, path: &Path) -> io::Result<u64> {
+        fs::metadata(path).map(|metadata| metadata.len())
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn read_bytes(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    /// Opens the file once and takes its length from the open handle, instead of a `stat`
+    /// followed by a separate open-and-read.
+    fn read_fragment(&self, path: &Path, limit: Option<u64>) -> io::Result<FragmentRead> {
+        // Failures to open keep the step-by-step path so they are reported the same way.
+        match fs::File::open(path) {
+            Ok(file) => read_open_fragment(file, limit),
+            Err(_) => read_fragment_in_steps(self, path, limit),
+        }
+    }
+
+    #[cfg(unix)]
+    fn read_fragments_in(
+        &self,
+        directory: &Path,
+        paths: &[PathBuf],
+        limit: Option<u64>,
+    ) -> Vec<io::Result<FragmentRead>> {
+        let dir = fs::File::open(directory).ok();
+        paths
+            .iter()
+            .map(|path| {
+                let opened = dir
+                    .as_ref()
+                    .zip(path.file_name())
+                    .and_then(|(dir, name)| open_at(dir, name).ok());
+                match opened {
+                    Some(file) => read_open_fragment(file, limit),
+                    None => self.read_fragment(path, limit),
+                }
+            })
+            .collect()
+    }
+
+    fn same_entry(&self, a: &Path, b: &Path) -> bool {
+        match (fs::canonicalize(a), fs::canonicalize(b)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink())
+    }
+}
+
+fn read_open_fragment(mut file: fs::File, limit: Option<u64>) -> io::Result<FragmentRead> {
+    let len = file.metadata()?.len();
+    if limit.is_some_and(|limit| len > limit) {
+        return Ok(FragmentRead {
+            len,
+            contents: None,
+        });
+    }
+
+    let mut contents = String::with_capacity(usize::try_from(len).unwrap_or(0));
+    let read = file.read_to_string(&mut contents).map(|_| contents);
+    Ok(FragmentRead {
+        len,
+        contents: Some(read),
+    })
+}
+
+/// Opens `name` in the already open `directory`, following a symlink as `File::open` does.
+#[cfg(unix)]
+fn open_at(directory: &fs::File, name: &std::ffi::OsStr) -> io::Result<fs::File> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+
+    let name = std::ffi::CString::new(name.as_bytes())?;
+    // SAFETY: the directory descriptor is open for the duration of the call and `name` is
+    // NUL-terminated.
+    let fd = unsafe {
+        libc::openat(
+            directory.as_raw_fd(),
+            name.as_ptr(),
+            libc::O_RDONLY | libc::O_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `openat` returned a new descriptor that nothing else owns.
+    Ok(unsafe { fs::File::from_raw_fd(fd) })
+}
+
+fn kind_from_file_type(file_type: fs::FileType) -> EntryKind {
+    if file_type.is_symlink() {
+        EntryKind::Symlink
+    } else if file_type.is_dir() {
+        EntryKind::Directory
+    } else if file_type.is_file() {
+        EntryKind::File
+    } else {
+        EntryKind::Other
+    }
+}
+
+/// A view of another source in which the entry at `from` appears at `to` instead.
+///
+/// Both paths live in the same namespace as the wrapped source (typically joined onto the
+/// FYAML root); nothing on disk is modified.
+pub struct MovedSource<'a> {
+    inner: &'a dyn TreeSource,
+    from: PathBuf,
+    to: PathBuf,
+}
+
+impl<'a> MovedSource<'a> {
+    pub fn new(inner: &'a dyn TreeSource, from: PathBuf, to: PathBuf) -> Self {
+        Self { inner, from, to }
+    }
+
+    /// Maps a path in the moved view back to the wrapped source, or `None` if the path
+    /// was vacated by the move.
+    fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        if let Ok(rest) = path.strip_prefix(&self.to) {
+            return Some(join_rest(&self.from, rest));
+        }
+        if path.starts_with(&self.from) {
+            return None;
+        }
+        Some(path.to_path_buf())
+    }
+
+    fn resolve_or_missing(&self, path: &Path) -> io::Result<PathBuf> {
+        self.resolve(path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "path vacated by move"))
+    }
+}
+
+impl TreeSource for MovedSource<'_> {
+    fn kind(&self, path: &Path) -> Option<EntryKind> {
+        self.inner.kind(&self.resolve(path)?)
+    }
+
+    fn read_dir(&self, directory: &Path) -> io::Result<Vec<io::Result<SourceEntry>>> {
+        let Some(real) = self.resolve(directory) else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "directory vacated by move",
+            ));
+        };
+
+        let mut entries = Vec::new();
+        for entry in self.inner.read_dir(&real)? {
+            match entry {
+                Ok(entry) => {
+                    let view_path = directory.join(&entry.name);
+                    if self.resolve(&view_path).as_deref() != Some(entry.path.as_path()) {
+                        continue;
+                    }
+                    entries.push(Ok(SourceEntry {
+                        path: view_path,
+                        ..entry
+                    }));
+                }
+                Err(err) => entries.push(Err(err)),
+            }
+        }
+
+        if self.to.parent() == Some(directory) {
+            if let Some(name) = self.to.file_name() {
+                entries.push(Ok(SourceEntry {
+                    name: name.to_string_lossy().into_owned(),
+                    path: self.to.clone(),
+                    kind: self.inner.kind(&self.from).ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::NotFound, "move source is missing")
+                    }),
+                }));
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn file_len(&self, path: &Path) -> io::Result<u64> {
+        self.inner.file_len(&self.resolve_or_missing(path)?)
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        self.inner.read_to_string(&self.resolve_or_missing(path)?)
+    }
+
+    fn read_bytes(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.inner.read_bytes(&self.resolve_or_missing(path)?)
+    }
+
+    fn read_fragment(&self, path: &Path, limit: Option<u64>) -> io::Result<FragmentRead> {
+        self.inner
+            .read_fragment(&self.resolve_or_missing(path)?, limit)
+    }
+
+    fn same_entry(&self, a: &Path, b: &Path) -> bool {
+        match (self.resolve(a), self.resolve(b)) {
+            (Some(a), Some(b)) => self.inner.same_entry(&a, &b),
+            _ => false,
+        }
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        self.resolve(path)
+            .is_some_and(|path| self.inner.is_symlink(&path))
+    }
+}
+
+fn join_rest(base: &Path, rest: &Path) -> PathBuf {
+    if rest.as_os_str().is_empty() {
+        base.to_path_buf()
+    } else {
+        base.join(rest)
+    }
+}
+
+/// A tree made only of an explicit list of files under `root`, with no directory scanning.
+///
+/// Directories exist only as ancestors of listed files. File contents are read through the
+/// wrapped source.
+pub struct ListSource<'a> {
+    inner: &'a dyn TreeSource,
+    files: BTreeSet<PathBuf>,
+    children: BTreeMap<PathBuf, BTreeSet<PathBuf>>,
+}
+
+impl<'a> ListSource<'a> {
+    /// `files` are paths relative to `root` without `..` or root components.
+    pub fn new(inner: &'a dyn TreeSource, root: &Path, files: &[PathBuf]) -> Self {
+        let mut listed = BTreeSet::new();
+        let mut children: BTreeMap<PathBuf, BTreeSet<PathBuf>> = BTreeMap::new();
+        children.insert(root.to_path_buf(), BTreeSet::new());
+
+        for file in files {
+            let path = root.join(file);
+            let mut child = path.clone();
+            while let Some(directory) = child.parent() {
+                if !directory.starts_with(root) {
+                    break;
+                }
+                let known = children.contains_key(directory);
+                children
+                    .entry(directory.to_path_buf())
+                    .or_default()
+                    .insert(child.clone());
+                if known {
+                    break;
+                }
+                child = directory.to_path_buf();
+            }
+            listed.insert(path);
+        }
+
+        Self {
+            inner,
+            files: listed,
+            children,
+        }
+    }
+}
+
+impl TreeSource for ListSource<'_> {
+    fn kind(&self, path: &Path) -> Option<EntryKind> {
+        if self.children.contains_key(path) {
+            Some(EntryKind::Directory)
+        } else if self.files.contains(path) {
+            self.inner.kind(path)
+        } else {
+            None
+        }
+    }
+
+    fn read_dir(&self, directory: &Path) -> io::Result<Vec<io::Result<SourceEntry>>> {
+        let Some(children) = self.children.get(directory) else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "directory is not part of the file list",
+            ));
+        };
+
+        let entries = children
+            .iter()
+            .map(|path| {
+                Ok(SourceEntry {
+                    name: path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    path: path.clone(),
+                    kind: self.kind(path).ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::NotFound, "listed file is missing")
+                    }),
+                })
+            })
+            .collect();
+        Ok(entries)
+    }
+
+    fn file_len(&self, path: &Path) -> io::Result<u64> {
+        self.inner.file_len(path)
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        self.inner.read_to_string(path)
+    }
+
+    fn read_bytes(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.inner.read_bytes(path)
+    }
+
+    fn read_fragment(&self, path: &Path, limit: Option<u64>) -> io::Result<FragmentRead> {
+        self.inner.read_fragment(path, limit)
+    }
+
+    fn read_fragments_in(
+        &self,
+        directory: &Path,
+        paths: &[PathBuf],
+        limit: Option<u64>,
+    ) -> Vec<io::Result<FragmentRead>> {
+        self.inner.read_fragments_in(directory, paths, limit)
+    }
+
+    fn same_entry(&self, a: &Path, b: &Path) -> bool {
+        a == b || self.inner.same_entry(a, b)
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        self.inner.is_symlink(path)
+    }
+}
+
+/// A tree held in memory: files under `root` with their contents, and no filesystem access.
+///
+/// Directories exist only as ancestors of files; a path that is also the parent of another
+/// file is a directory.
+#[derive(Debug, Default)]
+pub struct MemorySource {
+    files: BTreeMap<PathBuf, String>,
+    children: BTreeMap<PathBuf, BTreeSet<PathBuf>>,
+}
+
+impl MemorySource {
+    /// `files` are paths relative to `root` without `..` or root components; a later file
+    /// with the same path replaces an earlier one.
+    pub fn new(root: &Path, files: impl IntoIterator<Item = (PathBuf, String)>) -> Self {
+        let mut source = Self::default();
+        source.children.insert(root.to_path_buf(), BTreeSet::new());
+        for (file, contents) in files {
+            let path = root.join(file);
+            let mut child = path.clone();
+            while let Some(directory) = child.parent() {
+                if !directory.starts_with(root) {
+                    break;
+                }
+                let known = source.children.contains_key(directory);
+                source
+                    .children
+                    .entry(directory.to_path_buf())
+                    .or_default()
+                    .insert(child.clone());
+                if known {
+                    break;
+                }
+                child = directory.to_path_buf();
+            }
+            source.files.insert(path, contents);
+        }
+        source
+    }
+
+    fn contents(&self, path: &Path) -> io::Result<&String> {
+        if self.children.contains_key(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "entry is a directory",
+            ));
+        }
+        self.files
+            .get(path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no entry with this path"))
+    }
+}
+
+impl TreeSource for MemorySource {
+    fn kind(&self, path: &Path) -> Option<EntryKind> {
+        if self.children.contains_key(path) {
+            Some(EntryKind::Directory)
+        } else if self.files.contains_key(path) {
+            Some(EntryKind::File)
+        } else {
+            None
+        }
+    }
+
+    fn read_dir(&self, directory: &Path) -> io::Result<Vec<io::Result<SourceEntry>>> {
+        let Some(children) = self.children.get(directory) else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "directory is not part of the tree",
+            ));
+        };
+
+        let entries = children
+            .iter()
+            .map(|path| {
+                Ok(SourceEntry {
+                    name: path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    path: path.clone(),
+                    kind: Ok(self.kind(path).unwrap_or(EntryKind::Other)),
+                })
+            })
+            .collect();
+        Ok(entries)
+    }
+
+    fn file_len(&self, path: &Path) -> io::Result<u64> {
+        self.contents(path).map(|contents| contents.len() as u64)
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        self.contents(path).cloned()
+    }
+
+    fn read_bytes(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.contents(path)
+            .map(|contents| contents.clone().into_bytes())
+    }
+
+    fn same_entry(&self, a: &Path, b: &Path) -> bool {
+        a == b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn fs_read_fragment_matches_step_by_step_reads() {
+        let dir = tempdir().expect("temp dir");
+        let path = dir.path().join("app.yml");
+        fs::write(&path, "name: demo\n").expect("write");
+
+        let read = FsSource.read_fragment(&path, None).expect("readable");
+        assert_eq!(read.len, 11);
+        assert_eq!(read.contents.expect("read").expect("utf8"), "name: demo\n");
+
+        let over = FsSource.read_fragment(&path, Some(10)).expect("readable");
+        assert_eq!(over.len, 11);
+        assert!(over.contents.is_none());
+
+        let missing = dir.path().join("missing.yml");
+        let expected = FsSource.file_len(&missing).expect_err("missing").kind();
+        let actual = FsSource.read_fragment(&missing, None).expect_err("missing");
+        assert_eq!(actual.kind(), expected);
+    }
+
+    #[test]
+    fn fs_batched_reads_match_single_reads() {
+        let dir = tempdir().expect("temp dir");
+        fs::write(dir.path().join("a.yml"), "a: 1\n").expect("write");
+        fs::write(dir.path().join("big.yml"), "big: 123456789\n").expect("write");
+        let paths = ["a.yml", "big.yml", "missing.yml"].map(|name| dir.path().join(name));
+
+        let batched = FsSource.read_fragments_in(dir.path(), &paths, Some(10));
+        assert_eq!(batched.len(), paths.len());
+        for (path, batched) in paths.iter().zip(batched) {
+            match (FsSource.read_fragment(path, Some(10)), batched) {
+                (Ok(single), Ok(batched)) => {
+                    assert_eq!(single.len, batched.len);
+                    assert_eq!(
+                        single.contents.map(|read| read.expect("utf8")),
+                        batched.contents.map(|read| read.expect("utf8"))
+                    );
+                }
+                (Err(single), Err(batched)) => assert_eq!(single.kind(), batched.kind()),
+                (single, batched) => panic!("{path:?}: {single:?} vs {batched:?}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! `--source-map`: for each leaf of the packed document, the fragment file, line, and column
+//! its value is written at, as a JSON object keyed by derived key path, so editors and CI
+//! annotators can jump from packed output back to the fragments.
+//!
+//! Leaves inside a flow collection map to where the collection starts. Leaves not written in
+//! a YAML fragment, such as `.txt` files and schema defaults, and leaves of multi-document
+//! fragments are left out.
+
+use crate::keypath::{push_index, push_key, ROOT_PATH};
+use serde::Serialize;
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+/// Where a node starts in a fragment; `line` and `column` are one-based.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// The location of each leaf of `value` found in `positions`, in document order.
+pub fn source_map(
+    value: &Value,
+    positions: &HashMap<String, SourceLocation>,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut map = serde_json::Map::new();
+    leaves(value, &mut String::new(), None, positions, &mut map);
+    map
+}
+
+/// Writes `map` to `path` as pretty-printed JSON.
+pub fn write(path: &Path, map: &serde_json::Map<String, serde_json::Value>) -> io::Result<()> {
+    let mut text = serde_json::to_string_pretty(map)?;
+    text.push('\n');
+    std::fs::write(path, text)
+}
+
+fn leaves(
+    value: &Value,
+    path: &mut String,
+    enclosing: Option<&SourceLocation>,
+    positions: &HashMap<String, SourceLocation>,
+    map: &mut serde_json::Map<String, serde_json::Value>,
+) {
+    // Only nodes written inline are recorded, so a recorded collection is a flow collection.
+    let location = positions.get(path.as_str()).or(enclosing);
+    let len = path.len();
+    match value {
+        Value::Tagged(tagged) => leaves(&tagged.value, path, enclosing, positions, map),
+        Value::Mapping(entries) if !entries.is_empty() => {
+            for (key, child) in entries {
+                let Value::String(key) = key else { continue };
+                push_key(path, key);
+                leaves(child, path, location, positions, map);
+                path.truncate(len);
+            }
+        }
+        Value::Sequence(items) if !items.is_empty() => {
+            for (index, child) in items.iter().enumerate() {
+                push_index(path, index);
+                leaves(child, path, location, positions, map);
+                path.truncate(len);
+            }
+        }
+        _ => {
+            if let Some(location) = location {
+                let key = if path.is_empty() { ROOT_PATH } else { path };
+                map.insert(key.to_string(), serde_json::json!(location));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keypath::Segment;
+    use crate::preserve::Preserved;
+
+    #[test]
+    fn maps_leaves_to_where_they_are_written() {
+        let text = "\
+name: api  # the service
+ports:
+  - 80
+  - &tls 443
+hosts: [a, b]
+notes: |
+  text
+empty:
+  nested:
+fallback: *tls
+";
+        let positions = Preserved::default()
+            .scan(text, &[Segment::Key("svc".to_string())])
+            .into_iter()
+            .map(|(key_path, position)| {
+                let location = SourceLocation {
+                    file: "svc.yml".to_string(),
+                    line: position.line,
+                    column: position.column,
+                };
+                (key_path, location)
+            })
+            .collect();
+        let value =
+            serde_yaml::from_str::<Value>(&format!("svc:\n{}", indent(text))).expect("valid YAML");
+        let found = source_map(&value, &positions)
+            .into_iter()
+            .map(|(path, location)| format!("{path} {}:{}", location["line"], location["column"]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                "svc.name 1:7",
+                "svc.ports[0] 3:5",
+                "svc.ports[1] 4:10",
+                "svc.hosts[0] 5:8",
+                "svc.hosts[1] 5:8",
+                "svc.notes 6:8",
+                "svc.fallback 10:11",
+            ]
+        );
+    }
+
+    fn indent(text: &str) -> String {
+        text.lines().map(|line| format!("  {line}\n")).collect()
+    }
+}
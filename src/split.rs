@@ -0,0 +1,82 @@
+//! `pack --output-dir`: each top-level key of the packed mapping is written to its own file,
+//! alongside an `_index.yml` manifest mapping keys to file names.
+//!
+//! File names are the key with every character outside `[A-Za-z0-9._-]` replaced by `_`.
+//! Names that would collide, ignoring case so the layout is the same on every file system,
+//! get `-2`, `-3`, ... in document order, and the manifest's own name is never reused.
+
+use serde_yaml::{Mapping, Value};
+use std::collections::HashSet;
+
+/// The manifest written next to the split files.
+pub const INDEX_FILE: &str = "_index.yml";
+
+/// The file each top-level key of `map` is written to, in document order.
+pub fn file_names(map: &Mapping, extension: &str) -> Vec<(Value, String)> {
+    let mut taken = HashSet::from([INDEX_FILE.to_ascii_lowercase()]);
+    map.keys()
+        .map(|key| {
+            let stem = stem(key);
+            let mut name = format!("{stem}.{extension}");
+            let mut suffix = 2;
+            while !taken.insert(name.to_ascii_lowercase()) {
+                name = format!("{stem}-{suffix}.{extension}");
+                suffix += 1;
+            }
+            (key.clone(), name)
+        })
+        .collect()
+}
+
+fn stem(key: &Value) -> String {
+    let text = match key {
+        Value::String(text) => text.clone(),
+        other => serde_yaml::to_string(other)
+            .map(|text| text.trim_end().to_string())
+            .unwrap_or_default(),
+    };
+    let stem: String = text
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    // Keep names visible and clear of `.`/`..`.
+    if stem.is_empty() || stem.starts_with('.') {
+        format!("_{stem}")
+    } else {
+        stem
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_sanitized_and_collisions_numbered() {
+        let map: Mapping =
+            serde_yaml::from_str("db: 1\nDB: 2\n\"a/b\": 3\na_b: 4\n_index: 5\n.env: 6\n8080: 7\n")
+                .expect("valid YAML");
+        let names = file_names(&map, "yml")
+            .into_iter()
+            .map(|(_, name)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "db.yml",
+                "DB-2.yml",
+                "a_b.yml",
+                "a_b-2.yml",
+                "_index-2.yml",
+                "_.env.yml",
+                "8080.yml"
+            ]
+        );
+    }
+}
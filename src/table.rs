@@ -0,0 +1,256 @@
+//! CSV and TSV inventories of the leaves of a packed document.
+//!
+//! Each row is `path,type,value,source_file`: the key path as `fyaml flatten` writes it,
+//! the leaf's YAML type, its text (strings unquoted), and the fragment that defined it.
+
+use crate::emitter::{EmitError, EmitterOptions};
+use crate::engine::DerivedKey;
+use crate::flat::leaf_text;
+use crate::keypath::{push_index, push_key, ROOT_PATH};
+use crate::preserve::Preserved;
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    Comma,
+    Tab,
+}
+
+/// The fragment each derived key path came from, for filling in `source_file`, and under
+/// `--preserve` the comments and anchors read from the fragments.
+#[derive(Debug, Clone, Default)]
+pub struct SourceIndex {
+    files: HashMap<String, String>,
+    fallback: String,
+    preserved: Preserved,
+}
+
+impl SourceIndex {
+    /// Indexes the YAML files among `derived_keys`; leaves outside all of them (the root
+    /// file in file-root mode) are attributed to `fallback`.
+    pub fn from_derived_keys(derived_keys: &[DerivedKey], fallback: Option<String>) -> Self {
+        let files = derived_keys
+            .iter()
+            .filter(|key| is_yaml_path(&key.source))
+            .map(|key| (key.derived_key_path.clone(), key.source.clone()))
+            .collect();
+        Self {
+            files,
+            fallback: fallback.unwrap_or_default(),
+            preserved: Preserved::default(),
+        }
+    }
+
+    pub fn with_preserved(mut self, preserved: Preserved) -> Self {
+        self.preserved = preserved;
+        self
+    }
+
+    pub fn preserved(&self) -> &Preserved {
+        &self.preserved
+    }
+
+    /// Every leaf comes from `source`.
+    pub fn single(source: String) -> Self {
+        Self {
+            files: HashMap::new(),
+            fallback: source,
+            preserved: Preserved::default(),
+        }
+    }
+}
+
+fn is_yaml_path(path: &str) -> bool {
+    let lower = path.to_ascii_lowercase();
+    lower.ends_with(".yml") || lower.ends_with(".yaml")
+}
+
+pub fn write_table<W: Write>(
+    mut out: W,
+    value: &Value,
+    delimiter: Delimiter,
+    sources: &SourceIndex,
+    options: &EmitterOptions,
+) -> Result<(), EmitError> {
+    let mut table = Table {
+        out: &mut out,
+        delimiter,
+        sources,
+        options,
+    };
+    table.row(["path", "type", "value", "source_file"])?;
+    table.node(
+        value,
+        &mut String::new(),
+        &mut String::new(),
+        &sources.fallback,
+    )
+}
+
+struct Table<'a, W> {
+    out: &'a mut W,
+    delimiter: Delimiter,
+    sources: &'a SourceIndex,
+    options: &'a EmitterOptions,
+}
+
+impl<W: Write> Table<'_, W> {
+    /// `path` is the printed key path; `key_path` is the engine's unquoted form, which
+    /// derived keys are recorded under.
+    fn node(
+        &mut self,
+        value: &Value,
+        path: &mut String,
+        key_path: &mut String,
+        source: &str,
+    ) -> Result<(), EmitError> {
+        let source = self
+            .sources
+            .files
+            .get(key_path.as_str())
+            .map_or(source, String::as_str);
+        let (path_len, key_path_len) = (path.len(), key_path.len());
+        match value {
+            Value::Mapping(map) if !map.is_empty() => {
+                for (key, child) in map {
+                    let key = match key {
+                        Value::String(text) => text.clone(),
+                        other => leaf_text(other, self.options)?,
+                    };
+                    push_key(path, &key);
+                    if !key_path.is_empty() {
+                        key_path.push('.');
+                    }
+                    key_path.push_str(&key);
+                    self.node(child, path, key_path, source)?;
+                    path.truncate(path_len);
+                    key_path.truncate(key_path_len);
+                }
+            }
+            Value::Sequence(items) if !items.is_empty() => {
+                for (index, child) in items.iter().enumerate() {
+                    push_index(path, index);
+                    push_index(key_path, index);
+                    self.node(child, path, key_path, source)?;
+                    path.truncate(path_len);
+                    key_path.truncate(key_path_len);
+                }
+            }
+            leaf => {
+                let text = match leaf {
+                    Value::String(text) => text.clone(),
+                    other => leaf_text(other, self.options)?,
+                };
+                let shown = if path.is_empty() { ROOT_PATH } else { path };
+                self.row([shown, type_name(leaf), &text, source])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn row(&mut self, fields: [&str; 4]) -> Result<(), EmitError> {
+        let separator = match self.delimiter {
+            Delimiter::Comma => ",",
+            Delimiter::Tab => "\t",
+        };
+        let line = fields
+            .iter()
+            .map(|field| match self.delimiter {
+                Delimiter::Comma => csv_field(field),
+                Delimiter::Tab => tsv_field(field),
+            })
+            .collect::<Vec<_>>()
+            .join(separator);
+        // CSV rows end in CRLF (RFC 4180); TSV rows in LF.
+        match self.delimiter {
+            Delimiter::Comma => write!(self.out, "{line}\r\n")?,
+            Delimiter::Tab => writeln!(self.out, "{line}")?,
+        }
+        Ok(())
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(number) if number.is_f64() => "float",
+        Value::Number(_) => "int",
+        Value::String(_) => "string",
+        Value::Sequence(_) => "sequence",
+        Value::Mapping(_) => "mapping",
+        Value::Tagged(_) => "tagged",
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// TSV has no quoting, so tabs, line breaks, and backslashes are written as escapes.
+fn tsv_field(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(value: &Value, delimiter: Delimiter, sources: &SourceIndex) -> String {
+        let mut out = Vec::new();
+        write_table(
+            &mut out,
+            value,
+            delimiter,
+            sources,
+            &EmitterOptions::default(),
+        )
+        .expect("render");
+        String::from_utf8(out).expect("utf8")
+    }
+
+    #[test]
+    fn rows_carry_types_sources_and_escaping() {
+        let value: Value = serde_yaml::from_str(
+            "app:\n  config:\n    name: 'a, \"b\"'\n    ports: [80]\n  notes: \"x\\ty\"\nroot: !Ref r\n",
+        )
+        .expect("valid yaml");
+        let derived = [
+            ("app", "app"),
+            ("app/config.yml", "app.config"),
+            ("app/notes.yml", "app.notes"),
+        ]
+        .map(|(source, key)| DerivedKey {
+            source: source.to_string(),
+            derived_key_path: key.to_string(),
+        });
+        let sources = SourceIndex::from_derived_keys(&derived, Some("root.yml".to_string()));
+
+        assert_eq!(
+            render(&value, Delimiter::Comma, &sources),
+            "path,type,value,source_file\r\n\
+             app.config.name,string,\"a, \"\"b\"\"\",app/config.yml\r\n\
+             app.config.ports[0],int,80,app/config.yml\r\n\
+             app.notes,string,x\ty,app/notes.yml\r\n\
+             root,tagged,!Ref r,root.yml\r\n"
+        );
+        assert!(render(&value, Delimiter::Tab, &sources)
+            .contains("app.notes\tstring\tx\\ty\tapp/notes.yml\n"));
+    }
+}
@@ -0,0 +1,218 @@
+//! Helpers for regression tests of FYAML layouts in downstream crates (the `testing`
+//! feature).
+//!
+//! A tree is written inline, one file per `=== path` line followed by its contents, and
+//! indented as a whole if that reads better in the test:
+//!
+//! ```
+//! use fyaml::config::BuildOptions;
+//! use fyaml::testing::assert_packs_to;
+//!
+//! assert_packs_to(
+//!     "
+//!     === db/host.yml
+//!     db.internal
+//!     === db/port.yml
+//!     5432
+//!     ",
+//!     &BuildOptions::default(),
+//!     "db: {host: db.internal, port: 5432}",
+//! );
+//! ```
+//!
+//! Packing goes through [`crate::build_from_entries`], so nothing touches the filesystem.
+//! Expected documents are canonicalized before comparing, so their key order and style do
+//! not matter; a mismatch panics with a line diff of the two canonical documents.
+
+use crate::config::{BuildOptions, CanonicalOptions};
+use crate::engine::{apply_key_orders, build_from_entries};
+use crate::serializer::{canonicalize_yaml_into, emission_order, emit_json, emit_yaml, Header};
+use serde_yaml::Value;
+use std::path::PathBuf;
+
+const FILE_MARKER: &str = "=== ";
+
+/// Splits an inline tree specification into `(path, contents)` pairs.
+///
+/// Lines before the first `=== path` line must be blank. Each file's contents end with a
+/// newline, as files written in an editor do.
+pub fn parse_tree(spec: &str) -> Vec<(PathBuf, String)> {
+    let spec = dedent(spec);
+    let mut files: Vec<(PathBuf, String)> = Vec::new();
+    for line in spec.lines() {
+        if let Some(path) = line.strip_prefix(FILE_MARKER) {
+            files.push((PathBuf::from(path.trim()), String::new()));
+            continue;
+        }
+        match files.last_mut() {
+            Some((_, contents)) => {
+                contents.push_str(line);
+                contents.push('\n');
+            }
+            None => assert!(
+                line.trim().is_empty(),
+                "tree specification must start with a `{FILE_MARKER}path` line, found `{line}`"
+            ),
+        }
+    }
+    for (_, contents) in &mut files {
+        let trimmed = contents.trim_end().len();
+        contents.truncate(trimmed);
+        contents.push('\n');
+    }
+    files
+}
+
+/// Packs an inline tree as `fyaml pack` would, or returns its error diagnostics rendered
+/// for humans.
+pub fn pack(spec: &str, options: &BuildOptions) -> Result<Value, String> {
+    let outcome = build_from_entries(parse_tree(spec), options);
+    let errors = outcome
+        .diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.is_error())
+        .map(|diagnostic| diagnostic.render_human())
+        .collect::<String>();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let value = outcome.value.unwrap_or(Value::Null);
+    let mut value = emission_order(value, options, &CanonicalOptions::default());
+    apply_key_orders(&mut value, &outcome.key_orders);
+    Ok(value)
+}
+
+/// Packs an inline tree to YAML text, without the header comment.
+pub fn pack_yaml(spec: &str, options: &BuildOptions) -> Result<String, String> {
+    let value = pack(spec, options)?;
+    emit_yaml(&value, &Header::none(), &CanonicalOptions::default()).map_err(|err| err.to_string())
+}
+
+/// Packs an inline tree to pretty-printed JSON text.
+pub fn pack_json(spec: &str, options: &BuildOptions) -> Result<String, String> {
+    let value = pack(spec, options)?;
+    emit_json(&value, &CanonicalOptions::default()).map_err(|err| err.to_string())
+}
+
+/// Asserts that an inline tree packs to the YAML document `expected`.
+#[track_caller]
+pub fn assert_packs_to(spec: &str, options: &BuildOptions, expected: &str) {
+    let actual = pack_yaml(spec, options).unwrap_or_else(|errors| panic!("pack failed:\n{errors}"));
+    let expected: Value = serde_yaml::from_str(&dedent(expected))
+        .unwrap_or_else(|err| panic!("expected YAML is invalid: {err}"));
+    let expected = canonicalize_yaml_into(expected, &CanonicalOptions::default());
+    let expected = emit_yaml(&expected, &Header::none(), &CanonicalOptions::default())
+        .unwrap_or_else(|err| panic!("expected YAML cannot be emitted: {err}"));
+    assert_same(&expected, &actual);
+}
+
+/// Asserts that an inline tree packs to the JSON document `expected`.
+#[track_caller]
+pub fn assert_packs_to_json(spec: &str, options: &BuildOptions, expected: &str) {
+    let actual = pack_json(spec, options).unwrap_or_else(|errors| panic!("pack failed:\n{errors}"));
+    let expected: serde_json::Value = serde_json::from_str(expected)
+        .unwrap_or_else(|err| panic!("expected JSON is invalid: {err}"));
+    let actual: serde_json::Value = serde_json::from_str(&actual).expect("packed JSON parses");
+    assert_same(
+        &serde_json::to_string_pretty(&expected).expect("JSON serializes"),
+        &serde_json::to_string_pretty(&actual).expect("JSON serializes"),
+    );
+}
+
+#[track_caller]
+fn assert_same(expected: &str, actual: &str) {
+    if expected != actual {
+        panic!(
+            "packed document differs (- expected, + actual):\n{}",
+            line_diff(expected, actual)
+        );
+    }
+}
+
+/// A unified line diff of two texts, every line kept: ` ` unchanged, `-` only in
+/// `expected`, `+` only in `actual`.
+pub fn line_diff(expected: &str, actual: &str) -> String {
+    let old = expected.lines().collect::<Vec<_>>();
+    let new = actual.lines().collect::<Vec<_>>();
+    // Longest common subsequence lengths of every pair of suffixes.
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            out.push_str(&format!("  {}\n", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push_str(&format!("- {}\n", old[i]));
+            i += 1;
+        } else {
+            out.push_str(&format!("+ {}\n", new[j]));
+            j += 1;
+        }
+    }
+    out
+}
+
+/// Removes the indentation shared by every non-blank line.
+fn dedent(text: &str) -> String {
+    let indent = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches(' ').len())
+        .min()
+        .unwrap_or(0);
+    text.lines()
+        .map(|line| line.get(indent..).unwrap_or("").trim_end_matches(' '))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inline_trees_pack_and_mismatches_show_a_diff() {
+        let spec = "
+            === app.yml
+            name: demo
+            === db/port.yml
+            5432
+        ";
+        assert_eq!(
+            parse_tree(spec),
+            vec![
+                (PathBuf::from("app.yml"), "name: demo\n".to_string()),
+                (PathBuf::from("db/port.yml"), "5432\n".to_string()),
+            ]
+        );
+        assert_packs_to(
+            spec,
+            &BuildOptions::default(),
+            "db: {port: 5432}\napp: {name: demo}\n",
+        );
+        assert_packs_to_json(
+            spec,
+            &BuildOptions::default(),
+            r#"{"app": {"name": "demo"}, "db": {"port": 5432}}"#,
+        );
+
+        assert_eq!(
+            line_diff("a: 1\nb: 2\nc: 3\n", "a: 1\nb: 3\nc: 3\n"),
+            "  a: 1\n- b: 2\n+ b: 3\n  c: 3\n"
+        );
+        let errors = pack("=== a.yml\na: [\n", &BuildOptions::default()).expect_err("invalid");
+        assert!(errors.contains("error[E100]"), "{errors}");
+    }
+}
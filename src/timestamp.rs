@@ -0,0 +1,330 @@
+//! Recognition of ISO-8601 / YAML 1.1 `timestamp` scalars.
+//!
+//! Accepted forms are `YYYY-MM-DD` and `YYYY-M-D[Tt ]H:MM:SS[.frac][Z|±H[:MM]]`, with
+//! whitespace allowed before the offset as in the YAML 1.1 timestamp type.
+
+/// Tag written for timestamps under `--dates tag`.
+pub const TIMESTAMP_TAG: &str = "!!timestamp";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timestamp {
+    year: i64,
+    month: u32,
+    day: u32,
+    time: Option<TimeOfDay>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TimeOfDay {
+    hour: u32,
+    minute: u32,
+    second: u32,
+    /// Fractional second digits with trailing zeros removed.
+    fraction: String,
+    /// Offset east of UTC in minutes; `None` for naive datetimes.
+    offset_minutes: Option<i64>,
+}
+
+impl Timestamp {
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut cursor = Cursor::new(text);
+        let year = cursor.digits(4, 4)?;
+        cursor.expect(b'-')?;
+
+        let date_only = text.len() == 10;
+        let (min_width, max_width) = if date_only { (2, 2) } else { (1, 2) };
+        let month = cursor.digits(min_width, max_width)?;
+        cursor.expect(b'-')?;
+        let day = cursor.digits(min_width, max_width)?;
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year as i64, month) {
+            return None;
+        }
+
+        let mut timestamp = Self {
+            year: year as i64,
+            month,
+            day,
+            time: None,
+        };
+        if cursor.at_end() {
+            return date_only.then_some(timestamp);
+        }
+
+        if !cursor.eat(b'T') && !cursor.eat(b't') && cursor.whitespace() == 0 {
+            return None;
+        }
+        let hour = cursor.digits(1, 2)?;
+        cursor.expect(b':')?;
+        let minute = cursor.digits(2, 2)?;
+        cursor.expect(b':')?;
+        let second = cursor.digits(2, 2)?;
+        if hour > 23 || minute > 59 || second > 59 {
+            return None;
+        }
+
+        let fraction = if cursor.eat(b'.') {
+            cursor.digit_run().trim_end_matches('0').to_string()
+        } else {
+            String::new()
+        };
+
+        cursor.whitespace();
+        let offset_minutes = if cursor.at_end() {
+            None
+        } else if cursor.eat(b'Z') {
+            Some(0)
+        } else {
+            let sign = if cursor.eat(b'+') {
+                1
+            } else {
+                cursor.expect(b'-')?;
+                -1
+            };
+            let hours = cursor.digits(1, 2)?;
+            let minutes = if cursor.eat(b':') {
+                cursor.digits(2, 2)?
+            } else {
+                0
+            };
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            Some(sign * i64::from(hours * 60 + minutes))
+        };
+        if !cursor.at_end() {
+            return None;
+        }
+
+        timestamp.time = Some(TimeOfDay {
+            hour,
+            minute,
+            second,
+            fraction,
+            offset_minutes,
+        });
+        Some(timestamp)
+    }
+
+    /// A datetime with a UTC offset (`Z` included), which names one instant.
+    pub fn has_offset(&self) -> bool {
+        self.time
+            .as_ref()
+            .is_some_and(|time| time.offset_minutes.is_some())
+    }
+
+    /// A datetime without a UTC offset, which consumers interpret inconsistently.
+    pub fn is_naive(&self) -> bool {
+        self.time
+            .as_ref()
+            .is_some_and(|time| time.offset_minutes.is_none())
+    }
+
+    /// Renders the timestamp as `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM:SS[.frac]Z`.
+    ///
+    /// Returns `None` for naive datetimes, which cannot be placed on the UTC timeline.
+    pub fn to_utc_string(&self) -> Option<String> {
+        let Some(time) = &self.time else {
+            return Some(format!(
+                "{:04}-{:02}-{:02}",
+                self.year, self.month, self.day
+            ));
+        };
+        let offset = time.offset_minutes?;
+
+        let local_minutes = days_from_civil(self.year, self.month, self.day) * 1440
+            + i64::from(time.hour * 60 + time.minute);
+        let utc_minutes = local_minutes - offset;
+        let (year, month, day) = civil_from_days(utc_minutes.div_euclid(1440));
+        let minute_of_day = utc_minutes.rem_euclid(1440);
+
+        let mut out = format!(
+            "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+            minute_of_day / 60,
+            minute_of_day % 60,
+            time.second
+        );
+        if !time.fraction.is_empty() {
+            out.push('.');
+            out.push_str(&time.fraction);
+        }
+        out.push('Z');
+        Some(out)
+    }
+}
+
+/// Renders seconds since the Unix epoch as `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn utc_from_unix_seconds(seconds: i64) -> String {
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+    let second_of_day = seconds.rem_euclid(86_400);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        second_of_day / 3600,
+        second_of_day / 60 % 60,
+        second_of_day % 60
+    )
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            bytes: text.as_bytes(),
+            position: 0,
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.position == self.bytes.len()
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        if self.bytes.get(self.position) == Some(&byte) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        self.eat(byte).then_some(())
+    }
+
+    fn whitespace(&mut self) -> usize {
+        let start = self.position;
+        while matches!(self.bytes.get(self.position), Some(b' ' | b'\t')) {
+            self.position += 1;
+        }
+        self.position - start
+    }
+
+    fn digit_run(&mut self) -> &'a str {
+        let start = self.position;
+        while self
+            .bytes
+            .get(self.position)
+            .is_some_and(u8::is_ascii_digit)
+        {
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.position]).expect("ASCII digits")
+    }
+
+    fn digits(&mut self, min: usize, max: usize) -> Option<u32> {
+        let start = self.position;
+        while self.position - start < max
+            && self
+                .bytes
+                .get(self.position)
+                .is_some_and(u8::is_ascii_digit)
+        {
+            self.position += 1;
+        }
+        if self.position - start < min {
+            return None;
+        }
+        std::str::from_utf8(&self.bytes[start..self.position])
+            .ok()?
+            .parse()
+            .ok()
+    }
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = i64::from(month);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_yaml_timestamp_forms() {
+        for text in [
+            "2001-12-14",
+            "2001-12-14t21:59:43.10-05:00",
+            "2001-12-14 21:59:43.10 -5",
+            "2001-12-15T02:59:43.1Z",
+            "2001-2-3 4:05:06",
+        ] {
+            assert!(Timestamp::parse(text).is_some(), "{text}");
+        }
+        for text in [
+            "2001-2-3",
+            "2001-13-01",
+            "2001-02-29",
+            "2001-12-14T25:00:00Z",
+            "2001-12-14 21:59",
+            "v2001-12-14",
+            "2001-12-14Z",
+        ] {
+            assert!(Timestamp::parse(text).is_none(), "{text}");
+        }
+        assert!(Timestamp::parse("2001-12-14 21:59:43")
+            .expect("parses")
+            .is_naive());
+    }
+
+    #[test]
+    fn converts_offsets_to_utc() {
+        let cases = [
+            ("2001-12-14t21:59:43.10-05:00", "2001-12-15T02:59:43.1Z"),
+            ("2001-12-14 21:59:43.10 -5", "2001-12-15T02:59:43.1Z"),
+            ("2000-03-01T00:30:00+01:00", "2000-02-29T23:30:00Z"),
+            ("1969-12-31T23:00:00.000-02:00", "1970-01-01T01:00:00Z"),
+            ("2024-02-29", "2024-02-29"),
+        ];
+        for (input, expected) in cases {
+            let timestamp = Timestamp::parse(input).expect("parses");
+            assert_eq!(
+                timestamp.to_utc_string().as_deref(),
+                Some(expected),
+                "{input}"
+            );
+        }
+        let naive = Timestamp::parse("2001-12-14 21:59:43").expect("parses");
+        assert_eq!(naive.to_utc_string(), None);
+    }
+}
@@ -0,0 +1,113 @@
+//! `validate --fix unwrap`: removes the redundant top-level key reported by `W020`.
+//!
+//! The fragment is edited as text, so comments and formatting below the wrapper survive: the
+//! `key:` line is dropped and its contents are outdented. The edit is kept only when the
+//! result parses to exactly the wrapped value.
+
+use serde_yaml::Value;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Unwrap {
+    Done,
+    /// The fragment was left as it is, for the given reason.
+    Skipped(String),
+}
+
+/// Rewrites the fragment at `path` without its top-level `key`.
+pub fn unwrap_fragment(path: &Path, key: &str) -> io::Result<Unwrap> {
+    let text = fs::read_to_string(path)?;
+    let unwrapped = match unwrap_text(&text, key) {
+        Ok(unwrapped) => unwrapped,
+        Err(reason) => return Ok(Unwrap::Skipped(reason)),
+    };
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+    let mut temp_name = file_name.to_os_string();
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp = path.with_file_name(temp_name);
+    fs::write(&temp, unwrapped)
+        .and_then(|()| fs::rename(&temp, path))
+        .map_err(|err| {
+            let _ = fs::remove_file(&temp);
+            err
+        })?;
+    Ok(Unwrap::Done)
+}
+
+/// Returns `text` without its top-level `key:` line and with the contents outdented.
+pub fn unwrap_text(text: &str, key: &str) -> Result<String, String> {
+    let wrapped = match serde_yaml::from_str::<Value>(text) {
+        Ok(Value::Mapping(map)) if map.len() == 1 => map
+            .get(key)
+            .cloned()
+            .ok_or_else(|| format!("the only top-level key is not `{key}`"))?,
+        Ok(_) => return Err(format!("`{key}` is not the only top-level key")),
+        Err(err) => return Err(format!("the fragment does not parse: {err}")),
+    };
+
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let start = lines
+        .iter()
+        .position(|line| {
+            let line = line.trim();
+            !(line.is_empty() || line.starts_with('#') || line == "---")
+        })
+        .ok_or_else(|| "the fragment has no content".to_string())?;
+    let rest = lines[start]
+        .strip_prefix(key)
+        .and_then(|rest| rest.strip_prefix(':'))
+        .ok_or_else(|| format!("the wrapper is not a plain `{key}:` line"))?;
+    let rest = rest.trim();
+    if !(rest.is_empty() || rest.starts_with('#')) {
+        return Err(format!("the value starts on the `{key}:` line"));
+    }
+
+    let body = &lines[start + 1..];
+    let indent = body
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches(' ').len())
+        .min()
+        .unwrap_or(0);
+    let mut unwrapped = lines[..start].concat();
+    for line in body {
+        let spaces = line.len() - line.trim_start_matches(' ').len();
+        unwrapped.push_str(&line[spaces.min(indent)..]);
+    }
+
+    match serde_yaml::from_str::<Value>(&unwrapped) {
+        Ok(value) if value == wrapped => Ok(unwrapped),
+        _ => Err("outdenting the contents would change the value".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outdents_contents_and_keeps_comments() {
+        let text = "# primary database\ndatabase:\n  host: db # internal\n\n  ports:\n    - 5432\n";
+        assert_eq!(
+            unwrap_text(text, "database"),
+            Ok("# primary database\nhost: db # internal\n\nports:\n  - 5432\n".to_string())
+        );
+        assert_eq!(
+            unwrap_text("items:\n- a\n- b\n", "items"),
+            Ok("- a\n- b\n".to_string())
+        );
+    }
+
+    #[test]
+    fn leaves_other_shapes_alone() {
+        assert!(unwrap_text("database: {host: db}\n", "database").is_err());
+        assert!(unwrap_text("database:\n  host: db\nother: 1\n", "database").is_err());
+        assert!(unwrap_text("\"database\":\n  host: db\n", "database").is_err());
+        assert!(unwrap_text("db:\n  host: db\n", "database").is_err());
+    }
+}
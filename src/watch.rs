@@ -0,0 +1,208 @@
+//! Change detection for `fyaml watch`.
+//!
+//! The tree is polled rather than subscribed to: each scan records the size and
+//! modification time of every entry, and two scans are compared to find what changed.
+//! This needs no platform notifier and sees edits on network and container filesystems
+//! that do not deliver events.
+
+use crate::engine::CACHE_FILE_NAME;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+impl ChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Removed => "removed",
+        }
+    }
+}
+
+/// One changed entry, with its path relative to the watched root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+/// What one entry looked like when the tree was scanned. Directories only record that they
+/// exist; changes below them show up as their own entries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Stamp {
+    Dir,
+    File {
+        len: u64,
+        modified: Option<SystemTime>,
+    },
+}
+
+/// The state of every entry below a root, ignoring `skip` paths and the parse cache file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeStamp {
+    entries: BTreeMap<PathBuf, Stamp>,
+}
+
+impl TreeStamp {
+    /// Scans `root`. Unreadable entries are left out, so they read as removed until they
+    /// can be read again. Symlinks are stamped but not followed, as the build ignores them.
+    /// `skip` holds paths as [`resolve`] returns them.
+    pub fn scan(root: &Path, skip: &[PathBuf]) -> Self {
+        let mut stamp = Self::default();
+        stamp.scan_dir(&resolve(root), Path::new(""), skip);
+        stamp
+    }
+
+    fn scan_dir(&mut self, root: &Path, relative: &Path, skip: &[PathBuf]) {
+        let Ok(entries) = fs::read_dir(root.join(relative)) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = relative.join(entry.file_name());
+            if relative.as_os_str().is_empty()
+                && entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(CACHE_FILE_NAME)
+            {
+                continue;
+            }
+            if skip.iter().any(|skipped| *skipped == entry.path()) {
+                continue;
+            }
+            let Ok(metadata) = entry.path().symlink_metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                self.entries.insert(path.clone(), Stamp::Dir);
+                self.scan_dir(root, &path, skip);
+            } else {
+                let stamp = Stamp::File {
+                    len: metadata.len(),
+                    modified: metadata.modified().ok(),
+                };
+                self.entries.insert(path, stamp);
+            }
+        }
+    }
+
+    /// Every entry that differs between `self` and the later scan `newer`, in path order.
+    pub fn changes(&self, newer: &TreeStamp) -> Vec<Change> {
+        let mut changes = Vec::new();
+        let mut push = |path: &Path, kind| {
+            changes.push(Change {
+                path: path.to_string_lossy().replace('\\', "/"),
+                kind,
+            })
+        };
+        for (path, stamp) in &self.entries {
+            match newer.entries.get(path) {
+                None => push(path, ChangeKind::Removed),
+                Some(new) if new != stamp => push(path, ChangeKind::Modified),
+                Some(_) => {}
+            }
+        }
+        for path in newer.entries.keys() {
+            if !self.entries.contains_key(path) {
+                push(path, ChangeKind::Added);
+            }
+        }
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        changes
+    }
+}
+
+/// `path` made absolute with symlinks resolved, so scanned entries can be compared with it.
+/// A path that does not exist yet, such as an output file, is resolved through its parent.
+pub fn resolve(path: &Path) -> PathBuf {
+    if let Ok(resolved) = fs::canonicalize(path) {
+        return resolved;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => resolve(parent).join(name),
+        (_, Some(name)) => std::env::current_dir()
+            .map(|cwd| cwd.join(name))
+            .unwrap_or_else(|_| path.to_path_buf()),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Polls `root` every `poll` until it differs from `current`, then waits until it has been
+/// unchanged for `debounce`, so an editor or `git checkout` writing many files triggers one
+/// rebuild. Returns the settled scan and what changed since `current`.
+pub fn wait_for_change(
+    root: &Path,
+    skip: &[PathBuf],
+    current: &TreeStamp,
+    poll: Duration,
+    debounce: Duration,
+) -> (TreeStamp, Vec<Change>) {
+    loop {
+        thread::sleep(poll);
+        let mut latest = TreeStamp::scan(root, skip);
+        if latest == *current {
+            continue;
+        }
+        loop {
+            thread::sleep(debounce);
+            let settled = TreeStamp::scan(root, skip);
+            if settled == latest {
+                break;
+            }
+            latest = settled;
+        }
+        let changes = current.changes(&latest);
+        if !changes.is_empty() {
+            return (latest, changes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_report_added_modified_and_removed_entries() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let root = dir.path();
+        fs::create_dir(root.join("app")).expect("mkdir");
+        fs::write(root.join("app/config.yml"), "a: 1\n").expect("write");
+        fs::write(root.join("old.yml"), "x\n").expect("write");
+        fs::write(root.join("out.yml"), "packed\n").expect("write");
+        let skip = [resolve(&root.join("out.yml"))];
+        let before = TreeStamp::scan(root, &skip);
+
+        fs::write(root.join("app/config.yml"), "a: 12\n").expect("write");
+        fs::remove_file(root.join("old.yml")).expect("remove");
+        fs::write(root.join("new.yml"), "y\n").expect("write");
+        fs::write(root.join("out.yml"), "packed again\n").expect("write");
+        fs::write(root.join(CACHE_FILE_NAME), "cache").expect("write");
+        let after = TreeStamp::scan(root, &skip);
+
+        let changes: Vec<_> = before
+            .changes(&after)
+            .into_iter()
+            .map(|change| (change.path, change.kind))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("app/config.yml".to_string(), ChangeKind::Modified),
+                ("new.yml".to_string(), ChangeKind::Added),
+                ("old.yml".to_string(), ChangeKind::Removed),
+            ]
+        );
+        assert!(after.changes(&TreeStamp::scan(root, &skip)).is_empty());
+    }
+}
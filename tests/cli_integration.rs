@@ -72,6 +72,50 @@ fn validate_json_reports_collision() {
         .any(|d| d.get("code").and_then(Value::as_str) == Some("E001")));
 }
 
+#[test]
+fn validate_json_envelope_summarizes_the_run() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("auth.yml"), "kind: file\n");
+    write(&dir.path().join("auth/provider.yml"), "kind: dir\n");
+    write(&dir.path().join("notes.txt"), "ignored\n");
+
+    let output = cargo_bin_cmd!("fyaml")
+        .args([
+            "validate",
+            dir.path().to_str().expect("utf8 path"),
+            "--json",
+            "--json-layout",
+            "envelope",
+        ])
+        .assert()
+        .code(2)
+        .get_output()
+        .stdout
+        .clone();
+
+    let envelope: Value = serde_json::from_slice(&output).expect("envelope json");
+    assert_eq!(envelope["summary"]["errors"], 1);
+    assert_eq!(envelope["summary"]["warnings"], 1);
+    assert_eq!(envelope["summary"]["exit_code"], 2);
+    assert!(envelope["summary"]["duration_seconds"].is_f64());
+    assert_eq!(envelope["tool"]["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(envelope["tool"]["options"]["root_mode"], "map-root");
+    // Sorted by location: the scan summary for the root comes before `auth`.
+    assert_eq!(envelope["diagnostics"][0]["code"], "W050");
+    assert_eq!(envelope["diagnostics"][1]["code"], "E001");
+
+    let output = cargo_bin_cmd!("fyaml")
+        .args(["validate", "--json", "--diag-order", "discovery"])
+        .arg(dir.path())
+        .assert()
+        .code(2)
+        .get_output()
+        .stdout
+        .clone();
+    let diagnostics: Value = serde_json::from_slice(&output).expect("diagnostics json");
+    assert_eq!(diagnostics[0]["code"], "E001");
+}
+
 #[test]
 fn explain_lists_ignored_entries() {
     let dir = tempdir().expect("temp dir");
@@ -86,6 +130,34 @@ fn explain_lists_ignored_entries() {
         .stdout(predicate::str::contains("notes.txt"));
 }
 
+#[test]
+fn json_output_reports_yaml_only_constructs_with_key_paths() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("ports.yml"), "80: http\n\"80\": web\n");
+    write(&dir.path().join("stats.yml"), "ratio: .nan\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--format", "json"])
+        .arg(dir.path())
+        .assert()
+        .code(2)
+        .stdout("")
+        .stderr(predicate::str::contains("E314"))
+        .stderr(predicate::str::contains("Key path: ports.80"))
+        .stderr(predicate::str::contains("Key path: stats.ratio"));
+
+    // A lossy document is an input problem, not a write failure, with -o too.
+    let out = dir.path().join("out.json");
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--format", "json-compact", "-o"])
+        .arg(&out)
+        .arg(dir.path())
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("error[E314]"));
+    assert!(!out.exists());
+}
+
 #[test]
 fn diff_reports_equal_for_semantically_identical_trees() {
     let left = tempdir().expect("left temp dir");
@@ -113,6 +185,92 @@ fn diff_reports_equal_for_semantically_identical_trees() {
         .stdout(predicate::str::contains("equal"));
 }
 
+#[test]
+fn diff_json_lists_directory_mode_changes() {
+    let left = tempdir().expect("left temp dir");
+    let right = tempdir().expect("right temp dir");
+    write(&left.path().join("items/value.yml"), "a: 1\n");
+    write(&right.path().join("items/a.yml"), "1\n");
+
+    let output = cargo_bin_cmd!("fyaml")
+        .args([
+            "diff",
+            left.path().to_str().expect("utf8 path"),
+            right.path().to_str().expect("utf8 path"),
+            "--format",
+            "json",
+            "--directory-modes",
+            "--value-files",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let payload: Value = serde_json::from_slice(&output).expect("json output");
+    assert_eq!(payload["equal"], true);
+    assert_eq!(
+        payload["directory_mode_changes"],
+        serde_json::json!([{ "directory": "items", "left": "value", "right": "mapping" }])
+    );
+}
+
+#[test]
+fn diff_fast_and_hash_agree_on_changed_subtrees() {
+    let left = tempdir().expect("left temp dir");
+    let right = tempdir().expect("right temp dir");
+    for root in [left.path(), right.path()] {
+        write(
+            &root.join("env/prod/database.yml"),
+            "host: db\nport: 5432\n",
+        );
+    }
+    write(&left.path().join("env/prod/cache.yml"), "ttl: 60\n");
+    write(&right.path().join("env/prod/cache.yml"), "ttl: 30\n");
+
+    for mode in ["--fast", "--exact"] {
+        cargo_bin_cmd!("fyaml")
+            .args([
+                "diff",
+                left.path().to_str().expect("utf8 path"),
+                right.path().to_str().expect("utf8 path"),
+                mode,
+            ])
+            .assert()
+            .failure()
+            .stdout("different at $.env.prod.cache.ttl: scalar value differs\n");
+    }
+
+    let hashes = |root: &Path| -> Vec<(String, String)> {
+        let output = cargo_bin_cmd!("fyaml")
+            .args(["hash", root.to_str().expect("utf8 path"), "--depth", "3"])
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        String::from_utf8(output)
+            .expect("utf8")
+            .lines()
+            .map(|line| {
+                let (hash, path) = line.split_once("  ").expect("hash and path");
+                (path.to_string(), hash.to_string())
+            })
+            .collect()
+    };
+    let (left_hashes, right_hashes) = (hashes(left.path()), hashes(right.path()));
+    let changed = left_hashes
+        .iter()
+        .zip(&right_hashes)
+        .filter(|(l, r)| l != r)
+        .map(|(l, _)| l.0.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        changed,
+        vec!["$", "$.env", "$.env.prod", "$.env.prod.cache"]
+    );
+}
+
 #[test]
 fn reserved_word_filename_fails_by_default() {
     let dir = tempdir().expect("temp dir");
@@ -142,53 +300,3182 @@ fn reserved_word_filename_allowed_with_flag() {
 }
 
 #[test]
-fn scaffold_then_pack_keeps_semantics() {
-    let input_root = tempdir().expect("input temp dir");
-    let scaffold_root = tempdir().expect("scaffold temp dir");
-    let input = input_root.path().join("input.yml");
-    let scaffold_dir = scaffold_root.path().join("scaffold");
+fn ascii_keys_policy_rejects_or_transliterates_derived_keys() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("regions/zürich.yml"), "x: 1\n");
+    let root = dir.path().to_str().expect("utf8 path");
 
-    write(
-        &input,
-        "name: app\nsteps:\n  - extract\n  - transform\n  - load\n",
+    cargo_bin_cmd!("fyaml")
+        .args(["validate", root, "--ascii-keys", "error"])
+        .assert()
+        .failure()
+        .code(2)
+        .stderr(predicate::str::contains("E023"));
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", root, "--no-header", "--ascii-keys", "transliterate"])
+        .assert()
+        .success()
+        .stdout("regions:\n  zurich:\n    x: 1\n");
+
+    let output = cargo_bin_cmd!("fyaml")
+        .args(["explain", root, "--json", "--ascii-keys", "transliterate"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let report: Value = serde_json::from_slice(&output).expect("explain json");
+    assert_eq!(
+        report["explain"]["transliterated_keys"],
+        serde_json::json!([
+            {"source": "regions/zürich.yml", "original": "zürich", "key": "zurich"},
+        ])
     );
+}
+
+#[test]
+fn ordered_prefixes_set_emission_order() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("02-services.yml"), "web: 1\n");
+    write(&dir.path().join("01-database.yml"), "host: db\n");
+    write(&dir.path().join("app.yml"), "name: demo\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", root, "--no-header", "--ordered-prefixes"])
+        .assert()
+        .success()
+        .stdout("database:\n  host: db\nservices:\n  web: 1\napp:\n  name: demo\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["explain", root, "--ordered-prefixes"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Ordered Keys:\n  database <- 01-database.yml (order 1)\n",
+        ));
+}
+
+#[test]
+fn scaffold_seq_dir_round_trips_with_value_files() {
+    let dir = tempdir().expect("temp dir");
+    let input = dir.path().join("input.yml");
+    let scaffold_dir = dir.path().join("scaffold");
+    write(&input, "steps:\n- extract\n- name: load\n");
 
     cargo_bin_cmd!("fyaml")
         .args([
             "scaffold",
             input.to_str().expect("utf8 path"),
             scaffold_dir.to_str().expect("utf8 path"),
+            "--seq",
+            "dir",
         ])
         .assert()
         .success();
 
-    let packed_scaffold = cargo_bin_cmd!("fyaml")
+    cargo_bin_cmd!("fyaml")
         .args([
             "pack",
             scaffold_dir.to_str().expect("utf8 path"),
             "--no-header",
+            "--value-files",
+        ])
+        .assert()
+        .success()
+        .stdout("steps:\n- extract\n- name: load\n");
+}
+
+#[test]
+fn pack_does_not_read_its_output_back_in() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("app.yml"), "name: demo\n");
+    let output = dir.path().join("packed.yml");
+
+    for _ in 0..2 {
+        cargo_bin_cmd!("fyaml")
+            .args(["pack", "--no-header", "-o"])
+            .arg(&output)
+            .arg(dir.path())
+            .assert()
+            .success()
+            .stderr(predicate::str::contains("W050").not());
+    }
+    assert_eq!(
+        fs::read_to_string(&output).expect("read output"),
+        "app:\n  name: demo\n"
+    );
+}
+
+#[test]
+fn pack_lays_overlay_directories_over_the_base() {
+    let dir = tempdir().expect("temp dir");
+    write(
+        &dir.path().join("base/db.yml"),
+        "host: localhost\nport: 5432\n",
+    );
+    write(&dir.path().join("base/replicas.yml"), "1\n");
+    write(&dir.path().join("prod/db/host.yml"), "db.internal\n");
+    write(&dir.path().join("prod/replicas.yml"), "[3]\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header"])
+        .arg(dir.path().join("base"))
+        .arg(dir.path().join("prod"))
+        .assert()
+        .success()
+        .stdout("db:\n  host: db.internal\n  port: 5432\nreplicas:\n- 3\n")
+        .stderr(predicate::str::contains("warn[W025]"))
+        .stderr(predicate::str::contains("base/replicas.yml (number)"));
+}
+
+#[test]
+fn pack_select_emits_one_subtree() {
+    let dir = tempdir().expect("temp dir");
+    write(
+        &dir.path().join("services/api.yml"),
+        "port: 80\nhost: api\n",
+    );
+    write(&dir.path().join("services/web.yml"), "port: 8080\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--select", "services.api"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("host: api\nport: 80\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--select", "services.db.port"])
+        .arg(dir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error[E125]"))
+        .stderr(predicate::str::contains("Keys at `services`: api, web"));
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--no-header",
+            "--select",
+            "services.web",
+            "--prefix",
+            "spec.config",
+        ])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("spec:\n  config:\n    port: 8080\n");
+}
+
+#[test]
+fn validate_what_if_reports_skipped_hidden_files() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("app.yml"), "name: demo\n");
+    write(&dir.path().join(".secrets.yml"), "token: abc\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "validate",
+            "--what-if",
+            "include-hidden",
+            "--what-if",
+            "index-files",
+        ])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "warn[W026]: packed document would change with --include-hidden",
+        ))
+        .stderr(predicate::str::contains("Newly read: .secrets.yml"))
+        .stderr(predicate::str::contains(
+            "key missing on left side: .secrets",
+        ))
+        .stderr(predicate::str::contains(
+            "I201]: packed document is the same with --index-files",
+        ));
+}
+
+#[test]
+fn merge_keys_error_rejects_every_merge_key() {
+    let dir = tempdir().expect("temp dir");
+    write(
+        &dir.path().join("svc.yml"),
+        "base: &base {replicas: 2}\napi:\n  <<: *base\n  port: 80\n",
+    );
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--merge-keys", "error"])
+        .arg(dir.path())
+        .assert()
+        .code(3)
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::contains(
+            "error[E135]: merge key is not allowed",
+        ));
+    cargo_bin_cmd!("fyaml")
+        .args(["validate", "--no-daemon", "--json", "--merge-keys", "error"])
+        .arg(dir.path())
+        .assert()
+        .code(3)
+        .stdout(predicate::str::contains(
+            "\"code\": \"E135\",\n    \"severity\": \"error\",\n    \"message\": \"merge key is not allowed\",\n    \"paths\": [],\n    \"derived_key_path\": \"svc.api.<<\"",
+        ));
+
+    // The deprecated flag still expands, and is hidden from help.
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--resolve-merge-keys"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("svc:\n  api:\n    port: 80\n    replicas: 2\n  base:\n    replicas: 2\n");
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--help"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("--merge-keys"))
+        .stdout(predicate::str::contains("--resolve-merge-keys").not());
+    cargo_bin_cmd!("fyaml")
+        .args(["validate", "--no-daemon", "--what-if", "merge-keys"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "packed document would change with --merge-keys=expand",
+        ));
+}
+
+#[test]
+fn pack_wraps_the_document_as_a_block_string() {
+    let dir = tempdir().expect("temp dir");
+    write(
+        &dir.path().join("db.yml"),
+        "host: db.internal\nport: 5432\n",
+    );
+    write(
+        &dir.path().join("motd.yml"),
+        "\"  indented\\nsecond line\\n\\n\"\n",
+    );
+
+    let output = cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--no-header",
+            "--wrap-as-string",
+            "data[\"app.yaml\"]",
+            "--prefix",
+            "spec",
         ])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let text = String::from_utf8(output).expect("utf8");
+    assert!(
+        text.starts_with("spec:\n  data:\n    app.yaml: |"),
+        "{text}"
+    );
+    let wrapped: serde_yaml::Value = serde_yaml::from_str(&text).expect("yaml");
+    let inner = wrapped["spec"]["data"]["app.yaml"]
+        .as_str()
+        .expect("string");
+    let direct = cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header"])
+        .arg(dir.path())
         .assert()
         .success()
         .get_output()
         .stdout
         .clone();
+    assert_eq!(inner.as_bytes(), direct.as_slice());
+}
 
-    let packed_input = cargo_bin_cmd!("fyaml")
+#[test]
+fn pack_writes_compact_json_and_ndjson() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("0.yml"), "name: build\nsteps: 2\n");
+    write(&dir.path().join("1.yml"), "name: test\n");
+
+    cargo_bin_cmd!("fyaml")
         .args([
             "pack",
-            input_root.path().to_str().expect("utf8 path"),
             "--root-mode",
-            "file-root",
-            "--root-file",
-            "input.yml",
+            "seq-root",
+            "--format",
+            "json-compact",
+        ])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(r#"[{"name":"build","steps":2},{"name":"test"}]"#);
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--root-mode", "seq-root", "--format", "ndjson"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("{\"name\":\"build\",\"steps\":2}\n{\"name\":\"test\"}\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--format", "ndjson"])
+        .arg(dir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error[E316]"));
+}
+
+#[test]
+fn pack_explodes_sequence_items_into_documents() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("0.yml"), "kind: Service\n");
+    write(&dir.path().join("1.yml"), "kind: Deployment\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--root-mode",
+            "seq-root",
+            "--explode-docs",
             "--no-header",
         ])
+        .arg(dir.path())
         .assert()
         .success()
-        .get_output()
-        .stdout
-        .clone();
+        .stdout("---\nkind: Service\n---\nkind: Deployment\n");
 
-    assert_eq!(packed_scaffold, packed_input);
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--explode-docs"])
+        .arg(dir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error[E317]"));
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--root-mode",
+            "seq-root",
+            "--explode-docs",
+            "--format",
+            "json",
+        ])
+        .arg(dir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error[E317]"));
+}
+
+#[test]
+fn pack_splits_top_level_keys_into_files() {
+    let dir = tempdir().expect("temp dir");
+    let tree = dir.path().join("config");
+    write(&tree.join("db.yml"), "host: localhost\n");
+    write(&tree.join("feature flags.yml"), "beta: true\n");
+    let out = tree.join("out");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--output-dir"])
+        .arg(&out)
+        .arg(&tree)
+        .assert()
+        .success();
+    let read = |name: &str| fs::read_to_string(out.join(name)).expect("split file");
+    assert_eq!(read("db.yml"), "host: localhost\n");
+    assert_eq!(read("feature_flags.yml"), "beta: true\n");
+    assert_eq!(
+        read("_index.yml"),
+        "db: db.yml\nfeature flags: feature_flags.yml\n"
+    );
+
+    // The output directory inside the tree is not packed back in.
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--output-dir"])
+        .arg(&out)
+        .arg(&tree)
+        .assert()
+        .success();
+    assert!(!out.join("out.yml").exists());
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--select", "db.host", "--output-dir"])
+        .arg(&out)
+        .arg(&tree)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error[E318]"));
+}
+
+#[test]
+fn pack_writes_a_header_from_the_template() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("name.yml"), "demo\n");
+    let tree = dir.path().to_path_buf();
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--header-template",
+            "fyaml {version}\\nbuilt {timestamp}",
+            "--header-timestamp",
+        ])
+        .arg(&tree)
+        .env("SOURCE_DATE_EPOCH", "0")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::starts_with(format!(
+                "# fyaml {}\n# built 1970-01-01T00:00:00Z\n# fingerprint: sha256:",
+                env!("CARGO_PKG_VERSION")
+            ))
+            .and(predicate::str::ends_with("\nname: demo\n")),
+        );
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--header-template", "built {timestamp}"])
+        .arg(&tree)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error[E127]"));
+}
+
+#[test]
+fn verify_compares_a_packed_file_with_its_tree() {
+    let dir = tempdir().expect("temp dir");
+    let tree = dir.path().join("config");
+    write(&tree.join("db.yml"), "host: localhost\nport: 5432\n");
+    let packed = dir.path().join("config.yml");
+
+    cargo_bin_cmd!("fyaml")
+        .arg("pack")
+        .arg(&tree)
+        .arg("-o")
+        .arg(&packed)
+        .assert()
+        .success();
+    cargo_bin_cmd!("fyaml")
+        .arg("verify")
+        .arg(&tree)
+        .arg(&packed)
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("verified: "));
+
+    // Key order does not count; changed values do.
+    write(&tree.join("db.yml"), "port: 5432\nhost: localhost\n");
+    cargo_bin_cmd!("fyaml")
+        .arg("verify")
+        .arg(&tree)
+        .arg(&packed)
+        .assert()
+        .success();
+    write(&tree.join("db.yml"), "host: db.internal\nport: 5432\n");
+    cargo_bin_cmd!("fyaml")
+        .arg("verify")
+        .arg(&tree)
+        .arg(&packed)
+        .assert()
+        .code(2)
+        .stderr(
+            predicate::str::contains("error[E128]")
+                .and(predicate::str::contains("$.db.host (scalar value differs)")),
+        );
+
+    // Editing the packed file to match leaves its header behind.
+    let edited = fs::read_to_string(&packed)
+        .expect("packed file")
+        .replace("localhost", "db.internal");
+    write(&packed, &edited);
+    cargo_bin_cmd!("fyaml")
+        .arg("verify")
+        .arg(&tree)
+        .arg(&packed)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("warn[W027]"));
+}
+
+#[test]
+fn pack_check_fails_when_the_output_is_stale() {
+    let dir = tempdir().expect("temp dir");
+    let tree = dir.path().join("config");
+    write(&tree.join("db.yml"), "host: localhost\nport: 5432\n");
+    let packed = dir.path().join("config.yml");
+    let check = || {
+        let mut cmd = cargo_bin_cmd!("fyaml");
+        cmd.arg("pack")
+            .arg(&tree)
+            .arg("-o")
+            .arg(&packed)
+            .arg("--check");
+        cmd
+    };
+
+    check().assert().code(2).stderr(
+        predicate::str::contains("error[E130]").and(predicate::str::contains("could not be read")),
+    );
+    assert!(!packed.exists());
+
+    cargo_bin_cmd!("fyaml")
+        .arg("pack")
+        .arg(&tree)
+        .arg("-o")
+        .arg(&packed)
+        .assert()
+        .success();
+    check()
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("up to date: "));
+
+    write(&tree.join("db.yml"), "host: db.internal\nport: 5432\n");
+    let before = fs::read_to_string(&packed).expect("packed file");
+    check().assert().code(2).stderr(
+        predicate::str::contains("error[E130]")
+            .and(predicate::str::contains("at line 2"))
+            .and(predicate::str::contains("$.db.host (scalar value differs)")),
+    );
+    assert_eq!(fs::read_to_string(&packed).expect("packed file"), before);
+
+    // There is no stale check for split output, so the combination is refused unwritten.
+    let split = dir.path().join("split");
+    cargo_bin_cmd!("fyaml")
+        .arg("pack")
+        .arg(&tree)
+        .arg("--output-dir")
+        .arg(&split)
+        .arg("--check")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("cannot be used with"));
+    assert!(!split.exists());
+}
+
+#[test]
+fn gha_output_appends_step_outputs_and_a_summary() {
+    let dir = tempdir().expect("temp dir");
+    let tree = dir.path().join("config");
+    write(&tree.join("db.yml"), "host: localhost\n");
+    write(&tree.join("notes.txt"), "ignored\n");
+    let outputs = dir.path().join("github_output");
+    let summary = dir.path().join("step_summary");
+    let packed = dir.path().join("config.yml");
+    write(&outputs, "earlier=kept\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--gha-output", "-o"])
+        .arg(&packed)
+        .arg(&tree)
+        .env("GITHUB_OUTPUT", &outputs)
+        .env("GITHUB_STEP_SUMMARY", &summary)
+        .assert()
+        .success();
+    let written = fs::read_to_string(&outputs).expect("outputs");
+    assert!(written.starts_with(&format!(
+        "earlier=kept\nerror_count=0\nwarning_count=1\noutput_path={}\ncontent_hash=sha256:",
+        packed.display()
+    )));
+    let header = fs::read_to_string(&packed).expect("packed file");
+    let hash = written.lines().last().expect("content_hash line");
+    assert!(header.contains(&format!(
+        "# fingerprint: {}",
+        &hash["content_hash=".len()..]
+    )));
+    let table = fs::read_to_string(&summary).expect("summary");
+    assert!(table.contains("| Status | passed |"));
+    assert!(table.contains("| W050 |"));
+
+    cargo_bin_cmd!("fyaml")
+        .args(["validate", "--gha-output"])
+        .arg(&tree)
+        .env_remove("GITHUB_OUTPUT")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error[E320]"));
+}
+
+#[test]
+fn resume_from_cache_parses_only_fixed_fragments() {
+    let dir = tempdir().expect("temp dir");
+    for index in 0..5 {
+        write(
+            &dir.path().join(format!("service{index}.yml")),
+            &format!("replicas: {index}\n"),
+        );
+    }
+    write(&dir.path().join("broken.yml"), "ports: [80\n");
+    let resume = || {
+        let mut cmd = cargo_bin_cmd!("fyaml");
+        cmd.args(["validate", "--no-daemon", "--stats", "--resume-from-cache"])
+            .arg(dir.path());
+        cmd
+    };
+
+    resume()
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("warn[W061]"))
+        .stdout(predicate::str::contains("parsed 6 fragment(s), reused 0"));
+
+    write(&dir.path().join("broken.yml"), "ports: [80]\n");
+    resume()
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("W061").not())
+        .stdout(predicate::str::contains("parsed 1 fragment(s), reused 5"));
+}
+
+#[test]
+fn export_env_prints_shell_statements_for_a_subtree() {
+    let dir = tempdir().expect("temp dir");
+    write(
+        &dir.path().join("env/prod/database.yml"),
+        "host: db.internal\npassword: \"it's\"\n",
+    );
+    write(&dir.path().join("env/prod/hosts.yml"), "- a\n- b\n");
+    write(
+        &dir.path().join("env/dev/database.yml"),
+        "host: localhost\n",
+    );
+
+    cargo_bin_cmd!("fyaml")
+        .arg("export-env")
+        .arg(dir.path())
+        .args(["--prefix", "APP_", "--path", "env.prod", "--lists", "join"])
+        .assert()
+        .success()
+        .stdout(
+            "export APP_DATABASE_HOST='db.internal'\nexport APP_DATABASE_PASSWORD='it'\\''s'\n\
+             export APP_HOSTS='a,b'\n",
+        );
+
+    cargo_bin_cmd!("fyaml")
+        .arg("export-env")
+        .arg(dir.path())
+        .args(["--path", "env.prod", "--lists", "index"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("export HOSTS_1='b'\n"));
+
+    cargo_bin_cmd!("fyaml")
+        .arg("export-env")
+        .arg(dir.path())
+        .args(["--path", "env.staging"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error[E125]"));
+}
+
+#[test]
+fn pack_preserve_keeps_comments_and_anchors() {
+    let dir = tempdir().expect("temp dir");
+    write(
+        &dir.path().join("api.yml"),
+        "# The API service\n\nport: 8080  # public\nlimits: &limits\n  cpu: 2\n\
+         # the worker uses the same limits\nworker: *limits\n",
+    );
+
+    cargo_bin_cmd!("fyaml")
+        .arg("pack")
+        .arg(dir.path())
+        .args(["--preserve", "--no-header"])
+        .assert()
+        .success()
+        .stdout(
+            "# The API service\napi:\n  port: 8080 # public\n  limits: &limits\n    cpu: 2\n\
+             \x20 # the worker uses the same limits\n  worker: *limits\n",
+        );
+
+    cargo_bin_cmd!("fyaml")
+        .arg("pack")
+        .arg(dir.path())
+        .arg("--no-header")
+        .assert()
+        .success()
+        .stdout("api:\n  limits:\n    cpu: 2\n  port: 8080\n  worker:\n    cpu: 2\n");
+}
+
+#[test]
+fn pack_source_map_points_leaves_at_their_fragments() {
+    let dir = tempdir().expect("temp dir");
+    let tree = dir.path().join("tree");
+    write(
+        &tree.join("server.yml"),
+        "# web\nport: 8080\nhosts: [a, b]\n",
+    );
+    write(&tree.join("db/primary.yml"), "host:  db.internal\n");
+    let map = dir.path().join("out.map.json");
+
+    cargo_bin_cmd!("fyaml")
+        .arg("pack")
+        .arg(&tree)
+        .arg("--source-map")
+        .arg(&map)
+        .assert()
+        .success();
+
+    let map: Value =
+        serde_json::from_str(&fs::read_to_string(&map).expect("map written")).expect("valid JSON");
+    let server = tree.join("server.yml").display().to_string();
+    assert_eq!(
+        map["server.port"],
+        serde_json::json!({ "file": server, "line": 2, "column": 7 })
+    );
+    assert_eq!(map["server.hosts[1]"]["line"], 3);
+    assert_eq!(
+        map["db.primary.host"],
+        serde_json::json!({
+            "file": tree.join("db/primary.yml").display().to_string(),
+            "line": 1,
+            "column": 8
+        })
+    );
+}
+
+#[test]
+fn pack_compresses_output_and_reads_it_back() {
+    let dir = tempdir().expect("temp dir");
+    let tree = dir.path().join("tree");
+    write(&tree.join("server.yml"), "port: 8080\n");
+    let packed = dir.path().join("packed.yml.gz");
+
+    cargo_bin_cmd!("fyaml")
+        .arg("pack")
+        .arg(&tree)
+        .arg("-o")
+        .arg(&packed)
+        .args(["--compress", "gzip"])
+        .assert()
+        .success();
+    assert!(fs::read(&packed)
+        .expect("packed file")
+        .starts_with(&[0x1f, 0x8b]));
+
+    cargo_bin_cmd!("fyaml")
+        .arg("verify")
+        .arg(&tree)
+        .arg(&packed)
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("verified:"));
+    cargo_bin_cmd!("fyaml")
+        .arg("pack")
+        .arg(&tree)
+        .arg("-o")
+        .arg(&packed)
+        .arg("--check")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("up to date:"));
+
+    let split = dir.path().join("split");
+    cargo_bin_cmd!("fyaml")
+        .arg("pack")
+        .arg(&tree)
+        .arg("--output-dir")
+        .arg(&split)
+        .args(["--compress", "zstd"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("cannot be used with"));
+    assert!(!split.exists());
+}
+
+#[test]
+fn diff_reads_compressed_packed_files() {
+    let dir = tempdir().expect("temp dir");
+    let tree = dir.path().join("tree");
+    write(&tree.join("server.yml"), "port: 8080\n");
+
+    for (compression, name) in [("gzip", "packed.yml.gz"), ("zstd", "packed.yml.zst")] {
+        let packed = dir.path().join(name);
+        cargo_bin_cmd!("fyaml")
+            .arg("pack")
+            .arg(&tree)
+            .arg("-o")
+            .arg(&packed)
+            .args(["--compress", compression])
+            .assert()
+            .success();
+        cargo_bin_cmd!("fyaml")
+            .arg("diff")
+            .arg(&tree)
+            .arg(&packed)
+            .assert()
+            .success()
+            .stdout("equal\n");
+    }
+
+    write(&tree.join("server.yml"), "port: 9090\n");
+    for name in ["packed.yml.gz", "packed.yml.zst"] {
+        cargo_bin_cmd!("fyaml")
+            .arg("diff")
+            .arg(&tree)
+            .arg(dir.path().join(name))
+            .assert()
+            .code(2)
+            .stdout("different at $.server.port: scalar value differs\n");
+    }
+
+    let damaged = dir.path().join("damaged.yml.gz");
+    fs::write(&damaged, [0x1f, 0x8b, 0x08, 0x00]).expect("write");
+    cargo_bin_cmd!("fyaml")
+        .arg("diff")
+        .arg(&tree)
+        .arg(&damaged)
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("error[E129]"));
+}
+
+#[test]
+fn scaffold_report_lists_every_entry_and_its_rule() {
+    let input_root = tempdir().expect("input temp dir");
+    let scaffold_root = tempdir().expect("scaffold temp dir");
+    let input = input_root.path().join("input.yml");
+    let scaffold_dir = scaffold_root.path().join("scaffold");
+    let report = input_root.path().join("report.json");
+    write(&input, "server:\n  port: 80\nhosts: [a]\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "scaffold",
+            input.to_str().expect("utf8 path"),
+            scaffold_dir.to_str().expect("utf8 path"),
+            "--report",
+            report.to_str().expect("utf8 path"),
+        ])
+        .assert()
+        .success();
+
+    let report: Value = serde_json::from_str(&fs::read_to_string(&report).expect("report written"))
+        .expect("report json");
+    assert_eq!(report["layout"], "hybrid");
+    assert_eq!(
+        report["entries"],
+        serde_json::json!([
+            {"path": "hosts", "kind": "directory", "key_path": "hosts",
+             "rule": "sequence-directory", "transformations": []},
+            {"path": "hosts/0.yml", "kind": "file", "key_path": "hosts[0]",
+             "rule": "sequence-item-file", "transformations": []},
+            {"path": "server", "kind": "directory", "key_path": "server",
+             "rule": "mapping-directory", "transformations": []},
+            {"path": "server/port.yml", "kind": "file", "key_path": "server.port",
+             "rule": "scalar-file", "transformations": []},
+        ])
+    );
+}
+
+#[test]
+fn validate_only_scans_the_named_subtrees() {
+    let root = tempdir().expect("temp dir");
+    write(&root.path().join("env/prod/db.yml"), "host: prod-db\n");
+    write(&root.path().join("env/dev/db.yml"), "host: [unclosed\n");
+    write(&root.path().join("workflows/build/jobs.yml"), "- test\n");
+    write(&root.path().join("broken.yml"), "a: b: c\n");
+    let dir = root.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["validate", dir, "--no-daemon"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("env/dev/db.yml"));
+
+    cargo_bin_cmd!("fyaml")
+        .args(["validate", dir, "--no-daemon", "--only", "env.prod"])
+        .args(["--only", "workflows.*", "--jobs", "4"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("broken.yml").not())
+        .stderr(predicate::str::contains("W050").not());
+
+    cargo_bin_cmd!("fyaml")
+        .args(["validate", dir, "--no-daemon", "--only", "env.*.db"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("env/dev/db.yml"))
+        .stderr(predicate::str::contains("broken.yml").not());
+}
+
+#[test]
+fn diff_normalize_scalars_ignores_stylistic_differences() {
+    let left = tempdir().expect("left temp dir");
+    let right = tempdir().expect("right temp dir");
+    write(
+        &left.path().join("app.yml"),
+        "enabled: yes\nratio: 1.0\nhint: ~\n",
+    );
+    write(
+        &right.path().join("app.yml"),
+        "enabled: true\nratio: 1\nhint: null\n",
+    );
+
+    cargo_bin_cmd!("fyaml")
+        .arg("diff")
+        .arg(left.path())
+        .arg(right.path())
+        .assert()
+        .code(2)
+        .stdout("different at $.app.enabled: value type differs\n");
+
+    cargo_bin_cmd!("fyaml")
+        .arg("diff")
+        .arg(left.path())
+        .arg(right.path())
+        .arg("--normalize-scalars")
+        .assert()
+        .success();
+}
+
+#[test]
+fn output_schema_version_pins_json_layouts() {
+    let root = tempdir().expect("temp dir");
+    write(&root.path().join("app.yml"), "port: 80\n");
+    let dir = root.path().to_str().expect("utf8 path");
+
+    let output = cargo_bin_cmd!("fyaml")
+        .args([
+            "--output-schema-version",
+            "1",
+            "validate",
+            dir,
+            "--no-daemon",
+        ])
+        .args(["--json", "--json-layout", "envelope"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let envelope: Value = serde_json::from_slice(&output).expect("envelope json");
+    assert_eq!(envelope["tool"]["output_schema_version"], 1);
+
+    cargo_bin_cmd!("fyaml")
+        .args(["explain", dir, "--json", "--output-schema-version", "2"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicate::str::contains("E092"))
+        .stderr(predicate::str::contains("produces version 1"));
+}
+
+#[test]
+fn diff_utc_timestamps_compares_instants() {
+    let left = tempdir().expect("left temp dir");
+    let right = tempdir().expect("right temp dir");
+    write(
+        &left.path().join("release.yml"),
+        "at: 2024-03-01T10:00:00+02:00\n",
+    );
+    write(
+        &right.path().join("release.yml"),
+        "at: 2024-03-01T08:00:00Z\n",
+    );
+
+    cargo_bin_cmd!("fyaml")
+        .arg("diff")
+        .arg(left.path())
+        .arg(right.path())
+        .assert()
+        .code(2)
+        .stdout("different at $.release.at: scalar value differs\n");
+
+    cargo_bin_cmd!("fyaml")
+        .arg("diff")
+        .arg(left.path())
+        .arg(right.path())
+        .arg("--utc-timestamps")
+        .assert()
+        .success();
+}
+
+#[test]
+fn pack_folds_long_strings_at_the_line_width() {
+    let temp = tempdir().expect("tempdir");
+    let dir = temp.path().join("config");
+    fs::create_dir_all(&dir).expect("mkdir");
+    write(
+        &dir.join("app.yml"),
+        "summary: packed output stays readable in code review when long strings fold\nnotes: \"first\\nsecond\\n\"\n",
+    );
+
+    let folded = cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--max-line-width", "40"])
+        .arg(&dir)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    assert_eq!(
+        String::from_utf8(folded).expect("utf8"),
+        "app:\n  notes: |\n    first\n    second\n  summary: >-\n    packed output stays readable in code\n    review when long strings fold\n"
+    );
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--fold-strings", "plain"])
+        .arg(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# style: fold-strings=plain"))
+        .stdout(predicate::str::contains("  notes: \"first\\nsecond\\n\"\n"));
+}
+
+#[cfg(unix)]
+#[test]
+fn includes_do_not_follow_symlinks_out_of_the_root() {
+    let dir = tempdir().expect("temp dir");
+    let tree = dir.path().join("tree");
+    write(&dir.path().join("outside.yml"), "secret: hunter2\n");
+    write(&tree.join("app.yml"), "creds: !include link.yml\n");
+    std::os::unix::fs::symlink("../outside.yml", tree.join("link.yml")).expect("symlink");
+
+    cargo_bin_cmd!("fyaml")
+        .arg("pack")
+        .arg(&tree)
+        .arg("--includes=on")
+        .assert()
+        .code(4)
+        .stdout(predicate::str::contains("hunter2").not())
+        .stderr(predicate::str::contains("E106"))
+        .stderr(predicate::str::contains("symlink"));
+
+    // Off by default: the tag is kept as written.
+    cargo_bin_cmd!("fyaml")
+        .arg("pack")
+        .arg(&tree)
+        .arg("--no-header")
+        .assert()
+        .success()
+        .stdout("app:\n  creds: !include link.yml\n");
+}
+
+#[test]
+fn scaffold_refuses_an_output_directory_holding_the_input() {
+    let dir = tempdir().expect("temp dir");
+    let input = dir.path().join("app.yml");
+    write(&input, "app:\n  name: demo\n");
+
+    cargo_bin_cmd!("fyaml")
+        .arg("scaffold")
+        .arg(&input)
+        .arg(dir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("E215"));
+    assert_eq!(
+        fs::read_to_string(&input).expect("read input"),
+        "app:\n  name: demo\n"
+    );
+}
+
+#[test]
+fn scaffold_multi_doc_split_packs_back_as_a_sequence() {
+    let dir = tempdir().expect("temp dir");
+    let input = dir.path().join("bundle.yml");
+    let out = dir.path().join("out");
+    write(&input, "kind: Service\n---\nkind: Deployment\n---\nplain\n");
+
+    cargo_bin_cmd!("fyaml")
+        .arg("scaffold")
+        .arg(&input)
+        .arg(&out)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("E202"));
+
+    cargo_bin_cmd!("fyaml")
+        .arg("scaffold")
+        .arg(&input)
+        .arg(&out)
+        .args(["--multi-doc", "split"])
+        .assert()
+        .success();
+    assert!(out.join("1/kind.yml").is_file());
+
+    cargo_bin_cmd!("fyaml")
+        .arg("pack")
+        .arg(&out)
+        .args(["--root-mode", "seq-root", "--value-files", "--no-header"])
+        .assert()
+        .success()
+        .stdout("- kind: Service\n- kind: Deployment\n- plain\n");
+}
+
+#[test]
+fn scaffold_then_pack_keeps_semantics() {
+    let input_root = tempdir().expect("input temp dir");
+    let scaffold_root = tempdir().expect("scaffold temp dir");
+    let input = input_root.path().join("input.yml");
+    let scaffold_dir = scaffold_root.path().join("scaffold");
+
+    write(
+        &input,
+        "name: app\nsteps:\n  - extract\n  - transform\n  - load\n",
+    );
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "scaffold",
+            input.to_str().expect("utf8 path"),
+            scaffold_dir.to_str().expect("utf8 path"),
+        ])
+        .assert()
+        .success();
+
+    let packed_scaffold = cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            scaffold_dir.to_str().expect("utf8 path"),
+            "--no-header",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let packed_input = cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            input_root.path().to_str().expect("utf8 path"),
+            "--root-mode",
+            "file-root",
+            "--root-file",
+            "input.yml",
+            "--no-header",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_eq!(packed_scaffold, packed_input);
+}
+
+#[test]
+fn scaffold_keymap_round_trips_keys_differing_only_by_case() {
+    let input_root = tempdir().expect("input temp dir");
+    let scaffold_root = tempdir().expect("scaffold temp dir");
+    let input = input_root.path().join("input.yml");
+    let scaffold_dir = scaffold_root.path().join("scaffold");
+
+    write(
+        &input,
+        "API: {url: a}\napi: {url: b}\nsvc:\n  Port: 1\n  port: 2\n  PORT: 3\n",
+    );
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "scaffold",
+            input.to_str().expect("utf8 path"),
+            scaffold_dir.to_str().expect("utf8 path"),
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("W016"));
+
+    let keymap: Value = serde_json::from_str(
+        &fs::read_to_string(scaffold_dir.join("fyaml.keymap.json")).expect("keymap written"),
+    )
+    .expect("keymap json");
+    assert_eq!(
+        keymap["keys"],
+        serde_json::json!({"api~2": "api", "svc/Port~2": "Port", "svc/port~3": "port"})
+    );
+
+    let pack = |args: &[&str]| {
+        cargo_bin_cmd!("fyaml")
+            .arg("pack")
+            .args(args)
+            .arg("--no-header")
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone()
+    };
+    let packed_scaffold = pack(&[scaffold_dir.to_str().expect("utf8 path")]);
+    let packed_input = pack(&[
+        input_root.path().to_str().expect("utf8 path"),
+        "--root-mode",
+        "file-root",
+        "--root-file",
+        "input.yml",
+    ]);
+    assert_eq!(packed_scaffold, packed_input);
+}
+
+#[test]
+fn validate_fix_unwrap_removes_redundant_wrapper() {
+    let dir = tempdir().expect("temp dir");
+    let fragment = dir.path().join("database.yml");
+    write(&fragment, "# primary\ndatabase:\n  host: db\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["validate", root, "--no-daemon", "--strict"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("W020"));
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "validate",
+            root,
+            "--no-daemon",
+            "--strict",
+            "--fix",
+            "unwrap",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("unwrapped"));
+    assert_eq!(
+        fs::read_to_string(&fragment).expect("read fragment"),
+        "# primary\nhost: db\n"
+    );
+}
+
+#[test]
+fn validate_fix_collisions_applies_the_chosen_suggestion() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("auth.yml"), "mode: sso\n");
+    write(&dir.path().join("auth/provider.yml"), "name: okta\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    let output = cargo_bin_cmd!("fyaml")
+        .args(["validate", root, "--no-daemon", "--json"])
+        .assert()
+        .code(2)
+        .get_output()
+        .stdout
+        .clone();
+    let diagnostics: Value = serde_json::from_slice(&output).expect("diagnostics json");
+    let suggestions = &diagnostics[0]["suggestions"];
+    assert_eq!(suggestions[0]["title"], "rename auth.yml to auth-base.yml");
+    assert_eq!(
+        suggestions[2]["edits"][0],
+        serde_json::json!({"kind": "rename", "from": "auth.yml", "to": "auth/_.yml"})
+    );
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "validate",
+            root,
+            "--no-daemon",
+            "--index-files",
+            "--fix",
+            "collisions",
+            "--apply-suggestion",
+            "1",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("renamed"));
+    assert!(dir.path().join("auth/_.yml").exists());
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--index-files", root])
+        .assert()
+        .success()
+        .stdout("auth:\n  mode: sso\n  provider:\n    name: okta\n");
+}
+
+#[test]
+fn validate_fix_collisions_renames_the_odd_name_to_a_clean_one() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("auth.yml"), "mode: sso\n");
+    write(&dir.path().join("auth .yml"), "mode: ldap\n");
+    write(&dir.path().join("auth-base.yml"), "mode: none\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["validate", root, "--no-daemon"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "rename auth .yml to auth-base-2.yml",
+        ));
+    cargo_bin_cmd!("fyaml")
+        .args(["validate", root, "--no-daemon", "--fix", "collisions"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("renamed"));
+    assert!(dir.path().join("auth.yml").exists());
+    assert!(!dir.path().join("auth .yml").exists());
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", root])
+        .assert()
+        .success()
+        .stdout("auth:\n  mode: sso\nauth-base:\n  mode: none\nauth-base-2:\n  mode: ldap\n");
+}
+
+#[test]
+fn collisions_between_aliasing_names_rename_the_odd_extension() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("db.yml"), "port: 5432\n");
+    write(&dir.path().join("db.YML"), "port: 6432\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["validate", root, "--no-daemon"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("error[E001]"))
+        .stderr(predicate::str::contains(
+            "Suggestions:\n    1. rename db.YML to db-base.yml\n    2. rename db.yml to db-base.yml\n",
+        ));
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "validate",
+            root,
+            "--no-daemon",
+            "--require-extension",
+            "yaml",
+        ])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("1. rename db.YML to db-base.yaml"));
+
+    cargo_bin_cmd!("fyaml")
+        .args(["validate", root, "--no-daemon", "--fix", "collisions"])
+        .assert()
+        .success();
+    assert!(dir.path().join("db-base.yml").exists());
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", root])
+        .assert()
+        .success()
+        .stdout("db:\n  port: 5432\ndb-base:\n  port: 6432\n");
+}
+
+#[test]
+fn orphans_reports_misplaced_fragments() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("config/services/web.yml"), "port: 80\n");
+    write(&dir.path().join("services/worker.yml"), "replicas: 2\n");
+    write(&dir.path().join("docs/mkdocs.yml"), "site: x\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["orphans", root, "--tree", "config"])
+        .assert()
+        .failure()
+        .code(2)
+        .stdout(
+            "Orphaned YAML files:\n  services/worker.yml (looks like services.worker in config)\n",
+        );
+
+    cargo_bin_cmd!("fyaml")
+        .args(["orphans", root, "--tree", "config", "--skip", "services"])
+        .assert()
+        .success()
+        .stdout("no orphaned YAML files outside config\n");
+}
+
+#[test]
+fn plan_mv_reports_collision_without_moving() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("auth/provider.yml"), "kind: oidc\n");
+    write(&dir.path().join("identity.yml"), "kind: file\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "plan-mv",
+            dir.path().to_str().expect("utf8 path"),
+            "auth",
+            "identity",
+        ])
+        .assert()
+        .failure()
+        .code(2)
+        .stdout(predicate::str::contains(
+            "auth.provider -> identity.provider",
+        ))
+        .stdout(predicate::str::contains("error[E001]"));
+
+    assert!(dir.path().join("auth/provider.yml").exists());
+}
+
+#[test]
+fn pack_from_list_reads_only_listed_fragments() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("db/host.yml"), "db.internal\n");
+    write(&dir.path().join("db/port.yml"), "5432\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            dir.path().to_str().expect("utf8 path"),
+            "--no-header",
+            "--from-list",
+            "-",
+        ])
+        .write_stdin("# inputs\ndb/host.yml\n")
+        .assert()
+        .success()
+        .stdout("db:\n  host: db.internal\n");
+}
+
+#[test]
+fn inline_root_document_merges_like_a_root_file() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("jobs/build.yml"), "steps: []\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", root, "--no-header", "--root-mode", "file-root"])
+        .args(["--root-inline", "version: 2.1"])
+        .assert()
+        .success()
+        .stdout("jobs:\n  build:\n    steps: []\nversion: 2.1\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", root, "--no-header", "--root-mode", "file-root"])
+        .args(["--root-stdin", "--merge-under", "workflows"])
+        .write_stdin("version: 2.1\n")
+        .assert()
+        .success()
+        .stdout("version: 2.1\nworkflows:\n  jobs:\n    build:\n      steps: []\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["validate", root, "--root-mode", "file-root"])
+        .args(["--root-inline", "jobs: {}"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("E001").and(predicate::str::contains("<root-inline>")));
+}
+
+#[test]
+fn frozen_pack_fails_after_fragment_changes() {
+    let dir = tempdir().expect("temp dir");
+    let root = dir.path().to_str().expect("utf8 path");
+    write(&dir.path().join("app.yml"), "name: demo\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["manifest", root])
+        .assert()
+        .success();
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", root, "--frozen", "--strict", "--no-header"])
+        .assert()
+        .success()
+        .stdout("app:\n  name: demo\n");
+
+    write(&dir.path().join("app.yml"), "name: tampered\n");
+    write(&dir.path().join("extra.yml"), "x: 1\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", root, "--frozen"])
+        .assert()
+        .failure()
+        .code(2)
+        .stderr(predicate::str::contains("E083"))
+        .stderr(predicate::str::contains("E081"));
+}
+
+#[test]
+fn pack_collation_is_recorded_in_header() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("zebra.yml"), "1\n");
+    write(&dir.path().join("Äpfel.yml"), "2\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            dir.path().to_str().expect("utf8 path"),
+            "--collation",
+            "cldr:de",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "# collation: cldr:de\nÄpfel: 2\nzebra: 1\n",
+        ));
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            dir.path().to_str().expect("utf8 path"),
+            "--collation",
+            "klingon",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown collation"));
+
+    for unsupported in ["cldr:xx-invalid", "cldr:qaa"] {
+        cargo_bin_cmd!("fyaml")
+            .args([
+                "pack",
+                dir.path().to_str().expect("utf8 path"),
+                "--collation",
+                unsupported,
+            ])
+            .assert()
+            .code(2)
+            .stdout(predicate::str::is_empty())
+            .stderr(predicate::str::contains("CLDR has no data"));
+    }
+}
+
+#[test]
+fn pack_big_integers_follow_policy() {
+    let dir = tempdir().expect("temp dir");
+    write(
+        &dir.path().join("ids.yml"),
+        "order: 123456789012345678901234567890\nratio: 2.0\n",
+    );
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", dir.path().to_str().expect("utf8 path")])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("--big-integers=string"));
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            dir.path().to_str().expect("utf8 path"),
+            "--no-header",
+            "--big-integers",
+            "string",
+            "--integral-floats",
+            "int",
+        ])
+        .assert()
+        .success()
+        .stdout("ids:\n  order: '123456789012345678901234567890'\n  ratio: 2\n");
+}
+
+#[test]
+fn pack_dates_tag_marks_timestamps() {
+    let dir = tempdir().expect("temp dir");
+    write(
+        &dir.path().join("release.yml"),
+        "cut: 2001-12-14T21:59:43Z\nname: v1\n",
+    );
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            dir.path().to_str().expect("utf8 path"),
+            "--no-header",
+            "--dates",
+            "tag",
+        ])
+        .assert()
+        .success()
+        .stdout("release:\n  cut: !!timestamp 2001-12-14T21:59:43Z\n  name: v1\n");
+}
+
+#[test]
+fn pack_preserves_custom_tags_in_yaml_and_json() {
+    let dir = tempdir().expect("temp dir");
+    write(
+        &dir.path().join("stack.yml"),
+        "outputs: !Sub {z: 1, a: 2}\nbucket: !Ref Bucket\n",
+    );
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            dir.path().to_str().expect("utf8 path"),
+            "--no-header",
+        ])
+        .assert()
+        .success()
+        .stdout("stack:\n  bucket: !Ref Bucket\n  outputs: !Sub\n    a: 2\n    z: 1\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            dir.path().to_str().expect("utf8 path"),
+            "--format",
+            "json",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "\"bucket\": {\n      \"!Ref\": \"Bucket\"\n    }",
+        ));
+}
+
+#[test]
+fn check_file_applies_fragment_policies_to_a_packed_file() {
+    let dir = tempdir().expect("temp dir");
+    let packed = dir.path().join("packed.yml");
+    write(
+        &packed,
+        "bucket: !Ref Bucket
+name: demo
+",
+    );
+    write(
+        &dir.path().join("sibling.yml"),
+        "not: [valid
+",
+    );
+
+    cargo_bin_cmd!("fyaml")
+        .args(["check-file", packed.to_str().expect("utf8 path")])
+        .assert()
+        .success();
+
+    let output = cargo_bin_cmd!("fyaml")
+        .args([
+            "check-file",
+            packed.to_str().expect("utf8 path"),
+            "--tags",
+            "error",
+            "--json",
+        ])
+        .output()
+        .expect("run check-file");
+    assert_eq!(output.status.code(), Some(3));
+    let diagnostics: Value = serde_json::from_slice(&output.stdout).expect("json diagnostics");
+    let codes = diagnostics
+        .as_array()
+        .expect("diagnostics array")
+        .iter()
+        .map(|d| d["code"].as_str().expect("code"))
+        .collect::<Vec<_>>();
+    assert_eq!(codes, vec!["E102"]);
+
+    cargo_bin_cmd!("fyaml")
+        .args(["check-file", dir.path().to_str().expect("utf8 path")])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("E050"));
+}
+
+#[test]
+fn canonicalize_matches_pack_output_for_one_file() {
+    let dir = tempdir().expect("temp dir");
+    let input = dir.path().join("input.yml");
+    write(&input, "z: 1.50\na: {y: !Ref x, b: 2}\nlist: [3, 1]\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "canonicalize",
+            input.to_str().expect("utf8 path"),
+            "--no-header",
+        ])
+        .assert()
+        .success()
+        .stdout("a:\n  b: 2\n  y: !Ref x\nlist:\n- 3\n- 1\nz: 1.5\n");
+
+    let tree = dir.path().join("tree");
+    write(
+        &tree.join("input.yml"),
+        "z: 1.50\na: {y: !Ref x, b: 2}\nlist: [3, 1]\n",
+    );
+    let packed = cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            tree.to_str().expect("utf8 path"),
+            "--format",
+            "json",
+        ])
+        .output()
+        .expect("run pack");
+    let canonical = cargo_bin_cmd!("fyaml")
+        .args([
+            "canonicalize",
+            input.to_str().expect("utf8 path"),
+            "--format",
+            "json",
+        ])
+        .output()
+        .expect("run canonicalize");
+    let packed: Value = serde_json::from_slice(&packed.stdout).expect("json");
+    let canonical: Value = serde_json::from_slice(&canonical.stdout).expect("json");
+    assert_eq!(packed["input"], canonical);
+}
+
+#[test]
+fn flatten_then_unflatten_reproduces_pack() {
+    let dir = tempdir().expect("temp dir");
+    write(
+        &dir.path().join("app/config.yml"),
+        "name: demo\nports: [80, 443]\n",
+    );
+    write(
+        &dir.path().join("app/vars.yml"),
+        "\"dotted.key\": !Ref Bucket\nempty: {}\n",
+    );
+    let tree = dir.path().to_str().expect("utf8 path");
+
+    let flat = cargo_bin_cmd!("fyaml")
+        .args(["flatten", tree])
+        .output()
+        .expect("run flatten");
+    assert!(flat.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&flat.stdout),
+        "app.config.name: demo\napp.config.ports[0]: 80\napp.config.ports[1]: 443\n\
+         app.vars[\"dotted.key\"]: !Ref Bucket\napp.vars.empty: {}\n"
+    );
+
+    let packed = cargo_bin_cmd!("fyaml")
+        .args(["pack", tree])
+        .output()
+        .expect("run pack");
+    cargo_bin_cmd!("fyaml")
+        .args(["unflatten", "-"])
+        .write_stdin(flat.stdout)
+        .assert()
+        .success()
+        .stdout(String::from_utf8(packed.stdout).expect("utf8"));
+}
+
+#[test]
+fn pack_csv_lists_leaves_with_source_files() {
+    let dir = tempdir().expect("temp dir");
+    write(
+        &dir.path().join("app/config.yml"),
+        "name: demo\nports: [80]\n",
+    );
+    write(&dir.path().join("top.yml"), "\"a, b\"\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            dir.path().to_str().expect("utf8 path"),
+            "--format",
+            "csv",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            "path,type,value,source_file\r\n\
+             app.config.name,string,demo,app/config.yml\r\n\
+             app.config.ports[0],int,80,app/config.yml\r\n\
+             top,string,\"a, b\",top.yml\r\n",
+        );
+}
+
+#[test]
+fn watch_rewrites_output_when_a_fragment_changes() {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let dir = tempdir().expect("temp dir");
+    let tree = dir.path().join("tree");
+    let output = dir.path().join("out.yml");
+    write(&tree.join("app/config.yml"), "name: demo\n");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fyaml"))
+        .args([
+            "watch",
+            "--poll",
+            "50",
+            "--debounce",
+            "50",
+            "--no-header",
+            "-o",
+        ])
+        .arg(&output)
+        .arg(&tree)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("spawn fyaml watch");
+    let (lines, received) = mpsc::channel();
+    let stdout = child.stdout.take().expect("piped stdout");
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            let _ = lines.send(line);
+        }
+    });
+    let next_line = || received.recv_timeout(Duration::from_secs(10));
+
+    let first = next_line();
+    assert!(
+        matches!(&first, Ok(line) if line.starts_with("built")),
+        "{first:?}"
+    );
+    write(&tree.join("app/config.yml"), "name: demo\nport: 80\n");
+    let changed = next_line();
+    let rebuilt = next_line();
+    child.kill().expect("stop fyaml watch");
+    let _ = child.wait();
+
+    assert_eq!(changed.as_deref(), Ok("modified app/config.yml"));
+    assert!(
+        matches!(&rebuilt, Ok(line) if line.starts_with("rebuilt: 1 key path(s) changed")),
+        "{rebuilt:?}"
+    );
+    assert_eq!(
+        fs::read_to_string(&output).expect("output written"),
+        "app:\n  config:\n    name: demo\n    port: 80\n"
+    );
+}
+
+#[test]
+fn watch_prints_differences_from_the_last_output() {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let dir = tempdir().expect("temp dir");
+    let tree = dir.path().join("tree");
+    let output = dir.path().join("out.yml");
+    write(&tree.join("app/config.yml"), "name: demo\n");
+    write(&output, "app:\n  config:\n    name: old\n");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fyaml"))
+        .args(["watch", "--poll", "50", "--debounce", "50", "-o"])
+        .arg(&output)
+        .arg("--diff-against")
+        .arg(&output)
+        .arg(&tree)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("spawn fyaml watch");
+    let (lines, received) = mpsc::channel();
+    let stdout = child.stdout.take().expect("piped stdout");
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            let _ = lines.send(line);
+        }
+    });
+    let next_line = || received.recv_timeout(Duration::from_secs(10));
+
+    let built = next_line();
+    let first = next_line();
+    write(&tree.join("app/config.yml"), "name: demo\nport: 80\n");
+    let changed = next_line();
+    let rebuilt = next_line();
+    let second = next_line();
+    child.kill().expect("stop fyaml watch");
+    let _ = child.wait();
+
+    assert!(
+        matches!(&built, Ok(line) if line.starts_with("built")),
+        "{built:?}"
+    );
+    assert_eq!(
+        first.as_deref(),
+        Ok("  $.app.config.name: scalar value differs")
+    );
+    assert_eq!(changed.as_deref(), Ok("modified app/config.yml"));
+    assert!(
+        matches!(&rebuilt, Ok(line) if line.starts_with("rebuilt")),
+        "{rebuilt:?}"
+    );
+    assert_eq!(
+        second.as_deref(),
+        Ok("  $.app.config: key missing on left side: port")
+    );
+}
+
+#[test]
+fn metrics_out_records_failed_runs_in_textfile_format() {
+    let dir = tempdir().expect("temp dir");
+    let tree = dir.path().join("tree");
+    let metrics = dir.path().join("fyaml.prom");
+    write(&tree.join("good.yml"), "a: 1\n");
+    write(&tree.join("bad.yml"), "a: [\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", tree.to_str().expect("utf8 path"), "--metrics-out"])
+        .arg(&metrics)
+        .assert()
+        .code(3);
+
+    let text = fs::read_to_string(&metrics).expect("metrics written");
+    assert!(text.contains("# TYPE fyaml_errors gauge\nfyaml_errors{command=\"pack\"} 1\n"));
+    assert!(text.contains("fyaml_fragments{command=\"pack\"} 2\n"));
+    assert!(text.contains("fyaml_output_bytes{command=\"pack\"} 0\n"));
+}
+
+#[test]
+fn include_and_exclude_globs_filter_the_scan_and_explain_why() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("env/dev/db.yml"), "a: 1\n");
+    write(&dir.path().join("env/prod/db.yml"), "a: 2\n");
+    write(&dir.path().join("app/c.yml"), "x: 1\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", root, "--no-header", "--include", "env/**"])
+        .args(["--exclude", "env/dev/**"])
+        .assert()
+        .success()
+        .stdout("env:\n  prod:\n    db:\n      a: 2\n")
+        .stderr(predicate::str::contains("W050").not());
+
+    let output = cargo_bin_cmd!("fyaml")
+        .args(["explain", root, "--json", "--include", "env/**"])
+        .args(["--exclude", "env/dev/**"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let report: Value = serde_json::from_slice(&output).expect("explain json");
+    assert_eq!(
+        report["explain"]["ignored"],
+        serde_json::json!([
+            {"path": "app", "rule": "not matched by --include"},
+            {"path": "env/dev", "rule": "excluded by --exclude env/dev/**"},
+        ])
+    );
+}
+
+#[test]
+fn cloudformation_and_ansible_tags_round_trip_without_a_profile() {
+    let dir = tempdir().expect("temp dir");
+    write(
+        &dir.path().join("resources.yml"),
+        "role: !GetAtt Role.Arn\nname: !Sub '${AWS::StackName}-app'\nzone: !Select [0, !GetAZs '']\n",
+    );
+    write(
+        &dir.path().join("vars.yml"),
+        "db_password: !vault |\n  $ANSIBLE_VAULT;1.1;AES256\n  3863\n",
+    );
+
+    let output = cargo_bin_cmd!("fyaml")
+        .args(["pack", dir.path().to_str().expect("utf8 path")])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let packed = String::from_utf8(output).expect("utf8 output");
+    assert!(packed.contains("  role: !GetAtt Role.Arn\n"));
+    assert!(packed.contains("  db_password: !vault |\n    $ANSIBLE_VAULT;1.1;AES256\n    3863\n"));
+
+    let reparsed: serde_yaml::Value = serde_yaml::from_str(&packed).expect("output parses");
+    let vault = &reparsed["vars"]["db_password"];
+    let serde_yaml::Value::Tagged(vault) = vault else {
+        panic!("vault value keeps its tag: {vault:?}");
+    };
+    assert_eq!(vault.tag.to_string(), "!vault");
+    assert_eq!(vault.value, "$ANSIBLE_VAULT;1.1;AES256\n3863\n");
+}
+
+#[test]
+fn diagnostics_follow_lang_and_catalog_overrides() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("auth.yml"), "x\n");
+    write(&dir.path().join("auth/provider.yml"), "ok: true\n");
+    let catalog = dir.path().join("messages.json");
+    fs::write(
+        &catalog,
+        r#"{"E001": {"action": "Frag #plattform vor dem Umbenennen."}}"#,
+    )
+    .expect("write catalog");
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "validate",
+            dir.path().to_str().expect("utf8 path"),
+            "--lang",
+            "de",
+            "--catalog",
+            catalog.to_str().expect("utf8 path"),
+        ])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "error[E001]: Schlüsselkollision erkannt",
+        ))
+        .stderr(predicate::str::contains(
+            "Action: Frag #plattform vor dem Umbenennen.",
+        ));
+}
+
+#[test]
+fn explain_code_prints_registry_entry() {
+    cargo_bin_cmd!("fyaml")
+        .args(["explain-code", "W010"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "W010 (warn): dotted key derived from filename\n",
+        ))
+        .stdout(predicate::str::contains(
+            "Related options: --allow-dotted-keys, --expand-dotted-keys, --strict",
+        ));
+
+    cargo_bin_cmd!("fyaml")
+        .args(["explain-code", "E999"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "error[E091]: unknown diagnostic code",
+        ));
+}
+
+#[test]
+fn codes_json_lists_registry_with_metadata() {
+    let output = cargo_bin_cmd!("fyaml")
+        .args(["codes", "--json"])
+        .output()
+        .expect("run fyaml codes");
+    assert!(output.status.success());
+    let codes: Value = serde_json::from_slice(&output.stdout).expect("valid JSON");
+    let codes = codes.as_array().expect("array of codes");
+    let w010 = codes
+        .iter()
+        .find(|info| info["code"] == "W010")
+        .expect("W010 listed");
+    assert_eq!(w010["severity"], "warn");
+    assert_eq!(w010["category"], "invalid_input");
+    assert_eq!(w010["options"][0], "--allow-dotted-keys");
+    assert!(codes.iter().any(|info| info["code"] == "E001"));
+}
+
+#[test]
+fn diagnostics_to_separates_streams() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("db.primary.yml"), "host: a\n");
+    let log = dir.path().join("diagnostics.log");
+    let target = format!("file:{}", log.display());
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            dir.path().to_str().expect("utf8 path"),
+            "--diagnostics-to",
+            &target,
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("host: a"))
+        .stdout(predicate::str::contains("W010").not())
+        .stderr(predicate::str::is_empty());
+    let logged = fs::read_to_string(&log).expect("diagnostics file");
+    assert!(logged.contains("warn[W010]: dotted key derived from filename"));
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            dir.path().to_str().expect("utf8 path"),
+            "--diagnostics-to",
+            "stdout",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("warn[W010]"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn validate_multiple_roots_parses_identical_fragments_once() {
+    let root = tempdir().expect("temp dir");
+    let (a, b) = (root.path().join("a"), root.path().join("b"));
+    write(&a.join("defaults.yml"), "retries: 3\n");
+    write(&b.join("defaults.yml"), "retries: 3\n");
+    write(&b.join("db.primary.yml"), "host: b\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "validate",
+            a.to_str().expect("utf8 path"),
+            b.to_str().expect("utf8 path"),
+            "--stats",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "parsed 2 fragment(s), reused 1 identical fragment(s)",
+        ))
+        .stderr(predicate::str::contains(format!(
+            "Location: {}",
+            b.join("db.primary.yml").display()
+        )));
+}
+
+#[cfg(unix)]
+#[test]
+fn validate_delegates_to_running_daemon() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("tree/defaults.yml"), "retries: 3\n");
+    let tree = dir.path().join("tree");
+    let socket = dir.path().join("fyaml.sock");
+
+    let mut daemon = std::process::Command::new(assert_cmd::cargo::cargo_bin!("fyaml"))
+        .args(["daemon", "start"])
+        .env("FYAML_DAEMON_SOCKET", &socket)
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .expect("start daemon");
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while !socket.exists() {
+        assert!(std::time::Instant::now() < deadline, "daemon did not start");
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    use std::os::unix::fs::PermissionsExt;
+    let mode = fs::metadata(&socket).expect("socket").permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    // A client that never sends its request does not hold up the others.
+    let _idle = std::os::unix::net::UnixStream::connect(&socket).expect("connect");
+    for expected in [
+        "parsed 1 fragment(s), reused 0",
+        "parsed 0 fragment(s), reused 1",
+    ] {
+        cargo_bin_cmd!("fyaml")
+            .args(["validate", tree.to_str().expect("utf8 path"), "--stats"])
+            .env("FYAML_DAEMON_SOCKET", &socket)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(expected));
+    }
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "validate",
+            tree.to_str().expect("utf8 path"),
+            "--stats",
+            "--no-daemon",
+        ])
+        .env("FYAML_DAEMON_SOCKET", &socket)
+        .assert()
+        .stdout(predicate::str::contains("parsed 1 fragment(s), reused 0"));
+
+    cargo_bin_cmd!("fyaml")
+        .args(["daemon", "stop"])
+        .env("FYAML_DAEMON_SOCKET", &socket)
+        .assert()
+        .success();
+    assert!(daemon.wait().expect("daemon exits").success());
+    cargo_bin_cmd!("fyaml")
+        .args(["daemon", "status"])
+        .env("FYAML_DAEMON_SOCKET", &socket)
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("error[E310]"));
+}
+
+#[cfg(unix)]
+#[test]
+fn daemon_start_leaves_a_file_at_the_socket_path_alone() {
+    let dir = tempdir().expect("temp dir");
+    let socket = dir.path().join("notes.txt");
+    write(&socket, "keep me\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["daemon", "start"])
+        .env("FYAML_DAEMON_SOCKET", &socket)
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("error[E309]"))
+        .stderr(predicate::str::contains("is not a socket"));
+    assert_eq!(fs::read_to_string(&socket).expect("file kept"), "keep me\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn daemon_client_refuses_a_socket_path_that_is_not_a_socket() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("fyaml.sock"), "");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["daemon", "status"])
+        .env_remove("FYAML_DAEMON_SOCKET")
+        .env("XDG_RUNTIME_DIR", dir.path())
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("error[E310]"))
+        .stderr(predicate::str::contains("is not a socket"));
+}
+
+#[test]
+fn anonymize_replaces_values_and_is_deterministic() {
+    let dir = tempdir().expect("temp dir");
+    let tree = dir.path().join("tree");
+    write(
+        &tree.join("db/primary.yml"),
+        "host: db.internal\nport: 5432\nreplica: db.internal\nratio: 0.5\ntls: true\n",
+    );
+    write(&tree.join("notes.txt"), "echo hi\n");
+    write(&tree.join("broken.yml"), "a: [\n");
+    let tree_arg = tree.to_str().expect("utf8 path");
+    let out = dir.path().join("out");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["anonymize", tree_arg, out.to_str().expect("utf8 path")])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Anonymized 3 file(s) into"))
+        .stdout(predicate::str::contains(
+            "unparsed broken.yml (replaced by invalid YAML)",
+        ));
+    let primary = fs::read_to_string(out.join("db/primary.yml")).expect("anonymized file");
+    assert_eq!(
+        primary.trim_end(),
+        "host: string-1\nport: 1\nreplica: string-1\nratio: 2.5\ntls: true"
+    );
+    assert_eq!(
+        fs::read_to_string(out.join("notes.txt"))
+            .expect("anonymized text")
+            .trim_end(),
+        "text-1"
+    );
+    assert!(!primary.contains("db.internal"));
+
+    let hashed = |name: &str| {
+        let target = dir.path().join(name);
+        let output = cargo_bin_cmd!("fyaml")
+            .args([
+                "anonymize",
+                tree_arg,
+                target.to_str().expect("utf8 path"),
+                "--hash-keys",
+                "--json",
+            ])
+            .output()
+            .expect("run anonymize");
+        assert!(output.status.success());
+        let report: Value = serde_json::from_slice(&output.stdout).expect("json report");
+        (report, target)
+    };
+    let (first, first_dir) = hashed("hashed-1");
+    let (second, second_dir) = hashed("hashed-2");
+    assert_eq!(first["unparsed"], serde_json::json!(["broken.yml"]));
+    let files = first["files"].as_array().expect("files array");
+    assert_eq!(files.len(), 3);
+    assert_eq!(first["files"], second["files"]);
+    for file in files {
+        let name = file.as_str().expect("file name");
+        assert!(!name.contains("primary") && !name.contains("notes"));
+        assert_eq!(
+            fs::read(first_dir.join(name)).expect("first run"),
+            fs::read(second_dir.join(name)).expect("second run")
+        );
+    }
+
+    cargo_bin_cmd!("fyaml")
+        .args(["anonymize", tree_arg, out.to_str().expect("utf8 path")])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("error[E216]"));
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "anonymize",
+            tree_arg,
+            tree.join("copy").to_str().expect("utf8 path"),
+        ])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("error[E217]"));
+}
+
+#[test]
+fn env_report_describes_the_tree_build_and_changed_options() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("app/x.yml"), "a: 1\n");
+    write(&dir.path().join(".hidden.yml"), "b: 2\n");
+    let tree = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["env-report", tree])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "fyaml {}",
+            env!("CARGO_PKG_VERSION")
+        )))
+        .stdout(predicate::str::contains(
+            "1 directories, 2 files (2 YAML), 0 symlinks, 1 hidden entries",
+        ))
+        .stdout(predicate::str::contains(
+            "1 fragments, 2 derived keys, 1 ignored entries",
+        ))
+        .stdout(predicate::str::contains("0 errors, 1 warnings"))
+        .stdout(predicate::str::contains(
+            "Options changed from the defaults:\n  (none)",
+        ));
+
+    let output = cargo_bin_cmd!("fyaml")
+        .args(["env-report", tree, "--json", "--seq-start", "1", "--strict"])
+        .output()
+        .expect("run env-report");
+    assert_eq!(output.status.code(), Some(0));
+    let report: Value = serde_json::from_slice(&output.stdout).expect("json report");
+    assert_eq!(report["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(report["os"], std::env::consts::OS);
+    assert_eq!(report["tree"]["yaml_files"], 2);
+    assert_eq!(report["tree"]["max_depth"], 1);
+    assert_eq!(report["build"]["fragments"], 1);
+    assert_eq!(report["build"]["derived_keys"], 2);
+    assert_eq!(report["build"]["ignored_entries"], 1);
+    assert_eq!(report["build"]["directory_modes"]["mapping"], 2);
+    assert_eq!(report["options"]["seq_start"], 1);
+    assert_eq!(report["options"]["include_hidden"], false);
+    assert_eq!(
+        report["changed_options"],
+        serde_json::json!(["seq_start", "strict"])
+    );
+}
+
+#[test]
+fn index_files_merge_into_their_directory() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("db/_.yml"), "name: main\n");
+    write(&dir.path().join("db/conn.yml"), "port: 1\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", root])
+        .assert()
+        .success()
+        .stdout("db:\n  _:\n    name: main\n  conn:\n    port: 1\n");
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--index-files", root])
+        .assert()
+        .success()
+        .stdout("db:\n  conn:\n    port: 1\n  name: main\n");
+
+    write(&dir.path().join("db/index.yml"), "conn: shadowed\n");
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--index-files", root])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("error[E001]"))
+        .stderr(predicate::str::contains("Location: db/index.yml"));
+
+    write(&dir.path().join("db/index.yml"), "- a\n");
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--index-files", root])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "error[E005]: index file is not a mapping",
+        ));
+}
+
+#[test]
+fn on_collision_policies_pick_or_merge_the_colliding_values() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("a.yml"), "x: 1\nn:\n  p: 1\n");
+    write(&dir.path().join("a/x.yml"), "2\n");
+    write(&dir.path().join("a/n.yml"), "q: 2\n");
+    let root = dir.path().to_str().expect("utf8 path");
+    let pack = |policy: &str| {
+        let mut cmd = cargo_bin_cmd!("fyaml");
+        cmd.args(["pack", "--no-header", "--on-collision", policy, root]);
+        cmd
+    };
+
+    pack("error")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "error[E001]: key collision detected",
+        ))
+        .stderr(predicate::str::contains("Sources: a, a.yml"));
+    pack("first-wins")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("warn[W018]"))
+        .stdout("a:\n  n:\n    q: 2\n  x: 2\n");
+    pack("last-wins")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("warn[W018]"))
+        .stdout("a:\n  n:\n    p: 1\n  x: 1\n");
+    pack("deep-merge")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "error[E001]: key collision during merge",
+        ))
+        .stderr(predicate::str::contains(
+            "Existing value kind: number, incoming value kind: number",
+        ));
+
+    write(&dir.path().join("a.yml"), "n:\n  p: 1\nz: 3\n");
+    pack("deep-merge")
+        .assert()
+        .success()
+        .stdout("a:\n  n:\n    p: 1\n    q: 2\n  x: 2\n  z: 3\n");
+}
+
+#[test]
+fn yml_and_yaml_siblings_conflict_unless_one_is_preferred() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("a.yml"), "v: yml\n");
+    write(&dir.path().join("a.yaml"), "v: yaml\n");
+    write(&dir.path().join("b.yaml"), "w: 1\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", root])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("error[E006]"))
+        .stderr(predicate::str::contains("Sources: a.yml, a.yaml"));
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--no-header",
+            "--extension-conflict",
+            "prefer-yml",
+            root,
+        ])
+        .assert()
+        .success()
+        .stdout("a:\n  v: yml\nb:\n  w: 1\n");
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--no-header",
+            "--extension-conflict",
+            "prefer-yaml",
+            root,
+        ])
+        .assert()
+        .success()
+        .stdout("a:\n  v: yaml\nb:\n  w: 1\n");
+    cargo_bin_cmd!("fyaml")
+        .args(["explain", "--extension-conflict", "prefer-yml", root])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "a.yaml (shadowed by a.yml (--extension-conflict))",
+        ));
+
+    fs::remove_file(dir.path().join("a.yaml")).expect("remove a.yaml");
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--require-extension", "yml", root])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "warn[W019]: fragment does not use the .yml extension",
+        ))
+        .stderr(predicate::str::contains("Location: b.yaml"))
+        .stderr(predicate::str::contains("a.yml").not());
+}
+
+#[test]
+fn text_files_become_strings_keyed_by_their_stem() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("scripts/run.txt"), "#!/bin/sh\necho hi\n");
+    write(&dir.path().join("README.md"), "# Title\n");
+    write(&dir.path().join("c.yml"), "a: 1\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", root])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("warn[W050]: ignored 2 file(s)"))
+        .stdout("c:\n  a: 1\nscripts: {}\n");
+    let output = cargo_bin_cmd!("fyaml")
+        .args(["pack", "--text-files", "--format", "json", root])
+        .output()
+        .expect("run pack");
+    assert!(output.status.success());
+    let packed: Value = serde_json::from_slice(&output.stdout).expect("packed json");
+    assert_eq!(
+        packed,
+        serde_json::json!({
+            "README": "# Title\n",
+            "c": {"a": 1},
+            "scripts": {"run": "#!/bin/sh\necho hi\n"}
+        })
+    );
+
+    write(&dir.path().join("c.txt"), "x");
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--text-files", root])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("error[E001]"))
+        .stderr(predicate::str::contains("Sources: c.txt, c.yml"));
+}
+
+#[test]
+fn accept_binary_emits_matching_files_as_base64() {
+    let dir = tempdir().expect("temp dir");
+    fs::create_dir_all(dir.path().join("img")).expect("create img");
+    fs::write(dir.path().join("img/logo.png"), b"\x89PNG\x00\x01").expect("write png");
+    write(&dir.path().join("c.yml"), "a: 1\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", root])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Examples: img/logo.png"))
+        .stdout("c:\n  a: 1\nimg: {}\n");
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--accept-binary", "*.png", root])
+        .assert()
+        .success()
+        .stdout("c:\n  a: 1\nimg:\n  logo: !!binary iVBORwAB\n");
+    let output = cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--accept-binary",
+            "img/*.png",
+            "--format",
+            "json",
+            root,
+        ])
+        .output()
+        .expect("run pack");
+    assert!(output.status.success());
+    let packed: Value = serde_json::from_slice(&output.stdout).expect("packed json");
+    assert_eq!(
+        packed["img"]["logo"],
+        serde_json::json!({"!!binary": "iVBORwAB"})
+    );
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--no-header",
+            "--accept-binary",
+            "*.png",
+            "--max-yaml-bytes",
+            "5",
+            root,
+        ])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("error[E034]"))
+        .stderr(predicate::str::contains("Location: img/logo.png"))
+        .stderr(predicate::str::contains("Binary file is 6 bytes"))
+        .stderr(predicate::str::contains("Location: c.yml").not());
+}
+
+#[test]
+fn front_matter_reads_markdown_pages_with_their_body() {
+    let dir = tempdir().expect("temp dir");
+    write(
+        &dir.path().join("posts/hello.md"),
+        "---\ntitle: Hello\ntags: [a]\n---\nBody text\n",
+    );
+    write(&dir.path().join("posts/plain.md"), "Just text\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--front-matter", root])
+        .assert()
+        .success()
+        .stdout(
+            "posts:\n  hello:\n    content: |\n      Body text\n    tags:\n    - a\n    \
+             title: Hello\n  plain:\n    content: |\n      Just text\n",
+        );
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--no-header",
+            "--front-matter",
+            "--content-key",
+            "body",
+            root,
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("  hello:\n    body: |\n"))
+        .stdout(predicate::str::contains("content").not());
+
+    write(&dir.path().join("posts/bad.md"), "---\n- a\n---\nx\n");
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--front-matter", root])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains(
+            "error[E104]: front matter is not a mapping",
+        ));
+    write(
+        &dir.path().join("posts/bad.md"),
+        "---\ncontent: a\n---\nx\n",
+    );
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--front-matter", root])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("error[E105]"))
+        .stderr(predicate::str::contains("Location: posts/bad.md"));
+}
+
+#[test]
+fn file_root_mode_discovers_a_conventional_root_file() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("_root.yml"), "name: app\n");
+    write(&dir.path().join("svc/x.yml"), "a: 1\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--root-mode", "file-root", root])
+        .assert()
+        .success()
+        .stdout("name: app\nsvc:\n  x:\n    a: 1\n");
+    cargo_bin_cmd!("fyaml")
+        .args(["explain", "--root-mode", "file-root", root])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Root File:\n  _root.yml (auto-selected)",
+        ));
+    let output = cargo_bin_cmd!("fyaml")
+        .args(["explain", "--root-mode", "file-root", "--json", root])
+        .output()
+        .expect("run explain");
+    let report: Value = serde_json::from_slice(&output.stdout).expect("explain json");
+    assert_eq!(report["explain"]["auto_root_file"], "_root.yml");
+
+    write(&dir.path().join("root.yaml"), "name: other\n");
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--root-mode", "file-root", root])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("error[E048]"))
+        .stderr(predicate::str::contains("Found: _root.yml, root.yaml"));
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--no-header",
+            "--root-mode",
+            "file-root",
+            "--root-file",
+            "root.yaml",
+            root,
+        ])
+        .assert()
+        .success()
+        .stdout("_root:\n  name: app\nname: other\nsvc:\n  x:\n    a: 1\n");
+}
+
+#[test]
+fn shared_anchors_resolve_aliases_from_the_anchors_file() {
+    let dir = tempdir().expect("temp dir");
+    write(
+        &dir.path().join("_anchors.yml"),
+        "defaults:\n  retries: 3\nregion: eu\n",
+    );
+    write(
+        &dir.path().join("svc/a.yml"),
+        "api: *defaults\nzone: *region\nlocal: &region us\nuse: *region\nnote: \"*defaults\"\n",
+    );
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--shared-anchors", root])
+        .assert()
+        .success()
+        .stdout(
+            "svc:\n  a:\n    api:\n      retries: 3\n    local: us\n    note: '*defaults'\n    \
+             use: us\n    zone: eu\n",
+        );
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", root])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("error[E100]"));
+
+    write(&dir.path().join("svc/b.yml"), "x: *nope\n");
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--shared-anchors", root])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("error[E122]: unknown alias"))
+        .stderr(predicate::str::contains("Location: svc/b.yml"))
+        .stderr(predicate::str::contains("Shared anchors: defaults, region"));
+
+    fs::remove_file(dir.path().join("svc/b.yml")).expect("remove b.yml");
+    write(&dir.path().join("_anchors.yml"), "- a\n");
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--shared-anchors", root])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("error[E121]"));
+}
+
+#[test]
+fn merge_keys_expand_resolves_merged_entries_in_yaml_order() {
+    let dir = tempdir().expect("temp dir");
+    write(
+        &dir.path().join("m.yml"),
+        "a: &a {x: 1, y: 1}\nb: &b {y: 2, z: 2}\nsvc:\n  <<: [*a, *b]\n  x: 9\n",
+    );
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", root])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("  svc:\n    <<:\n"))
+        .stderr(predicate::str::contains("W024").not());
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--merge-keys", "expand", root])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "  svc:\n    x: 9\n    y: 1\n    z: 2\n",
+        ))
+        .stderr(predicate::str::contains(
+            "warn[W024]: explicit key overrides a merged value",
+        ))
+        .stderr(predicate::str::contains("Key path: m.svc.x"));
+
+    write(&dir.path().join("bad.yml"), "svc:\n  <<: 3\n");
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--merge-keys", "expand", root])
+        .assert()
+        .code(3)
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::contains(
+            "error[E123]: merge key value is not a mapping",
+        ))
+        .stderr(predicate::str::contains("Key path: bad.svc"));
+}
+
+#[test]
+fn uniform_sequences_and_item_keys_check_sequence_items() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("wf/ci/steps/0.yml"), "name: a\nuses: x\n");
+    write(&dir.path().join("wf/ci/steps/1.yml"), "name: b\n");
+    write(&dir.path().join("wf/ci/steps/2.yml"), "hello\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", root])
+        .assert()
+        .success();
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--uniform-sequences", root])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("error[E007]"))
+        .stderr(predicate::str::contains("Location: wf/ci/steps/2.yml"))
+        .stderr(predicate::str::contains(
+            "Item 2 is a string, but most items of `wf.ci.steps` are a mapping.",
+        ));
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--no-header",
+            "--item-keys",
+            "wf.*.steps=name,uses",
+            root,
+        ])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "Location: wf/ci/steps/1.yml\n  Cause: Item 1 of `wf.ci.steps` lacks a required key.",
+        ))
+        .stderr(predicate::str::contains("Missing keys: uses"))
+        .stderr(predicate::str::contains("Missing keys: name, uses"));
+
+    write(&dir.path().join("wf/ci/steps/1.yml"), "name: b\nuses: y\n");
+    fs::remove_file(dir.path().join("wf/ci/steps/2.yml")).expect("remove item");
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--no-header",
+            "--uniform-sequences",
+            "--item-keys",
+            "wf.*.steps=name,uses",
+            root,
+        ])
+        .assert()
+        .success();
+}
+
+#[test]
+fn item_count_bounds_sequence_directories() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("wf/ci/jobs/0.yml"), "a: 1\n");
+    write(&dir.path().join("wf/cd/jobs/notes.txt"), "x\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", root])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("  cd:\n    jobs: {}\n"));
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--no-header",
+            "--item-count",
+            "wf.*.jobs:min=1",
+            root,
+        ])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("error[E009]"))
+        .stderr(predicate::str::contains("Location: wf/cd/jobs\n"))
+        .stderr(predicate::str::contains(
+            "`wf.cd.jobs` has 0 item(s); --item-count wf.*.jobs:min=1 requires at least 1.",
+        ))
+        .stderr(predicate::str::contains(
+            "Ignored entries: wf/cd/jobs/notes.txt (non-YAML file ignored)",
+        ))
+        .stderr(predicate::str::contains("Location: wf/ci/jobs").not());
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--no-header",
+            "--item-count",
+            "wf.ci.jobs:max=0",
+            root,
+        ])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "`wf.ci.jobs` has 1 item(s); --item-count wf.ci.jobs:max=0 requires at most 0.",
+        ));
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--no-header",
+            "--item-count",
+            "wf.ci.jobs:min=1,max=1",
+            root,
+        ])
+        .assert()
+        .success();
+}
+
+#[test]
+fn seq_start_and_seq_order_set_sequence_numbering() {
+    let dir = tempdir().expect("temp dir");
+    write(&dir.path().join("steps/1.yml"), "s1\n");
+    write(&dir.path().join("steps/2.yml"), "s2\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", root])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Missing ranges: 0..0"));
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--seq-start", "1", root])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout("steps:\n- s1\n- s2\n");
+
+    write(&dir.path().join("steps/10.yml"), "s10\n");
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--seq-start", "1", root])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Missing ranges: 3..9\n"))
+        .stdout("steps:\n- s1\n- s2\n- s10\n");
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--no-header",
+            "--seq-start",
+            "1",
+            "--seq-order",
+            "lexical",
+            root,
+        ])
+        .assert()
+        .success()
+        .stdout("steps:\n- s1\n- s10\n- s2\n");
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--no-header",
+            "--seq-start",
+            "2",
+            "--seq-gaps",
+            "error",
+            root,
+        ])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("error[E003]"))
+        .stderr(predicate::str::contains(
+            "Missing ranges: 3..9. Below --seq-start 2: 1",
+        ));
+    cargo_bin_cmd!("fyaml")
+        .args(["explain", "--seq-start", "1", root])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Sequence Numbering:\n  first index 1, numeric order, gaps: warn",
+        ));
+}
+
+#[test]
+fn sort_rules_override_key_order_per_subtree() {
+    let dir = tempdir().expect("temp dir");
+    write(
+        &dir.path().join("hand.yml"),
+        "z: 1\na: 2\nsub:\n  y: 1\n  b: 2\n",
+    );
+    write(&dir.path().join("auto.yml"), "z: 1\na: 2\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--no-header",
+            "--preserve",
+            "--sort",
+            "auto=keys",
+            root,
+        ])
+        .assert()
+        .success()
+        .stdout("auto:\n  a: 2\n  z: 1\nhand:\n  z: 1\n  a: 2\n  sub:\n    y: 1\n    b: 2\n");
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            "--no-header",
+            "--sort",
+            "hand=none",
+            "--sort",
+            "hand.sub=keys",
+            root,
+        ])
+        .assert()
+        .success()
+        .stdout("auto:\n  a: 2\n  z: 1\nhand:\n  z: 1\n  a: 2\n  sub:\n    b: 2\n    y: 1\n");
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--sort", "hand=bogus", root])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "unknown sort `bogus`; expected keys or none",
+        ));
+}
+
+#[test]
+fn yaml_indent_and_quote_strings_change_layout_only() {
+    let dir = tempdir().expect("temp dir");
+    let tree = dir.path().join("tree");
+    write(
+        &tree.join("d.yml"),
+        "a:\n  b:\n    c: on\n  list:\n  - x\nk: \"a\\tb\"\nj: \"it's\"\n",
+    );
+    let root = tree.to_str().expect("utf8 path");
+    let pack = |flags: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("fyaml");
+        cmd.arg("pack").args(flags).arg(root);
+        cmd
+    };
+
+    pack(&["--yaml-indent", "4"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# style: yaml-indent=4\n"))
+        .stdout(predicate::str::contains(
+            "d:\n    a:\n        b:\n            c: on\n        list:\n        - x\n",
+        ));
+    pack(&["--no-header", "--quote-strings", "ambiguous"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "      c: 'on'\n    list:\n    - x\n",
+        ));
+    pack(&["--no-header", "--quote-strings", "single"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "'d':\n  'a':\n    'b':\n      'c': 'on'\n",
+        ))
+        .stdout(predicate::str::contains(
+            "  'j': 'it''s'\n  'k': \"a\\tb\"\n",
+        ));
+    pack(&["--no-header", "--quote-strings", "double"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"d\":\n  \"a\":\n"))
+        .stdout(predicate::str::contains("  \"j\": \"it's\"\n"));
+    pack(&["--yaml-indent", "1"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("1 is not in 2..=8"));
+
+    let styled = dir.path().join("styled.yaml");
+    pack(&[
+        "--yaml-indent",
+        "8",
+        "--quote-strings",
+        "double",
+        "-o",
+        styled.to_str().expect("utf8 path"),
+    ])
+    .assert()
+    .success();
+    cargo_bin_cmd!("fyaml")
+        .args(["diff", root, styled.to_str().expect("utf8 path")])
+        .assert()
+        .success()
+        .stdout("equal\n");
+}
+
+#[test]
+fn flow_max_items_writes_small_scalar_collections_inline() {
+    let dir = tempdir().expect("temp dir");
+    let tree = dir.path().join("tree");
+    write(
+        &tree.join("d.yml"),
+        "ports: [80, 443]\nenv: {a: 1, b: 2}\nbig: [1, 2, 3, 4]\nnested: [[1]]\n\
+         tagged: [!Ref x]\nlines: [\"a\\nb\"]\n",
+    );
+    let root = tree.to_str().expect("utf8 path");
+    let packed = dir.path().join("packed.yaml");
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "pack",
+            root,
+            "--flow-max-items",
+            "3",
+            "-o",
+            packed.to_str().expect("utf8 path"),
+        ])
+        .assert()
+        .success();
+    let text = fs::read_to_string(&packed).expect("packed output");
+    assert!(text.contains("# style: flow-max-items=3\n"));
+    assert!(text.ends_with(
+        "d:\n  big:\n  - 1\n  - 2\n  - 3\n  - 4\n  env: {a: 1, b: 2}\n  lines:\n  - |-\n    a\n    b\n  \
+         nested:\n  - [1]\n  ports: [80, 443]\n  tagged:\n  - !Ref x\n"
+    ));
+    cargo_bin_cmd!("fyaml")
+        .args(["diff", root, packed.to_str().expect("utf8 path")])
+        .assert()
+        .success()
+        .stdout("equal\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", "--flow-max-items", "0", root])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("  ports:\n  - 80\n  - 443\n"));
+}
+
+#[test]
+fn scaffold_group_leaves_writes_small_scalar_mappings_as_one_file() {
+    let input_root = tempdir().expect("input temp dir");
+    let scaffold_root = tempdir().expect("scaffold temp dir");
+    let input = input_root.path().join("input.yml");
+    let scaffold_dir = scaffold_root.path().join("scaffold");
+    let report = input_root.path().join("report.json");
+    write(
+        &input,
+        "db:\n  host: a\n  port: 1\nbig:\n  a: 1\n  b: 2\n  c: 3\nlong:\n  s: \"x\\ny\"\n",
+    );
+
+    cargo_bin_cmd!("fyaml")
+        .args([
+            "scaffold",
+            input.to_str().expect("utf8 path"),
+            scaffold_dir.to_str().expect("utf8 path"),
+            "--layout",
+            "nested",
+            "--group-leaves",
+            "3",
+            "--report",
+            report.to_str().expect("utf8 path"),
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "--group-leaves 3 wrote each mapping of fewer than 3 one-line scalars as one file: \
+             db.yml (2 keys)",
+        ));
+    assert_eq!(
+        fs::read_to_string(scaffold_dir.join("db.yml")).expect("grouped file"),
+        "host: a\nport: 1\n"
+    );
+    assert!(scaffold_dir.join("big/c.yml").is_file());
+    assert!(scaffold_dir.join("long/s.yml").is_file());
+
+    let report: Value = serde_json::from_str(&fs::read_to_string(&report).expect("report written"))
+        .expect("report json");
+    assert_eq!(report["group_leaves"], 3);
+    let grouped = report["entries"]
+        .as_array()
+        .expect("entries")
+        .iter()
+        .find(|entry| entry["path"] == "db.yml")
+        .expect("grouped entry");
+    assert_eq!(grouped["key_path"], "db");
+    assert_eq!(grouped["rule"], "grouped-leaves");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header"])
+        .arg(&scaffold_dir)
+        .assert()
+        .success()
+        .stdout(
+            "big:\n  a: 1\n  b: 2\n  c: 3\ndb:\n  host: a\n  port: 1\nlong:\n  s: |-\n    x\n    y\n",
+        );
+}
+
+#[test]
+fn natural_collation_orders_digit_runs_by_value() {
+    let dir = tempdir().expect("temp dir");
+    let left = dir.path().join("left");
+    let right = dir.path().join("right");
+    write(&left.join("d.yml"), "item10: 1\nitem2: 2\nItem1: 0\n");
+    write(&right.join("d.yml"), "item10: 9\nitem2: 3\nItem1: 0\n");
+    let left = left.to_str().expect("utf8 path");
+    let right = right.to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", left, "--collation", "natural"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# collation: natural\n"))
+        .stdout(predicate::str::ends_with(
+            "d:\n  Item1: 0\n  item2: 2\n  item10: 1\n",
+        ));
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", left])
+        .assert()
+        .success()
+        .stdout("d:\n  Item1: 0\n  item10: 1\n  item2: 2\n");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["diff", left, right])
+        .assert()
+        .code(2)
+        .stdout("different at $.d.item10: scalar value differs\n");
+    cargo_bin_cmd!("fyaml")
+        .args(["diff", left, right, "--collation", "natural"])
+        .assert()
+        .code(2)
+        .stdout("different at $.d.item2: scalar value differs\n");
+}
+
+#[test]
+fn numeric_keys_sort_integer_keyed_mappings_by_value() {
+    let dir = tempdir().expect("temp dir");
+    write(
+        &dir.path().join("ports.yml"),
+        "\"10\": a\n\"2\": b\n\"02\": c\n1: d\n",
+    );
+    write(&dir.path().join("mixed.yml"), "b10: 1\n\"2\": x\n");
+    let root = dir.path().to_str().expect("utf8 path");
+
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--no-header", root])
+        .assert()
+        .success()
+        .stdout("mixed:\n  '2': x\n  b10: 1\nports:\n  '02': c\n  1: d\n  '10': a\n  '2': b\n");
+    cargo_bin_cmd!("fyaml")
+        .args(["pack", "--numeric-keys", root])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "# numeric keys: integer-keyed mappings sorted by value\n",
+        ))
+        .stdout(predicate::str::ends_with(
+            "mixed:\n  '2': x\n  b10: 1\nports:\n  1: d\n  '02': c\n  '2': b\n  '10': a\n",
+        ));
 }